@@ -1,12 +1,18 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
 use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 use tauri::{Manager, State};
 
-use crate::ha_client::normalize_server_url;
-use reqwest::Client;
-use crate::sensors::collector::SensorListItem;
+use crate::diagnostics::{ErrorCode, FailureKind, SimulatedFailure, MAX_SIMULATED_UPDATES};
+use crate::ha_client::{normalize_server_url, Transport};
+use crate::registration::ALREADY_REGISTERING_ERROR;
+use crate::sensors::collector::{GroupStatus, SensorListItem};
+use crate::sensors::network::{self, PUBLIC_IP_DISABLED};
+use crate::sensors::system_info::{self, DisplayInfo};
+use crate::sensors::validation::SensorWarning;
+use crate::settings::AppSettings;
 use crate::AppState;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,6 +26,10 @@ pub struct SettingsResponse {
     pub enabled_sensors: HashMap<String, bool>,
     pub autostart: bool,
     pub is_registered: bool,
+    pub dashboard_enabled: bool,
+    pub public_ip_enabled: bool,
+    /// Device name in HA; None = the hostname
+    pub device_name: Option<String>,
 }
 
 /// Get current settings
@@ -38,10 +48,15 @@ pub async fn get_settings(state: State<'_, Arc<AppState>>) -> Result<SettingsRes
         enabled_sensors: settings.enabled_sensors.clone(),
         autostart: settings.autostart,
         is_registered,
+        dashboard_enabled: settings.dashboard_enabled,
+        public_ip_enabled: settings.public_ip.enabled,
+        device_name: settings.device_name.clone(),
     })
 }
 
 /// Save settings and reinitialize connection
+// Tauri passes each setting from the form as its own argument
+#[allow(clippy::too_many_arguments)]
 #[tauri::command]
 pub async fn save_settings(
     state: State<'_, Arc<AppState>>,
@@ -51,58 +66,113 @@ pub async fn save_settings(
     update_interval: u64,
     language: String,
     autostart: bool,
+    device_name: Option<String>,
 ) -> Result<(), String> {
     let server_url = normalize_server_url(&server_url);
     let access_token = access_token.trim().to_string();
 
     let mut settings = state.settings.lock().await;
+    // A source address that can't be used would send traffic out the default route
+    if let Err(e) = settings.source_address() {
+        log::error!("[HA] Save settings refused: {}", e);
+        return Err(e);
+    }
+    // Not passed (setup screen): keep the current name; empty: back to the hostname
+    let device_name = match device_name {
+        Some(name) => Some(name.trim().to_string()).filter(|name| !name.is_empty()),
+        None => settings.device_name.clone(),
+    };
     let url_changed = settings.server_url != server_url;
     let token_changed = settings.access_token != access_token;
+    let name_changed = settings.device_name != device_name;
 
     settings.server_url = server_url.clone();
     settings.access_token = access_token.clone();
     settings.update_interval = update_interval;
     settings.language = language;
     settings.autostart = autostart;
+    settings.device_name = device_name;
 
     if let Err(e) = settings.save(&app) {
         log::error!("[HA] Save settings failed: {}", e);
         return Err(e);
     }
+    apply_autostart(&app, autostart);
+    state.settings_changed.notify_one();
 
     // If server URL or token changed, re-register
     if url_changed || token_changed {
         let mut ha_client = state.ha_client.lock().await;
         ha_client.update_config(server_url, access_token);
+        ha_client.set_webhook_secret(None);
 
         // Clear registration status - will re-register on next cycle
         if settings.webhook_id.is_some() {
             settings.webhook_id = None;
+            settings.webhook_secret = None;
             *state.is_registered.lock().await = false;
             if let Err(e) = settings.save(&app) {
                 log::error!("[HA] Save settings failed: {}", e);
                 return Err(e);
             }
         }
+    } else if name_changed && *state.is_registered.lock().await {
+        // Rename the existing device; a new registration picks the name up anyway. The
+        // settings stay unlocked while the metadata is collected and HA answers.
+        let saved = settings.clone();
+        drop(settings);
+        let registration =
+            tokio::task::block_in_place(|| crate::registration::registration_request(&saved));
+        let result = {
+            let ha_client = state.ha_client.lock().await;
+            if ha_client.transport() != Transport::Webhook {
+                return Ok(());
+            }
+            ha_client.update_registration(&registration).await
+        };
+        match result {
+            Ok(()) => log::info!("[HA] Device renamed to '{}'", registration.device_name),
+            Err(e) => {
+                log::error!("[HA] Renaming the device failed: {}", e);
+                state.record_error("save_settings", format!("Rename device: {}", e)).await;
+            }
+        }
     }
 
     Ok(())
 }
 
+/// Write the settings to disk right away, so the UI can restart the app (e.g. to apply an
+/// update) without losing a change the store hasn't written yet
+#[tauri::command]
+pub async fn persist_now(state: State<'_, Arc<AppState>>, app: tauri::AppHandle) -> Result<(), String> {
+    let settings = state.settings.lock().await;
+    settings.persist(&app).map_err(|e| {
+        log::error!("[HA] Persisting settings failed: {}", e);
+        e
+    })?;
+    log::info!("[HA] Settings written to disk");
+    Ok(())
+}
+
 /// Register device with HA
 #[tauri::command]
 pub async fn register_device(
     state: State<'_, Arc<AppState>>,
     app: tauri::AppHandle,
 ) -> Result<String, String> {
+    let Some(_registering) = state.begin_registration() else {
+        log::warn!("[HA] register_device: {}", ALREADY_REGISTERING_ERROR);
+        return Err(ALREADY_REGISTERING_ERROR.to_string());
+    };
+    let all_sensors = state.collect(|c| c.collect_all()).await;
     let mut settings = state.settings.lock().await;
     let mut ha_client = state.ha_client.lock().await;
-    let mut collector = state.collector.lock().await;
 
     let webhook_id = match crate::registration::register_device(
         &mut settings,
         &mut ha_client,
-        &mut collector,
+        &all_sensors,
         &app,
     )
     .await
@@ -110,11 +180,63 @@ pub async fn register_device(
         Ok(id) => id,
         Err(e) => {
             log::error!("[HA] Registration failed: {}", e);
+            state.record_error("register_device", &e).await;
+            return Err(e);
+        }
+    };
+    drop(ha_client);
+    state.collector.lock().await.record_sent(&all_sensors);
+
+    *state.is_registered.lock().await = true;
+    start_seed_burst(&state, &app, &settings);
+
+    Ok(webhook_id)
+}
+
+/// Start the opt-in seed burst in the background; it waits its spacing before the first update,
+/// so the registration locks are released by then
+fn start_seed_burst(state: &Arc<AppState>, app: &tauri::AppHandle, settings: &AppSettings) {
+    if settings.seed_burst.count == 0 {
+        return;
+    }
+    let config = settings.seed_burst.clone();
+    tauri::async_runtime::spawn(crate::send_seed_burst(state.clone(), app.clone(), config));
+}
+
+/// Adopt the device HA already has for this device_id (after the user confirmed it)
+#[tauri::command]
+pub async fn adopt_existing_device(
+    state: State<'_, Arc<AppState>>,
+    app: tauri::AppHandle,
+) -> Result<String, String> {
+    let Some(_registering) = state.begin_registration() else {
+        log::warn!("[HA] adopt_existing_device: {}", ALREADY_REGISTERING_ERROR);
+        return Err(ALREADY_REGISTERING_ERROR.to_string());
+    };
+    let all_sensors = state.collect(|c| c.collect_all()).await;
+    let mut settings = state.settings.lock().await;
+    let mut ha_client = state.ha_client.lock().await;
+
+    let webhook_id = match crate::registration::adopt_existing_device(
+        &mut settings,
+        &mut ha_client,
+        &all_sensors,
+        &app,
+    )
+    .await
+    {
+        Ok(id) => id,
+        Err(e) => {
+            log::error!("[HA] Adopting existing device failed: {}", e);
+            state.record_error("adopt_existing_device", &e).await;
             return Err(e);
         }
     };
+    drop(ha_client);
+    state.collector.lock().await.record_sent(&all_sensors);
 
     *state.is_registered.lock().await = true;
+    start_seed_burst(&state, &app, &settings);
 
     Ok(webhook_id)
 }
@@ -126,26 +248,164 @@ pub async fn get_sensor_list(state: State<'_, Arc<AppState>>) -> Result<Vec<Sens
     Ok(collector.get_sensor_list())
 }
 
-/// Force immediate sensor update
+#[derive(Debug, Clone, Serialize)]
+pub struct ElevationStatus {
+    /// Running as administrator (Windows) or root
+    pub elevated: bool,
+    /// Running elevated unlocks more sensors here (CPU temperature from ACPI thermal
+    /// zones on Windows), so the UI should suggest it
+    pub elevation_helps: bool,
+}
+
+/// Whether the app runs elevated, so the UI can explain sensors that need admin rights
+#[tauri::command]
+pub async fn get_elevation_status() -> Result<ElevationStatus, String> {
+    let elevated = system_info::is_elevated();
+    Ok(ElevationStatus {
+        elevated,
+        elevation_helps: cfg!(windows) && !elevated,
+    })
+}
+
+/// Currently connected displays (also sent with the `displays-changed` event on hotplug)
+#[tauri::command]
+pub async fn get_displays() -> Result<Vec<DisplayInfo>, String> {
+    tauri::async_runtime::spawn_blocking(system_info::current_displays)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Per sensor group: enabled, and the error from the last collection if it failed.
+/// Tells a sensor missing because collection failed apart from one that's disabled.
+#[tauri::command]
+pub async fn get_collection_status(state: State<'_, Arc<AppState>>) -> Result<Vec<GroupStatus>, String> {
+    let collector = state.collector.lock().await;
+    Ok(collector.group_status())
+}
+
+/// Force immediate sensor update. Errors start with an `ErrorCode` (e.g. "auth: ...")
 #[tauri::command]
 pub async fn update_sensors_now(state: State<'_, Arc<AppState>>) -> Result<(), String> {
     let is_registered = *state.is_registered.lock().await;
     if !is_registered {
         log::error!("[HA] update_sensors_now: device not registered");
-        return Err("Device not registered".to_string());
+        return Err(ErrorCode::NotRegistered.with_message("Device not registered"));
     }
 
-    let sensor_data = {
-        let mut collector = state.collector.lock().await;
-        collector.collect_dynamic()
-    };
+    if *state.device_offline.lock().await {
+        return Err(ErrorCode::DeviceOffline.with_message("Device is marked offline"));
+    }
+
+    let sensor_data = state.collect(|c| c.collect_dynamic()).await;
 
     let ha_client = state.ha_client.lock().await;
     if let Err(e) = ha_client.update_sensors(&sensor_data).await {
         log::error!("[HA] Update sensors failed: {}", e);
+        state.record_error("update_sensors_now", &e).await;
+        let code = ErrorCode::classify(&*e);
+        return Err(code.with_message(format!("Update failed: {}", e)));
+    }
+    drop(ha_client);
+    state.collector.lock().await.record_sent(&sensor_data);
+
+    Ok(())
+}
+
+/// Send the current state of every sensor, static ones included, without registering them
+/// again; e.g. after a recorder purge or when entities show unavailable. Returns how many
+/// sensors were sent. Errors start with an `ErrorCode`, like `update_sensors_now`.
+#[tauri::command]
+pub async fn resend_all_states(state: State<'_, Arc<AppState>>) -> Result<usize, String> {
+    let is_registered = *state.is_registered.lock().await;
+    if !is_registered {
+        log::error!("[HA] resend_all_states: device not registered");
+        return Err(ErrorCode::NotRegistered.with_message("Device not registered"));
+    }
+
+    if *state.device_offline.lock().await {
+        return Err(ErrorCode::DeviceOffline.with_message("Device is marked offline"));
+    }
+
+    let all_sensors = state.collect(|c| c.collect_all()).await;
+
+    let ha_client = state.ha_client.lock().await;
+    if let Err(e) = ha_client.update_sensors(&all_sensors).await {
+        log::error!("[HA] Re-sending all states failed: {}", e);
+        state.record_error("resend_all_states", &e).await;
+        let code = ErrorCode::classify(&*e);
+        return Err(code.with_message(format!("Update failed: {}", e)));
+    }
+    drop(ha_client);
+    state.collector.lock().await.record_sent(&all_sensors);
+
+    log::info!("[HA] Re-sent the states of {} sensors", all_sensors.len());
+    Ok(all_sensors.len())
+}
+
+/// Fake a failure so HA automations around the companion's health can be tested (dev mode
+/// only: start with --dev). `fail_updates` makes the next `count` updates fail, `unavailable`
+/// reports the dynamic sensors as unavailable and `spike` reports `value` (default 100) for
+/// `sensor_id` (default cpu_usage), each for `count` updates (default 1).
+#[tauri::command]
+pub async fn simulate_failure(
+    state: State<'_, Arc<AppState>>,
+    kind: FailureKind,
+    count: Option<u32>,
+    sensor_id: Option<String>,
+    value: Option<f64>,
+) -> Result<(), String> {
+    if !state.dev_mode {
+        return Err("simulate_failure is only available when started with --dev".to_string());
+    }
+    let count = count.unwrap_or(1).clamp(1, MAX_SIMULATED_UPDATES);
+    log::warn!("[Debug] Simulating {:?} for the next {} update(s)", kind, count);
+
+    if kind == FailureKind::FailUpdates {
+        state.ha_client.lock().await.simulate_failures(count);
+        return Ok(());
+    }
+    state.collector.lock().await.simulate(SimulatedFailure {
+        kind,
+        remaining: count,
+        sensor_id: sensor_id.unwrap_or_else(|| "cpu_usage".to_string()),
+        value: value.unwrap_or(100.0),
+    });
+    Ok(())
+}
+
+/// Manually mark the device offline (all sensors unavailable in HA), e.g. before a planned
+/// shutdown or maintenance. Stays offline until set online again, which sends a full update.
+#[tauri::command]
+pub async fn set_device_online(state: State<'_, Arc<AppState>>, online: bool) -> Result<(), String> {
+    let is_registered = *state.is_registered.lock().await;
+    if !is_registered {
+        return Err("Device not registered".to_string());
+    }
+
+    // Set before sending, so the update loop can't overwrite the unavailable states; put
+    // back when sending fails, so the loop doesn't stay paused for a device HA still sees
+    let was_offline = std::mem::replace(&mut *state.device_offline.lock().await, !online);
+
+    let all_sensors = state.collect(|c| c.collect_all()).await;
+    let ha_client = state.ha_client.lock().await;
+    let result = if online {
+        ha_client.update_sensors(&all_sensors).await
+    } else {
+        ha_client.mark_unavailable(&all_sensors).await
+    };
+    if let Err(e) = result {
+        drop(ha_client);
+        *state.device_offline.lock().await = was_offline;
+        log::error!("[HA] Marking device {} failed: {}", if online { "online" } else { "offline" }, e);
+        state.record_error("set_device_online", &e).await;
         return Err(format!("Update failed: {}", e));
     }
+    drop(ha_client);
+    if online {
+        state.collector.lock().await.record_sent(&all_sensors);
+    }
 
+    log::info!("[HA] Device marked {}", if online { "online" } else { "offline" });
     Ok(())
 }
 
@@ -157,17 +417,145 @@ pub async fn toggle_sensor(
     sensor_id: String,
     enabled: bool,
 ) -> Result<(), String> {
+    let was_enabled = enabled_sensor_ids(&state).await.contains(&sensor_id);
     let mut settings = state.settings.lock().await;
-    settings.enabled_sensors.insert(sensor_id, enabled);
+    settings.enabled_sensors.insert(sensor_id.clone(), enabled);
     if let Err(e) = settings.save(&app) {
         log::error!("[HA] Save settings failed: {}", e);
         return Err(e);
     }
 
-    // Update collector
+    // Update collector; toggles from the config file still win
+    let toggles = state.sensor_toggles(&settings).await;
+    let mut collector = state.collector.lock().await;
+    collector.set_enabled_sensors(toggles);
+    drop(collector);
+    drop(settings);
+
+    if enabled && !was_enabled {
+        report_switched_on(&state, &app, vec![sensor_id]);
+    }
+    Ok(())
+}
+
+/// Enable/disable several sensors at once, with a single settings write.
+/// Unknown sensor ids are skipped with a warning.
+#[tauri::command]
+pub async fn set_sensors_enabled(
+    state: State<'_, Arc<AppState>>,
+    app: tauri::AppHandle,
+    changes: Vec<(String, bool)>,
+) -> Result<(), String> {
+    apply_sensor_changes(&state, &app, changes).await
+}
+
+/// Enable/disable every sensor in a group (see `SensorListItem::group`)
+#[tauri::command]
+pub async fn set_group_enabled(
+    state: State<'_, Arc<AppState>>,
+    app: tauri::AppHandle,
+    group: String,
+    enabled: bool,
+) -> Result<(), String> {
+    let ids: Vec<String> = state
+        .collector
+        .lock()
+        .await
+        .get_sensor_list()
+        .into_iter()
+        .filter(|s| s.group == group)
+        .map(|s| s.id)
+        .collect();
+    if ids.is_empty() {
+        log::warn!("[Sensors] Unknown sensor group '{}'", group);
+        return Err(format!("Unknown sensor group: {}", group));
+    }
+    apply_sensor_changes(&state, &app, ids.into_iter().map(|id| (id, enabled)).collect()).await
+}
+
+/// Register or remove the login item. Enabling again rewrites an existing entry, so one
+/// created by an older version also gets the `--autostart` argument.
+pub(crate) fn apply_autostart(app: &tauri::AppHandle, enabled: bool) {
+    use tauri_plugin_autostart::ManagerExt;
+
+    let autolaunch = app.autolaunch();
+    let result = if enabled {
+        autolaunch.enable().map_err(|e| e.to_string())
+    } else if autolaunch.is_enabled().unwrap_or(false) {
+        autolaunch.disable().map_err(|e| e.to_string())
+    } else {
+        Ok(())
+    };
+    if let Err(e) = result {
+        log::error!("[Autostart] {} failed: {}", if enabled { "Enable" } else { "Disable" }, e);
+    }
+}
+
+/// Ids of the sensor toggles that are on
+async fn enabled_sensor_ids(state: &AppState) -> HashSet<String> {
+    state
+        .collector
+        .lock()
+        .await
+        .get_sensor_list()
+        .into_iter()
+        .filter(|s| s.enabled)
+        .map(|s| s.id)
+        .collect()
+}
+
+/// Report the sensors of toggles that were just switched on in the background, so they show
+/// up in HA right away instead of after the next cycle
+fn report_switched_on(state: &Arc<AppState>, app: &tauri::AppHandle, ids: Vec<String>) {
+    let state = state.clone();
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let sensors = state.collect(move |c| c.collect_switched_on(&ids)).await;
+        state.report_new_sensors(&app, &sensors, "toggle_sensor").await;
+    });
+}
+
+async fn apply_sensor_changes(
+    state: &Arc<AppState>,
+    app: &tauri::AppHandle,
+    changes: Vec<(String, bool)>,
+) -> Result<(), String> {
+    let sensor_list = state.collector.lock().await.get_sensor_list();
+    let known: HashSet<&str> = sensor_list.iter().map(|s| s.id.as_str()).collect();
+    let switched_on: Vec<String> = sensor_list
+        .iter()
+        .filter(|s| !s.enabled && changes.contains(&(s.id.clone(), true)))
+        .map(|s| s.id.clone())
+        .collect();
+
+    let mut settings = state.settings.lock().await;
+    let mut applied = 0;
+    for (sensor_id, enabled) in changes {
+        if !known.contains(sensor_id.as_str()) {
+            log::warn!("[Sensors] Ignoring unknown sensor id '{}'", sensor_id);
+            continue;
+        }
+        settings.enabled_sensors.insert(sensor_id, enabled);
+        applied += 1;
+    }
+    if applied == 0 {
+        return Ok(());
+    }
+    if let Err(e) = settings.save(app) {
+        log::error!("[HA] Save settings failed: {}", e);
+        return Err(e);
+    }
+    log::info!("[Sensors] Updated {} sensor toggles", applied);
+
+    let toggles = state.sensor_toggles(&settings).await;
     let mut collector = state.collector.lock().await;
-    collector.set_enabled_sensors(settings.enabled_sensors.clone());
+    collector.set_enabled_sensors(toggles);
+    drop(collector);
+    drop(settings);
 
+    if !switched_on.is_empty() {
+        report_switched_on(state, app, switched_on);
+    }
     Ok(())
 }
 
@@ -181,9 +569,11 @@ pub async fn get_current_language(state: State<'_, Arc<AppState>>) -> Result<Str
 /// Open the HA dashboard as a child webview inside the main window.
 /// Only injects hassTokens in localStorage (no externalApp, which would
 /// hijack the auth flow and break it for long-lived tokens).
+/// Callers must check `AppSettings::dashboard_enabled` first.
 pub fn open_dashboard_view<R: tauri::Runtime, M: Manager<R>>(
     manager: &M,
     server_url: &str,
+    dashboard_path: Option<&str>,
     token: &str,
 ) -> Result<(), String> {
     let base_url = server_url.trim_end_matches('/');
@@ -223,9 +613,7 @@ pub fn open_dashboard_view<R: tauri::Runtime, M: Manager<R>>(
         "#
     );
 
-    let url: url::Url = base_url
-        .parse()
-        .map_err(|e: url::ParseError| format!("Invalid URL '{}': {}", base_url, e))?;
+    let url = dashboard_url(base_url, dashboard_path)?;
 
     let window = manager.get_window("main").ok_or("Main window not found")?;
     let scale = window.scale_factor().map_err(|e| e.to_string())?;
@@ -248,6 +636,32 @@ pub fn open_dashboard_view<R: tauri::Runtime, M: Manager<R>>(
     Ok(())
 }
 
+/// URL the dashboard opens at: the server URL, or `dashboard_path` below it. A path that
+/// would leave the server URL (another host, `..` above it) is ignored with a warning.
+fn dashboard_url(base_url: &str, dashboard_path: Option<&str>) -> Result<url::Url, String> {
+    let base: url::Url = base_url
+        .parse()
+        .map_err(|e: url::ParseError| format!("Invalid URL '{}': {}", base_url, e))?;
+    let Some(path) = dashboard_path else {
+        return Ok(base);
+    };
+
+    let joined = format!("{}/{}", base_url, path.trim_start_matches('/'));
+    match joined.parse::<url::Url>() {
+        Ok(url) if url.origin() == base.origin() && url.path().starts_with(base.path()) => {
+            log::info!("[Dashboard] Opening at {}", url.path());
+            Ok(url)
+        }
+        _ => {
+            log::warn!(
+                "[Dashboard] dashboard_path '{}' is not a path under {}, opening the default dashboard",
+                path, base_url
+            );
+            Ok(base)
+        }
+    }
+}
+
 /// Remove the HA dashboard child webview (to reveal the main HTML underneath).
 pub fn close_dashboard_view<R: tauri::Runtime, M: Manager<R>>(manager: &M) {
     if let Some(wv) = manager.get_webview("ha-view") {
@@ -263,11 +677,16 @@ pub async fn load_dashboard(
     state: State<'_, Arc<AppState>>,
 ) -> Result<(), String> {
     let settings = state.settings.lock().await;
+    if !settings.dashboard_enabled {
+        log::warn!("[Dashboard] Dashboard is disabled in settings, not opening it");
+        return Err("Dashboard is disabled".to_string());
+    }
     let server_url = settings.server_url.clone();
+    let dashboard_path = settings.dashboard_path.clone();
     let token = settings.access_token.clone();
     drop(settings);
 
-    open_dashboard_view(&app, &server_url, &token)
+    open_dashboard_view(&app, &server_url, dashboard_path.as_deref(), &token)
 }
 
 /// Tauri command: close the HA dashboard view (used when opening settings)
@@ -278,19 +697,136 @@ pub async fn hide_dashboard(app: tauri::AppHandle) -> Result<(), String> {
 }
 
 /// Get this machine's public (outbound) IP. Use this in your reverse proxy allowlist.
+/// Goes out via the configured source address, like the requests to HA.
+/// Refuses without contacting anything unless `public_ip.enabled` is set.
 #[tauri::command]
-pub async fn get_my_public_ip() -> Result<String, String> {
-    let client = Client::builder()
-        .timeout(std::time::Duration::from_secs(5))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
-    let body = client
-        .get("https://api.ipify.org")
-        .send()
-        .await
-        .map_err(|e| format!("Network error: {}", e))?
-        .text()
-        .await
-        .map_err(|e| format!("Failed to read response: {}", e))?;
-    Ok(body.trim().to_string())
+pub async fn get_my_public_ip(state: State<'_, Arc<AppState>>) -> Result<String, String> {
+    public_ip(&state).await
+}
+
+async fn public_ip(state: &AppState) -> Result<String, String> {
+    let settings = state.settings.lock().await;
+    if !settings.public_ip.enabled {
+        return Err(PUBLIC_IP_DISABLED.to_string());
+    }
+    let config = settings.public_ip.clone();
+    let local_address = settings.source_address()?;
+    drop(settings);
+
+    network::lookup_public_ip(&config, local_address).await
+}
+
+/// Outbound public IP next to the IP Home Assistant sees, for setting up a proxy allowlist
+#[derive(Debug, Clone, Serialize)]
+pub struct AllowlistIpCheck {
+    /// Public IP according to ipify
+    pub public_ip: String,
+    /// Client IP Home Assistant saw; None if the integration doesn't echo it
+    pub ha_seen_ip: Option<String>,
+    /// Whether both are the same address; None when HA's view is unknown
+    pub matches: Option<bool>,
+    /// HA saw a private, loopback or CGNAT address: HA is reached over the LAN or VPN, or a
+    /// proxy in between doesn't pass on the original client IP
+    pub ha_seen_local: bool,
+}
+
+/// Look up the public IP and ask HA which IP it sees, so a mismatch (proxy, CGNAT, LAN
+/// access) shows up before it ends up in an allowlist. Needs `public_ip.enabled`.
+#[tauri::command]
+pub async fn check_allowlist_ip(state: State<'_, Arc<AppState>>) -> Result<AllowlistIpCheck, String> {
+    let public_ip = public_ip(&state).await?;
+    let ha_seen_ip = state.ha_client.lock().await.seen_client_ip().await;
+
+    let parse = |ip: &str| ip.parse::<IpAddr>().ok().map(|ip| ip.to_canonical());
+    let seen = ha_seen_ip.as_deref().and_then(parse);
+    let matches = ha_seen_ip
+        .as_deref()
+        .map(|ip| match (parse(&public_ip), seen) {
+            (Some(a), Some(b)) => a == b,
+            _ => ip == public_ip,
+        });
+    let ha_seen_local = seen.is_some_and(|ip| !network::is_public_ip(ip));
+    if matches == Some(false) {
+        log::warn!(
+            "[Network] HA sees {} but the public IP is {}",
+            ha_seen_ip.as_deref().unwrap_or_default(),
+            public_ip
+        );
+    }
+
+    Ok(AllowlistIpCheck {
+        public_ip,
+        ha_seen_ip,
+        matches,
+        ha_seen_local,
+    })
+}
+
+/// Write a JSON support bundle (version, redacted settings, current sensor values,
+/// recent errors, platform info) to `path`. Secrets are redacted unless `include_secrets`.
+#[tauri::command]
+pub async fn generate_support_bundle(
+    state: State<'_, Arc<AppState>>,
+    path: String,
+    include_secrets: bool,
+) -> Result<String, String> {
+    let mut settings_json = {
+        let settings = state.settings.lock().await;
+        serde_json::to_value(&*settings).map_err(|e| e.to_string())?
+    };
+    if !include_secrets {
+        for key in ["access_token", "webhook_id", "webhook_secret"] {
+            if let Some(value) = settings_json.get_mut(key) {
+                if !value.is_null() {
+                    *value = serde_json::json!(crate::diagnostics::REDACTED);
+                }
+            }
+        }
+        if let Some(proxy) = settings_json.pointer_mut("/public_ip/proxy") {
+            if let Some(url) = proxy.as_str() {
+                *proxy = serde_json::json!(crate::diagnostics::mask_credentials(url));
+            }
+        }
+    }
+
+    let is_registered = *state.is_registered.lock().await;
+    let (sensor_list, sensors) = state
+        .collect(|c| (c.get_sensor_list(), c.collect_all()))
+        .await;
+    let recent_errors = state.recent_errors.lock().await.entries();
+    let platform = crate::sensors::system_info::collect();
+
+    let bundle = serde_json::json!({
+        "generated_at": crate::diagnostics::unix_now(),
+        "app_version": env!("CARGO_PKG_VERSION"),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "is_registered": is_registered,
+        "settings": settings_json,
+        "sensor_list": sensor_list,
+        "sensors": sensors,
+        "recent_errors": recent_errors,
+        "platform": platform,
+    });
+
+    let body = serde_json::to_string_pretty(&bundle).map_err(|e| e.to_string())?;
+    std::fs::write(&path, body).map_err(|e| {
+        log::error!("[Support] Failed to write bundle to {}: {}", path, e);
+        format!("Failed to write support bundle: {}", e)
+    })?;
+    log::info!("[Support] Support bundle written to {}", path);
+
+    Ok(path)
+}
+
+/// Check the metadata of all enabled sensors against HA's device_class rules.
+/// Catches unit/state_class mistakes that make long-term statistics fail.
+#[tauri::command]
+pub async fn validate_sensors(state: State<'_, Arc<AppState>>) -> Result<Vec<SensorWarning>, String> {
+    let sensors = state.collect(|c| c.collect_all()).await;
+    let warnings = crate::sensors::validation::validate(&sensors);
+    for w in &warnings {
+        log::warn!("[Validate] {}: {}", w.unique_id, w.message);
+    }
+    Ok(warnings)
 }
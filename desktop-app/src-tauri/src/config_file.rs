@@ -0,0 +1,229 @@
+//! Optional declarative sensor configuration in TOML (`config_file` in settings.json), for
+//! version-controlled setups and fleets. Values in the file override the store; anything the
+//! file leaves out keeps its stored value. The file is never written into the store, so
+//! removing a key (or the whole file) brings the stored value back. Example:
+//!
+//! ```toml
+//! update_interval = 30
+//!
+//! [sensors]
+//! keyboard = true
+//! gpu_process_count = false
+//!
+//! [overrides.cpu_usage]
+//! name = "Office PC CPU"
+//! icon = "mdi:chip"
+//!
+//! [overrides.disk_usage_c]
+//! interval = 300
+//!
+//! [[custom_sensors]]
+//! id = "plex_streams"
+//! name = "Plex Streams"
+//! command = "curl -s ..."
+//! interval = 60
+//! ```
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::sensors::custom::CustomSensor;
+use crate::settings::AppSettings;
+
+/// Name/icon shown in HA instead of the built-in one, and how often the sensor is sent
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SensorOverride {
+    pub name: Option<String>,
+    pub icon: Option<String>,
+    /// Send the sensor at most every this many seconds. The update loop runs every
+    /// `update_interval`, so this slows a sensor down but can't make it faster.
+    pub interval: Option<u64>,
+}
+
+impl SensorOverride {
+    /// Whether a sensor HA last accepted at `last_sent` is due to be sent again
+    pub fn is_due(&self, last_sent: Option<Instant>, now: Instant) -> bool {
+        match (self.interval, last_sent) {
+            (Some(secs), Some(at)) => now.duration_since(at).as_secs() >= secs,
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SensorConfig {
+    pub update_interval: Option<u64>,
+    /// Sensor toggles by id, as in the settings sensor list
+    pub sensors: HashMap<String, bool>,
+    /// Name/icon overrides per sensor unique_id
+    pub overrides: HashMap<String, SensorOverride>,
+    /// Replaces `custom_sensors` from the store when present
+    pub custom_sensors: Option<Vec<CustomSensor>>,
+    pub custom_sensors_enabled: Option<bool>,
+}
+
+impl SensorConfig {
+    /// Read and parse the file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        toml::from_str(&content).map_err(|e| format!("Invalid config file {}: {}", path.display(), e))
+    }
+
+    /// Update interval in effect with `stored` from the settings
+    pub fn update_interval(&self, stored: u64) -> u64 {
+        // Same range as the settings UI
+        self.update_interval.map_or(stored, |interval| interval.clamp(10, 3600))
+    }
+
+    /// Sensor toggles in effect: the stored ones with the file's over them
+    pub fn enabled_sensors(&self, stored: &HashMap<String, bool>) -> HashMap<String, bool> {
+        let mut enabled = stored.clone();
+        enabled.extend(self.sensors.iter().map(|(id, on)| (id.clone(), *on)));
+        enabled
+    }
+
+    /// Copy of the stored settings with the file's values merged over them. Only for
+    /// configuring the collector and the update loop; never save it.
+    pub fn applied_to(&self, settings: &AppSettings) -> AppSettings {
+        let mut settings = settings.clone();
+        settings.update_interval = self.update_interval(settings.update_interval);
+        settings.enabled_sensors = self.enabled_sensors(&settings.enabled_sensors);
+        if let Some(ref custom) = self.custom_sensors {
+            settings.custom_sensors = custom.clone();
+        }
+        if let Some(enabled) = self.custom_sensors_enabled {
+            settings.custom_sensors_enabled = enabled;
+        }
+        settings
+    }
+}
+
+/// The settings in effect: `settings` as stored, with `config` merged over them if loaded
+pub fn effective(settings: &AppSettings, config: Option<&SensorConfig>) -> AppSettings {
+    match config {
+        Some(config) => config.applied_to(settings),
+        None => settings.clone(),
+    }
+}
+
+/// Load the configured file, if any. Errors are logged and the stored settings stay in effect.
+pub fn load_configured(settings: &AppSettings) -> Option<SensorConfig> {
+    let path = settings.config_file.as_ref()?;
+    match SensorConfig::load(Path::new(path)) {
+        Ok(config) => {
+            log::info!("[Config] Loaded {}", path);
+            Some(config)
+        }
+        Err(e) => {
+            log::error!("[Config] {}; using stored settings", e);
+            None
+        }
+    }
+}
+
+/// Notices changes to the config file by its modification time, for hot reload
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    /// Start watching; the file as it is now counts as already loaded
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let modified = modified_time(&path);
+        Self { path, modified }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The new configuration if the file changed since the last poll and parses. A removed
+    /// file gives an empty configuration, so the stored settings apply again.
+    pub fn poll(&mut self) -> Option<SensorConfig> {
+        let modified = modified_time(&self.path);
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        if modified.is_none() {
+            log::info!("[Config] {} removed, using stored settings", self.path.display());
+            return Some(SensorConfig::default());
+        }
+        match SensorConfig::load(&self.path) {
+            Ok(config) => {
+                log::info!("[Config] {} changed, reloading", self.path.display());
+                Some(config)
+            }
+            Err(e) => {
+                log::error!("[Config] {}; keeping the previous configuration", e);
+                None
+            }
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applied_to_leaves_the_stored_settings_alone() {
+        let stored = AppSettings {
+            update_interval: 60,
+            enabled_sensors: HashMap::from([("keyboard".to_string(), false)]),
+            ..AppSettings::default()
+        };
+        let config: SensorConfig =
+            toml::from_str("update_interval = 30\n[sensors]\nkeyboard = true\n").unwrap();
+
+        let effective = config.applied_to(&stored);
+        assert_eq!(effective.update_interval, 30);
+        assert_eq!(effective.enabled_sensors.get("keyboard"), Some(&true));
+        assert_eq!(stored.update_interval, 60);
+        assert_eq!(stored.enabled_sensors.get("keyboard"), Some(&false));
+
+        // A reload without the keys falls back to the stored values
+        let effective = SensorConfig::default().applied_to(&stored);
+        assert_eq!(effective.update_interval, 60);
+        assert_eq!(effective.enabled_sensors.get("keyboard"), Some(&false));
+    }
+
+    #[test]
+    fn sensor_interval_holds_back_until_elapsed() {
+        let config: SensorConfig =
+            toml::from_str("[overrides.disk_usage_c]\ninterval = 300\n").unwrap();
+        let sensor = &config.overrides["disk_usage_c"];
+        let sent = Instant::now();
+        assert!(sensor.is_due(None, sent));
+        assert!(!sensor.is_due(Some(sent), sent + std::time::Duration::from_secs(299)));
+        assert!(sensor.is_due(Some(sent), sent + std::time::Duration::from_secs(300)));
+        // Without an interval the sensor goes out every cycle
+        assert!(SensorOverride::default().is_due(Some(sent), sent));
+    }
+
+    #[test]
+    fn removed_file_polls_as_empty_config() {
+        let path = std::env::temp_dir().join(format!("ha-companion-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "update_interval = 30\n").unwrap();
+        let mut watcher = ConfigWatcher::new(&path);
+        assert!(watcher.poll().is_none());
+
+        std::fs::remove_file(&path).unwrap();
+        let config = watcher.poll().expect("removal is a change");
+        assert_eq!(config.update_interval, None);
+        assert!(config.sensors.is_empty());
+        assert!(watcher.poll().is_none());
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ha_client::HttpError;
+
+/// Number of errors kept for support bundles
+const MAX_RECENT_ERRORS: usize = 50;
+
+/// Default number of consecutive 410/404 webhook responses before re-registering
+pub const DEFAULT_WEBHOOK_GONE_THRESHOLD: u32 = 3;
+
+/// Webhook failures further apart than this no longer count as consecutive
+const WEBHOOK_GONE_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+/// Default time between checks that the webhook still exists (0 = never check)
+pub const DEFAULT_WEBHOOK_VERIFY_INTERVAL_SECS: u64 = 60 * 60;
+/// Shortest time between two webhook checks
+pub const MIN_WEBHOOK_VERIFY_INTERVAL_SECS: u64 = 5 * 60;
+
+/// Placeholder for secrets in support bundles and logs
+pub const REDACTED: &str = "***redacted***";
+
+/// Shorter values are too likely to occur in ordinary text to mask them everywhere
+const MIN_SECRET_LEN: usize = 8;
+
+/// Access tokens and webhook ids in use, masked wherever they show up in logs
+static SECRETS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Places secrets show up without being registered (e.g. HA's registration response),
+/// with the characters that end the secret
+const SECRET_MARKERS: &[(&str, &[char])] = &[
+    ("/api/webhook/", &['"', '/', '?', ' ', ',']),
+    ("Bearer ", &['"', ' ', ',']),
+    ("\"webhook_id\":\"", &['"']),
+    ("\"webhook_id\": \"", &['"']),
+    ("\"cloudhook_url\":\"", &['"']),
+    ("\"cloudhook_url\": \"", &['"']),
+    ("\"secret\":\"", &['"']),
+    ("\"secret\": \"", &['"']),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorEntry {
+    /// UNIX timestamp (seconds)
+    pub timestamp: u64,
+    /// Where the error happened, e.g. "update_loop" or "register_device"
+    pub context: String,
+    pub message: String,
+}
+
+/// Ring buffer of the most recent errors
+#[derive(Debug, Default)]
+pub struct RecentErrors {
+    entries: VecDeque<ErrorEntry>,
+}
+
+impl RecentErrors {
+    pub fn push(&mut self, context: &str, message: String) {
+        if self.entries.len() >= MAX_RECENT_ERRORS {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(ErrorEntry {
+            timestamp: unix_now(),
+            context: context.to_string(),
+            message: redact(&message),
+        });
+    }
+
+    pub fn entries(&self) -> Vec<ErrorEntry> {
+        self.entries.iter().cloned().collect()
+    }
+}
+
+/// Start logging to stderr (dev builds and `--oneshot`), with secrets masked in every line
+pub fn init_logging() {
+    let _ = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+        .format(|buf, record| {
+            writeln!(
+                buf,
+                "[{} {:<5} {}] {}",
+                buf.timestamp(),
+                record.level(),
+                record.target(),
+                redact(&record.args().to_string())
+            )
+        })
+        .try_init();
+}
+
+/// Mask `secret` (an access token or webhook id) from now on in logs and recorded errors
+pub fn register_secret(secret: &str) {
+    let secret = secret.trim();
+    if secret.len() < MIN_SECRET_LEN {
+        return;
+    }
+    if let Ok(mut secrets) = SECRETS.lock() {
+        if !secrets.iter().any(|s| s == secret) {
+            secrets.push(secret.to_string());
+        }
+    }
+}
+
+/// `text` with registered secrets, bearer tokens, webhook URLs and the secrets in HA's
+/// registration response replaced by [`REDACTED`]
+pub fn redact(text: &str) -> String {
+    let mut redacted = text.to_string();
+    if let Ok(secrets) = SECRETS.lock() {
+        for secret in secrets.iter() {
+            if redacted.contains(secret.as_str()) {
+                redacted = redacted.replace(secret.as_str(), REDACTED);
+            }
+        }
+    }
+    for (marker, ends) in SECRET_MARKERS {
+        if redacted.contains(marker) {
+            redacted = mask_after(&redacted, marker, ends);
+        }
+    }
+    redacted
+}
+
+/// Replace what follows each `marker`, up to one of `ends`, with [`REDACTED`]
+fn mask_after(text: &str, marker: &str, ends: &[char]) -> String {
+    let mut masked = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(pos) = rest.find(marker) {
+        let start = pos + marker.len();
+        masked.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        let end = tail.find(ends).unwrap_or(tail.len());
+        if end > 0 {
+            masked.push_str(REDACTED);
+        }
+        rest = &tail[end..];
+    }
+    masked.push_str(rest);
+    masked
+}
+
+/// `url` with its `user:password@` part replaced by [`REDACTED`], e.g. for proxy URLs
+pub fn mask_credentials(url: &str) -> String {
+    let start = url.find("://").map_or(0, |i| i + 3);
+    let authority_end = url[start..]
+        .find(['/', '?', '#'])
+        .map_or(url.len(), |i| start + i);
+    match url[start..authority_end].rfind('@') {
+        Some(at) => format!("{}{}{}", &url[..start], REDACTED, &url[start + at..]),
+        None => url.to_string(),
+    }
+}
+
+/// Payload of the `webhook-healed` event: the webhook was gone and the device registered again
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookHeal {
+    /// Whether registering again worked
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+/// Most updates a single `simulate_failure` call affects
+pub const MAX_SIMULATED_UPDATES: u32 = 100;
+
+/// What `simulate_failure` fakes, so HA automations around the companion's health can be
+/// tested without breaking anything
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// The next updates fail as a server error, without being sent
+    FailUpdates,
+    /// The next updates report every dynamic sensor as unavailable
+    Unavailable,
+    /// The next updates report a spiked value for one sensor
+    Spike,
+}
+
+/// A simulated `unavailable` or `spike` for the next `remaining` regular update cycles
+#[derive(Debug, Clone)]
+pub struct SimulatedFailure {
+    pub kind: FailureKind,
+    pub remaining: u32,
+    /// Sensor that spikes
+    pub sensor_id: String,
+    pub value: f64,
+}
+
+/// Whether an HA error means the webhook is gone (410) or unknown (404)
+pub fn is_webhook_gone(error: &(dyn std::error::Error + Send + Sync + 'static)) -> bool {
+    HttpError::status_of(error).is_some_and(is_webhook_gone_status)
+}
+
+/// Whether an HA response status means the webhook is gone (410) or unknown (404)
+pub fn is_webhook_gone_status(status: reqwest::StatusCode) -> bool {
+    status == reqwest::StatusCode::GONE || status == reqwest::StatusCode::NOT_FOUND
+}
+
+/// Machine-readable error category, sent as a `code: ` prefix of command errors so the UI
+/// can tell "re-register" apart from "check your token" or "check your network"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    NotRegistered,
+    DeviceOffline,
+    WebhookGone,
+    Auth,
+    Network,
+    Server,
+    Unknown,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NotRegistered => "not_registered",
+            Self::DeviceOffline => "device_offline",
+            Self::WebhookGone => "webhook_gone",
+            Self::Auth => "auth",
+            Self::Network => "network",
+            Self::Server => "server",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    /// Category of an error returned by `HaClient`
+    pub fn classify(error: &(dyn std::error::Error + Send + Sync + 'static)) -> Self {
+        if error.downcast_ref::<reqwest::Error>().is_some() {
+            return Self::Network;
+        }
+        match HttpError::status_of(error) {
+            Some(status) if is_webhook_gone_status(status) => Self::WebhookGone,
+            Some(reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN) => Self::Auth,
+            Some(status) if status.is_server_error() => Self::Server,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// `message` prefixed with this code, e.g. "webhook_gone: Update failed: 410 Gone"
+    pub fn with_message(&self, message: impl std::fmt::Display) -> String {
+        format!("{}: {}", self.as_str(), message)
+    }
+}
+
+/// Consecutive "webhook gone" responses. HA answers 404/410 briefly while it restarts,
+/// so the webhook only counts as expired after several failures in a row.
+#[derive(Debug, Default)]
+pub struct WebhookFailures {
+    count: u32,
+    first_at: Option<Instant>,
+}
+
+impl WebhookFailures {
+    /// Record a failure; true once `threshold` consecutive failures happened within the window
+    pub fn record(&mut self, threshold: u32) -> bool {
+        let now = Instant::now();
+        match self.first_at {
+            Some(first) if now.duration_since(first) <= WEBHOOK_GONE_WINDOW => self.count += 1,
+            _ => {
+                self.first_at = Some(now);
+                self.count = 1;
+            }
+        }
+        self.count >= threshold
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// A request through the webhook succeeded
+    pub fn reset(&mut self) {
+        self.count = 0;
+        self.first_at = None;
+    }
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_registered_tokens() {
+        let token = "eyJhbGciOiJIUzI1NiJ9.test-token";
+        register_secret(token);
+        assert_eq!(
+            redact(&format!("token={} failed", token)),
+            format!("token={} failed", REDACTED)
+        );
+        // Too short to mask everywhere
+        register_secret("abc");
+        assert_eq!(redact("abc"), "abc");
+    }
+
+    #[test]
+    fn redacts_webhook_ids_in_urls() {
+        assert_eq!(
+            redact("POST https://ha.local/api/webhook/0123abcd?x=1 failed"),
+            format!("POST https://ha.local/api/webhook/{}?x=1 failed", REDACTED)
+        );
+        assert_eq!(
+            redact(r#"{"webhook_id":"0123abcd","secret": "s3cr3t"}"#),
+            format!(r#"{{"webhook_id":"{0}","secret": "{0}"}}"#, REDACTED)
+        );
+        assert_eq!(
+            redact("Authorization: Bearer abc.def, retrying"),
+            format!("Authorization: Bearer {}, retrying", REDACTED)
+        );
+    }
+
+    #[test]
+    fn masks_url_credentials() {
+        assert_eq!(
+            mask_credentials("http://user:p@ss@proxy.lan:3128/path?a=b@c"),
+            format!("http://{}@proxy.lan:3128/path?a=b@c", REDACTED)
+        );
+        assert_eq!(
+            mask_credentials("socks5://bob@proxy"),
+            format!("socks5://{}@proxy", REDACTED)
+        );
+        assert_eq!(mask_credentials("proxy.lan:3128"), "proxy.lan:3128");
+        assert_eq!(
+            mask_credentials("http://proxy.lan/a@b"),
+            "http://proxy.lan/a@b"
+        );
+    }
+
+    #[test]
+    fn masks_markers_at_the_end() {
+        assert_eq!(
+            redact("url http://ha/api/webhook/0123abcd"),
+            format!("url http://ha/api/webhook/{}", REDACTED)
+        );
+        assert_eq!(redact("Bearer abc"), format!("Bearer {}", REDACTED));
+        // Nothing after the marker: nothing to mask
+        assert_eq!(mask_after("Bearer ", "Bearer ", &[' ']), "Bearer ");
+        assert_eq!(
+            mask_after("/api/webhook/a/api/webhook/b", "/api/webhook/", &['/']),
+            format!("/api/webhook/{0}/api/webhook/{0}", REDACTED)
+        );
+    }
+
+    #[test]
+    fn webhook_failures_count_up_to_the_threshold() {
+        let mut failures = WebhookFailures::default();
+        assert!(!failures.record(3));
+        assert!(!failures.record(3));
+        assert!(failures.record(3));
+        assert_eq!(failures.count(), 3);
+        // Past the threshold it stays tripped
+        assert!(failures.record(3));
+    }
+
+    #[test]
+    fn webhook_failures_reset_on_success() {
+        let mut failures = WebhookFailures::default();
+        failures.record(3);
+        failures.record(3);
+        failures.reset();
+        assert_eq!(failures.count(), 0);
+        assert!(!failures.record(3));
+        assert_eq!(failures.count(), 1);
+    }
+
+    #[test]
+    fn webhook_failures_outside_the_window_start_over() {
+        let mut failures = WebhookFailures::default();
+        failures.record(2);
+        let Some(long_ago) = Instant::now().checked_sub(WEBHOOK_GONE_WINDOW * 2) else {
+            return;
+        };
+        failures.first_at = Some(long_ago);
+        assert!(!failures.record(2));
+        assert_eq!(failures.count(), 1);
+    }
+
+    fn http_error(status: u16, message: &str) -> Box<dyn std::error::Error + Send + Sync> {
+        let status = reqwest::StatusCode::from_u16(status).unwrap();
+        Box::new(HttpError::new(status, message))
+    }
+
+    #[test]
+    fn classifies_errors_by_status_not_message() {
+        let cases = [
+            (410, "410 Gone - webhook expired", ErrorCode::WebhookGone),
+            (404, "Webhook not found", ErrorCode::WebhookGone),
+            (403, "Forbidden", ErrorCode::Auth),
+            (
+                500,
+                "Sensor update failed (500): token 401 rejected",
+                ErrorCode::Server,
+            ),
+            (
+                502,
+                "Sensor update failed (502): 410 bytes of HTML",
+                ErrorCode::Server,
+            ),
+            (
+                400,
+                "Sensor update failed (400): bad request",
+                ErrorCode::Unknown,
+            ),
+        ];
+        for (status, message, code) in cases {
+            assert_eq!(
+                ErrorCode::classify(&*http_error(status, message)),
+                code,
+                "{}",
+                status
+            );
+        }
+        // Without a status nothing is guessed from the text
+        let plain: Box<dyn std::error::Error + Send + Sync> = "410 Gone (500)".into();
+        assert_eq!(ErrorCode::classify(&*plain), ErrorCode::Unknown);
+        assert!(!is_webhook_gone(&*plain));
+        assert!(!is_webhook_gone(&*http_error(
+            502,
+            "upstream sent 410 bytes"
+        )));
+    }
+}
@@ -0,0 +1,132 @@
+//! Webhook encryption as in HA's mobile_app (`webhook_encryption` in settings.json). With the
+//! `secret` HA returns at registration, webhook bodies go out as
+//! `{"type": ..., "encrypted": true, "encrypted_data": "..."}`: the JSON sealed with libsodium's
+//! secretbox (XSalsa20-Poly1305), base64 encoded with the nonce in front. HA answers an
+//! encrypted request the same way.
+
+use base64::Engine;
+use crypto_secretbox::aead::{Aead, AeadCore, KeyInit, OsRng};
+use crypto_secretbox::{Nonce, XSalsa20Poly1305};
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 24;
+
+pub struct WebhookCipher {
+    cipher: XSalsa20Poly1305,
+}
+
+impl WebhookCipher {
+    /// Key from the registration secret: the hex of the 32 key bytes, as current HA sends it.
+    /// Any other secret is used like HA's legacy scheme does: its first 32 bytes, zero padded.
+    pub fn new(secret: &str) -> Self {
+        let key = decode_hex(secret).unwrap_or_else(|| {
+            let mut key = [0u8; KEY_LEN];
+            let bytes = secret.as_bytes();
+            let len = bytes.len().min(KEY_LEN);
+            key[..len].copy_from_slice(&bytes[..len]);
+            key
+        });
+        Self {
+            cipher: XSalsa20Poly1305::new(&key.into()),
+        }
+    }
+
+    /// Seal `plaintext` under a fresh random nonce; base64 of nonce + ciphertext
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<String, String> {
+        let nonce = XSalsa20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| "Failed to encrypt the webhook payload".to_string())?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        Ok(base64::engine::general_purpose::STANDARD.encode(sealed))
+    }
+
+    /// Open `encrypted_data` as produced by [`encrypt`](Self::encrypt) or by HA
+    pub fn decrypt(&self, encrypted_data: &str) -> Result<Vec<u8>, String> {
+        let sealed = base64::engine::general_purpose::STANDARD
+            .decode(encrypted_data.trim())
+            .map_err(|e| format!("Encrypted data is not base64: {}", e))?;
+        if sealed.len() < NONCE_LEN {
+            return Err("Encrypted data is shorter than a nonce".to_string());
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let nonce: [u8; NONCE_LEN] = nonce.try_into().map_err(|_| "Invalid nonce".to_string())?;
+        self.cipher
+            .decrypt(&Nonce::from(nonce), ciphertext)
+            .map_err(|_| "Failed to decrypt: wrong secret or tampered data".to_string())
+    }
+}
+
+/// The 32 key bytes of a 64-character hex secret; None for anything else
+fn decode_hex(secret: &str) -> Option<[u8; KEY_LEN]> {
+    let secret = secret.trim();
+    if secret.len() != KEY_LEN * 2 || !secret.is_ascii() {
+        return None;
+    }
+    let mut key = [0u8; KEY_LEN];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&secret[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEX_SECRET: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    #[test]
+    fn round_trip() {
+        let cipher = WebhookCipher::new(HEX_SECRET);
+        let sealed = cipher.encrypt(b"{\"sensors\":[]}").unwrap();
+        assert_eq!(cipher.decrypt(&sealed).unwrap(), b"{\"sensors\":[]}");
+        // A fresh nonce per message
+        assert_ne!(cipher.encrypt(b"x").unwrap(), cipher.encrypt(b"x").unwrap());
+    }
+
+    #[test]
+    fn hex_and_legacy_keys() {
+        let key: Vec<u8> = (0u8..32).collect();
+        assert_eq!(decode_hex(HEX_SECRET).map(Vec::from), Some(key));
+        assert_eq!(decode_hex("not hex"), None);
+        assert_eq!(decode_hex(&"zz".repeat(KEY_LEN)), None);
+
+        // A short secret is zero padded to the key, like HA's legacy scheme
+        let sealed = WebhookCipher::new("short").encrypt(b"data").unwrap();
+        let mut padded = [0u8; KEY_LEN];
+        padded[..5].copy_from_slice(b"short");
+        let legacy = WebhookCipher {
+            cipher: XSalsa20Poly1305::new(&padded.into()),
+        };
+        assert_eq!(legacy.decrypt(&sealed).unwrap(), b"data");
+
+        // The hex secret is decoded, not used as text
+        let sealed = WebhookCipher::new(HEX_SECRET).encrypt(b"data").unwrap();
+        let as_text = WebhookCipher::new(&HEX_SECRET[..KEY_LEN]);
+        assert!(as_text.decrypt(&sealed).is_err());
+    }
+
+    #[test]
+    fn rejects_tampered_and_short_data() {
+        let cipher = WebhookCipher::new(HEX_SECRET);
+        let mut sealed = base64::engine::general_purpose::STANDARD
+            .decode(cipher.encrypt(b"data").unwrap())
+            .unwrap();
+        *sealed.last_mut().unwrap() ^= 1;
+        let tampered = base64::engine::general_purpose::STANDARD.encode(sealed);
+        assert!(cipher.decrypt(&tampered).is_err());
+
+        let short = base64::engine::general_purpose::STANDARD.encode([0u8; NONCE_LEN - 1]);
+        assert_eq!(
+            cipher.decrypt(&short).unwrap_err(),
+            "Encrypted data is shorter than a nonce"
+        );
+        assert!(cipher.decrypt("not base64!").is_err());
+        assert!(WebhookCipher::new("other")
+            .decrypt(&cipher.encrypt(b"data").unwrap())
+            .is_err());
+    }
+}
@@ -0,0 +1,139 @@
+//! Events that trigger a sensor update right away instead of at the next interval
+//! (`immediate_updates` in settings.json): the AC adapter plugged in or out, a display
+//! attached or removed, an IP address added to or removed from an interface, and the CPU
+//! usage crossing the spike threshold. A watcher thread
+//! polls these cheap signals every few seconds and sends what changed to the update loop.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sysinfo::{Networks, System};
+use tokio::sync::mpsc;
+
+use crate::sensors::battery;
+use crate::sensors::network;
+use crate::sensors::system_info::DisplayWatcher;
+
+/// How often the watcher looks at the signals
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Shortest time between the start of two update cycles when events keep firing
+pub const MIN_EVENT_GAP: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SensorEvent {
+    AcPower,
+    Display,
+    Network,
+    CpuSpike,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImmediateUpdateConfig {
+    /// Events that trigger an update (empty = only the interval)
+    pub events: Vec<SensorEvent>,
+    /// CPU usage (%) that counts as a spike when it is crossed from below
+    pub cpu_spike_percent: f32,
+}
+
+impl Default for ImmediateUpdateConfig {
+    fn default() -> Self {
+        Self {
+            events: vec![SensorEvent::AcPower, SensorEvent::Display, SensorEvent::Network],
+            cpu_spike_percent: 90.0,
+        }
+    }
+}
+
+/// Start watching the configured events. None when there are none (or the thread can't start).
+/// `hidden_batteries` are left out of the AC power state, like in the power sensors.
+pub fn spawn_watcher(
+    config: &ImmediateUpdateConfig,
+    hidden_batteries: Vec<String>,
+) -> Option<mpsc::UnboundedReceiver<SensorEvent>> {
+    if config.events.is_empty() {
+        return None;
+    }
+    log::info!("[Events] Updating right away on {:?}", config.events);
+    let (tx, rx) = mpsc::unbounded_channel();
+    let config = config.clone();
+    let spawned = std::thread::Builder::new()
+        .name("event-watcher".into())
+        .spawn(move || watch(config, hidden_batteries, tx));
+    if let Err(e) = spawned {
+        log::error!("[Events] Failed to start the event watcher: {}", e);
+        return None;
+    }
+    Some(rx)
+}
+
+/// Poll the signals until the update loop stops listening
+fn watch(
+    config: ImmediateUpdateConfig,
+    hidden_batteries: Vec<String>,
+    tx: mpsc::UnboundedSender<SensorEvent>,
+) {
+    let wants = |event| config.events.contains(&event);
+    let mut on_ac_power = wants(SensorEvent::AcPower).then(|| battery::on_ac_power(&hidden_batteries));
+    let mut displays = wants(SensorEvent::Display).then(DisplayWatcher::new);
+    // Kept between polls; refresh_list updates the interfaces and their addresses in place
+    let mut addresses = wants(SensorEvent::Network).then(|| {
+        let networks = Networks::new_with_refreshed_list();
+        let current = sorted_addresses(&networks);
+        (networks, current)
+    });
+    let mut cpu = wants(SensorEvent::CpuSpike).then(|| {
+        let mut sys = System::new();
+        sys.refresh_cpu_usage();
+        (sys, false)
+    });
+
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        let mut fired = Vec::new();
+
+        if let Some(previous) = on_ac_power.as_mut() {
+            let current = battery::on_ac_power(&hidden_batteries);
+            if current != *previous {
+                *previous = current;
+                fired.push(SensorEvent::AcPower);
+            }
+        }
+        if displays.as_mut().is_some_and(|d| d.poll()) {
+            fired.push(SensorEvent::Display);
+        }
+        if let Some((networks, previous)) = addresses.as_mut() {
+            networks.refresh_list();
+            let current = sorted_addresses(networks);
+            if current != *previous {
+                *previous = current;
+                fired.push(SensorEvent::Network);
+            }
+        }
+        if let Some((sys, spiking)) = cpu.as_mut() {
+            sys.refresh_cpu_usage();
+            let above = sys.global_cpu_usage() >= config.cpu_spike_percent;
+            // Only the crossing counts, not every sample above the threshold
+            if above && !*spiking {
+                fired.push(SensorEvent::CpuSpike);
+            }
+            *spiking = above;
+        }
+
+        for event in fired {
+            log::debug!("[Events] {:?}", event);
+            if tx.send(event).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Interface addresses in a stable order, so a changed enumeration order isn't a change
+fn sorted_addresses(networks: &Networks) -> Vec<(String, std::net::IpAddr)> {
+    let mut addresses = network::addresses(networks);
+    addresses.sort();
+    addresses
+}
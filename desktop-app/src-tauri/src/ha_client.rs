@@ -1,9 +1,60 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::time::Duration;
 
+use crate::diagnostics;
+use crate::encryption::WebhookCipher;
 use crate::sensors::collector::SensorValue;
 
+/// Default cap on the number of items in list-valued attributes (e.g. `ip_addresses`)
+pub const DEFAULT_MAX_ATTRIBUTE_ITEMS: usize = 20;
+
+/// Maximum length (in characters) of a single string attribute value
+const MAX_ATTRIBUTE_STRING_LEN: usize = 1024;
+
+/// List attributes with one item per CPU thread. Cutting them off at `max_attribute_items`
+/// would drop cores on big CPUs, so they get [`MAX_PER_CORE_ITEMS`] (or `max_attribute_items`
+/// when that is higher).
+const PER_CORE_ATTRIBUTES: &[&str] = &["core_frequencies_mhz"];
+
+/// Cap on per-core lists: room for the largest workstation CPUs (192 threads)
+const MAX_PER_CORE_ITEMS: usize = 256;
+
+/// Default retries of a failed state update, and the wait before the first one (doubling
+/// after that)
+pub const DEFAULT_UPDATE_RETRIES: u32 = 3;
+pub const DEFAULT_UPDATE_RETRY_DELAY_SECS: u64 = 1;
+
+/// Bodies smaller than this are sent uncompressed even when compression is on
+const MIN_COMPRESS_BYTES: usize = 1024;
+
+/// How long an idle connection stays in the pool. Longer than the default update interval,
+/// so each cycle reuses the connection (and its TLS session) instead of handshaking again;
+/// HA's own keep-alive timeout (75s in aiohttp) closes it first after longer intervals.
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// TCP keep-alive probes on pooled connections, so a dead one (NAT timeout, sleep) is noticed
+const TCP_KEEPALIVE: Duration = Duration::from_secs(30);
+
+/// Header carrying our device_id so HA admins can identify companion traffic in access logs
+const DEVICE_ID_HEADER: &str = "X-HA-Companion-Device-Id";
+
+/// User-Agent sent on every request, e.g. `HA-Companion/1.0.0 (windows; x86_64)`
+fn user_agent() -> String {
+    format!(
+        "HA-Companion/{} ({}; {})",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    )
+}
+
 /// Normalize server URL: trim whitespace and strip trailing /api so we never build double /api/api/ paths.
 pub fn normalize_server_url(url: &str) -> String {
     let s = url.trim().trim_end_matches('/');
@@ -22,6 +73,15 @@ pub struct RegistrationRequest {
     pub os_name: Option<String>,
     pub os_version: Option<String>,
     pub app_version: Option<String>,
+    /// Area HA puts a newly created device in (ignored for an existing device)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggested_area: Option<String>,
+    /// Link on the device page in HA ("Visit"), e.g. a local status page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub configuration_url: Option<String>,
+    /// Ask for a `secret` to encrypt the webhook with (`webhook_encryption`)
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub supports_encryption: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,6 +89,113 @@ pub struct RegistrationResponse {
     pub success: bool,
     pub webhook_id: Option<String>,
     pub error: Option<String>,
+    /// Set by HA when a device with this device_id already exists (webhook_id is the existing one)
+    #[serde(default)]
+    pub already_registered: bool,
+    /// Features the integration announces; older integrations send none
+    #[serde(default)]
+    pub capabilities: Option<Capabilities>,
+    /// Key for webhook encryption; only sent when `supports_encryption` was asked for
+    #[serde(default)]
+    pub secret: Option<String>,
+}
+
+/// What the integration supports, from the `capabilities` in its registration response:
+/// `{"webhook_id": "...", "capabilities": {"version": "1.2.0", "batch_updates": false,
+/// "compression": true, "availability": true}}`. Every key is optional and unknown keys are
+/// ignored. Features it doesn't mention (older integrations mention none) fall back to what
+/// protocol v1 guarantees, so an unknown integration gets exactly the behavior v1 was built on.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Capabilities {
+    /// Integration version, for the log
+    pub version: Option<String>,
+    /// `update_sensor_states` accepts several sensors per request (v1: yes)
+    pub batch_updates: Option<bool>,
+    /// Gzip-compressed request bodies are accepted (v1: only when `compress_payloads` is on)
+    pub compression: Option<bool>,
+    /// An "unavailable" state marks the entity unavailable (v1: yes)
+    pub availability: Option<bool>,
+}
+
+impl Capabilities {
+    pub fn batch_updates(&self) -> bool {
+        self.batch_updates.unwrap_or(true)
+    }
+
+    /// Whether compression may be used when the user enabled it
+    pub fn compression(&self) -> bool {
+        self.compression.unwrap_or(true)
+    }
+
+    pub fn availability(&self) -> bool {
+        self.availability.unwrap_or(true)
+    }
+}
+
+impl RegistrationResponse {
+    /// HA reports the device as already registered and returned its existing webhook
+    pub fn is_existing_device(&self) -> bool {
+        self.webhook_id.is_some() && (self.already_registered || !self.success)
+    }
+}
+
+/// Version of the webhook payload schema the defaults speak: the desktop_app integration's
+/// command names and `sensor_*` fields. Forks can be targeted with `webhook_commands` and
+/// `payload_fields` without a new version.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Field names of the v1 sensor payloads that `payload_fields` may rename
+pub const PAYLOAD_FIELDS: &[&str] = &[
+    "sensor_unique_id",
+    "sensor_name",
+    "sensor_type",
+    "sensor_state",
+    "sensor_device_class",
+    "sensor_unit_of_measurement",
+    "sensor_state_class",
+    "sensor_icon",
+    "sensor_attributes",
+];
+
+/// `type` of the webhook commands, configurable for integration forks or protocol changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookCommands {
+    pub register_sensor: String,
+    pub update_sensor_states: String,
+}
+
+impl Default for WebhookCommands {
+    fn default() -> Self {
+        Self {
+            register_sensor: "register_sensor".to_string(),
+            update_sensor_states: "update_sensor_states".to_string(),
+        }
+    }
+}
+
+/// How sensor states reach HA (`transport` in settings.json)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transport {
+    /// Webhook of the desktop_app integration: one device with all sensors
+    #[default]
+    Webhook,
+    /// HA's REST states API (`POST /api/states/<entity_id>`) with the access token. Needs no
+    /// integration, but the entities belong to no device, have no unique_id (so they can't be
+    /// renamed in the UI) and are gone after an HA restart until their next update.
+    RestStates,
+}
+
+/// Outcome of [`HaClient::check_webhook`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebhookCheck {
+    Ok,
+    /// HA answered 410 or 404: the webhook is gone
+    Gone(reqwest::StatusCode),
+    /// Any other answer, or no answer at all. Says nothing about the webhook.
+    Failed(String),
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -44,6 +211,7 @@ struct SensorRegistration {
     sensor_name: String,
     sensor_type: String,
     sensor_state: serde_json::Value,
+    sensor_attributes: serde_json::Value,
     sensor_device_class: Option<String>,
     sensor_unit_of_measurement: Option<String>,
     sensor_state_class: Option<String>,
@@ -58,30 +226,530 @@ struct SensorStateUpdate {
     sensor_icon: Option<String>,
 }
 
+// Payloads as collected; `HaClient::registration_payload`/`state_update_payload` then apply
+// the unique_id prefix and attribute limits, so both commands describe a sensor the same way.
+impl From<&SensorValue> for SensorRegistration {
+    fn from(sensor: &SensorValue) -> Self {
+        Self {
+            sensor_unique_id: sensor.unique_id.clone(),
+            sensor_name: sensor.name.clone(),
+            sensor_type: sensor.sensor_type.clone(),
+            sensor_state: sensor.state.clone(),
+            sensor_attributes: serde_json::to_value(&sensor.attributes).unwrap_or_default(),
+            sensor_device_class: sensor.device_class.clone(),
+            sensor_unit_of_measurement: sensor.unit_of_measurement.clone(),
+            sensor_state_class: sensor.state_class.clone(),
+            sensor_icon: sensor.icon.clone(),
+        }
+    }
+}
+
+impl From<&SensorValue> for SensorStateUpdate {
+    fn from(sensor: &SensorValue) -> Self {
+        Self {
+            sensor_unique_id: sensor.unique_id.clone(),
+            sensor_state: sensor.state.clone(),
+            sensor_attributes: serde_json::to_value(&sensor.attributes).unwrap_or_default(),
+            sensor_icon: sensor.icon.clone(),
+        }
+    }
+}
+
 pub struct HaClient {
     client: Client,
     server_url: String,
     access_token: String,
     webhook_id: Option<String>,
+    device_id: Option<String>,
+    max_attribute_items: usize,
+    compress_payloads: bool,
+    /// Register sensors on their first update instead of all at once
+    lazy_registration: bool,
+    /// unique_ids registered with HA during this session (for lazy registration)
+    registered: std::sync::Mutex<HashSet<String>>,
+    /// "unique_id/attribute" of attributes whose truncation was logged (logged once each)
+    truncated_attributes: std::sync::Mutex<HashSet<String>>,
+    /// Source address for outgoing connections (None = chosen by the OS)
+    local_address: Option<IpAddr>,
+    /// Attributes to send per sensor (see `allowed_attributes`); sensors without an entry send all
+    attribute_allowlist: HashMap<String, Vec<String>>,
+    commands: WebhookCommands,
+    /// v1 payload field name -> name sent instead (empty = the v1 schema)
+    payload_fields: HashMap<String, String>,
+    /// Prepended to every unique_id sent to HA (empty = ids as collected)
+    unique_id_prefix: String,
+    transport: Transport,
+    /// Negotiated at registration; the v1 baseline until the integration announces more
+    capabilities: Capabilities,
+    /// Updates still to fail on purpose (`simulate_failure`, dev mode only)
+    simulated_failures: AtomicU32,
+    /// Retries of a failed state update in `update_sensors_with_retry`, and the first wait
+    update_retries: u32,
+    update_retry_delay: Duration,
+    /// Encrypts webhook bodies when registration returned a secret
+    cipher: Option<WebhookCipher>,
 }
 
-impl HaClient {
-    pub fn new(server_url: String, access_token: String, webhook_id: Option<String>) -> Self {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(30))
-            .danger_accept_invalid_certs(true) // Allow self-signed certs for local HA
-            .build()
-            .unwrap_or_default();
+/// Per-sensor outcome of an `update_sensor_states` call
+#[derive(Debug, Default)]
+struct SensorUpdateResult {
+    /// unique_ids HA doesn't know (error code `not_registered`)
+    not_registered: Vec<String>,
+    /// unique_ids whose entity is disabled in HA
+    disabled: Vec<String>,
+}
+
+/// Parse HA's response, e.g. `{"cpu_usage": {"success": true},
+/// "disk_usage_c": {"success": false, "error": {"code": "not_registered", ...}}}`.
+/// Unknown or non-JSON bodies yield an empty result.
+fn parse_update_response(body: &str) -> SensorUpdateResult {
+    let mut result = SensorUpdateResult::default();
+    let entries = match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(serde_json::Value::Object(map)) => map,
+        _ => return result,
+    };
+
+    for (unique_id, entry) in entries {
+        if entry.get("is_disabled").and_then(|v| v.as_bool()) == Some(true) {
+            result.disabled.push(unique_id.clone());
+        }
+        if entry.get("success").and_then(|v| v.as_bool()) != Some(false) {
+            continue;
+        }
+        let code = entry
+            .get("error")
+            .and_then(|e| e.get("code"))
+            .and_then(|c| c.as_str())
+            .unwrap_or("");
+        if code == "not_registered" {
+            result.not_registered.push(unique_id);
+        } else {
+            log::warn!("[HA] Sensor {} update rejected: {}", unique_id, entry);
+        }
+    }
+    result
+}
+
+/// `update_registration` data: the device name and whichever metadata is known. Unknown
+/// fields are left out, so HA keeps what it has instead of clearing it.
+fn update_registration_data(registration: &RegistrationRequest) -> serde_json::Value {
+    let mut data = serde_json::Map::new();
+    data.insert("device_name".into(), serde_json::json!(registration.device_name));
+    let optional = [
+        ("manufacturer", &registration.manufacturer),
+        ("model", &registration.model),
+        ("os_version", &registration.os_version),
+        ("app_version", &registration.app_version),
+    ];
+    for (key, value) in optional {
+        if let Some(value) = value {
+            data.insert(key.into(), serde_json::json!(value));
+        }
+    }
+    serde_json::Value::Object(data)
+}
+
+/// State as the REST states API expects it: a string, `on`/`off` for binary sensors
+fn rest_state(state: &serde_json::Value) -> String {
+    match state {
+        serde_json::Value::Null => "unknown".to_string(),
+        serde_json::Value::Bool(on) => if *on { "on" } else { "off" }.to_string(),
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Gzip a request body (HA's aiohttp server decompresses `Content-Encoding: gzip` bodies)
+fn gzip(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+/// Whether a response is an HTML page (typically a reverse proxy error page) instead of JSON
+fn is_html(content_type: &str, body: &str) -> bool {
+    if content_type.to_ascii_lowercase().contains("text/html") {
+        return true;
+    }
+    let start: String = body.trim_start().chars().take(15).collect::<String>().to_ascii_lowercase();
+    start.starts_with("<!doctype html") || start.starts_with("<html")
+}
+
+/// Error for an HTML response where HA should have answered with JSON
+fn html_error_page(status: reqwest::StatusCode) -> String {
+    format!(
+        "Server returned an HTML error page ({}) instead of JSON, likely a reverse proxy misconfiguration. \
+        Check that the Server URL is the HA base URL and that the proxy forwards /api/ to HA.",
+        status
+    )
+}
+
+/// Entry of a per-sensor setting for `unique_id`. Keys are a unique_id, a prefix ending
+/// in `*` (e.g. `disk_usage_*`) or `*` for all sensors; the most specific key wins.
+pub(crate) fn most_specific_match<'a, T>(
+    map: &'a HashMap<String, T>,
+    unique_id: &str,
+) -> Option<&'a T> {
+    if let Some(value) = map.get(unique_id) {
+        return Some(value);
+    }
+    map.iter()
+        .filter_map(|(key, value)| {
+            let prefix = key.strip_suffix('*')?;
+            unique_id.starts_with(prefix).then_some((prefix.len(), value))
+        })
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, value)| value)
+}
 
+/// Attribute names to send for a sensor (see `most_specific_match` for the keys).
+/// None means no entry matches and all attributes are sent.
+fn allowed_attributes<'a>(
+    allowlist: &'a HashMap<String, Vec<String>>,
+    unique_id: &str,
+) -> Option<&'a [String]> {
+    most_specific_match(allowlist, unique_id).map(|names| names.as_slice())
+}
+
+/// Cap oversized attribute values so a single fat attribute can't make HA reject the whole update.
+/// Lists are truncated to `max_items` entries ([`PER_CORE_ATTRIBUTES`] to [`MAX_PER_CORE_ITEMS`])
+/// and long strings are cut to `MAX_ATTRIBUTE_STRING_LEN`. Each truncated attribute is logged
+/// once; `logged` holds the "unique_id/attribute" keys already logged.
+fn limit_attributes(
+    unique_id: &str,
+    attributes: &HashMap<String, serde_json::Value>,
+    max_items: usize,
+    logged: &mut HashSet<String>,
+) -> serde_json::Value {
+    let mut limited = serde_json::Map::new();
+    for (key, value) in attributes {
+        let max_items = if PER_CORE_ATTRIBUTES.contains(&key.as_str()) {
+            max_items.max(MAX_PER_CORE_ITEMS)
+        } else {
+            max_items
+        };
+        let value = match value {
+            serde_json::Value::Array(items) if items.len() > max_items => {
+                if logged.insert(format!("{}/{}", unique_id, key)) {
+                    log::warn!(
+                        "[HA] Truncating attribute '{}' of {} from {} to {} items",
+                        key, unique_id, items.len(), max_items
+                    );
+                }
+                serde_json::Value::Array(items.iter().take(max_items).cloned().collect())
+            }
+            serde_json::Value::String(s) if s.chars().count() > MAX_ATTRIBUTE_STRING_LEN => {
+                if logged.insert(format!("{}/{}", unique_id, key)) {
+                    log::warn!(
+                        "[HA] Truncating attribute '{}' of {} to {} characters",
+                        key, unique_id, MAX_ATTRIBUTE_STRING_LEN
+                    );
+                }
+                serde_json::Value::String(s.chars().take(MAX_ATTRIBUTE_STRING_LEN).collect())
+            }
+            other => other.clone(),
+        };
+        limited.insert(key.clone(), value);
+    }
+    serde_json::Value::Object(limited)
+}
+
+/// HA answered with an error status. Callers decide on `status`; the message is for the
+/// user and the log.
+#[derive(Debug)]
+pub struct HttpError {
+    pub status: reqwest::StatusCode,
+    message: String,
+}
+
+impl HttpError {
+    pub fn new(status: reqwest::StatusCode, message: impl Into<String>) -> Self {
         Self {
-            client,
+            status,
+            message: message.into(),
+        }
+    }
+
+    /// Status of an error returned by `HaClient`; None for network and other errors
+    pub fn status_of(
+        error: &(dyn std::error::Error + Send + Sync + 'static),
+    ) -> Option<reqwest::StatusCode> {
+        error.downcast_ref::<Self>().map(|e| e.status)
+    }
+}
+
+impl std::fmt::Display for HttpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+/// Network errors and 5xx responses are worth retrying; auth/404/410 are not.
+pub fn is_transient(e: &(dyn std::error::Error + Send + Sync + 'static)) -> bool {
+    if let Some(re) = e.downcast_ref::<reqwest::Error>() {
+        return re.is_connect() || re.is_timeout() || re.is_request();
+    }
+    HttpError::status_of(e).is_some_and(|status| status.is_server_error())
+}
+
+/// [`HaClient::update_sensors`], retrying network errors and 5xx responses with exponential
+/// backoff (the retry delay, then doubling, each wait capped at `max_delay`). 404/410 and
+/// other permanent errors are returned right away. The client is only locked during each
+/// attempt, so UI commands and other tasks get it between the waits.
+pub async fn update_sensors_with_retry(
+    client: &tokio::sync::Mutex<HaClient>,
+    sensors: &[SensorValue],
+    max_delay: Duration,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut retry = 0;
+    loop {
+        let guard = client.lock().await;
+        let (retries, first_delay) = (guard.update_retries, guard.update_retry_delay);
+        let result = guard.update_sensors(sensors).await;
+        drop(guard);
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) if retry < retries && is_transient(&*e) => {
+                let delay = first_delay.saturating_mul(1 << retry.min(16)).min(max_delay);
+                retry += 1;
+                log::warn!(
+                    "[HA] Sensor update failed, retry {}/{} in {}s: {}",
+                    retry,
+                    retries,
+                    delay.as_secs(),
+                    e
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// HTTP client for HA, optionally bound to a local source address. Built once and only
+/// rebuilt when the source address changes, so connections are reused between cycles.
+/// HTTP/2 is offered through ALPN on https: HA itself only speaks HTTP/1.1, but a reverse
+/// proxy in front of it may accept h2. No prior knowledge, which would break plain HA.
+fn build_client(local_address: Option<IpAddr>) -> Result<Client, String> {
+    Client::builder()
+        .timeout(Duration::from_secs(30))
+        .user_agent(user_agent())
+        .danger_accept_invalid_certs(true) // Allow self-signed certs for local HA
+        .local_address(local_address)
+        .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+        .tcp_keepalive(TCP_KEEPALIVE)
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {}", e))
+}
+
+impl HaClient {
+    pub fn new(
+        server_url: String,
+        access_token: String,
+        webhook_id: Option<String>,
+    ) -> Result<Self, String> {
+        diagnostics::register_secret(&access_token);
+        if let Some(ref id) = webhook_id {
+            diagnostics::register_secret(id);
+        }
+        Ok(Self {
+            client: build_client(None)?,
             server_url: normalize_server_url(&server_url),
             access_token: access_token.trim().to_string(),
             webhook_id,
+            device_id: None,
+            max_attribute_items: DEFAULT_MAX_ATTRIBUTE_ITEMS,
+            compress_payloads: false,
+            lazy_registration: false,
+            registered: std::sync::Mutex::new(HashSet::new()),
+            truncated_attributes: std::sync::Mutex::new(HashSet::new()),
+            local_address: None,
+            attribute_allowlist: HashMap::new(),
+            commands: WebhookCommands::default(),
+            payload_fields: HashMap::new(),
+            unique_id_prefix: String::new(),
+            transport: Transport::default(),
+            capabilities: Capabilities::default(),
+            simulated_failures: AtomicU32::new(0),
+            update_retries: DEFAULT_UPDATE_RETRIES,
+            update_retry_delay: Duration::from_secs(DEFAULT_UPDATE_RETRY_DELAY_SECS),
+            cipher: None,
+        })
+    }
+
+    /// Send all requests from this local address; rebuilds the HTTP client when it changes.
+    /// On error the current client and address stay in place.
+    pub fn set_local_address(&mut self, local_address: Option<IpAddr>) -> Result<(), String> {
+        if self.local_address == local_address {
+            return Ok(());
         }
+        self.client = build_client(local_address)?;
+        self.local_address = local_address;
+        match local_address {
+            Some(addr) => log::info!("[HA] Binding outgoing requests to {}", addr),
+            None => log::info!("[HA] Outgoing requests use the default route"),
+        }
+        Ok(())
+    }
+
+    /// Set the device_id sent in the identifying header on every request
+    pub fn set_device_id(&mut self, device_id: String) {
+        self.device_id = Some(device_id);
+    }
+
+    /// Set the maximum number of items sent for list-valued attributes
+    pub fn set_max_attribute_items(&mut self, max_items: usize) {
+        self.max_attribute_items = max_items;
+    }
+
+    /// Gzip-compress large sensor update bodies (`Content-Encoding: gzip`)
+    pub fn set_compress_payloads(&mut self, enabled: bool) {
+        self.compress_payloads = enabled;
+    }
+
+    /// Only send the listed attributes for matching sensors (smaller payloads, less recorder data)
+    pub fn set_attribute_allowlist(&mut self, allowlist: HashMap<String, Vec<String>>) {
+        self.attribute_allowlist = allowlist;
+    }
+
+    /// Use these webhook command names instead of the desktop_app integration's defaults
+    pub fn set_webhook_commands(&mut self, commands: WebhookCommands) {
+        self.commands = commands;
+    }
+
+    /// Rename fields of the sensor payloads for an integration fork, e.g.
+    /// `{"sensor_unique_id": "unique_id"}`. Names outside [`PAYLOAD_FIELDS`] are ignored.
+    pub fn set_payload_fields(&mut self, fields: HashMap<String, String>) {
+        self.payload_fields = fields
+            .into_iter()
+            .filter(|(from, to)| {
+                let known = PAYLOAD_FIELDS.contains(&from.as_str());
+                if !known {
+                    log::warn!(
+                        "[HA] Ignoring payload_fields entry '{}': not a v{} field",
+                        from,
+                        PROTOCOL_VERSION
+                    );
+                }
+                known && !to.trim().is_empty()
+            })
+            .collect();
+        if !self.payload_fields.is_empty() {
+            log::info!(
+                "[HA] Renaming v{} payload fields: {:?}",
+                PROTOCOL_VERSION,
+                self.payload_fields
+            );
+        }
+    }
+
+    /// A sensor payload in the configured schema: v1 with the `payload_fields` renames applied
+    fn with_schema(&self, payload: impl Serialize) -> serde_json::Result<serde_json::Value> {
+        let value = serde_json::to_value(payload)?;
+        if self.payload_fields.is_empty() {
+            return Ok(value);
+        }
+        Ok(match value {
+            serde_json::Value::Object(fields) => serde_json::Value::Object(
+                fields
+                    .into_iter()
+                    .map(|(key, value)| match self.payload_fields.get(&key) {
+                        Some(renamed) => (renamed.clone(), value),
+                        None => (key, value),
+                    })
+                    .collect(),
+            ),
+            other => other,
+        })
+    }
+
+    /// Prefix every unique_id sent to HA, so machines sharing a config can't collide.
+    /// Only the wire format changes; everything else keeps using the collected ids.
+    pub fn set_unique_id_prefix(&mut self, prefix: String) {
+        self.unique_id_prefix = prefix;
+    }
+
+    /// unique_id as sent to HA
+    fn ha_unique_id(&self, unique_id: &str) -> String {
+        format!("{}{}", self.unique_id_prefix, unique_id)
+    }
+
+    /// Send states through the integration's webhook or HA's REST states API
+    pub fn set_transport(&mut self, transport: Transport) {
+        self.transport = transport;
+    }
+
+    pub fn transport(&self) -> Transport {
+        self.transport
+    }
+
+    /// Entity id used with the REST states API, e.g. `sensor.desktop_cpu_usage`
+    fn rest_entity_id(&self, sensor: &SensorValue) -> String {
+        let domain = if sensor.sensor_type == "binary_sensor" {
+            "binary_sensor"
+        } else {
+            "sensor"
+        };
+        let object_id: String = self
+            .ha_unique_id(&sensor.unique_id)
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        format!("{}.desktop_{}", domain, object_id)
+    }
+
+    /// Make the next `count` sensor updates fail without sending anything
+    pub fn simulate_failures(&self, count: u32) {
+        self.simulated_failures.store(count, Ordering::SeqCst);
+    }
+
+    /// Adapt to what the integration announced at registration
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.capabilities = capabilities;
+    }
+
+    /// How often a failed state update is retried, and the wait before the first retry
+    pub fn set_update_retry(&mut self, retries: u32, delay_secs: u64) {
+        self.update_retries = retries;
+        self.update_retry_delay = Duration::from_secs(delay_secs);
+    }
+
+    /// Encrypt webhook requests with the secret from registration (None = plain JSON)
+    pub fn set_webhook_secret(&mut self, secret: Option<&str>) {
+        if let Some(secret) = secret {
+            diagnostics::register_secret(secret);
+        }
+        self.cipher = secret.map(WebhookCipher::new);
+    }
+
+    /// Register sensors the first time they are updated instead of up front
+    pub fn set_lazy_registration(&mut self, enabled: bool) {
+        self.lazy_registration = enabled;
+    }
+
+    pub fn is_lazy_registration(&self) -> bool {
+        self.lazy_registration
+    }
+
+    /// Remember sensors that HA already knows, so lazy registration skips them
+    pub fn mark_registered<I: IntoIterator<Item = String>>(&self, unique_ids: I) {
+        if let Ok(mut registered) = self.registered.lock() {
+            registered.extend(unique_ids);
+        }
+    }
+
+    fn is_registered_this_session(&self, unique_id: &str) -> bool {
+        self.registered
+            .lock()
+            .map(|r| r.contains(unique_id))
+            .unwrap_or(false)
     }
 
     pub fn update_config(&mut self, server_url: String, access_token: String) {
+        diagnostics::register_secret(&access_token);
         self.server_url = normalize_server_url(&server_url);
         self.access_token = access_token.trim().to_string();
     }
@@ -91,12 +759,21 @@ impl HaClient {
         self.server_url.trim_end_matches('/')
     }
 
+    /// Start a request with the identifying headers applied
+    fn request(&self, method: reqwest::Method, url: &str) -> reqwest::RequestBuilder {
+        let builder = self.client.request(method, url);
+        match self.device_id {
+            Some(ref id) => builder.header(DEVICE_ID_HEADER, id),
+            None => builder,
+        }
+    }
+
     /// Check if the Desktop App integration is reachable (GET /api/desktop_app/ping, no auth).
     /// Returns Ok(()) if reachable, Err with message if 404 or connection failed.
     pub async fn check_integration_reachable(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let url = format!("{}/api/desktop_app/ping", self.base_url());
         log::info!("[HA] GET {}", url);
-        let response = self.client.get(&url).send().await.map_err(|e| {
+        let response = self.request(reqwest::Method::GET, &url).send().await.map_err(|e| {
             log::error!("[HA] Ping request failed (connection/network): {}", e);
             e
         })?;
@@ -107,18 +784,71 @@ impl HaClient {
                 Install the integration in HA, restart HA, and ensure the server URL is correct (base URL without /api). \
                 If using a reverse proxy, ensure /api/ is forwarded to Home Assistant.";
             log::error!("[HA] Ping failed: {} - URL was: {}", msg, url);
-            return Err(msg.into());
+            return Err(HttpError::new(status, msg).into());
+        }
+        if !status.is_success() {
+            let err = format!("Server returned {} for {}", status, url);
+            log::error!("[HA] {}", err);
+            return Err(HttpError::new(status, err).into());
+        }
+        Ok(())
+    }
+
+    /// The client IP Home Assistant saw for our request, if the integration echoes it
+    /// (`client_ip` in the GET /api/desktop_app/ping response). None when it doesn't.
+    pub async fn seen_client_ip(&self) -> Option<String> {
+        let url = format!("{}/api/desktop_app/ping", self.base_url());
+        let response = match self.request(reqwest::Method::GET, &url).send().await {
+            Ok(r) if r.status().is_success() => r,
+            Ok(r) => {
+                log::warn!("[HA] Client IP check: server returned {}", r.status());
+                return None;
+            }
+            Err(e) => {
+                log::warn!("[HA] Client IP check failed: {}", e);
+                return None;
+            }
+        };
+        // Older integrations answer the ping without a body
+        let body: serde_json::Value = response.json().await.ok()?;
+        body.get("client_ip")
+            .and_then(|v| v.as_str())
+            .map(|ip| ip.trim().to_string())
+            .filter(|ip| !ip.is_empty())
+    }
+
+    /// Check that the REST API accepts the access token (GET /api/), for the states transport
+    pub async fn check_states_api(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let url = format!("{}/api/", self.base_url());
+        log::info!("[HA] GET {}", url);
+        let response = self
+            .request(reqwest::Method::GET, &url)
+            .header("Authorization", format!("Bearer {}", self.access_token.trim()))
+            .send()
+            .await?;
+        let status = response.status();
+        if status.as_u16() == 401 {
+            return Err(HttpError::new(
+                status,
+                "401 Unauthorized: Invalid or expired access token.",
+            )
+            .into());
         }
-        if !response.status().is_success() {
-            let err = format!("Server returned {} for {}", response.status(), url);
+        if !status.is_success() {
+            let err = format!("Server returned {} for {}", status, url);
             log::error!("[HA] {}", err);
-            return Err(err.into());
+            return Err(HttpError::new(status, err).into());
         }
         Ok(())
     }
 
     pub fn set_webhook_id(&mut self, webhook_id: String) {
+        diagnostics::register_secret(&webhook_id);
         self.webhook_id = Some(webhook_id);
+        // New webhook: nothing is registered under it yet
+        if let Ok(mut registered) = self.registered.lock() {
+            registered.clear();
+        }
     }
 
     pub fn webhook_id(&self) -> Option<&str> {
@@ -134,8 +864,7 @@ impl HaClient {
         log::info!("[HA] POST {}", url);
 
         let response = self
-            .client
-            .post(&url)
+            .request(reqwest::Method::POST, &url)
             .header("Authorization", format!("Bearer {}", self.access_token.trim()))
             .header("Content-Type", "application/json")
             .json(registration)
@@ -143,6 +872,12 @@ impl HaClient {
             .await?;
 
         let status = response.status();
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
         let body = response.text().await.unwrap_or_default();
         log::info!("[HA] registration response: {} body_len={}", status, body.len());
         if !body.is_empty() && body.len() <= 500 {
@@ -152,33 +887,109 @@ impl HaClient {
             if status.as_u16() == 404 {
                 let msg = "404 Not Found: Desktop App integration not loaded or URL not reachable.";
                 log::error!("[HA] Registration {} - URL: {} body: {}", msg, url, body);
-                return Err(
+                return Err(HttpError::new(
+                    status,
                     "404 Not Found: Desktop App integration not loaded or URL not reachable. \
-                    Check: (1) Integration installed in HA and HA restarted, (2) Server URL is the HA base URL without /api, (3) Reverse proxy forwards /api/ to HA."
-                        .into(),
-                );
+                    Check: (1) Integration installed in HA and HA restarted, (2) Server URL is the HA base URL without /api, (3) Reverse proxy forwards /api/ to HA.",
+                )
+                .into());
             }
             if status.as_u16() == 401 {
                 log::error!("[HA] Registration 401 Unauthorized - URL: {}", url);
-                return Err("401 Unauthorized: Invalid or expired access token.".into());
+                return Err(HttpError::new(
+                    status,
+                    "401 Unauthorized: Invalid or expired access token.",
+                )
+                .into());
+            }
+            if status.as_u16() == 409 {
+                // Device already exists: the body carries the existing webhook_id
+                if let Ok(existing) = serde_json::from_str::<RegistrationResponse>(&body) {
+                    if existing.webhook_id.is_some() {
+                        log::warn!("[HA] Registration 409: device already registered in HA");
+                        return Ok(RegistrationResponse {
+                            already_registered: true,
+                            ..existing
+                        });
+                    }
+                }
             }
             log::error!("[HA] Registration failed {} - URL: {} body: {}", status, url, body);
-            return Err(format!("Registration failed ({}): {}", status, body).into());
+            if is_html(&content_type, &body) {
+                return Err(HttpError::new(status, html_error_page(status)).into());
+            }
+            return Err(HttpError::new(
+                status,
+                format!("Registration failed ({}): {}", status, body),
+            )
+            .into());
         }
 
         let result: RegistrationResponse = serde_json::from_str(&body).map_err(|e| {
-            let err = format!("Invalid JSON response: {} body: {}", e, body);
-            log::error!("[HA] {}", err);
+            let err = if is_html(&content_type, &body) {
+                html_error_page(status)
+            } else {
+                format!("Invalid JSON response ({}): {} body: {}", status, e, body)
+            };
+            log::error!("[HA] {} - URL: {}", err, url);
             err
         })?;
         Ok(result)
     }
 
+    /// Update the metadata of the registered device (name, model, versions) through the
+    /// webhook (`update_registration`), without registering again
+    pub async fn update_registration(
+        &self,
+        registration: &RegistrationRequest,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let webhook_id = self
+            .webhook_id
+            .as_ref()
+            .ok_or("No webhook_id configured")?;
+
+        let url = format!("{}/api/webhook/{}", self.base_url(), webhook_id);
+
+        let payload = WebhookPayload {
+            command_type: "update_registration".to_string(),
+            data: update_registration_data(registration),
+        };
+
+        let response = self
+            .request(reqwest::Method::POST, &url)
+            .header("Content-Type", "application/json")
+            .body(self.webhook_body(&payload)?)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if status.as_u16() == 410 {
+            log::error!("[HA] Registration update 410 Gone - webhook expired");
+            return Err(HttpError::new(status, "410 Gone - webhook expired").into());
+        }
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            log::error!("[HA] Registration update failed {} - URL: {} body: {}", status, url, body);
+            return Err(HttpError::new(
+                status,
+                format!("Registration update failed ({}): {}", status, body),
+            )
+            .into());
+        }
+        Ok(())
+    }
+
     /// Register a single sensor with HA
     pub async fn register_sensor(
         &self,
         sensor: &SensorValue,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if self.transport == Transport::RestStates {
+            // The states API creates the entity with its first state
+            self.mark_registered([sensor.unique_id.clone()]);
+            return Ok(());
+        }
+
         let webhook_id = self
             .webhook_id
             .as_ref()
@@ -187,38 +998,33 @@ impl HaClient {
         let url = format!("{}/api/webhook/{}", self.base_url(), webhook_id);
 
         let payload = WebhookPayload {
-            command_type: "register_sensor".to_string(),
-            data: serde_json::to_value(SensorRegistration {
-                sensor_unique_id: sensor.unique_id.clone(),
-                sensor_name: sensor.name.clone(),
-                sensor_type: sensor.sensor_type.clone(),
-                sensor_state: sensor.state.clone(),
-                sensor_device_class: sensor.device_class.clone(),
-                sensor_unit_of_measurement: sensor.unit_of_measurement.clone(),
-                sensor_state_class: sensor.state_class.clone(),
-                sensor_icon: sensor.icon.clone(),
-            })?,
+            command_type: self.commands.register_sensor.clone(),
+            data: self.with_schema(self.registration_payload(sensor))?,
         };
 
         let response = self
-            .client
-            .post(&url)
+            .request(reqwest::Method::POST, &url)
             .header("Content-Type", "application/json")
-            .json(&payload)
+            .body(self.webhook_body(&payload)?)
             .send()
             .await?;
 
         let status = response.status();
         if status.as_u16() == 410 {
             log::error!("[HA] Sensor registration 410 Gone - webhook expired");
-            return Err("410 Gone - webhook expired".into());
+            return Err(HttpError::new(status, "410 Gone - webhook expired").into());
         }
         if !status.is_success() {
             let body = response.text().await.unwrap_or_default();
             log::error!("[HA] Sensor registration failed {} - URL: {} body: {}", status, url, body);
-            return Err(format!("Sensor registration failed ({}): {}", status, body).into());
+            return Err(HttpError::new(
+                status,
+                format!("Sensor registration failed ({}): {}", status, body),
+            )
+            .into());
         }
 
+        self.mark_registered([sensor.unique_id.clone()]);
         Ok(())
     }
 
@@ -233,15 +1039,128 @@ impl HaClient {
         Ok(())
     }
 
-    /// Batch update sensor states
+    /// Batch update sensor states. Sensors HA reports as not registered (it can forget
+    /// entities without expiring the webhook) are re-registered and sent once more.
     pub async fn update_sensors(
         &self,
         sensors: &[SensorValue],
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        if sensors.is_empty() {
+        let simulated = self
+            .simulated_failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1));
+        if let Ok(left) = simulated {
+            log::warn!("[HA] Simulated update failure ({} more to go)", left - 1);
+            return Err(HttpError::new(
+                reqwest::StatusCode::SERVICE_UNAVAILABLE,
+                "Sensor update failed (503): simulated by simulate_failure",
+            )
+            .into());
+        }
+
+        if self.lazy_registration {
+            let new_sensors: Vec<SensorValue> = sensors
+                .iter()
+                .filter(|s| !self.is_registered_this_session(&s.unique_id))
+                .cloned()
+                .collect();
+            if !new_sensors.is_empty() {
+                log::info!("[HA] Lazily registering {} new sensor(s)", new_sensors.len());
+                self.register_sensors(&new_sensors).await?;
+            }
+        }
+
+        let result = self.send_sensor_states(sensors).await?;
+
+        if !result.disabled.is_empty() {
+            log::debug!("[HA] Sensors disabled in HA: {}", result.disabled.join(", "));
+        }
+        if result.not_registered.is_empty() {
             return Ok(());
         }
 
+        log::warn!(
+            "[HA] HA reports {} sensor(s) as not registered, re-registering: {}",
+            result.not_registered.len(),
+            result.not_registered.join(", ")
+        );
+        let missing: Vec<SensorValue> = sensors
+            .iter()
+            .filter(|s| result.not_registered.contains(&s.unique_id))
+            .cloned()
+            .collect();
+        self.register_sensors(&missing).await?;
+        self.send_sensor_states(&missing).await?;
+        Ok(())
+    }
+
+    /// Report the given sensors as "unavailable", which HA shows as the device being offline.
+    /// Attributes are dropped; the next regular update restores them.
+    pub async fn mark_unavailable(
+        &self,
+        sensors: &[SensorValue],
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if !self.capabilities.availability() {
+            return Err("The integration doesn't support unavailable states".into());
+        }
+        let unavailable: Vec<SensorValue> = sensors
+            .iter()
+            .map(|s| SensorValue {
+                state: serde_json::json!("unavailable"),
+                attributes: HashMap::new(),
+                ..s.clone()
+            })
+            .collect();
+        self.send_sensor_states(&unavailable).await?;
+        Ok(())
+    }
+
+    /// Send the states and collect HA's per-sensor result: in one request, or one request
+    /// per sensor when the integration announced `"batch_updates": false` (see
+    /// [`Capabilities`]). One failing sensor doesn't keep the others from being sent; the
+    /// first error is returned once all were tried, unless the webhook is gone.
+    async fn send_sensor_states(
+        &self,
+        sensors: &[SensorValue],
+    ) -> Result<SensorUpdateResult, Box<dyn std::error::Error + Send + Sync>> {
+        if self.transport == Transport::RestStates || self.capabilities.batch_updates() {
+            return self.send_sensor_batch(sensors).await;
+        }
+        let mut result = SensorUpdateResult::default();
+        let mut errors = Vec::new();
+        for sensor in sensors {
+            match self.send_sensor_batch(std::slice::from_ref(sensor)).await {
+                Ok(single) => {
+                    result.not_registered.extend(single.not_registered);
+                    result.disabled.extend(single.disabled);
+                }
+                // The rest would fail the same way
+                Err(e) if diagnostics::is_webhook_gone(&*e) => return Err(e),
+                Err(e) => {
+                    log::warn!("[HA] Update of {} failed: {}", sensor.unique_id, e);
+                    errors.push(e);
+                }
+            }
+        }
+        if errors.is_empty() {
+            return Ok(result);
+        }
+        log::error!("[HA] {} of {} sensor updates failed", errors.len(), sensors.len());
+        Err(errors.swap_remove(0))
+    }
+
+    /// Send one `update_sensor_states` request and parse HA's per-sensor result
+    async fn send_sensor_batch(
+        &self,
+        sensors: &[SensorValue],
+    ) -> Result<SensorUpdateResult, Box<dyn std::error::Error + Send + Sync>> {
+        if sensors.is_empty() {
+            return Ok(SensorUpdateResult::default());
+        }
+        if self.transport == Transport::RestStates {
+            self.post_states(sensors).await?;
+            return Ok(SensorUpdateResult::default());
+        }
+
         let webhook_id = self
             .webhook_id
             .as_ref()
@@ -249,76 +1168,684 @@ impl HaClient {
 
         let url = format!("{}/api/webhook/{}", self.base_url(), webhook_id);
 
-        let sensor_updates: Vec<SensorStateUpdate> = sensors
+        let sensor_updates = sensors
             .iter()
-            .map(|s| SensorStateUpdate {
-                sensor_unique_id: s.unique_id.clone(),
-                sensor_state: s.state.clone(),
-                sensor_attributes: serde_json::to_value(&s.attributes).unwrap_or_default(),
-                sensor_icon: s.icon.clone(),
-            })
-            .collect();
+            .map(|s| self.with_schema(self.state_update_payload(s)))
+            .collect::<serde_json::Result<Vec<_>>>()?;
 
         let payload = WebhookPayload {
-            command_type: "update_sensor_states".to_string(),
+            command_type: self.commands.update_sensor_states.clone(),
             data: serde_json::json!({
                 "sensors": sensor_updates
             }),
         };
 
-        let response = self
-            .client
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .json(&payload)
-            .send()
-            .await?;
+        let body = self.webhook_body(&payload)?;
+        let mut builder = self
+            .request(reqwest::Method::POST, &url)
+            .header("Content-Type", "application/json");
+        let compress = self.compress_payloads && self.capabilities.compression();
+        builder = if compress && body.len() >= MIN_COMPRESS_BYTES {
+            builder.header("Content-Encoding", "gzip").body(gzip(&body)?)
+        } else {
+            builder.body(body)
+        };
+        let response = builder.send().await?;
 
         let status = response.status();
         if status.as_u16() == 410 {
             log::error!("[HA] Sensor update 410 Gone - webhook expired, URL: {}", url);
-            return Err("410 Gone - webhook expired".into());
+            return Err(HttpError::new(status, "410 Gone - webhook expired").into());
         }
         if status.as_u16() == 404 {
             log::error!("[HA] Sensor update 404 - webhook not found, URL: {}", url);
-            return Err(
-                "404 Not Found: Webhook not found. Device may not be registered yet, or the Desktop App integration was removed/restarted. Try re-registering in the app.".into(),
-            );
+            return Err(HttpError::new(
+                status,
+                "404 Not Found: Webhook not found. Device may not be registered yet, or the Desktop App integration was removed/restarted. Try re-registering in the app.",
+            )
+            .into());
         }
         if !status.is_success() {
             let body = response.text().await.unwrap_or_default();
             log::error!("[HA] Sensor update failed {} - URL: {} body: {}", status, url, body);
-            return Err(format!("Sensor update failed ({}): {}", status, body).into());
+            return Err(HttpError::new(
+                status,
+                format!("Sensor update failed ({}): {}", status, body),
+            )
+            .into());
         }
 
+        let body = self.webhook_response(response.text().await.unwrap_or_default());
+        // HA answers with the ids as sent; map them back to the collected ids
+        let mut result = parse_update_response(&body);
+        if !self.unique_id_prefix.is_empty() {
+            for ids in [&mut result.not_registered, &mut result.disabled] {
+                for id in ids.iter_mut() {
+                    if let Some(stripped) = id.strip_prefix(&self.unique_id_prefix) {
+                        *id = stripped.to_string();
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// JSON body of a webhook request, encrypted when registration returned a secret
+    fn webhook_body(
+        &self,
+        payload: &WebhookPayload,
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(cipher) = &self.cipher else {
+            return Ok(serde_json::to_vec(payload)?);
+        };
+        let encrypted = serde_json::json!({
+            "type": payload.command_type,
+            "encrypted": true,
+            "encrypted_data": cipher.encrypt(&serde_json::to_vec(&payload.data)?)?,
+        });
+        Ok(serde_json::to_vec(&encrypted)?)
+    }
+
+    /// A webhook response body, decrypted when HA encrypted it. Bodies that can't be
+    /// decrypted are returned as they are.
+    fn webhook_response(&self, body: String) -> String {
+        let Some(cipher) = &self.cipher else {
+            return body;
+        };
+        let Ok(value) = serde_json::from_str::<serde_json::Value>(&body) else {
+            return body;
+        };
+        let Some(data) = value
+            .get("encrypted_data")
+            .and_then(|d| d.as_str())
+            .filter(|_| value.get("encrypted").and_then(|e| e.as_bool()) == Some(true))
+        else {
+            return body;
+        };
+        match cipher.decrypt(data) {
+            Ok(plain) => String::from_utf8_lossy(&plain).into_owned(),
+            Err(e) => {
+                log::warn!("[HA] Could not decrypt the webhook response: {}", e);
+                body
+            }
+        }
+    }
+
+    /// `register_sensor` data for a sensor, with the unique_id as HA knows it and the same
+    /// attributes a state update would carry
+    fn registration_payload(&self, sensor: &SensorValue) -> SensorRegistration {
+        SensorRegistration {
+            sensor_unique_id: self.ha_unique_id(&sensor.unique_id),
+            sensor_attributes: self.state_attributes(sensor),
+            ..SensorRegistration::from(sensor)
+        }
+    }
+
+    /// `update_sensor_states` entry for a sensor, with the unique_id as HA knows it and only
+    /// the attributes that may be sent
+    fn state_update_payload(&self, sensor: &SensorValue) -> SensorStateUpdate {
+        SensorStateUpdate {
+            sensor_unique_id: self.ha_unique_id(&sensor.unique_id),
+            sensor_attributes: self.state_attributes(sensor),
+            ..SensorStateUpdate::from(sensor)
+        }
+    }
+
+    /// Attributes to send with a state: the allowlisted ones, with oversized values capped
+    fn state_attributes(&self, sensor: &SensorValue) -> serde_json::Value {
+        let attributes = match allowed_attributes(&self.attribute_allowlist, &sensor.unique_id) {
+            Some(names) => sensor
+                .attributes
+                .iter()
+                .filter(|(key, _)| names.contains(key))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+            None => sensor.attributes.clone(),
+        };
+        let mut logged = self
+            .truncated_attributes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        limit_attributes(&sensor.unique_id, &attributes, self.max_attribute_items, &mut logged)
+    }
+
+    /// Set each sensor's state through the REST states API (one request per sensor). The
+    /// metadata the webhook registers once goes along as attributes with every state.
+    async fn post_states(
+        &self,
+        sensors: &[SensorValue],
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        for sensor in sensors {
+            let mut attributes = self.state_attributes(sensor);
+            if let Some(map) = attributes.as_object_mut() {
+                map.insert("friendly_name".into(), serde_json::json!(sensor.name));
+                let metadata = [
+                    ("unit_of_measurement", &sensor.unit_of_measurement),
+                    ("device_class", &sensor.device_class),
+                    ("state_class", &sensor.state_class),
+                    ("icon", &sensor.icon),
+                ];
+                for (key, value) in metadata {
+                    if let Some(value) = value {
+                        map.insert(key.into(), serde_json::json!(value));
+                    }
+                }
+            }
+            let body = serde_json::json!({
+                "state": rest_state(&sensor.state),
+                "attributes": attributes,
+            });
+
+            let url = format!("{}/api/states/{}", self.base_url(), self.rest_entity_id(sensor));
+            let response = self
+                .request(reqwest::Method::POST, &url)
+                .header("Authorization", format!("Bearer {}", self.access_token.trim()))
+                .json(&body)
+                .send()
+                .await?;
+            let status = response.status();
+            if status.as_u16() == 401 {
+                log::error!("[HA] States API 401 Unauthorized - URL: {}", url);
+                return Err(HttpError::new(
+                    status,
+                    "401 Unauthorized: Invalid or expired access token.",
+                )
+                .into());
+            }
+            if !status.is_success() {
+                let body = response.text().await.unwrap_or_default();
+                log::error!("[HA] State update failed {} - URL: {} body: {}", status, url, body);
+                return Err(HttpError::new(
+                    status,
+                    format!("State update failed ({}): {}", status, body),
+                )
+                .into());
+            }
+        }
         Ok(())
     }
 
     /// Check if the webhook is still valid
-    pub async fn check_webhook(&self) -> bool {
+    pub async fn check_webhook(&self) -> WebhookCheck {
         let webhook_id = match &self.webhook_id {
             Some(id) => id,
-            None => return false,
+            None => return WebhookCheck::Failed("No webhook registered".to_string()),
         };
 
         let url = format!("{}/api/webhook/{}", self.base_url(), webhook_id);
 
         // Send a minimal payload to check if webhook exists
         let payload = WebhookPayload {
-            command_type: "update_sensor_states".to_string(),
+            command_type: self.commands.update_sensor_states.clone(),
             data: serde_json::json!({"sensors": []}),
         };
 
+        let body = match self.webhook_body(&payload) {
+            Ok(body) => body,
+            Err(e) => return WebhookCheck::Failed(e.to_string()),
+        };
         match self
-            .client
-            .post(&url)
+            .request(reqwest::Method::POST, &url)
             .header("Content-Type", "application/json")
-            .json(&payload)
+            .body(body)
             .send()
             .await
         {
-            Ok(response) => response.status().is_success(),
-            Err(_) => false,
+            Ok(response) if response.status().is_success() => WebhookCheck::Ok,
+            Ok(response) if diagnostics::is_webhook_gone_status(response.status()) => {
+                WebhookCheck::Gone(response.status())
+            }
+            Ok(response) => WebhookCheck::Failed(format!("Server returned {}", response.status())),
+            Err(e) => WebhookCheck::Failed(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const SECRET: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    #[test]
+    fn update_response_lists_unregistered_and_disabled_sensors() {
+        let result = parse_update_response(
+            r#"{
+                "cpu_usage": {"success": true},
+                "gpu_usage_0": {"success": true, "is_disabled": true},
+                "disk_usage_c": {"success": false, "error": {"code": "not_registered", "message": "Entity is not registered"}},
+                "memory_used": {"success": false, "error": {"code": "invalid_format"}}
+            }"#,
+        );
+        assert_eq!(result.not_registered, ["disk_usage_c"]);
+        assert_eq!(result.disabled, ["gpu_usage_0"]);
+    }
+
+    #[test]
+    fn update_response_without_errors_is_empty() {
+        let result = parse_update_response(r#"{"cpu_usage": {"success": true}}"#);
+        assert!(result.not_registered.is_empty());
+        assert!(result.disabled.is_empty());
+
+        for body in ["", "{}", "[]", "null", "<html>502</html>"] {
+            let result = parse_update_response(body);
+            assert!(
+                result.not_registered.is_empty() && result.disabled.is_empty(),
+                "{}",
+                body
+            );
+        }
+    }
+
+    const PROXY_ERROR_PAGE: &str =
+        "\n  <!DOCTYPE html>\n<html><head><title>502 Bad Gateway</title></head>\
+        <body><center><h1>502 Bad Gateway</h1></center><hr><center>nginx</center></body></html>";
+
+    #[test]
+    fn detects_html_error_pages() {
+        assert!(is_html("text/html; charset=utf-8", ""));
+        assert!(is_html("TEXT/HTML", "{}"));
+        assert!(is_html("application/octet-stream", PROXY_ERROR_PAGE));
+        assert!(is_html("", "<HTML><body>Bad Gateway</body></HTML>"));
+        assert!(!is_html("application/json", r#"{"webhook_id": "abc"}"#));
+        assert!(!is_html("", "502 Bad Gateway"));
+    }
+
+    /// Answers each request with the next `(status, body)`; returns its base URL and the
+    /// bodies of the requests it got
+    async fn mock_server(responses: Vec<(u16, &'static str)>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        tokio::spawn(async move {
+            for (status, body) in responses {
+                let Ok((mut stream, _)) = listener.accept().await else {
+                    return;
+                };
+                let request = read_request(&mut stream).await;
+                seen.lock().unwrap().push(request);
+                let response = format!(
+                    "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+                let _ = stream.shutdown().await;
+            }
+        });
+        (url, requests)
+    }
+
+    /// Body of one HTTP/1.1 request (the headers are read up to Content-Length)
+    async fn read_request(stream: &mut tokio::net::TcpStream) -> String {
+        let mut data = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = stream.read(&mut buf).await.unwrap_or(0);
+            if n == 0 {
+                break;
+            }
+            data.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&data);
+            let Some(end) = text.find("\r\n\r\n") else {
+                continue;
+            };
+            let length: usize = text[..end]
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                .and_then(|(_, value)| value.trim().parse().ok())
+                .unwrap_or(0);
+            if data.len() >= end + 4 + length {
+                return String::from_utf8_lossy(&data[end + 4..end + 4 + length]).into_owned();
+            }
+        }
+        String::new()
+    }
+
+    fn registration() -> RegistrationRequest {
+        RegistrationRequest {
+            device_id: "device".into(),
+            device_name: "Desk".into(),
+            manufacturer: None,
+            model: None,
+            os_name: None,
+            os_version: None,
+            app_version: None,
+            suggested_area: None,
+            configuration_url: None,
+            supports_encryption: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn registration_reports_proxy_error_pages() {
+        // The mock labels every body as JSON, like proxies that keep the upstream content type
+        let (url, _) = mock_server(vec![(502, PROXY_ERROR_PAGE), (200, PROXY_ERROR_PAGE)]).await;
+        let client = HaClient::new(url, "token".to_string(), None).unwrap();
+        for status in [reqwest::StatusCode::BAD_GATEWAY, reqwest::StatusCode::OK] {
+            let err = client.register_device(&registration()).await.unwrap_err();
+            assert_eq!(err.to_string(), html_error_page(status));
+        }
+    }
+
+    fn sensor(unique_id: &str) -> SensorValue {
+        SensorValue {
+            unique_id: unique_id.into(),
+            name: "CPU Usage".into(),
+            state: serde_json::json!(12),
+            sensor_type: "sensor".into(),
+            device_class: None,
+            unit_of_measurement: Some("%".into()),
+            state_class: Some("measurement".into()),
+            icon: Some("mdi:cpu-64-bit".into()),
+            attributes: HashMap::new(),
+            update_at_interval: true,
+        }
+    }
+
+    fn client() -> HaClient {
+        HaClient::new(
+            "http://127.0.0.1:1".to_string(),
+            "token".to_string(),
+            Some("webhook".to_string()),
+        )
+        .unwrap()
+    }
+
+    fn sensor_with_attributes() -> SensorValue {
+        let mut sensor = sensor("cpu_usage");
+        sensor
+            .attributes
+            .insert("cores".into(), serde_json::json!(8));
+        sensor
+    }
+
+    #[test]
+    fn registration_payload_shape() {
+        let payload =
+            serde_json::to_value(client().registration_payload(&sensor_with_attributes()));
+        assert_eq!(
+            payload.unwrap(),
+            serde_json::json!({
+                "sensor_unique_id": "cpu_usage",
+                "sensor_name": "CPU Usage",
+                "sensor_type": "sensor",
+                "sensor_state": 12,
+                "sensor_attributes": {"cores": 8},
+                "sensor_device_class": null,
+                "sensor_unit_of_measurement": "%",
+                "sensor_state_class": "measurement",
+                "sensor_icon": "mdi:cpu-64-bit"
+            })
+        );
+    }
+
+    #[test]
+    fn state_update_payload_shape() {
+        let payload =
+            serde_json::to_value(client().state_update_payload(&sensor_with_attributes()));
+        assert_eq!(
+            payload.unwrap(),
+            serde_json::json!({
+                "sensor_unique_id": "cpu_usage",
+                "sensor_state": 12,
+                "sensor_attributes": {"cores": 8},
+                "sensor_icon": "mdi:cpu-64-bit"
+            })
+        );
+    }
+
+    fn payload() -> WebhookPayload {
+        WebhookPayload {
+            command_type: "update_sensor_states".to_string(),
+            data: serde_json::json!([{"unique_id": "cpu_usage", "state": 12}]),
+        }
+    }
+
+    #[test]
+    fn webhook_body_is_plain_without_secret() {
+        let body: serde_json::Value =
+            serde_json::from_slice(&client().webhook_body(&payload()).unwrap()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "type": "update_sensor_states",
+                "data": [{"unique_id": "cpu_usage", "state": 12}],
+            })
+        );
+    }
+
+    #[test]
+    fn webhook_body_is_encrypted_with_secret() {
+        let mut client = client();
+        client.set_webhook_secret(Some(SECRET));
+        let body: serde_json::Value =
+            serde_json::from_slice(&client.webhook_body(&payload()).unwrap()).unwrap();
+        assert_eq!(body["type"], "update_sensor_states");
+        assert_eq!(body["encrypted"], true);
+        assert!(body.get("data").is_none());
+
+        let plain = WebhookCipher::new(SECRET)
+            .decrypt(body["encrypted_data"].as_str().unwrap())
+            .unwrap();
+        let data: serde_json::Value = serde_json::from_slice(&plain).unwrap();
+        assert_eq!(data, payload().data);
+    }
+
+    #[test]
+    fn webhook_response_is_decrypted() {
+        let mut client = client();
+        let plain = r#"{"cpu_usage":{"success":true}}"#;
+        let sealed = WebhookCipher::new(SECRET)
+            .encrypt(plain.as_bytes())
+            .unwrap();
+        let encrypted =
+            serde_json::json!({"encrypted": true, "encrypted_data": sealed}).to_string();
+
+        // Without a secret bodies pass through untouched
+        assert_eq!(client.webhook_response(encrypted.clone()), encrypted);
+
+        client.set_webhook_secret(Some(SECRET));
+        assert_eq!(client.webhook_response(encrypted), plain);
+        assert_eq!(client.webhook_response(plain.to_string()), plain);
+
+        // Undecryptable bodies are returned as they came
+        let wrong = serde_json::json!({"encrypted": true, "encrypted_data": "AAAA"}).to_string();
+        assert_eq!(client.webhook_response(wrong.clone()), wrong);
+    }
+
+    fn retrying_client(url: String) -> tokio::sync::Mutex<HaClient> {
+        let mut client = HaClient::new(url, "token".to_string(), Some("webhook".to_string())).unwrap();
+        client.set_update_retry(3, 0);
+        tokio::sync::Mutex::new(client)
+    }
+
+    #[tokio::test]
+    async fn retries_transient_errors_until_success() {
+        let (url, requests) = mock_server(vec![(500, "{}"), (502, "{}"), (200, "{}")]).await;
+        let client = retrying_client(url);
+        let sensors = [sensor("cpu_usage")];
+        let result = update_sensors_with_retry(&client, &sensors, Duration::ZERO);
+        assert!(result.await.is_ok());
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_configured_retries() {
+        let (url, requests) = mock_server(vec![(500, "{}"); 5]).await;
+        let client = retrying_client(url);
+        let sensors = [sensor("cpu_usage")];
+        let result = update_sensors_with_retry(&client, &sensors, Duration::ZERO);
+        let error = result.await.unwrap_err().to_string();
+        assert!(error.contains("500"), "{}", error);
+        assert_eq!(requests.lock().unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_client_errors_that_mention_a_5xx() {
+        let (url, requests) = mock_server(vec![(400, "value out of range (5 max)"); 2]).await;
+        let client = retrying_client(url);
+        let sensors = [sensor("cpu_usage")];
+        let result = update_sensors_with_retry(&client, &sensors, Duration::ZERO);
+        assert!(result.await.is_err());
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_gone_webhook() {
+        let (url, requests) = mock_server(vec![(410, ""), (200, "{}")]).await;
+        let client = retrying_client(url);
+        let sensors = [sensor("cpu_usage")];
+        let result = update_sensors_with_retry(&client, &sensors, Duration::ZERO);
+        let error = result.await.unwrap_err();
+        assert!(diagnostics::is_webhook_gone(&*error), "{}", error);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn client_is_unlocked_between_attempts() {
+        let (url, _) = mock_server(vec![(503, "{}"), (200, "{}")]).await;
+        let client = Arc::new(retrying_client(url));
+        client.lock().await.set_update_retry(1, 1);
+        let task = {
+            let client = client.clone();
+            tokio::spawn(async move {
+                update_sensors_with_retry(&client, &[sensor("cpu_usage")], Duration::from_secs(1))
+                    .await
+                    .is_ok()
+            })
+        };
+        // Within the 1s backoff the lock is free for others
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let locked = tokio::time::timeout(Duration::from_millis(100), client.lock()).await;
+        assert!(locked.is_ok());
+        drop(locked);
+        assert!(task.await.unwrap());
+    }
+
+    #[test]
+    fn update_registration_leaves_out_unknown_fields() {
+        let mut registration = registration();
+        registration.model = Some("B550".into());
+        registration.app_version = Some("1.0.0".into());
+        assert_eq!(
+            update_registration_data(&registration),
+            serde_json::json!({"device_name": "Desk", "model": "B550", "app_version": "1.0.0"})
+        );
+    }
+
+    #[test]
+    fn per_core_attributes_have_their_own_cap() {
+        let mut attributes = HashMap::new();
+        attributes.insert(
+            "core_frequencies_mhz".to_string(),
+            serde_json::json!(vec![3600; 32]),
+        );
+        attributes.insert(
+            "ip_addresses".to_string(),
+            serde_json::json!(vec!["10.0.0.1"; 32]),
+        );
+        let mut logged = HashSet::new();
+        let limited = limit_attributes(
+            "cpu_frequency",
+            &attributes,
+            DEFAULT_MAX_ATTRIBUTE_ITEMS,
+            &mut logged,
+        );
+        assert_eq!(
+            limited["core_frequencies_mhz"].as_array().unwrap().len(),
+            32
+        );
+        assert_eq!(
+            limited["ip_addresses"].as_array().unwrap().len(),
+            DEFAULT_MAX_ATTRIBUTE_ITEMS
+        );
+        assert_eq!(
+            logged,
+            HashSet::from(["cpu_frequency/ip_addresses".to_string()])
+        );
+
+        attributes.insert(
+            "core_frequencies_mhz".to_string(),
+            serde_json::json!(vec![3600; 300]),
+        );
+        let limited = limit_attributes("cpu_frequency", &attributes, 1, &mut logged);
+        assert_eq!(
+            limited["core_frequencies_mhz"].as_array().unwrap().len(),
+            MAX_PER_CORE_ITEMS
+        );
+        assert_eq!(limited["ip_addresses"].as_array().unwrap().len(), 1);
+    }
+
+    fn unbatched_client(url: String) -> HaClient {
+        let mut client = HaClient::new(url, "token".to_string(), Some("webhook".to_string())).unwrap();
+        client.set_capabilities(Capabilities {
+            batch_updates: Some(false),
+            ..Capabilities::default()
+        });
+        client
+    }
+
+    #[tokio::test]
+    async fn unbatched_updates_continue_after_a_failure() {
+        let (url, requests) = mock_server(vec![(500, "{}"), (200, "{}"), (200, "{}")]).await;
+        let client = unbatched_client(url);
+        let sensors = [
+            sensor("cpu_usage"),
+            sensor("memory_usage"),
+            sensor("disk_usage"),
+        ];
+        let error = client
+            .update_sensors(&sensors)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("500"), "{}", error);
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn unbatched_updates_stop_when_the_webhook_is_gone() {
+        let (url, requests) = mock_server(vec![(410, ""), (200, "{}")]).await;
+        let client = unbatched_client(url);
+        let sensors = [sensor("cpu_usage"), sensor("memory_usage")];
+        let error = client.update_sensors(&sensors).await.unwrap_err();
+        assert!(diagnostics::is_webhook_gone(&*error), "{}", error);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn webhook_check_only_reports_404_and_410_as_gone() {
+        let (url, _) = mock_server(vec![
+            (500, "{}"),
+            (502, "{}"),
+            (410, ""),
+            (404, ""),
+            (200, "{}"),
+        ])
+        .await;
+        let client = HaClient::new(url, "token".to_string(), Some("webhook".to_string())).unwrap();
+        // A broken HA or proxy must not make the verify loop register again
+        for _ in 0..2 {
+            assert!(matches!(
+                client.check_webhook().await,
+                WebhookCheck::Failed(_)
+            ));
         }
+        assert_eq!(
+            client.check_webhook().await,
+            WebhookCheck::Gone(reqwest::StatusCode::GONE)
+        );
+        assert_eq!(
+            client.check_webhook().await,
+            WebhookCheck::Gone(reqwest::StatusCode::NOT_FOUND)
+        );
+        assert_eq!(client.check_webhook().await, WebhookCheck::Ok);
     }
 }
@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tauri::{
     image::Image,
@@ -6,18 +9,24 @@ use tauri::{
     Manager, RunEvent, WindowEvent,
     Emitter,
 };
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, Mutex, Notify};
 
 mod commands;
+mod config_file;
+mod diagnostics;
+mod encryption;
+mod events;
 mod ha_client;
+mod oneshot;
 mod registration;
 mod sensors;
 mod settings;
 
 use commands::*;
-use ha_client::HaClient;
-use sensors::collector::SensorCollector;
-use settings::AppSettings;
+use diagnostics::{RecentErrors, WebhookFailures};
+use ha_client::{HaClient, Transport, WebhookCheck};
+use sensors::collector::{SensorCollector, SensorValue};
+use settings::{AppSettings, CloseBehavior};
 
 /// Shared application state
 pub struct AppState {
@@ -25,13 +34,213 @@ pub struct AppState {
     pub ha_client: Mutex<HaClient>,
     pub collector: Mutex<SensorCollector>,
     pub is_registered: Mutex<bool>,
+    pub recent_errors: Mutex<RecentErrors>,
+    /// Consecutive 410/404 responses from the webhook (reset on success)
+    pub webhook_failures: Mutex<WebhookFailures>,
+    /// Sensor config file as last loaded; merged over `settings` for the collector and the
+    /// update loop, never saved into the store
+    pub sensor_config: Mutex<Option<config_file::SensorConfig>>,
+    /// Wakes the update loop when settings change, so a new interval applies right away
+    pub settings_changed: Notify,
+    /// Set by `set_device_online(false)`: sensors were marked unavailable and the update loop
+    /// sends nothing until the device is set online again
+    pub device_offline: Mutex<bool>,
+    /// What the window close button does; read once at startup
+    pub close_behavior: CloseBehavior,
+    /// Started with --dev (or a debug build): enables debug-only commands like simulate_failure
+    pub dev_mode: bool,
+    /// Set while a registration runs, so a second one can't race it on webhook_id/settings
+    /// and create a duplicate device in HA
+    registering: AtomicBool,
 }
 
-pub fn run(dev_mode: bool) {
+type CollectJob = Box<dyn FnOnce() + Send>;
+
+/// Jobs for the collector thread; None until the first collection starts it
+static COLLECT_JOBS: std::sync::Mutex<Option<std::sync::mpsc::Sender<CollectJob>>> =
+    std::sync::Mutex::new(None);
+
+/// Hand `job` to the collector thread, starting it (again, after a panic ended it) if
+/// needed. False when the thread can't be started.
+fn submit_collect_job(job: CollectJob) -> bool {
+    let Ok(mut jobs) = COLLECT_JOBS.lock() else {
+        return false;
+    };
+    let job = match jobs.as_ref() {
+        Some(sender) => match sender.send(job) {
+            Ok(()) => return true,
+            Err(std::sync::mpsc::SendError(job)) => job,
+        },
+        None => job,
+    };
+    let (sender, queue) = std::sync::mpsc::channel::<CollectJob>();
+    let spawned = std::thread::Builder::new()
+        .name("collector".to_string())
+        .spawn(move || {
+            for job in queue {
+                job();
+            }
+        });
+    if let Err(e) = spawned {
+        log::error!("[Collector] Failed to start the collector thread: {}", e);
+        return false;
+    }
+    let _ = sender.send(job);
+    *jobs = Some(sender);
+    true
+}
+
+/// Clears `AppState::registering` when the registration ends, also on errors
+pub struct RegistrationGuard<'a>(&'a AtomicBool);
+
+impl Drop for RegistrationGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+impl AppState {
+    /// Claim the registration slot. None when another registration is already running;
+    /// the slot is released when the returned guard is dropped.
+    pub fn begin_registration(&self) -> Option<RegistrationGuard<'_>> {
+        self.registering
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .ok()
+            .map(|_| RegistrationGuard(&self.registering))
+    }
+
+    /// Update interval in effect: the stored one unless the config file sets it
+    pub async fn update_interval(&self) -> u64 {
+        let stored = self.settings.lock().await.update_interval;
+        match self.sensor_config.lock().await.as_ref() {
+            Some(config) => config.update_interval(stored),
+            None => stored,
+        }
+    }
+
+    /// Sensor toggles in effect: `settings`' with the config file's over them
+    pub async fn sensor_toggles(&self, settings: &AppSettings) -> HashMap<String, bool> {
+        match self.sensor_config.lock().await.as_ref() {
+            Some(config) => config.enabled_sensors(&settings.enabled_sensors),
+            None => settings.enabled_sensors.clone(),
+        }
+    }
+
+    /// Remember an error so it shows up in support bundles
+    pub async fn record_error(&self, context: &str, message: impl ToString) {
+        self.recent_errors.lock().await.push(context, message.to_string());
+    }
+
+    /// Count a 410/404 from the webhook. Only after `webhook_gone_threshold` failures in a row
+    /// is the device marked unregistered, so a brief HA restart doesn't force re-registration.
+    async fn webhook_gone(&self) {
+        let threshold = self.settings.lock().await.webhook_gone_threshold;
+        let mut failures = self.webhook_failures.lock().await;
+        if failures.record(threshold) {
+            log::warn!(
+                "Webhook gone after {} consecutive failures, need to re-register",
+                failures.count()
+            );
+            failures.reset();
+            *self.is_registered.lock().await = false;
+        } else {
+            log::warn!(
+                "Webhook not found ({}/{}), HA may be restarting; retrying next cycle",
+                failures.count(),
+                threshold
+            );
+        }
+    }
+
+    /// Register sensors that appeared between the periodic re-registrations (monitor attached,
+    /// sensor switched on) and send their states right away. Nothing while unregistered/offline.
+    pub async fn report_new_sensors(
+        &self,
+        handle: &tauri::AppHandle,
+        sensors: &[SensorValue],
+        context: &str,
+    ) {
+        let is_registered = *self.is_registered.lock().await;
+        if sensors.is_empty() || !is_registered || *self.device_offline.lock().await {
+            return;
+        }
+        let ha_client = self.ha_client.lock().await;
+        let result = match ha_client.register_sensors(sensors).await {
+            Ok(()) => ha_client.update_sensors(sensors).await,
+            Err(e) => Err(e),
+        };
+        drop(ha_client);
+        match result {
+            Ok(()) => {
+                log::info!("Registered {} new sensors", sensors.len());
+                self.collector.lock().await.record_sent(sensors);
+                let mut settings = self.settings.lock().await;
+                registration::remember_fingerprints(&mut settings, sensors);
+                if let Err(e) = settings.save(handle) {
+                    log::error!("Failed to save sensor fingerprints: {}", e);
+                }
+            }
+            Err(e) => {
+                log::error!("Failed to report new sensors: {}", e);
+                self.record_error(context, format!("New sensors: {}", e)).await;
+            }
+        }
+    }
+
+    /// Run a collection on the collector thread. WMI queries and subprocesses
+    /// (system_profiler, rocm-smi, ssh) block, and would otherwise stall an async worker
+    /// that commands from the UI also need. Always the same thread, so the thread-local
+    /// WMI connections are set up once instead of on every pool thread.
+    pub async fn collect<T, F>(self: &Arc<Self>, f: F) -> T
+    where
+        T: Default + Send + 'static,
+        F: FnOnce(&mut SensorCollector) -> T + Send + 'static,
+    {
+        let state = self.clone();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let job: CollectJob = Box::new(move || {
+            let started = std::time::Instant::now();
+            let mut collector = state.collector.blocking_lock();
+            let value = f(&mut collector);
+            log::debug!("[Collector] Collection took {:?}", started.elapsed());
+            let panics = collector.take_panics();
+            if !panics.is_empty() {
+                let mut errors = state.recent_errors.blocking_lock();
+                for panic in panics {
+                    errors.push("collector", format!("Collector panicked: {}", panic));
+                }
+            }
+            let _ = tx.send(value);
+        });
+        if !submit_collect_job(job) {
+            return T::default();
+        }
+        match rx.await {
+            Ok(value) => value,
+            // The job was dropped without an answer: it panicked
+            Err(_) => {
+                log::error!("[Collector] Collection task failed: panicked");
+                T::default()
+            }
+        }
+    }
+}
+
+/// Collect and send sensor states once without starting the UI; returns the exit code.
+/// `settings_path` overrides the default settings.json location.
+pub fn run_oneshot(settings_path: Option<std::path::PathBuf>) -> i32 {
+    oneshot::run(settings_path)
+}
+
+/// Argument the login item is registered with, so an autostart launch can be told apart
+/// from a manual one
+pub const AUTOSTART_ARG: &str = "--autostart";
+
+/// Start the app. `autostarted` = launched at login (window stays in the tray).
+pub fn run(dev_mode: bool, autostarted: bool) {
     // In dev/debug builds, init logger so log::info!/error! show in terminal
     if dev_mode || cfg!(debug_assertions) {
-        let _ = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
-            .try_init();
+        diagnostics::init_logging();
     }
 
     let app = tauri::Builder::default()
@@ -44,27 +253,90 @@ pub fn run(dev_mode: bool) {
         }))
         .plugin(tauri_plugin_autostart::init(
             tauri_plugin_autostart::MacosLauncher::LaunchAgent,
-            Some(vec![]),
+            Some(vec![AUTOSTART_ARG]),
         ))
         .plugin(tauri_plugin_store::Builder::default().build())
         .setup(move |app| {
             let handle = app.handle().clone();
 
             // Load settings
-            let app_settings = AppSettings::load(&handle);
-            let ha_client = HaClient::new(
+            let mut app_settings = AppSettings::load(&handle);
+            let migrated_gpus = app_settings.migrate_gpu_ids();
+            let migrated_batteries = app_settings.migrate_battery_ids();
+            if migrated_gpus || migrated_batteries {
+                if let Err(e) = app_settings.save(&handle) {
+                    log::error!("Failed to save migrated GPU/battery ids: {}", e);
+                }
+            }
+            let sensor_config = config_file::load_configured(&app_settings);
+            // The collector gets the file's values; the state keeps the stored ones for saving
+            let effective = config_file::effective(&app_settings, sensor_config.as_ref());
+            let mut ha_client = HaClient::new(
                 app_settings.server_url.clone(),
                 app_settings.access_token.clone(),
                 app_settings.webhook_id.clone(),
+            )?;
+            ha_client.set_device_id(app_settings.device_id.clone());
+            ha_client.set_max_attribute_items(app_settings.max_attribute_items);
+            ha_client.set_compress_payloads(app_settings.compress_payloads);
+            ha_client.set_lazy_registration(app_settings.lazy_registration);
+            // Refuse to start rather than send from an address the user didn't choose
+            if let Err(e) = app_settings
+                .source_address()
+                .and_then(|addr| ha_client.set_local_address(addr))
+            {
+                log::error!("[HA] {}", e);
+                return Err(e.into());
+            }
+            ha_client.set_attribute_allowlist(app_settings.sensor_attributes.clone());
+            ha_client.set_webhook_commands(app_settings.webhook_commands.clone());
+            ha_client.set_payload_fields(app_settings.payload_fields.clone());
+            ha_client.set_unique_id_prefix(app_settings.unique_id_prefix());
+            ha_client.set_transport(app_settings.transport);
+            ha_client.set_capabilities(app_settings.integration_capabilities.clone());
+            ha_client.set_webhook_secret(app_settings.webhook_secret.as_deref());
+            ha_client.set_update_retry(
+                app_settings.update_retries,
+                app_settings.update_retry_delay_secs,
+            );
+            let mut collector = SensorCollector::new(&effective.enabled_sensors);
+            collector.set_remote_hosts(app_settings.remote_hosts.clone());
+            collector.set_memory_used_mode(app_settings.memory_used_mode);
+            collector.set_disk_usage_mode(app_settings.disk_usage_mode);
+            collector.set_cpu_overheat(app_settings.cpu_overheat);
+            collector.set_temperature_range(app_settings.temperature_range);
+            collector.set_force_temperature_sensors(app_settings.force_temperature_sensors);
+            sensors::probe::set_timeout(app_settings.probe_timeout_secs);
+            collector.set_battery_saver(app_settings.battery_saver.clone());
+            collector.set_state_rounding(app_settings.state_rounding.clone());
+            collector.set_uptime_ledger(app_settings.uptime_ledger);
+            collector.set_gpu_ids(app_settings.gpu_ids.clone());
+            collector.set_battery_ids(
+                app_settings.battery_ids.clone(),
+                app_settings.hidden_batteries.clone(),
             );
-            let collector = SensorCollector::new(&app_settings.enabled_sensors);
+            if let Some(ref config) = sensor_config {
+                collector.set_sensor_overrides(config.overrides.clone());
+            }
+            collector.set_custom_sensors(effective.active_custom_sensors());
+            if app_settings.autostart {
+                commands::apply_autostart(&handle, true);
+            }
 
             // Create shared state
             let state = Arc::new(AppState {
                 settings: Mutex::new(app_settings.clone()),
                 ha_client: Mutex::new(ha_client),
                 collector: Mutex::new(collector),
-                is_registered: Mutex::new(app_settings.webhook_id.is_some()),
+                sensor_config: Mutex::new(sensor_config),
+                is_registered: Mutex::new(app_settings.is_registered()),
+                recent_errors: Mutex::new(RecentErrors::default()),
+                webhook_failures: Mutex::new(WebhookFailures::default()),
+                settings_changed: Notify::new(),
+                device_offline: Mutex::new(false),
+                close_behavior: app_settings.close_behavior,
+                dev_mode: dev_mode || cfg!(debug_assertions),
+                registering: AtomicBool::new(false),
             });
 
             app.manage(state.clone());
@@ -141,14 +413,35 @@ pub fn run(dev_mode: bool) {
             // Spawn background sensor update loop
             let bg_state = state.clone();
             let bg_handle = handle.clone();
+            let event_rx = events::spawn_watcher(
+                &app_settings.immediate_updates,
+                app_settings.hidden_batteries.clone(),
+            );
             tauri::async_runtime::spawn(async move {
-                sensor_update_loop(bg_state, bg_handle).await;
+                sensor_update_loop(bg_state, bg_handle, event_rx).await;
             });
 
+            // Catch a webhook HA dropped between updates, e.g. while no state changed
+            if app_settings.webhook_verify_interval_secs > 0 {
+                let interval =
+                    tokio::time::Duration::from_secs(app_settings.webhook_verify_interval_secs);
+                tauri::async_runtime::spawn(webhook_verify_loop(
+                    state.clone(),
+                    handle.clone(),
+                    interval,
+                ));
+            }
+
             // Show the main window — the JS initApp() will decide what to show.
             // If already registered it will call load_dashboard to add the HA child webview.
-            if let Some(w) = app.get_window("main") {
-                let _ = w.show();
+            // At login or with start_hidden it stays in the tray, unless setup still has to be done.
+            let needs_setup = app_settings.server_url.is_empty() || !app_settings.is_registered();
+            if !(app_settings.start_hidden || autostarted) || needs_setup {
+                if let Some(w) = app.get_window("main") {
+                    let _ = w.show();
+                }
+            } else {
+                log::info!("Started hidden, keeping the window in the tray");
             }
 
             Ok(())
@@ -156,14 +449,27 @@ pub fn run(dev_mode: bool) {
         .invoke_handler(tauri::generate_handler![
             get_settings,
             save_settings,
+            persist_now,
             register_device,
+            adopt_existing_device,
             get_sensor_list,
+            get_collection_status,
+            get_displays,
+            get_elevation_status,
             update_sensors_now,
+            resend_all_states,
+            simulate_failure,
+            set_device_online,
             toggle_sensor,
+            set_sensors_enabled,
+            set_group_enabled,
             get_current_language,
             get_my_public_ip,
+            check_allowlist_ip,
             load_dashboard,
             hide_dashboard,
+            generate_support_bundle,
+            validate_sensors,
         ])
         .build(tauri::generate_context!())
         .expect("Error building Tauri application");
@@ -175,74 +481,398 @@ pub fn run(dev_mode: bool) {
                 event: WindowEvent::CloseRequested { api, .. },
                 ..
             } => {
-                // Hide main window instead of closing (keep in tray)
+                // Hide (keep in tray), minimize or quit depending on close_behavior
                 if label == "main" {
+                    let behavior = app_handle
+                        .try_state::<Arc<AppState>>()
+                        .map(|s| s.close_behavior)
+                        .unwrap_or_default();
+                    if behavior == CloseBehavior::Quit {
+                        app_handle.exit(0);
+                        return;
+                    }
                     api.prevent_close();
                     // Use get_window (not get_webview_window) because with the
                     // unstable multi-webview feature, Window and Webview are separate.
                     if let Some(window) = app_handle.get_window("main") {
-                        let _ = window.hide();
+                        let _ = match behavior {
+                            CloseBehavior::Minimize => window.minimize(),
+                            _ => window.hide(),
+                        };
                     }
                 }
             }
+            RunEvent::Exit => {
+                // Keep the uptime since the last full update
+                if let Some(state) = app_handle.try_state::<Arc<AppState>>() {
+                    tauri::async_runtime::block_on(save_uptime_ledger(&state, app_handle));
+                }
+            }
             _ => {}
         }
     });
 }
 
-/// Background task that periodically updates sensors
-async fn sensor_update_loop(state: Arc<AppState>, _handle: tauri::AppHandle) {
+/// Persist the uptime summed over boots, so total_uptime survives restarts and reboots.
+/// Runs with the full updates, on a new boot and on exit; a crash or power loss loses at
+/// most the time since the last full update.
+async fn save_uptime_ledger(state: &AppState, handle: &tauri::AppHandle) {
+    let ledger = state.collector.lock().await.uptime_ledger();
+    let mut settings = state.settings.lock().await;
+    if settings.uptime_ledger == ledger {
+        return;
+    }
+    settings.uptime_ledger = ledger;
+    if let Err(e) = settings.save(handle) {
+        log::error!("Failed to save the uptime ledger: {}", e);
+    }
+}
+
+/// Save the ledger right away when the collector counted a boot the store doesn't know
+/// yet, e.g. when Windows fast startup resumed the app on a new boot
+async fn save_uptime_ledger_on_new_boot(state: &AppState, handle: &tauri::AppHandle) {
+    let boots = state.collector.lock().await.uptime_ledger().boots;
+    if state.settings.lock().await.uptime_ledger.boots != boots {
+        log::info!("New boot detected, saving the uptime ledger");
+        save_uptime_ledger(state, handle).await;
+    }
+}
+
+/// Apply a reloaded sensor config file to the running app. Everything is recomputed from the
+/// stored settings, so keys removed from the file fall back to their stored value.
+async fn apply_sensor_config(state: &AppState, config: config_file::SensorConfig) {
+    let settings = state.settings.lock().await;
+    let effective = config.applied_to(&settings);
+    drop(settings);
+    let mut collector = state.collector.lock().await;
+    collector.set_enabled_sensors(effective.enabled_sensors.clone());
+    collector.set_custom_sensors(effective.active_custom_sensors());
+    collector.set_sensor_overrides(config.overrides.clone());
+    drop(collector);
+    *state.sensor_config.lock().await = Some(config);
+    // A changed update_interval applies to the sleep right away
+    state.settings_changed.notify_one();
+}
+
+/// Monitors were attached or removed: re-query the display sensors, register the new set
+/// with HA and tell the UI
+async fn refresh_displays(state: &Arc<AppState>, handle: &tauri::AppHandle) {
+    sensors::invalidate_static_info();
+    let info = tauri::async_runtime::spawn_blocking(sensors::system_info::current_displays)
+        .await
+        .unwrap_or_default();
+    let _ = handle.emit("displays-changed", info);
+
+    let displays = state.collect(|c| c.collect_displays()).await;
+    state.report_new_sensors(handle, &displays, "update_loop").await;
+}
+
+/// Send the configured seed burst after a registration: a few quick updates of the dynamic
+/// sensors, each reported as a `seed-burst-progress` event. Stops early when the device is
+/// unregistered or set offline meanwhile.
+async fn send_seed_burst(
+    state: Arc<AppState>,
+    handle: tauri::AppHandle,
+    config: registration::SeedBurstConfig,
+) {
+    let (total, spacing) = config.bounded();
+    if total == 0 {
+        return;
+    }
+    log::info!("[HA] Seed burst: {} updates, {}s apart", total, spacing.as_secs());
+    for current in 1..=total {
+        tokio::time::sleep(spacing).await;
+        if !*state.is_registered.lock().await || *state.device_offline.lock().await {
+            log::info!("[HA] Seed burst stopped after {} updates", current - 1);
+            return;
+        }
+
+        let sensor_data = state.collect(|c| c.collect_dynamic()).await;
+        let result = state.ha_client.lock().await.update_sensors(&sensor_data).await;
+        if result.is_ok() {
+            state.collector.lock().await.record_sent(&sensor_data);
+        }
+        let error = result.err().map(|e| e.to_string());
+        if let Some(ref e) = error {
+            log::error!("[HA] Seed burst update {}/{} failed: {}", current, total, e);
+            state.record_error("seed_burst", e).await;
+        }
+        let _ = handle.emit(
+            "seed-burst-progress",
+            registration::SeedBurstProgress {
+                current,
+                total,
+                error,
+            },
+        );
+    }
+}
+
+/// Check every `interval` that the webhook still exists. When it fails while the
+/// integration answers its ping, HA dropped the webhook: register again (adopting the device
+/// if HA still has it) instead of waiting for updates to fail.
+async fn webhook_verify_loop(
+    state: Arc<AppState>,
+    handle: tauri::AppHandle,
+    interval: tokio::time::Duration,
+) {
+    log::info!("[Webhook] Verifying the webhook every {}s", interval.as_secs());
+    loop {
+        tokio::time::sleep(interval).await;
+
+        // Set up with a webhook; is_registered may already be off after 410s from updates
+        let configured = state.settings.lock().await.is_registered();
+        if !configured || *state.device_offline.lock().await {
+            continue;
+        }
+        // Lock the client per request, so updates aren't held up by both checks
+        let check = {
+            let ha_client = state.ha_client.lock().await;
+            if ha_client.transport() == Transport::RestStates {
+                continue;
+            }
+            ha_client.check_webhook().await
+        };
+        match check {
+            WebhookCheck::Ok => {
+                state.webhook_failures.lock().await.reset();
+                continue;
+            }
+            WebhookCheck::Failed(e) => {
+                log::debug!("[Webhook] Check failed, not counting it as gone: {}", e);
+                continue;
+            }
+            WebhookCheck::Gone(status) => {
+                // HA answers 404/410 briefly while it restarts, like for updates
+                let threshold = state.settings.lock().await.webhook_gone_threshold;
+                let mut failures = state.webhook_failures.lock().await;
+                if !failures.record(threshold) {
+                    log::warn!(
+                        "[Webhook] Webhook answered {} ({}/{}), checking again later",
+                        status,
+                        failures.count(),
+                        threshold
+                    );
+                    continue;
+                }
+            }
+        }
+        // A proxy in front of a stopped HA can answer 404 too; only heal when the API answers
+        let reachable = state.ha_client.lock().await.check_integration_reachable().await;
+        if let Err(e) = reachable {
+            log::debug!("[Webhook] Check failed, but HA isn't reachable either: {}", e);
+            continue;
+        }
+        heal_webhook(&state, &handle).await;
+    }
+}
+
+/// Register again after the webhook disappeared, and emit `webhook-healed`
+async fn heal_webhook(state: &Arc<AppState>, handle: &tauri::AppHandle) {
+    let Some(_registering) = state.begin_registration() else {
+        log::info!("[Webhook] Webhook gone, but a registration is already running");
+        return;
+    };
+    log::warn!("[Webhook] Webhook gone while Home Assistant is reachable, registering again");
+
+    let all_sensors = state.collect(|c| c.collect_all()).await;
+    let mut settings = state.settings.lock().await;
+    let mut ha_client = state.ha_client.lock().await;
+    let result = registration::adopt_existing_device(
+        &mut settings,
+        &mut ha_client,
+        &all_sensors,
+        handle,
+    )
+    .await;
+    drop(ha_client);
+    drop(settings);
+
+    let error = match result {
+        Ok(_) => {
+            state.collector.lock().await.record_sent(&all_sensors);
+            log::info!("[Webhook] Registered again, sending updates to the new webhook");
+            state.webhook_failures.lock().await.reset();
+            *state.is_registered.lock().await = true;
+            None
+        }
+        Err(e) => {
+            log::error!("[Webhook] Registering again failed: {}", e);
+            state.record_error("webhook_verify", &e).await;
+            Some(e)
+        }
+    };
+    let _ = handle.emit(
+        "webhook-healed",
+        diagnostics::WebhookHeal {
+            success: error.is_none(),
+            error,
+        },
+    );
+}
+
+/// Next event from the watcher; never resolves without one
+async fn next_event(
+    event_rx: &mut Option<mpsc::UnboundedReceiver<events::SensorEvent>>,
+) -> Option<events::SensorEvent> {
+    match event_rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Background task that periodically updates sensors, and right away on watched events
+async fn sensor_update_loop(
+    state: Arc<AppState>,
+    handle: tauri::AppHandle,
+    mut event_rx: Option<mpsc::UnboundedReceiver<events::SensorEvent>>,
+) {
     // Wait a bit for app to initialize
     tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
 
     let mut cycle_count: u64 = 0;
+    let mut last_cycle_wall = std::time::SystemTime::now();
+    let mut config_watcher = state
+        .settings
+        .lock()
+        .await
+        .config_file
+        .as_ref()
+        .map(config_file::ConfigWatcher::new);
+    let mut display_watcher = sensors::system_info::DisplayWatcher::new();
 
     loop {
-        let interval_secs = {
-            let settings = state.settings.lock().await;
-            settings.update_interval
-        };
+        let cycle_started = tokio::time::Instant::now();
+
+        // Follow config_file itself: load a newly set file, drop the overlay of an unset one
+        let settings = state.settings.lock().await;
+        let config_path = settings.config_file.clone();
+        if config_watcher.as_ref().map(|w| w.path()) != config_path.as_deref().map(Path::new) {
+            let config = config_file::load_configured(&settings).unwrap_or_default();
+            drop(settings);
+            config_watcher = config_path.map(config_file::ConfigWatcher::new);
+            apply_sensor_config(&state, config).await;
+        } else {
+            drop(settings);
+        }
+
+        // Hot-reload the sensor config file when it changed
+        if let Some(config) = config_watcher.as_mut().and_then(|w| w.poll()) {
+            apply_sensor_config(&state, config).await;
+        }
+
+        // Far more wall-clock time passed than the interval: the machine slept. Docks and
+        // monitors may have changed, so re-query the cached static hardware info, and
+        // start the delta sensors over instead of reporting the whole sleep as one interval.
+        let interval_secs = state.update_interval().await;
+        let now_wall = std::time::SystemTime::now();
+        if let Ok(gap) = now_wall.duration_since(last_cycle_wall) {
+            if gap.as_secs() > interval_secs * 2 + 60 {
+                log::info!("Resumed after {}s, refreshing static hardware info", gap.as_secs());
+                sensors::invalidate_static_info();
+                state.collector.lock().await.reset_baselines();
+            }
+        }
+        last_cycle_wall = now_wall;
+
+        // Docked/undocked: the display sensors are static, so refresh them now
+        if display_watcher.poll() {
+            refresh_displays(&state, &handle).await;
+        }
 
         let is_registered = *state.is_registered.lock().await;
+        let device_offline = *state.device_offline.lock().await;
 
-        if is_registered {
+        if is_registered && !device_offline {
             // Every 10 cycles (or on first cycle), re-register all sensors
             // and send a full update (including static sensors).
             // This ensures entities exist in HA even after HA restarts.
             if cycle_count % 10 == 0 {
-                let all_sensors = {
-                    let mut collector = state.collector.lock().await;
-                    collector.collect_all()
+                let all_sensors = state.collect(|c| c.collect_all()).await;
+                save_uptime_ledger(&state, &handle).await;
+                // On startup, only re-register sensors whose metadata changed since the
+                // last run; the periodic pass re-registers everything in case HA forgot them.
+                let to_register = if cycle_count == 0 {
+                    let settings = state.settings.lock().await;
+                    registration::changed_sensors(&settings, &all_sensors)
+                } else {
+                    all_sensors.clone()
                 };
                 let ha_client = state.ha_client.lock().await;
-                if let Err(e) = ha_client.register_sensors(&all_sensors).await {
+                if let Err(e) = ha_client.register_sensors(&to_register).await {
                     log::error!("Failed to re-register sensors: {}", e);
-                    if e.to_string().contains("410") {
-                        log::warn!("Webhook expired, need to re-register");
-                        *state.is_registered.lock().await = false;
+                    state.record_error("update_loop", format!("Re-register sensors: {}", e)).await;
+                    if diagnostics::is_webhook_gone(&*e) {
+                        drop(ha_client);
+                        state.webhook_gone().await;
                     }
                 } else {
-                    log::debug!("Re-registered {} sensors with HA", all_sensors.len());
+                    log::debug!("Re-registered {} sensors with HA", to_register.len());
+                    state.webhook_failures.lock().await.reset();
+                    // Unchanged sensors are still known to HA; lazy registration can skip them
+                    ha_client.mark_registered(all_sensors.iter().map(|s| s.unique_id.clone()));
+                    drop(ha_client);
                     // Also send state update for ALL sensors (including static)
-                    if let Err(e) = ha_client.update_sensors(&all_sensors).await {
-                        log::error!("Failed to update all sensors: {}", e);
+                    let max_delay = tokio::time::Duration::from_secs(interval_secs);
+                    let result = ha_client::update_sensors_with_retry(
+                        &state.ha_client,
+                        &all_sensors,
+                        max_delay,
+                    )
+                    .await;
+                    match result {
+                        Ok(()) => state.collector.lock().await.record_sent(&all_sensors),
+                        Err(e) => {
+                            log::error!("Failed to update all sensors: {}", e);
+                            state.record_error("update_loop", format!("Update all sensors: {}", e)).await;
+                        }
+                    }
+
+                    if !to_register.is_empty() {
+                        let mut settings = state.settings.lock().await;
+                        registration::remember_fingerprints(&mut settings, &to_register);
+                        if let Err(e) = settings.save(&handle) {
+                            log::error!("Failed to save sensor fingerprints: {}", e);
+                        }
                     }
                 }
             } else {
-                // Normal cycle: only update dynamic sensors
-                let sensor_data = {
-                    let mut collector = state.collector.lock().await;
-                    collector.collect_dynamic()
-                };
+                // Normal cycle: only update dynamic sensors that changed (on battery, only
+                // interesting ones)
+                let sensor_data = state
+                    .collect(|c| {
+                        let mut sensors = c.collect_dynamic();
+                        c.apply_simulation(&mut sensors);
+                        let sensors = c.battery_saver_filter(sensors);
+                        let sensors = c.due_only(sensors);
+                        c.changed_only(sensors)
+                    })
+                    .await;
+                save_uptime_ledger_on_new_boot(&state, &handle).await;
 
-                let ha_client = state.ha_client.lock().await;
-                if let Err(e) = ha_client.update_sensors(&sensor_data).await {
-                    log::error!("Failed to update sensors: {}", e);
+                // Nothing worth sending: skip the request so the network can stay idle
+                if !sensor_data.is_empty() {
+                    let max_delay = tokio::time::Duration::from_secs(interval_secs);
+                    let result = ha_client::update_sensors_with_retry(
+                        &state.ha_client,
+                        &sensor_data,
+                        max_delay,
+                    )
+                    .await;
+                    match result {
+                        Ok(()) => {
+                            state.webhook_failures.lock().await.reset();
+                            state.collector.lock().await.record_sent(&sensor_data);
+                        }
+                        Err(e) => {
+                            log::error!("Failed to update sensors: {}", e);
+                            state.record_error("update_loop", format!("Update sensors: {}", e)).await;
 
-                    // If 410 Gone, we need to re-register
-                    if e.to_string().contains("410") {
-                        log::warn!("Webhook expired, need to re-register");
-                        *state.is_registered.lock().await = false;
+                            // 410 Gone / 404: re-register once this keeps happening
+                            if diagnostics::is_webhook_gone(&*e) {
+                                state.webhook_gone().await;
+                            }
+                        }
                     }
                 }
             }
@@ -250,6 +880,34 @@ async fn sensor_update_loop(state: Arc<AppState>, _handle: tauri::AppHandle) {
             cycle_count += 1;
         }
 
-        tokio::time::sleep(tokio::time::Duration::from_secs(interval_secs)).await;
+        // Sleep until the next cycle. A settings change re-reads the interval, so lowering
+        // it takes effect now instead of after the old interval has passed. A watched event
+        // starts the next cycle early (but not right after the last one).
+        loop {
+            let interval_secs = state.update_interval().await;
+            let next_cycle = cycle_started + tokio::time::Duration::from_secs(interval_secs);
+            tokio::select! {
+                _ = tokio::time::sleep_until(next_cycle) => break,
+                _ = state.settings_changed.notified() => {
+                    log::debug!("Settings changed, re-reading the update interval");
+                }
+                Some(event) = next_event(&mut event_rx) => {
+                    log::info!("{:?} changed, updating sensors now", event);
+                    let mut fired = vec![event];
+                    tokio::time::sleep_until(cycle_started + events::MIN_EVENT_GAP).await;
+                    // The coming cycle covers whatever else fired meanwhile
+                    if let Some(rx) = event_rx.as_mut() {
+                        while let Ok(event) = rx.try_recv() {
+                            fired.push(event);
+                        }
+                    }
+                    // Addresses are only re-read when the interfaces are enumerated again
+                    if fired.contains(&events::SensorEvent::Network) {
+                        state.collector.lock().await.relist_networks();
+                    }
+                    break;
+                }
+            }
+        }
     }
 }
@@ -2,6 +2,17 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
-    let dev_mode = std::env::args().any(|a| a == "--dev");
-    ha_companion_lib::run(dev_mode);
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|a| a == "--oneshot") {
+        let settings_path = args
+            .iter()
+            .position(|a| a == "--settings")
+            .and_then(|i| args.get(i + 1))
+            .map(std::path::PathBuf::from);
+        std::process::exit(ha_companion_lib::run_oneshot(settings_path));
+    }
+
+    let dev_mode = args.iter().any(|a| a == "--dev");
+    let autostarted = args.iter().any(|a| a == ha_companion_lib::AUTOSTART_ARG);
+    ha_companion_lib::run(dev_mode, autostarted);
 }
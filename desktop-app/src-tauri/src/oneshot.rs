@@ -0,0 +1,245 @@
+//! `--oneshot` mode: collect and send sensor states once, then exit.
+//!
+//! Meant for cron / Task Scheduler. Reuses the settings file, `HaClient` and the collector
+//! of the desktop app, but runs without the Tauri event loop (no window, no tray).
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config_file;
+use crate::diagnostics;
+use crate::ha_client::{self, HaClient};
+use crate::registration::{self, RegistrationHost};
+use crate::sensors::collector::{SensorCollector, SensorValue};
+use crate::sensors::probe;
+use crate::settings::AppSettings;
+
+/// Sensors were sent (and the device registered if needed)
+pub const EXIT_OK: i32 = 0;
+/// Registration or the sensor update failed
+pub const EXIT_FAILURE: i32 = 1;
+/// No usable settings (app never set up, or settings file unreadable)
+pub const EXIT_CONFIG: i32 = 2;
+
+/// Progress goes to the log and settings to the settings.json file
+struct FileHost {
+    path: PathBuf,
+}
+
+impl RegistrationHost for FileHost {
+    fn progress(&self, step: &str, current: usize, total: usize) {
+        if total > 0 {
+            log::info!("[Oneshot] Registration: {} ({}/{})", step, current, total);
+        } else {
+            log::info!("[Oneshot] Registration: {}", step);
+        }
+    }
+
+    fn save_settings(&self, settings: &AppSettings) -> Result<(), String> {
+        settings.save_to_file(&self.path)
+    }
+}
+
+/// Run one collection cycle and return the process exit code
+pub fn run(settings_path: Option<PathBuf>) -> i32 {
+    diagnostics::init_logging();
+
+    let path = match settings_path.or_else(AppSettings::store_file_path) {
+        Some(p) => p,
+        None => {
+            log::error!("[Oneshot] Cannot determine the settings location, use --settings <path>");
+            return EXIT_CONFIG;
+        }
+    };
+
+    // Collection runs on the blocking pool (see `collect`), so one thread is enough
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => {
+            log::error!("[Oneshot] Failed to start async runtime: {}", e);
+            return EXIT_FAILURE;
+        }
+    };
+
+    runtime.block_on(run_once(&path))
+}
+
+/// Run `f` (WMI, subprocesses) on the blocking pool instead of the runtime thread, like the
+/// app's collector thread does. The collector comes back with the sensors.
+async fn collect(
+    mut collector: SensorCollector,
+    f: fn(&mut SensorCollector) -> Vec<SensorValue>,
+) -> Result<(SensorCollector, Vec<SensorValue>), String> {
+    tokio::task::spawn_blocking(move || {
+        let sensors = f(&mut collector);
+        (collector, sensors)
+    })
+    .await
+    .map_err(|e| format!("Collection failed: {}", e))
+}
+
+async fn run_once(path: &Path) -> i32 {
+    let mut settings = match AppSettings::load_from_file(path) {
+        Ok(s) => s,
+        Err(e) => {
+            log::error!("[Oneshot] {}", e);
+            return EXIT_CONFIG;
+        }
+    };
+    if settings.server_url.is_empty() || settings.access_token.is_empty() {
+        log::error!(
+            "[Oneshot] Server URL or access token missing in {}; set up the app first",
+            path.display()
+        );
+        return EXIT_CONFIG;
+    }
+    let migrated_gpus = settings.migrate_gpu_ids();
+    let migrated_batteries = settings.migrate_battery_ids();
+    if migrated_gpus || migrated_batteries {
+        if let Err(e) = settings.save_to_file(path) {
+            log::error!("[Oneshot] Failed to save migrated GPU/battery ids: {}", e);
+        }
+    }
+    let sensor_config = config_file::load_configured(&settings);
+    // Only the collector gets the file's values; `settings` is saved back to the store
+    let effective = config_file::effective(&settings, sensor_config.as_ref());
+
+    let mut ha_client = match HaClient::new(
+        settings.server_url.clone(),
+        settings.access_token.clone(),
+        settings.webhook_id.clone(),
+    ) {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("[Oneshot] {}", e);
+            return EXIT_FAILURE;
+        }
+    };
+    ha_client.set_device_id(settings.device_id.clone());
+    ha_client.set_max_attribute_items(settings.max_attribute_items);
+    ha_client.set_compress_payloads(settings.compress_payloads);
+    ha_client.set_lazy_registration(settings.lazy_registration);
+    if let Err(e) = settings
+        .source_address()
+        .and_then(|addr| ha_client.set_local_address(addr))
+    {
+        log::error!("[Oneshot] {}", e);
+        return EXIT_CONFIG;
+    }
+    ha_client.set_attribute_allowlist(settings.sensor_attributes.clone());
+    ha_client.set_webhook_commands(settings.webhook_commands.clone());
+    ha_client.set_payload_fields(settings.payload_fields.clone());
+    ha_client.set_unique_id_prefix(settings.unique_id_prefix());
+    ha_client.set_transport(settings.transport);
+    ha_client.set_capabilities(settings.integration_capabilities.clone());
+    ha_client.set_webhook_secret(settings.webhook_secret.as_deref());
+    ha_client.set_update_retry(settings.update_retries, settings.update_retry_delay_secs);
+    // Sensors with a stored fingerprint were registered by an earlier run
+    ha_client.mark_registered(settings.sensor_fingerprints.keys().cloned());
+    let mut collector = SensorCollector::new(&effective.enabled_sensors);
+    collector.set_remote_hosts(settings.remote_hosts.clone());
+    collector.set_memory_used_mode(settings.memory_used_mode);
+    collector.set_disk_usage_mode(settings.disk_usage_mode);
+    collector.set_cpu_overheat(settings.cpu_overheat);
+    collector.set_temperature_range(settings.temperature_range);
+    collector.set_force_temperature_sensors(settings.force_temperature_sensors);
+    collector.set_state_rounding(settings.state_rounding.clone());
+    collector.set_uptime_ledger(settings.uptime_ledger);
+    probe::set_timeout(settings.probe_timeout_secs);
+    collector.set_gpu_ids(settings.gpu_ids.clone());
+    collector.set_battery_ids(settings.battery_ids.clone(), settings.hidden_batteries.clone());
+    if let Some(config) = sensor_config {
+        collector.set_sensor_overrides(config.overrides);
+    }
+    collector.set_custom_sensors(effective.active_custom_sensors());
+
+    let host = FileHost {
+        path: path.to_path_buf(),
+    };
+
+    // Not registered yet: a full registration also sends all sensor states
+    if !settings.is_registered() {
+        let (_, all_sensors) = match collect(collector, SensorCollector::collect_all).await {
+            Ok(collected) => collected,
+            Err(e) => {
+                log::error!("[Oneshot] {}", e);
+                return EXIT_FAILURE;
+            }
+        };
+        return match registration::register_device(
+            &mut settings,
+            &mut ha_client,
+            &all_sensors,
+            &host,
+        )
+        .await
+        {
+            Ok(_) => EXIT_OK,
+            Err(e) => {
+                log::error!("[Oneshot] Registration failed: {}", e);
+                EXIT_FAILURE
+            }
+        };
+    }
+
+    // CPU usage needs two samples
+    tokio::time::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL).await;
+    let (collector, sensor_data) =
+        match collect(collector, SensorCollector::collect_dynamic).await {
+            Ok(collected) => collected,
+            Err(e) => {
+                log::error!("[Oneshot] {}", e);
+                return EXIT_FAILURE;
+            }
+        };
+    if settings.uptime_ledger != collector.uptime_ledger() {
+        settings.uptime_ledger = collector.uptime_ledger();
+        if let Err(e) = host.save_settings(&settings) {
+            log::error!("[Oneshot] Failed to save the uptime ledger: {}", e);
+        }
+    }
+
+    let max_delay = Duration::from_secs(effective.update_interval);
+    // Retries lock the client per attempt; nothing else shares it here
+    let shared = tokio::sync::Mutex::new(ha_client);
+    let result = ha_client::update_sensors_with_retry(&shared, &sensor_data, max_delay).await;
+    let mut ha_client = shared.into_inner();
+    match result {
+        Ok(()) => {
+            log::info!("[Oneshot] Sent {} sensor states", sensor_data.len());
+            EXIT_OK
+        }
+        Err(e) if diagnostics::is_webhook_gone(&*e) => {
+            // Like the app's webhook check: take over the device HA still has, if any
+            log::warn!("[Oneshot] Webhook gone ({}), registering again", e);
+            let all_sensors = match collect(collector, SensorCollector::collect_all).await {
+                Ok((_, all_sensors)) => all_sensors,
+                Err(e) => {
+                    log::error!("[Oneshot] {}", e);
+                    return EXIT_FAILURE;
+                }
+            };
+            match registration::adopt_existing_device(
+                &mut settings,
+                &mut ha_client,
+                &all_sensors,
+                &host,
+            )
+            .await
+            {
+                Ok(_) => EXIT_OK,
+                Err(e) => {
+                    log::error!("[Oneshot] Registration failed: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        Err(e) => {
+            log::error!("[Oneshot] Failed to update sensors: {}", e);
+            EXIT_FAILURE
+        }
+    }
+}
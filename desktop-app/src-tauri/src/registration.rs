@@ -1,13 +1,157 @@
-use crate::ha_client::{HaClient, RegistrationRequest};
-use crate::sensors::collector::SensorCollector;
+use std::future::Future;
+
+use serde::{Deserialize, Serialize};
+use tauri::Emitter;
+
+use crate::ha_client::{is_transient, HaClient, RegistrationRequest, Transport};
+use crate::sensors::collector::SensorValue;
 use crate::settings::AppSettings;
 
-/// Perform full device registration with HA
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Default attempts per network step before giving up on transient failures
+pub const DEFAULT_REGISTRATION_ATTEMPTS: u32 = 3;
+
+/// Start of the error returned when HA already has this device and adoption wasn't confirmed.
+/// The frontend matches on it to offer adopting the existing device.
+pub const DEVICE_EXISTS_ERROR: &str = "Device already registered";
+
+/// Error returned when a registration is started while another one is still running
+pub const ALREADY_REGISTERING_ERROR: &str = "Registration already in progress";
+
+/// Most updates a seed burst sends, and the shortest gap between them, so it can't flood HA
+pub const MAX_SEED_BURST_COUNT: u32 = 10;
+pub const MIN_SEED_BURST_SPACING_SECS: u64 = 2;
+
+/// Extra updates right after registration, so HA's history starts with more than one point
+/// and a broken pipeline shows up right away (`seed_burst` in settings.json; off by default)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SeedBurstConfig {
+    /// Number of extra updates (0 = off, at most [`MAX_SEED_BURST_COUNT`])
+    pub count: u32,
+    /// Seconds between them (at least [`MIN_SEED_BURST_SPACING_SECS`])
+    pub spacing_secs: u64,
+}
+
+impl Default for SeedBurstConfig {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            spacing_secs: 5,
+        }
+    }
+}
+
+impl SeedBurstConfig {
+    /// Count and spacing clamped to the bounds
+    pub fn bounded(&self) -> (u32, std::time::Duration) {
+        (
+            self.count.min(MAX_SEED_BURST_COUNT),
+            std::time::Duration::from_secs(self.spacing_secs.max(MIN_SEED_BURST_SPACING_SECS)),
+        )
+    }
+}
+
+/// Payload of the `seed-burst-progress` event, sent after each update of the burst
+#[derive(Debug, Clone, Serialize)]
+pub struct SeedBurstProgress {
+    pub current: u32,
+    pub total: u32,
+    /// Why this update failed; None when it was sent
+    pub error: Option<String>,
+}
+
+/// Payload of the `registration-progress` event
+#[derive(Debug, Clone, Serialize)]
+pub struct RegistrationProgress {
+    /// pinging | registering_device | waiting | registering_sensors | sending_states | done
+    pub step: String,
+    pub current: usize,
+    pub total: usize,
+}
+
+/// Where registration reports progress and persists settings.
+/// The app uses its `AppHandle`; `--oneshot` mode runs without a Tauri runtime.
+pub trait RegistrationHost {
+    fn progress(&self, step: &str, current: usize, total: usize);
+    fn save_settings(&self, settings: &AppSettings) -> Result<(), String>;
+}
+
+impl RegistrationHost for tauri::AppHandle {
+    fn progress(&self, step: &str, current: usize, total: usize) {
+        let _ = self.emit(
+            "registration-progress",
+            RegistrationProgress {
+                step: step.to_string(),
+                current,
+                total,
+            },
+        );
+    }
+
+    fn save_settings(&self, settings: &AppSettings) -> Result<(), String> {
+        settings.save(self)
+    }
+}
+
+/// Run a registration step, retrying transient failures with a short backoff (1s, 2s, ...)
+async fn retry_transient<T, F, Fut>(what: &str, attempts: u32, mut op: F) -> Result<T, BoxError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, BoxError>>,
+{
+    let mut attempt = 1;
+    loop {
+        match op().await {
+            Ok(v) => return Ok(v),
+            Err(e) if attempt < attempts && is_transient(&*e) => {
+                let delay = tokio::time::Duration::from_secs(1 << (attempt - 1));
+                log::warn!(
+                    "[HA] {} failed (attempt {}/{}), retrying in {}s: {}",
+                    what,
+                    attempt,
+                    attempts,
+                    delay.as_secs(),
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Perform full device registration with HA, registering and sending `all_sensors`. Callers
+/// collect them before taking the settings and client locks, and record them as sent on success.
+/// Fails with [`DEVICE_EXISTS_ERROR`] when HA already has a device with this device_id.
 pub async fn register_device(
     settings: &mut AppSettings,
     ha_client: &mut HaClient,
-    collector: &mut SensorCollector,
-    app_handle: &tauri::AppHandle,
+    all_sensors: &[SensorValue],
+    host: &impl RegistrationHost,
+) -> Result<String, String> {
+    register(settings, ha_client, all_sensors, host, false).await
+}
+
+/// Register like [`register_device`], but reuse the webhook of a device HA already has
+/// (e.g. after a reinstall) instead of failing, so no duplicate device is created.
+pub async fn adopt_existing_device(
+    settings: &mut AppSettings,
+    ha_client: &mut HaClient,
+    all_sensors: &[SensorValue],
+    host: &impl RegistrationHost,
+) -> Result<String, String> {
+    register(settings, ha_client, all_sensors, host, true).await
+}
+
+async fn register(
+    settings: &mut AppSettings,
+    ha_client: &mut HaClient,
+    all_sensors: &[SensorValue],
+    host: &impl RegistrationHost,
+    adopt_existing: bool,
 ) -> Result<String, String> {
     // Validate settings
     if settings.server_url.is_empty() {
@@ -18,34 +162,43 @@ pub async fn register_device(
         log::error!("[HA] Registration: access token is empty");
         return Err("Access token is not configured".to_string());
     }
+    if ha_client.transport() == Transport::RestStates {
+        return connect_states_api(settings, ha_client, all_sensors, host).await;
+    }
 
-    // Collect device metadata
-    let sys_info = crate::sensors::system_info::collect();
-
-    let registration = RegistrationRequest {
-        device_id: settings.device_id.clone(),
-        device_name: sys_info.hostname.clone(),
-        manufacturer: sys_info.motherboard_manufacturer.clone(),
-        model: sys_info.motherboard_model.clone(),
-        os_name: Some(sys_info.os_name.clone()),
-        os_version: Some(sys_info.os_version.clone()),
-        app_version: Some(env!("CARGO_PKG_VERSION").to_string()),
-    };
+    let registration = registration_request(settings);
 
     // Check that the integration is reachable first (clearer 404 message)
-    if let Err(e) = ha_client.check_integration_reachable().await {
+    let attempts = settings.registration_attempts.max(1);
+    host.progress("pinging", 0, 0);
+    let client: &HaClient = ha_client;
+    if let Err(e) = retry_transient("Ping", attempts, || client.check_integration_reachable()).await
+    {
         let msg = format!("Cannot reach Home Assistant Desktop App API. {}", e);
         log::error!("[HA] {}", msg);
         return Err(msg);
     }
 
     // Register device
-    let response = ha_client
-        .register_device(&registration)
-        .await
-        .map_err(|e| format!("Registration failed: {}", e))?;
+    host.progress("registering_device", 0, 0);
+    let response = retry_transient("Device registration", attempts, || {
+        client.register_device(&registration)
+    })
+    .await
+    .map_err(|e| format!("Registration failed: {}", e))?;
 
-    if !response.success {
+    let existing = response.is_existing_device();
+    if existing && !adopt_existing {
+        let err = format!(
+            "{} in Home Assistant (device_id {}). Adopt the existing device, or delete it in HA first",
+            DEVICE_EXISTS_ERROR, settings.device_id
+        );
+        log::warn!("[HA] {}", err);
+        return Err(err);
+    }
+    if existing {
+        log::info!("[HA] Adopting existing device registration");
+    } else if !response.success {
         let err = format!(
             "Registration rejected: {}",
             response.error.unwrap_or_else(|| "Unknown error".to_string())
@@ -54,6 +207,27 @@ pub async fn register_device(
         return Err(err);
     }
 
+    // An integration that announces nothing gets the v1 baseline
+    let capabilities = response.capabilities.clone().unwrap_or_default();
+    log::info!("[HA] Integration capabilities: {:?}", capabilities);
+    settings.integration_capabilities = capabilities.clone();
+    ha_client.set_capabilities(capabilities);
+
+    // HA refuses plain requests once it handed out a secret, so the secret (not the setting)
+    // decides how the webhook is used until the next registration
+    let secret = response.secret.clone();
+    match (settings.webhook_encryption, secret.is_some()) {
+        (true, false) => {
+            log::warn!("[HA] Encryption was requested, but HA returned no secret; sending plain JSON")
+        }
+        (false, true) => {
+            log::warn!("[HA] Encryption is off, but HA returned a secret; encrypting the webhook anyway")
+        }
+        _ => {}
+    }
+    ha_client.set_webhook_secret(secret.as_deref());
+    settings.webhook_secret = secret;
+
     let webhook_id = response.webhook_id.ok_or_else(|| {
         log::error!("[HA] Registration response missing webhook_id");
         "No webhook_id in response".to_string()
@@ -62,7 +236,7 @@ pub async fn register_device(
     // Save webhook_id
     settings.webhook_id = Some(webhook_id.clone());
     ha_client.set_webhook_id(webhook_id.clone());
-    if let Err(e) = settings.save(app_handle) {
+    if let Err(e) = host.save_settings(settings) {
         log::error!("[HA] Failed to save settings: {}", e);
         return Err(format!("Failed to save settings: {}", e));
     }
@@ -70,43 +244,125 @@ pub async fn register_device(
     // Wait for HA to finish setting up the config entry and sensor platforms.
     // The webhook handler and dispatcher listeners need time to initialize
     // before we can register sensors via the webhook.
-    log::info!("[HA] Waiting 3s for HA platform setup to complete...");
-    tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
+    // An adopted device's config entry is already set up.
+    if !existing {
+        log::info!("[HA] Waiting 3s for HA platform setup to complete...");
+        host.progress("waiting", 0, 0);
+        tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
+    }
+
+    // Register all sensors
+    let client: &HaClient = ha_client;
 
-    // Collect and register all sensors
-    let all_sensors = collector.collect_all();
+    let total = all_sensors.len();
+    if client.is_lazy_registration() {
+        // update_sensors registers each sensor right before its first state is sent
+        log::info!("[HA] Lazy registration: {} sensors register on first update", total);
+    } else {
+        for (i, sensor) in all_sensors.iter().enumerate() {
+            host.progress("registering_sensors", i + 1, total);
+            let result = retry_transient("Sensor registration", attempts, || {
+                client.register_sensor(sensor)
+            })
+            .await;
+            if let Err(e) = result {
+                log::error!("[HA] Sensor registration failed: {}", e);
+                return Err(format!("Sensor registration failed: {}", e));
+            }
+        }
+    }
 
-    if let Err(e) = ha_client.register_sensors(&all_sensors).await {
-        log::error!("[HA] Sensor registration failed: {}", e);
-        return Err(format!("Sensor registration failed: {}", e));
+    // Fresh webhook: everything registered now is all HA knows about
+    settings.sensor_fingerprints.clear();
+    remember_fingerprints(settings, all_sensors);
+    if let Err(e) = host.save_settings(settings) {
+        log::error!("[HA] Failed to save sensor fingerprints: {}", e);
     }
 
     // Send initial sensor states
-    if let Err(e) = ha_client.update_sensors(&all_sensors).await {
+    host.progress("sending_states", 0, 0);
+    let result = retry_transient("Initial sensor update", attempts, || {
+        client.update_sensors(all_sensors)
+    })
+    .await;
+    if let Err(e) = result {
         log::error!("[HA] Initial sensor update failed: {}", e);
         return Err(format!("Initial sensor update failed: {}", e));
     }
 
     log::info!("Device registered successfully with webhook_id: {}", webhook_id);
+    host.progress("done", total, total);
 
     Ok(webhook_id)
 }
 
-/// Re-register device (when server URL or token changes)
-#[allow(dead_code)]
-pub async fn re_register(
-    settings: &mut AppSettings,
-    ha_client: &mut HaClient,
-    collector: &mut SensorCollector,
-    app_handle: &tauri::AppHandle,
+/// Device metadata sent to HA. The device is named `device_name` from the settings, or the
+/// hostname when that is unset.
+pub fn registration_request(settings: &AppSettings) -> RegistrationRequest {
+    let sys_info = crate::sensors::system_info::collect();
+    RegistrationRequest {
+        device_id: settings.device_id.clone(),
+        device_name: settings.device_name.clone().unwrap_or(sys_info.hostname),
+        manufacturer: sys_info.motherboard_manufacturer,
+        model: sys_info.motherboard_model,
+        os_name: Some(sys_info.os_name),
+        os_version: Some(sys_info.os_version),
+        app_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        suggested_area: settings.suggested_area.clone(),
+        configuration_url: settings.configuration_url.clone(),
+        supports_encryption: settings.webhook_encryption,
+    }
+}
+
+/// "Registration" with the REST states transport: check the token and send the first states.
+/// There is no device or webhook; returns an empty webhook_id.
+async fn connect_states_api(
+    settings: &AppSettings,
+    ha_client: &HaClient,
+    all_sensors: &[SensorValue],
+    host: &impl RegistrationHost,
 ) -> Result<String, String> {
-    // Clear existing webhook_id
-    settings.webhook_id = None;
-    settings.save(app_handle).map_err(|e| format!("Failed to save settings: {}", e))?;
+    let attempts = settings.registration_attempts.max(1);
+    host.progress("pinging", 0, 0);
+    if let Err(e) = retry_transient("Ping", attempts, || ha_client.check_states_api()).await {
+        let msg = format!("Cannot reach Home Assistant REST API. {}", e);
+        log::error!("[HA] {}", msg);
+        return Err(msg);
+    }
 
-    // Update HA client
-    ha_client.update_config(settings.server_url.clone(), settings.access_token.clone());
+    let total = all_sensors.len();
+    host.progress("sending_states", 0, 0);
+    let result = retry_transient("Initial sensor update", attempts, || {
+        ha_client.update_sensors(all_sensors)
+    })
+    .await;
+    if let Err(e) = result {
+        log::error!("[HA] Initial sensor update failed: {}", e);
+        return Err(format!("Initial sensor update failed: {}", e));
+    }
 
-    // Perform fresh registration
-    register_device(settings, ha_client, collector, app_handle).await
+    log::info!("Connected to the REST states API, {} sensors sent", total);
+    host.progress("done", total, total);
+    Ok(String::new())
+}
+
+/// Sensors whose registration metadata changed (or that were never registered)
+/// since the fingerprints were last stored.
+pub fn changed_sensors(settings: &AppSettings, sensors: &[SensorValue]) -> Vec<SensorValue> {
+    sensors
+        .iter()
+        .filter(|s| {
+            settings.sensor_fingerprints.get(&s.unique_id) != Some(&s.registration_fingerprint())
+        })
+        .cloned()
+        .collect()
+}
+
+/// Store the fingerprints of sensors that were just registered with HA
+pub fn remember_fingerprints(settings: &mut AppSettings, sensors: &[SensorValue]) {
+    for sensor in sensors {
+        settings
+            .sensor_fingerprints
+            .insert(sensor.unique_id.clone(), sensor.registration_fingerprint());
+    }
 }
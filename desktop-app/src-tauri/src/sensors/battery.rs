@@ -1,8 +1,17 @@
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// How far back the charge history used for the rate goes
+const HISTORY_WINDOW: Duration = Duration::from_secs(30 * 60);
+/// Shortest history a rate is computed from (charge is reported in whole percents)
+const MIN_HISTORY_SPAN: Duration = Duration::from_secs(5 * 60);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BatteryData {
     pub batteries: Vec<BatteryInfo>,
+    /// True when the machine runs from line power (always true on desktops without a battery)
+    pub on_ac_power: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,11 +21,217 @@ pub struct BatteryInfo {
     pub state_of_health: Option<f32>,
     pub cycle_count: Option<u32>,
     pub is_charging: bool,
+    pub vendor: Option<String>,
+    pub model: Option<String>,
+    pub serial_number: Option<String>,
+}
+
+impl BatteryInfo {
+    /// "vendor model serial" as far as known; `battery_ids` and `hidden_batteries` match on it
+    pub fn identity(&self) -> String {
+        [&self.vendor, &self.model, &self.serial_number]
+            .into_iter()
+            .flatten()
+            .map(|part| part.trim())
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
 }
 
-pub fn collect() -> BatteryData {
+/// Entity id suffix per battery (e.g. `_mouse` in `battery_level_mouse`), stable across
+/// reconnects. `ids` pins batteries by a case-insensitive part of their identity
+/// (`{"logitech": "mouse"}`; the longest matching key wins). Other batteries are named after
+/// their serial number or else their model, with `_2`, `_3` for identical ones. A single
+/// battery without a pin keeps the bare ids (`battery_level`).
+pub fn entity_suffixes(batteries: &[BatteryInfo], ids: &HashMap<String, String>) -> Vec<String> {
+    let identities: Vec<String> = batteries.iter().map(BatteryInfo::identity).collect();
+    // Without serial or model there is nothing stable left but the position
+    let fallbacks: Vec<String> = batteries
+        .iter()
+        .enumerate()
+        .map(|(i, battery)| {
+            battery
+                .serial_number
+                .as_deref()
+                .or(battery.model.as_deref())
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map_or_else(|| i.to_string(), str::to_string)
+        })
+        .collect();
+    let devices: Vec<(&str, &str)> = identities
+        .iter()
+        .zip(&fallbacks)
+        .map(|(identity, fallback)| (identity.as_str(), fallback.as_str()))
+        .collect();
+    super::entity_suffixes(&devices, ids)
+}
+
+/// Whether the battery matches one of `hidden` (case-insensitive parts of the identity)
+pub fn is_hidden(battery: &BatteryInfo, hidden: &[String]) -> bool {
+    let identity = battery.identity().to_lowercase();
+    hidden
+        .iter()
+        .any(|part| !part.is_empty() && identity.contains(&part.to_lowercase()))
+}
+
+/// Recent charge samples of one battery, for a charge/discharge rate and time estimate where
+/// the OS doesn't report them. Starts over when the battery switches between charging and
+/// discharging.
+#[derive(Debug, Clone, Default)]
+pub struct ChargeHistory {
+    state: String,
+    samples: VecDeque<(Instant, f32)>,
+}
+
+impl ChargeHistory {
+    pub fn record(&mut self, battery: &BatteryInfo, now: Instant) {
+        if battery.state != self.state {
+            self.samples.clear();
+            self.state = battery.state.clone();
+        }
+        self.samples.push_back((now, battery.percentage));
+        while self.samples.len() > 2
+            && self
+                .samples
+                .front()
+                .is_some_and(|(at, _)| now.duration_since(*at) > HISTORY_WINDOW)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Percentage points per hour (negative while discharging); None until the history
+    /// spans a few minutes
+    pub fn rate_per_hour(&self) -> Option<f32> {
+        let (first_at, first) = *self.samples.front()?;
+        let (last_at, last) = *self.samples.back()?;
+        let span = last_at.duration_since(first_at);
+        if span < MIN_HISTORY_SPAN {
+            return None;
+        }
+        Some((last - first) / span.as_secs_f32() * 3600.0)
+    }
+
+    /// Minutes until empty (discharging) or full (charging) at the current rate
+    pub fn minutes_remaining(&self) -> Option<f32> {
+        let rate = self.rate_per_hour()?;
+        let (_, current) = *self.samples.back()?;
+        let left = if rate < 0.0 {
+            current
+        } else if rate > 0.0 {
+            100.0 - current
+        } else {
+            return None;
+        };
+        Some(left / rate.abs() * 60.0)
+    }
+}
+
+/// Batteries and the AC state. Batteries matching `hidden` (see [`is_hidden`]) are still
+/// listed, but a discharging one (a wireless mouse) doesn't mean the machine is on battery.
+pub fn collect(hidden: &[String]) -> BatteryData {
     let batteries = collect_batteries();
-    BatteryData { batteries }
+    let on_ac_power = collect_ac_power(&batteries, hidden);
+    BatteryData {
+        batteries,
+        on_ac_power,
+    }
+}
+
+/// Whether the machine runs from line power, without the rest of the battery details
+pub fn on_ac_power(hidden: &[String]) -> bool {
+    collect_ac_power(&collect_batteries(), hidden)
+}
+
+/// Fallback when the platform doesn't report the AC adapter directly:
+/// we are on AC unless some (not hidden) battery is discharging.
+fn ac_power_from_batteries(batteries: &[BatteryInfo], hidden: &[String]) -> bool {
+    !batteries
+        .iter()
+        .any(|b| b.state == "Discharging" && !is_hidden(b, hidden))
+}
+
+#[cfg(target_os = "linux")]
+fn collect_ac_power(batteries: &[BatteryInfo], hidden: &[String]) -> bool {
+    // Look for line-power supplies (AC*, ADP*, ...) reporting type "Mains". Only a Mains
+    // supply whose `online` could be read says we're off AC; an unreadable one says nothing.
+    let mut offline_mains = false;
+    if let Ok(entries) = std::fs::read_dir("/sys/class/power_supply") {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let supply_type = std::fs::read_to_string(path.join("type")).unwrap_or_default();
+            if supply_type.trim() != "Mains" {
+                continue;
+            }
+            match std::fs::read_to_string(path.join("online")) {
+                Ok(online) if online.trim() == "1" => return true,
+                Ok(_) => offline_mains = true,
+                Err(e) => {
+                    log::debug!("[Battery] Cannot read {}/online: {}", path.display(), e)
+                }
+            }
+        }
+    }
+
+    if offline_mains {
+        false
+    } else {
+        log::debug!("[Battery] No readable Mains supply in sysfs, deriving AC state from batteries");
+        ac_power_from_batteries(batteries, hidden)
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn collect_ac_power(batteries: &[BatteryInfo], hidden: &[String]) -> bool {
+    // First line of `pmset -g batt` is e.g. "Now drawing from 'AC Power'"
+    if let Ok(output) = std::process::Command::new("pmset").arg("-g").arg("batt").output() {
+        if output.status.success() {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            if let Some(first) = stdout.lines().next() {
+                return first.contains("AC Power");
+            }
+        }
+    }
+    ac_power_from_batteries(batteries, hidden)
+}
+
+#[cfg(windows)]
+fn collect_ac_power(batteries: &[BatteryInfo], hidden: &[String]) -> bool {
+    #[repr(C)]
+    #[derive(Default)]
+    struct SystemPowerStatus {
+        ac_line_status: u8,
+        battery_flag: u8,
+        battery_life_percent: u8,
+        system_status_flag: u8,
+        battery_life_time: u32,
+        battery_full_life_time: u32,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetSystemPowerStatus(status: *mut SystemPowerStatus) -> i32;
+    }
+
+    // The adapter state itself, so desktops and UPS setups without a battery report AC
+    let mut status = SystemPowerStatus::default();
+    // SAFETY: `status` is a properly sized SYSTEM_POWER_STATUS
+    if unsafe { GetSystemPowerStatus(&mut status) } != 0 {
+        match status.ac_line_status {
+            0 => return false,
+            1 => return true,
+            // 255: unknown
+            _ => {}
+        }
+    }
+    ac_power_from_batteries(batteries, hidden)
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+fn collect_ac_power(batteries: &[BatteryInfo], hidden: &[String]) -> bool {
+    ac_power_from_batteries(batteries, hidden)
 }
 
 #[cfg(any(windows, target_os = "linux", target_os = "macos"))]
@@ -46,6 +261,9 @@ fn collect_batteries() -> Vec<BatteryInfo> {
                     state_of_health: Some(battery.state_of_health().value * 100.0),
                     cycle_count: battery.cycle_count(),
                     is_charging,
+                    vendor: battery.vendor().map(str::to_string),
+                    model: battery.model().map(str::to_string),
+                    serial_number: battery.serial_number().map(str::to_string),
                 });
             }
         }
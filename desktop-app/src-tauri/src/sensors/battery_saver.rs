@@ -0,0 +1,210 @@
+//! Battery saver: while the machine runs on battery, only send sensor updates whose value
+//! is "interesting" (`battery_saver` in settings.json), so the network isn't woken every
+//! interval for values nobody acts on.
+//!
+//! A sensor is sent on battery when:
+//! - it was never sent before, or wasn't sent for `max_quiet_secs`
+//! - its state is not a number (binary sensors, text) and changed
+//! - its state is a number and its rule matches: above/below a threshold (and once more when
+//!   it returns), or moved at least `change` since the last value sent
+//!
+//! Numeric sensors without a rule only go out on the `max_quiet_secs` heartbeat, and so does
+//! `last_seen`, which changes every cycle.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use super::collector::SensorValue;
+use crate::ha_client::most_specific_match;
+
+/// The heartbeat sensor: a new timestamp every cycle, so a change says nothing
+const LAST_SEEN: &str = "last_seen";
+
+/// When a numeric sensor is worth sending on battery; unset fields don't apply
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InterestRule {
+    /// Send while the value is above this (and once when it drops back)
+    pub above: Option<f64>,
+    /// Send while the value is below this (and once when it rises back)
+    pub below: Option<f64>,
+    /// Send when the value moved at least this much since the last value sent
+    pub change: Option<f64>,
+}
+
+impl InterestRule {
+    fn is_interesting(&self, previous: f64, value: f64) -> bool {
+        let above = self.above.is_some_and(|t| value > t || previous > t);
+        let below = self.below.is_some_and(|t| value < t || previous < t);
+        let change = self.change.is_some_and(|d| (value - previous).abs() >= d);
+        above || below || change
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatterySaverConfig {
+    pub enabled: bool,
+    /// Rules per sensor unique_id, `prefix*` or `*` (the most specific key wins)
+    pub rules: HashMap<String, InterestRule>,
+    /// Every sensor is still sent at least this often, so HA doesn't show stale values forever
+    pub max_quiet_secs: u64,
+}
+
+impl Default for BatterySaverConfig {
+    fn default() -> Self {
+        let rule = |above: Option<f64>, change: Option<f64>| InterestRule {
+            above,
+            below: None,
+            change,
+        };
+        Self {
+            enabled: false,
+            rules: HashMap::from([
+                ("cpu_usage".to_string(), rule(Some(80.0), None)),
+                ("memory_usage".to_string(), rule(Some(90.0), None)),
+                ("battery_level*".to_string(), rule(None, Some(5.0))),
+            ]),
+            max_quiet_secs: 900,
+        }
+    }
+}
+
+/// Remembers what was last sent per sensor and filters updates while on battery
+#[derive(Debug, Default)]
+pub struct BatterySaver {
+    config: BatterySaverConfig,
+    last_sent: HashMap<String, (serde_json::Value, Instant)>,
+}
+
+impl BatterySaver {
+    pub fn set_config(&mut self, config: BatterySaverConfig) {
+        self.config = config;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Sensors to send this cycle. On AC power (or when disabled) that is all of them.
+    pub fn filter(&self, on_battery: bool, sensors: Vec<SensorValue>) -> Vec<SensorValue> {
+        if !self.config.enabled || !on_battery {
+            return sensors;
+        }
+        let now = Instant::now();
+        let total = sensors.len();
+        let sensors: Vec<SensorValue> = sensors
+            .into_iter()
+            .filter(|s| self.is_interesting(s, now))
+            .collect();
+        if sensors.len() < total {
+            log::debug!(
+                "[BatterySaver] On battery, sending {} of {} sensors",
+                sensors.len(),
+                total
+            );
+        }
+        sensors
+    }
+
+    /// Remember states HA accepted; a failed update leaves them to be tried again
+    pub fn record_sent(&mut self, sensors: &[SensorValue]) {
+        if !self.config.enabled {
+            return;
+        }
+        let now = Instant::now();
+        for sensor in sensors {
+            self.last_sent
+                .insert(sensor.unique_id.clone(), (sensor.state.clone(), now));
+        }
+    }
+
+    fn is_interesting(&self, sensor: &SensorValue, now: Instant) -> bool {
+        let Some((last, sent_at)) = self.last_sent.get(&sensor.unique_id) else {
+            return true;
+        };
+        if now.duration_since(*sent_at) >= Duration::from_secs(self.config.max_quiet_secs) {
+            return true;
+        }
+        if sensor.state == *last || sensor.unique_id == LAST_SEEN {
+            return false;
+        }
+        let (Some(value), Some(previous)) = (as_number(&sensor.state), as_number(last)) else {
+            return true;
+        };
+        most_specific_match(&self.config.rules, &sensor.unique_id)
+            .is_some_and(|rule| rule.is_interesting(previous, value))
+    }
+}
+
+/// Numeric value of a state; most sensors send numbers as formatted strings
+fn as_number(state: &serde_json::Value) -> Option<f64> {
+    state
+        .as_f64()
+        .or_else(|| state.as_str()?.trim().parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn saver() -> BatterySaver {
+        let mut saver = BatterySaver::default();
+        saver.set_config(BatterySaverConfig {
+            enabled: true,
+            ..BatterySaverConfig::default()
+        });
+        saver
+    }
+
+    fn sensor(state: f64) -> SensorValue {
+        SensorValue {
+            unique_id: "cpu_usage".into(),
+            name: "CPU Usage".into(),
+            state: serde_json::json!(state),
+            sensor_type: "sensor".into(),
+            device_class: None,
+            unit_of_measurement: Some("%".into()),
+            state_class: Some("measurement".into()),
+            icon: None,
+            attributes: HashMap::new(),
+            update_at_interval: true,
+        }
+    }
+
+    #[test]
+    fn failed_updates_are_not_recorded() {
+        let mut saver = saver();
+        saver.record_sent(&[sensor(10.0)]);
+
+        // Above the 80% threshold: interesting, but the update fails and isn't recorded
+        assert_eq!(saver.filter(true, vec![sensor(95.0)]).len(), 1);
+        assert_eq!(saver.filter(true, vec![sensor(12.0)]).len(), 0);
+        assert_eq!(saver.filter(true, vec![sensor(95.0)]).len(), 1);
+
+        saver.record_sent(&[sensor(95.0)]);
+        assert!(saver.filter(true, vec![sensor(95.0)]).is_empty());
+        // Dropping back below the threshold is sent once
+        assert_eq!(saver.filter(true, vec![sensor(12.0)]).len(), 1);
+    }
+
+    #[test]
+    fn a_new_last_seen_alone_is_not_sent() {
+        let last_seen = |state: &str| SensorValue {
+            unique_id: "last_seen".into(),
+            state: serde_json::json!(state),
+            device_class: Some("timestamp".into()),
+            unit_of_measurement: None,
+            ..sensor(0.0)
+        };
+        let mut saver = saver();
+        saver.record_sent(&[sensor(10.0), last_seen("2026-10-16T10:00:00+00:00")]);
+
+        let cycle = vec![sensor(10.0), last_seen("2026-10-16T10:01:00+00:00")];
+        assert!(saver.filter(true, cycle.clone()).is_empty());
+        // On AC power the heartbeat goes out as usual
+        assert_eq!(saver.filter(false, cycle).len(), 2);
+    }
+}
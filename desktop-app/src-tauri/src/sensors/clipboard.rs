@@ -0,0 +1,213 @@
+//! Clipboard activity (opt-in): only the time of the last change is tracked.
+//!
+//! The clipboard contents are never read, stored or sent. Each platform uses a
+//! change counter or notification that does not expose the data:
+//! - Windows: `GetClipboardSequenceNumber`
+//! - macOS: `NSPasteboard.changeCount` (via osascript)
+//! - Linux: `wl-paste --watch` (Wayland) or `clipnotify` (X11), if installed
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::diagnostics::unix_now;
+
+/// Watches for clipboard changes on a background thread until dropped
+pub struct ClipboardWatcher {
+    /// UNIX timestamp of the last change (0 = none seen yet)
+    last_change: Arc<AtomicU64>,
+    stop: Arc<AtomicBool>,
+    #[cfg(target_os = "linux")]
+    child: Arc<std::sync::Mutex<Option<std::process::Child>>>,
+}
+
+impl ClipboardWatcher {
+    pub fn start() -> Self {
+        let watcher = Self {
+            last_change: Arc::new(AtomicU64::new(0)),
+            stop: Arc::new(AtomicBool::new(false)),
+            #[cfg(target_os = "linux")]
+            child: Arc::new(std::sync::Mutex::new(None)),
+        };
+
+        let last_change = watcher.last_change.clone();
+        let stop = watcher.stop.clone();
+        #[cfg(target_os = "linux")]
+        let child = watcher.child.clone();
+        let spawned = std::thread::Builder::new()
+            .name("clipboard-watch".into())
+            .spawn(move || {
+                #[cfg(target_os = "linux")]
+                watch(&last_change, &stop, &child);
+                #[cfg(not(target_os = "linux"))]
+                watch(&last_change, &stop);
+            });
+        if let Err(e) = spawned {
+            log::error!("[Clipboard] Failed to start watcher thread: {}", e);
+        }
+        watcher
+    }
+
+    /// Time of the last clipboard change seen since the watcher started
+    pub fn last_change(&self) -> Option<u64> {
+        match self.last_change.load(Ordering::Relaxed) {
+            0 => None,
+            ts => Some(ts),
+        }
+    }
+}
+
+impl Drop for ClipboardWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        #[cfg(target_os = "linux")]
+        if let Ok(mut slot) = self.child.lock() {
+            if let Some(mut child) = slot.take() {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+fn watch(last_change: &AtomicU64, stop: &AtomicBool) {
+    #[link(name = "user32")]
+    extern "system" {
+        fn GetClipboardSequenceNumber() -> u32;
+    }
+
+    // SAFETY: takes no arguments and only returns a counter
+    let mut last_seq = unsafe { GetClipboardSequenceNumber() };
+    while !stop.load(Ordering::Relaxed) {
+        std::thread::sleep(Duration::from_secs(1));
+        let seq = unsafe { GetClipboardSequenceNumber() };
+        if seq != last_seq {
+            last_seq = seq;
+            last_change.store(unix_now(), Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn watch(last_change: &AtomicU64, stop: &AtomicBool) {
+    // changeCount increments on every copy; the pasteboard contents are not touched
+    fn change_count() -> Option<i64> {
+        let output = std::process::Command::new("osascript")
+            .args([
+                "-l",
+                "JavaScript",
+                "-e",
+                "ObjC.import('AppKit'); $.NSPasteboard.generalPasteboard.changeCount",
+            ])
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+    }
+
+    let mut last_count = change_count();
+    if last_count.is_none() {
+        log::warn!("[Clipboard] Cannot read the pasteboard change count; clipboard sensor stays empty");
+        return;
+    }
+    while !stop.load(Ordering::Relaxed) {
+        std::thread::sleep(Duration::from_secs(5));
+        let count = change_count();
+        if count.is_some() && count != last_count {
+            last_count = count;
+            last_change.store(unix_now(), Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn watch(
+    last_change: &AtomicU64,
+    stop: &AtomicBool,
+    child: &std::sync::Mutex<Option<std::process::Child>>,
+) {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+
+    while !stop.load(Ordering::Relaxed) {
+        let mut command = if wayland {
+            // Runs the command on every change; the clipboard is piped to it and discarded
+            let mut c = Command::new("wl-paste");
+            c.args(["--watch", "sh", "-c", "cat > /dev/null; echo"]);
+            c
+        } else {
+            // clipnotify exits as soon as the clipboard changes
+            Command::new("clipnotify")
+        };
+        let spawned = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut process = match spawned {
+            Ok(p) => p,
+            Err(e) => {
+                log::warn!(
+                    "[Clipboard] {} not available ({}); clipboard sensor stays empty",
+                    if wayland { "wl-paste" } else { "clipnotify" },
+                    e
+                );
+                return;
+            }
+        };
+        let stdout = process.stdout.take();
+        if let Ok(mut slot) = child.lock() {
+            *slot = Some(process);
+        }
+
+        if wayland {
+            // One line per change; wl-paste also fires once for the current selection at start
+            if let Some(out) = stdout {
+                for _ in BufReader::new(out).lines().skip(1) {
+                    if stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    last_change.store(unix_now(), Ordering::Relaxed);
+                }
+            }
+            // wl-paste exited (or was killed): don't spin if it keeps failing
+            std::thread::sleep(Duration::from_secs(5));
+        } else {
+            // Poll instead of wait() so Drop can take the lock and kill clipnotify
+            let status = loop {
+                let polled = child
+                    .lock()
+                    .ok()
+                    .and_then(|mut slot| slot.as_mut().map(|p| p.try_wait()));
+                match polled {
+                    Some(Ok(None)) if !stop.load(Ordering::Relaxed) => {
+                        std::thread::sleep(Duration::from_millis(250))
+                    }
+                    Some(Ok(Some(status))) => break Some(status),
+                    _ => break None,
+                }
+            };
+            match status {
+                Some(s) if s.success() => {
+                    last_change.store(unix_now(), Ordering::Relaxed);
+                }
+                _ if stop.load(Ordering::Relaxed) => {}
+                _ => std::thread::sleep(Duration::from_secs(5)),
+            }
+        }
+
+        if let Ok(mut slot) = child.lock() {
+            if let Some(mut p) = slot.take() {
+                let _ = p.kill();
+                let _ = p.wait();
+            }
+        }
+    }
+}
+
+#[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+fn watch(_last_change: &AtomicU64, _stop: &AtomicBool) {
+    log::warn!("[Clipboard] Clipboard change detection is not supported on this platform");
+}
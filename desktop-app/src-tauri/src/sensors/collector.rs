@@ -1,9 +1,38 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
 
 use serde::{Deserialize, Serialize};
-use sysinfo::System;
+use sysinfo::{
+    CpuRefreshKind, Disks, MemoryRefreshKind, Networks, ProcessesToUpdate, RefreshKind, System,
+};
 
-use super::{battery, cpu, disk, gpu, memory, network, system_info};
+use crate::config_file::SensorOverride;
+use crate::diagnostics::{FailureKind, SimulatedFailure};
+
+use super::{
+    battery, battery_saver, clipboard, connection, cpu, custom, disk, gpu, idle, keyboard, lid,
+    memory, network, power_plan, probe, remote, state_rounding, system_info, updates,
+    TemperatureRange,
+};
+
+/// Sensors that are off unless the user explicitly enables them (privacy-sensitive, or
+/// gpu_pcie which adds ~40ms of NVML sampling per GPU to every cycle)
+const OPT_IN_SENSORS: &[&str] = &[
+    "last_clipboard_change",
+    "keyboard",
+    "gpu_pcie",
+    "user_idle_seconds",
+];
+
+/// Package names sent in the `packages` attribute of os_updates_pending
+const MAX_UPDATE_PACKAGES_ATTRIBUTE: usize = 50;
+
+/// Processes listed in the `top_processes` attribute of gpu_process_count
+const MAX_GPU_TOP_PROCESSES: usize = 5;
+
+/// How often the network interface and disk lists are enumerated again; in between only
+/// the known ones are refreshed
+const DEVICE_RELIST_INTERVAL: Duration = Duration::from_secs(5 * 60);
 
 /// Represents a single sensor value for HA
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,10 +49,107 @@ pub struct SensorValue {
     pub update_at_interval: bool,
 }
 
+impl SensorValue {
+    /// Stable hash of the metadata HA stores at registration time (not the state).
+    /// Used to skip re-registering sensors whose metadata hasn't changed.
+    pub fn registration_fingerprint(&self) -> String {
+        let metadata = [
+            self.name.as_str(),
+            self.sensor_type.as_str(),
+            self.device_class.as_deref().unwrap_or(""),
+            self.unit_of_measurement.as_deref().unwrap_or(""),
+            self.state_class.as_deref().unwrap_or(""),
+            self.icon.as_deref().unwrap_or(""),
+        ]
+        .join("|");
+
+        // FNV-1a (64-bit): stable across Rust versions, unlike DefaultHasher
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in metadata.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        format!("{:016x}", hash)
+    }
+}
+
+/// What of `sys` a collection refreshes: CPU usage and frequencies, memory, this process
+#[derive(Debug, Clone, Copy, Default)]
+struct SystemParts {
+    cpu: bool,
+    memory: bool,
+    process: bool,
+}
+
 /// Collects all sensor data and formats for HA
 pub struct SensorCollector {
     sys: System,
     enabled_sensors: HashMap<String, bool>,
+    remote_hosts: Vec<remote::RemoteHost>,
+    /// Previous /proc/stat sample per remote host id (for CPU usage deltas)
+    remote_cpu_prev: HashMap<String, Option<remote::CpuTimes>>,
+    memory_used_mode: memory::MemoryUsedMode,
+    /// User-defined command/file sensors (empty unless explicitly enabled in settings)
+    custom_sensors: Vec<custom::CustomSensor>,
+    /// Last run and state per custom sensor unique_id (for per-sensor intervals)
+    custom_last: HashMap<String, (Instant, serde_json::Value)>,
+    /// Running only while the opt-in clipboard sensor is enabled
+    clipboard: Option<clipboard::ClipboardWatcher>,
+    /// Checks for pending OS updates on its own slow cadence while os_updates_pending is enabled
+    update_check: Option<updates::PeriodicCheck<updates::PendingUpdates>>,
+    /// Checks whether the OS wants a reboot while reboot_required is enabled
+    reboot_check: Option<updates::PeriodicCheck<bool>>,
+    /// CPU time counters from the previous cycle (for the cpu_breakdown deltas)
+    cpu_times_prev: Option<cpu::CpuTimes>,
+    /// CPU/GPU temperatures outside this range are reported as unavailable
+    temperature_range: TemperatureRange,
+    /// A CPU temperature was read at some point; until then the temperature sensors are
+    /// left out unless `force_temperature_sensors` is set
+    cpu_temperature_seen: bool,
+    force_temperature_sensors: bool,
+    /// CPU temperature history for the cpu_overheating binary sensor
+    overheat: cpu::OverheatDetector,
+    /// "group: message" for each collector that panicked since the last `take_panics`
+    panics: Vec<String>,
+    /// Errors per sensor group in the collection in progress
+    cycle_failures: BTreeMap<String, String>,
+    /// Errors per sensor group in the last completed collection (missing = collected fine)
+    last_failures: BTreeMap<String, String>,
+    /// GPU entity ids pinned by part of the GPU name (see `gpu::entity_suffixes`)
+    gpu_ids: HashMap<String, String>,
+    /// Name/icon/interval overrides per unique_id from the sensor config file
+    overrides: HashMap<String, SensorOverride>,
+    /// When HA last accepted each sensor that has an interval override
+    interval_sent: HashMap<String, Instant>,
+    /// Last available state per unique_id, kept while the sensor is switched off
+    last_values: HashMap<String, serde_json::Value>,
+    /// AC power state seen by the power group in the last collection (None = not collected)
+    on_ac_power: Option<bool>,
+    /// Drops uninteresting updates while on battery (`battery_saver` in settings)
+    battery_saver: battery_saver::BatterySaver,
+    /// Rounds jittery states and skips unchanged ones (`state_rounding` in settings)
+    state_rounding: state_rounding::StateRounding,
+    /// Fake unavailable/spiked states for the next collections (`simulate_failure`)
+    simulation: Option<SimulatedFailure>,
+    /// Uptime of earlier boots for total_uptime; persisted by the caller
+    uptime_ledger: system_info::UptimeLedger,
+    /// Whether disk_usage counts reserved space as used (`disk_usage_mode` in settings)
+    disk_usage_mode: disk::DiskUsageMode,
+    /// Recent charge per battery entity suffix, for the charge rate attributes
+    battery_history: HashMap<String, battery::ChargeHistory>,
+    /// Battery entity id suffix per part of the battery identity (vendor, model, serial)
+    battery_ids: HashMap<String, String>,
+    /// Batteries (parts of their identity) that get no sensors, e.g. peripherals
+    hidden_batteries: Vec<String>,
+    /// Kept between cycles: refreshing known interfaces/disks is much cheaper than
+    /// enumerating them again every cycle
+    networks: Networks,
+    disks: Disks,
+    /// When the interface/disk lists were last enumerated (None = before the next collection)
+    networks_listed: Option<Instant>,
+    disks_listed: Option<Instant>,
+    /// What the last cycle refreshed of `sys`; CPU usage is measured between two refreshes
+    system_refreshed: SystemParts,
 }
 
 impl SensorCollector {
@@ -34,16 +160,144 @@ impl SensorCollector {
         Self {
             sys,
             enabled_sensors: enabled_sensors.clone(),
+            remote_hosts: Vec::new(),
+            remote_cpu_prev: HashMap::new(),
+            memory_used_mode: memory::MemoryUsedMode::default(),
+            custom_sensors: Vec::new(),
+            custom_last: HashMap::new(),
+            clipboard: None,
+            update_check: None,
+            reboot_check: None,
+            cpu_times_prev: None,
+            temperature_range: TemperatureRange::default(),
+            cpu_temperature_seen: false,
+            force_temperature_sensors: false,
+            overheat: cpu::OverheatDetector::default(),
+            panics: Vec::new(),
+            cycle_failures: BTreeMap::new(),
+            last_failures: BTreeMap::new(),
+            gpu_ids: HashMap::new(),
+            overrides: HashMap::new(),
+            interval_sent: HashMap::new(),
+            last_values: HashMap::new(),
+            on_ac_power: None,
+            battery_saver: battery_saver::BatterySaver::default(),
+            state_rounding: state_rounding::StateRounding::default(),
+            simulation: None,
+            uptime_ledger: system_info::UptimeLedger::default(),
+            disk_usage_mode: disk::DiskUsageMode::default(),
+            battery_history: HashMap::new(),
+            battery_ids: HashMap::new(),
+            hidden_batteries: Vec::new(),
+            networks: Networks::new(),
+            disks: Disks::new(),
+            networks_listed: None,
+            disks_listed: None,
+            system_refreshed: SystemParts::default(),
+        }
+    }
+
+    /// Record that (part of) a sensor group failed to collect in this cycle
+    fn group_failed(&mut self, group: &str, message: String) {
+        self.cycle_failures
+            .entry(group.to_string())
+            .and_modify(|existing| {
+                existing.push_str("; ");
+                existing.push_str(&message);
+            })
+            .or_insert(message);
+    }
+
+    /// Run a sensor group's collector if `enabled`. A panic (e.g. from an unexpected WMI or
+    /// driver response) or a platform probe that timed out only skips that group for this
+    /// cycle instead of all sensor reporting.
+    fn guarded<T>(
+        &mut self,
+        group: &str,
+        enabled: bool,
+        collect: impl FnOnce(&mut Self) -> T,
+    ) -> Option<T> {
+        if !enabled {
+            return None;
+        }
+        // Failures left over from probes run outside a group (e.g. by a command)
+        probe::take_failures();
+        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| collect(self))) {
+            Ok(value) => {
+                let failures = probe::take_failures();
+                if failures.is_empty() {
+                    return Some(value);
+                }
+                log::error!("[Collector] {} sensors skipped: {}", group, failures.join("; "));
+                self.group_failed(group, failures.join("; "));
+                None
+            }
+            Err(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown panic".into());
+                log::error!("[Collector] {} sensors skipped, collector panicked: {}", group, message);
+                self.panics.push(format!("{}: {}", group, message));
+                self.group_failed(group, format!("collector panicked: {}", message));
+                None
+            }
         }
     }
 
+    /// Collector panics since the last call, for the error log in support bundles
+    pub fn take_panics(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.panics)
+    }
+
     fn is_enabled(&self, sensor_id: &str) -> bool {
-        *self.enabled_sensors.get(sensor_id).unwrap_or(&true)
+        match self.enabled_sensors.get(sensor_id) {
+            Some(enabled) => *enabled,
+            None => !OPT_IN_SENSORS.contains(&sensor_id),
+        }
+    }
+
+    /// What the enabled sensors read from `sys`, instead of everything sysinfo tracks
+    fn system_parts(&self) -> SystemParts {
+        let cpu = [
+            "cpu_usage",
+            "cpu_frequency",
+            "cpu_temperature",
+            "cpu_overheating",
+            "companion_cpu",
+        ]
+        .iter()
+        .any(|id| self.is_enabled(id));
+        let memory = ["memory_usage", "memory_used", "memory_total", "swap_usage"]
+            .iter()
+            .any(|id| self.is_enabled(id));
+        SystemParts {
+            cpu,
+            memory,
+            process: self.is_enabled("companion_cpu") || self.is_enabled("companion_memory"),
+        }
+    }
+
+    fn refresh_system(&mut self, parts: SystemParts) {
+        let mut kind = RefreshKind::new();
+        if parts.cpu {
+            kind = kind.with_cpu(CpuRefreshKind::everything());
+        }
+        if parts.memory {
+            kind = kind.with_memory(MemoryRefreshKind::everything());
+        }
+        self.sys.refresh_specifics(kind);
+
+        if parts.process {
+            if let Ok(pid) = sysinfo::get_current_pid() {
+                self.sys.refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+            }
+        }
     }
 
     /// Collect all sensors (both static and dynamic) — used at startup
     pub fn collect_all(&mut self) -> Vec<SensorValue> {
-        self.sys.refresh_all();
         let mut sensors = Vec::new();
 
         sensors.extend(self.collect_static());
@@ -54,15 +308,55 @@ impl SensorCollector {
 
     /// Collect only dynamic sensors — used at interval
     pub fn collect_dynamic(&mut self) -> Vec<SensorValue> {
-        self.sys.refresh_all();
+        self.system_refreshed = self.system_parts();
+        self.refresh_system(self.system_refreshed);
+        let mut sensors = self.read_dynamic();
+
+        // Heartbeat (dynamic, always on): changes every cycle, so HA can tell the device went
+        // quiet from its last_changed even when every other sensor is skipped as unchanged
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        sensors.push(SensorValue {
+            unique_id: "last_seen".into(),
+            name: "Last Seen".into(),
+            state: serde_json::json!(chrono_from_timestamp(now)),
+            sensor_type: "sensor".into(),
+            device_class: Some("timestamp".into()),
+            unit_of_measurement: None,
+            state_class: None,
+            icon: Some("mdi:heart-pulse".into()),
+            attributes: HashMap::new(),
+            update_at_interval: true,
+        });
+
+        self.finish_dynamic(&mut sensors);
+        sensors
+    }
+
+    /// Round, apply overrides to and remember freshly read dynamic sensors
+    fn finish_dynamic(&mut self, sensors: &mut [SensorValue]) {
+        self.state_rounding.round(sensors);
+        self.apply_overrides(sensors);
+        self.remember(sensors);
+    }
+
+    /// Read the enabled dynamic sensors from the data refreshed for them
+    fn read_dynamic(&mut self) -> Vec<SensorValue> {
+        self.on_ac_power = None;
         let mut sensors = Vec::new();
 
         // CPU sensors (dynamic) — collect once, reuse
-        let cpu_enabled =
-            self.is_enabled("cpu_usage") || self.is_enabled("cpu_frequency") || self.is_enabled("cpu_temperature");
-        if cpu_enabled {
-            let cpu_data = cpu::collect(&self.sys);
-
+        let cpu_enabled = self.is_enabled("cpu_usage")
+            || self.is_enabled("cpu_frequency")
+            || self.is_enabled("cpu_temperature")
+            || self.is_enabled("cpu_overheating");
+        let temperature_enabled =
+            self.is_enabled("cpu_temperature") || self.is_enabled("cpu_overheating");
+        if let Some(cpu_data) = self.guarded("cpu", cpu_enabled, |c| {
+            cpu::collect(&c.sys, temperature_enabled, c.temperature_range)
+        }) {
             if self.is_enabled("cpu_usage") {
                 sensors.push(SensorValue {
                     unique_id: "cpu_usage".into(),
@@ -88,12 +382,33 @@ impl SensorCollector {
                     unit_of_measurement: Some("MHz".into()),
                     state_class: Some("measurement".into()),
                     icon: Some("mdi:speedometer".into()),
-                    attributes: HashMap::new(),
+                    attributes: {
+                        let mut attrs = HashMap::new();
+                        attrs.insert("min_mhz".into(), serde_json::json!(cpu_data.frequency_min_mhz));
+                        attrs.insert("max_mhz".into(), serde_json::json!(cpu_data.frequency_max_mhz));
+                        attrs.insert("avg_mhz".into(), serde_json::json!(cpu_data.frequency_mhz));
+                        if let Some(base) = cpu_data.base_frequency_mhz {
+                            attrs.insert("base_mhz".into(), serde_json::json!(base));
+                        }
+                        attrs.insert(
+                            "core_frequencies_mhz".into(),
+                            serde_json::json!(cpu_data.core_frequencies_mhz),
+                        );
+                        attrs
+                    },
                     update_at_interval: true,
                 });
             }
 
-            if self.is_enabled("cpu_temperature") {
+            // Without any source the temperature sensors would be unknown in HA forever. Once
+            // a reading was seen, a later failure is still reported (as unknown).
+            self.cpu_temperature_seen |= cpu_data.temperature.is_some();
+            let report_temperature = self.cpu_temperature_seen || self.force_temperature_sensors;
+            if temperature_enabled && !report_temperature {
+                log::debug!("[Sensors] No CPU temperature source, temperature sensors left out");
+            }
+
+            if self.is_enabled("cpu_temperature") && report_temperature {
                 let temp_state = match cpu_data.temperature {
                     Some(temp) => serde_json::json!(format!("{:.1}", temp)),
                     None => serde_json::json!(null),
@@ -107,6 +422,67 @@ impl SensorCollector {
                     unit_of_measurement: Some("°C".into()),
                     state_class: Some("measurement".into()),
                     icon: Some("mdi:thermometer".into()),
+                    attributes: temperature_source_attributes(cpu_data.temperature_source.as_deref()),
+                    update_at_interval: true,
+                });
+            }
+
+            if self.is_enabled("cpu_overheating") && report_temperature {
+                let config = self.overheat.config();
+                let state = match cpu_data.temperature {
+                    Some(temp) => serde_json::json!(self.overheat.update(temp, Instant::now())),
+                    None => serde_json::json!(null),
+                };
+                let mut attrs = HashMap::new();
+                attrs.insert(
+                    "temperature".into(),
+                    serde_json::json!(cpu_data.temperature.map(|t| format!("{:.1}", t))),
+                );
+                attrs.insert("threshold".into(), serde_json::json!(config.threshold_c));
+                attrs.insert("hysteresis".into(), serde_json::json!(config.hysteresis_c));
+                attrs.insert("duration_secs".into(), serde_json::json!(config.duration_secs));
+                sensors.push(SensorValue {
+                    unique_id: "cpu_overheating".into(),
+                    name: "CPU Overheating".into(),
+                    state,
+                    sensor_type: "binary_sensor".into(),
+                    device_class: Some("problem".into()),
+                    unit_of_measurement: None,
+                    state_class: None,
+                    icon: Some("mdi:thermometer-alert".into()),
+                    attributes: attrs,
+                    update_at_interval: true,
+                });
+            }
+        }
+
+        // CPU time breakdown (dynamic) — needs a previous reading, so nothing on the first cycle
+        let breakdown = self
+            .guarded("cpu", self.is_enabled("cpu_breakdown"), |c| {
+                let current = cpu::read_cpu_times()?;
+                let previous = c.cpu_times_prev.replace(current)?;
+                cpu::breakdown_between(previous, current)
+            })
+            .flatten();
+        if let Some(breakdown) = breakdown {
+            let parts = [
+                ("user", "User", Some(breakdown.user), "mdi:account"),
+                ("system", "System", Some(breakdown.system), "mdi:cog"),
+                ("idle", "Idle", Some(breakdown.idle), "mdi:sleep"),
+                ("iowait", "I/O Wait", breakdown.iowait, "mdi:timer-sand"),
+            ];
+            for (id, label, value, icon) in parts {
+                // iowait is Linux-only
+                let Some(value) = value else { continue };
+                sensors.push(SensorValue {
+                    unique_id: format!("cpu_{}", id),
+                    name: format!("CPU {}", label),
+                    state: serde_json::json!(format!("{:.1}", value)),
+                    sensor_type: "sensor".into(),
+                    device_class: None,
+                    unit_of_measurement: Some("%".into()),
+                    state_class: Some("measurement".into()),
+                    icon: Some(icon.into()),
                     attributes: HashMap::new(),
                     update_at_interval: true,
                 });
@@ -117,9 +493,8 @@ impl SensorCollector {
         let mem_enabled = self.is_enabled("memory_usage")
             || self.is_enabled("memory_used")
             || self.is_enabled("swap_usage");
-        if mem_enabled {
-            let mem_data = memory::collect(&self.sys);
-
+        let mem_data = self.guarded("memory", mem_enabled, |c| memory::collect(&c.sys, c.memory_used_mode));
+        if let Some(mem_data) = mem_data {
             if self.is_enabled("memory_usage") {
                 sensors.push(SensorValue {
                     unique_id: "memory_usage".into(),
@@ -145,18 +520,24 @@ impl SensorCollector {
                     unit_of_measurement: Some("GB".into()),
                     state_class: Some("measurement".into()),
                     icon: Some("mdi:memory".into()),
-                    attributes: HashMap::new(),
+                    attributes: {
+                        let mut attrs = HashMap::new();
+                        if cfg!(target_os = "linux") {
+                            attrs.insert(
+                                "calculation".into(),
+                                serde_json::json!(self.memory_used_mode.description()),
+                            );
+                        }
+                        attrs
+                    },
                     update_at_interval: true,
                 });
             }
 
             // Swap sensors
             if self.is_enabled("swap_usage") && mem_data.swap_total_bytes > 0 {
-                let swap_usage_pct = if mem_data.swap_total_bytes > 0 {
-                    (mem_data.swap_used_bytes as f32 / mem_data.swap_total_bytes as f32) * 100.0
-                } else {
-                    0.0
-                };
+                let swap_usage_pct =
+                    super::usage_percent(mem_data.swap_used_bytes, mem_data.swap_total_bytes);
                 let swap_used_gb = mem_data.swap_used_bytes as f64 / 1_073_741_824.0;
                 let swap_total_gb = mem_data.swap_total_bytes as f64 / 1_073_741_824.0;
 
@@ -181,8 +562,10 @@ impl SensorCollector {
         }
 
         // Disk sensors (dynamic)
-        if self.is_enabled("disk_usage") {
-            let disk_data = disk::collect();
+        if let Some(disk_data) = self.guarded("disk", self.is_enabled("disk_usage"), |c| {
+            let relist = relist_due(&mut c.disks_listed);
+            disk::collect(&mut c.disks, relist, c.disk_usage_mode)
+        }) {
             for partition in &disk_data.partitions {
                 let safe_name = partition
                     .mount_point
@@ -213,6 +596,18 @@ impl SensorCollector {
                                 format!("{:.1}", partition.used_bytes as f64 / 1_073_741_824.0)
                             ),
                         );
+                        // A disk the OS didn't report free space for falls back to `available`
+                        let mode = match partition.reserved_bytes {
+                            Some(reserved) => {
+                                attrs.insert(
+                                    "reserved_gb".into(),
+                                    serde_json::json!(format!("{:.1}", reserved as f64 / 1_073_741_824.0)),
+                                );
+                                disk::DiskUsageMode::Filesystem
+                            }
+                            None => disk::DiskUsageMode::Available,
+                        };
+                        attrs.insert("calculation".into(), serde_json::json!(mode.description()));
                         attrs.insert("filesystem".into(), serde_json::json!(partition.filesystem));
                         attrs.insert("disk_type".into(), serde_json::json!(partition.disk_type));
                         attrs
@@ -222,20 +617,64 @@ impl SensorCollector {
             }
         }
 
+        // Which GPU of a hybrid laptop is spun up; before the GPU sensors, whose NVML
+        // queries wake a sleeping dGPU
+        let active_gpu_enabled = self.is_enabled("active_gpu");
+        let active = self.guarded("active_gpu", active_gpu_enabled, |_| gpu::active_gpu());
+        if let Some(active) = active.flatten() {
+            let gpus: Vec<serde_json::Value> = active
+                .gpus
+                .iter()
+                .map(|g| {
+                    serde_json::json!({
+                        "name": g.name,
+                        "integrated": g.integrated,
+                        "power_state": g.power_state,
+                    })
+                })
+                .collect();
+            sensors.push(SensorValue {
+                unique_id: "active_gpu".into(),
+                name: "Active GPU".into(),
+                state: serde_json::json!(active.state),
+                sensor_type: "sensor".into(),
+                device_class: None,
+                unit_of_measurement: None,
+                state_class: None,
+                icon: Some("mdi:expansion-card".into()),
+                attributes: {
+                    let mut attrs = HashMap::new();
+                    attrs.insert("gpus".into(), serde_json::json!(gpus));
+                    attrs
+                },
+                update_at_interval: true,
+            });
+        }
+
         // GPU sensors (dynamic)
-        if self.is_enabled("gpu") {
-            let gpu_data = gpu::collect();
-            for (i, gpu_info) in gpu_data.gpus.iter().enumerate() {
-                let suffix = if gpu_data.gpus.len() > 1 {
-                    format!("_{}", i)
-                } else {
-                    String::new()
-                };
+        let gpu_enabled = self.is_enabled("gpu");
+        let gpu_processes_enabled = self.is_enabled("gpu_process_count");
+        let gpu_memory_util_enabled = self.is_enabled("gpu_memory_util");
+        let gpu_pcie_enabled = self.is_enabled("gpu_pcie");
+        let gpu_throttle_enabled = self.is_enabled("gpu_throttle_reason");
+        let any_gpu_enabled = gpu_enabled
+            || gpu_processes_enabled
+            || gpu_memory_util_enabled
+            || gpu_pcie_enabled
+            || gpu_throttle_enabled;
+        let gpu_data = self.guarded("gpu", any_gpu_enabled, |c| {
+            gpu::collect(gpu_processes_enabled, gpu_pcie_enabled, c.temperature_range)
+        });
+        if let Some(gpu_data) = gpu_data {
+            let suffixes = gpu::entity_suffixes(&gpu_data.gpus, &self.gpu_ids);
+            for (gpu_info, suffix) in gpu_data.gpus.iter().zip(suffixes) {
+                // "_dgpu" -> " dgpu" for entity names
+                let name_suffix = suffix.replacen('_', " ", 1);
 
-                if let Some(usage) = gpu_info.usage_percent {
+                if let Some(usage) = gpu_info.usage_percent.filter(|_| gpu_enabled) {
                     sensors.push(SensorValue {
                         unique_id: format!("gpu_usage{}", suffix),
-                        name: format!("GPU Usage{}", if suffix.is_empty() { "".to_string() } else { format!(" {}", i) }),
+                        name: format!("GPU Usage{}", name_suffix),
                         state: serde_json::json!(format!("{:.1}", usage)),
                         sensor_type: "sensor".into(),
                         device_class: None,
@@ -247,25 +686,27 @@ impl SensorCollector {
                     });
                 }
 
-                if let Some(temp) = gpu_info.temperature {
+                if let Some(temp) = gpu_info.temperature.filter(|_| gpu_enabled) {
                     sensors.push(SensorValue {
                         unique_id: format!("gpu_temperature{}", suffix),
-                        name: format!("GPU Temperature{}", if suffix.is_empty() { "".to_string() } else { format!(" {}", i) }),
+                        name: format!("GPU Temperature{}", name_suffix),
                         state: serde_json::json!(format!("{:.1}", temp)),
                         sensor_type: "sensor".into(),
                         device_class: Some("temperature".into()),
                         unit_of_measurement: Some("°C".into()),
                         state_class: Some("measurement".into()),
                         icon: Some("mdi:thermometer".into()),
-                        attributes: HashMap::new(),
+                        attributes: temperature_source_attributes(
+                            gpu_info.temperature_source.as_deref(),
+                        ),
                         update_at_interval: true,
                     });
                 }
 
-                if let Some(vram_used) = gpu_info.vram_used_mb {
+                if let Some(vram_used) = gpu_info.vram_used_mb.filter(|_| gpu_enabled) {
                     sensors.push(SensorValue {
                         unique_id: format!("gpu_vram_used{}", suffix),
-                        name: format!("GPU VRAM Used{}", if suffix.is_empty() { "".to_string() } else { format!(" {}", i) }),
+                        name: format!("GPU VRAM Used{}", name_suffix),
                         state: serde_json::json!(format!("{:.0}", vram_used)),
                         sensor_type: "sensor".into(),
                         device_class: Some("data_size".into()),
@@ -276,105 +717,396 @@ impl SensorCollector {
                         update_at_interval: true,
                     });
                 }
+
+                // Memory util and PCIe throughput are NVIDIA only; other vendors get no sensor
+                if let Some(memory_util) = gpu_info.memory_util_percent.filter(|_| gpu_memory_util_enabled) {
+                    sensors.push(SensorValue {
+                        unique_id: format!("gpu_memory_util{}", suffix),
+                        name: format!("GPU Memory Utilization{}", name_suffix),
+                        state: serde_json::json!(format!("{:.1}", memory_util)),
+                        sensor_type: "sensor".into(),
+                        device_class: None,
+                        unit_of_measurement: Some("%".into()),
+                        state_class: Some("measurement".into()),
+                        icon: Some("mdi:memory".into()),
+                        attributes: HashMap::new(),
+                        update_at_interval: true,
+                    });
+                }
+
+                // NVIDIA only: why the clocks are held back, "none" when they aren't
+                if let Some(bits) = gpu_info.throttle_reasons.filter(|_| gpu_throttle_enabled) {
+                    let reasons = gpu::throttle_reasons(bits);
+                    let state = if reasons.is_empty() {
+                        "none".to_string()
+                    } else {
+                        reasons.iter().map(|(id, _)| *id).collect::<Vec<_>>().join(", ")
+                    };
+                    sensors.push(SensorValue {
+                        unique_id: format!("gpu_throttle_reason{}", suffix),
+                        name: format!("GPU Throttle Reason{}", name_suffix),
+                        state: serde_json::json!(state),
+                        sensor_type: "sensor".into(),
+                        device_class: None,
+                        unit_of_measurement: None,
+                        state_class: None,
+                        icon: Some("mdi:speedometer-slow".into()),
+                        attributes: {
+                            let mut attrs = HashMap::new();
+                            let descriptions: Vec<&str> = reasons.iter().map(|(_, d)| *d).collect();
+                            attrs.insert("reasons".into(), serde_json::json!(descriptions));
+                            attrs.insert("bitmask".into(), serde_json::json!(format!("0x{:x}", bits)));
+                            attrs
+                        },
+                        update_at_interval: true,
+                    });
+                }
+
+                let pcie = [
+                    ("gpu_pcie_tx", "GPU PCIe TX", gpu_info.pcie_tx_kbps, "mdi:upload"),
+                    ("gpu_pcie_rx", "GPU PCIe RX", gpu_info.pcie_rx_kbps, "mdi:download"),
+                ];
+                for (id, label, kbps, icon) in pcie {
+                    let Some(kbps) = kbps.filter(|_| gpu_pcie_enabled) else {
+                        continue;
+                    };
+                    sensors.push(SensorValue {
+                        unique_id: format!("{}{}", id, suffix),
+                        name: format!("{}{}", label, name_suffix),
+                        state: serde_json::json!(kbps),
+                        sensor_type: "sensor".into(),
+                        device_class: Some("data_rate".into()),
+                        unit_of_measurement: Some("kB/s".into()),
+                        state_class: Some("measurement".into()),
+                        icon: Some(icon.into()),
+                        attributes: HashMap::new(),
+                        update_at_interval: true,
+                    });
+                }
+
+                // Only NVIDIA reports processes; other vendors get no sensor
+                if let Some(ref processes) = gpu_info.processes {
+                    let top: Vec<serde_json::Value> = processes
+                        .iter()
+                        .take(MAX_GPU_TOP_PROCESSES)
+                        .map(|p| {
+                            serde_json::json!({
+                                "pid": p.pid,
+                                "name": p.name,
+                                "vram_used_mb": p.vram_used_mb,
+                            })
+                        })
+                        .collect();
+                    sensors.push(SensorValue {
+                        unique_id: format!("gpu_process_count{}", suffix),
+                        name: format!("GPU Process Count{}", name_suffix),
+                        state: serde_json::json!(processes.len()),
+                        sensor_type: "sensor".into(),
+                        device_class: None,
+                        unit_of_measurement: Some("processes".into()),
+                        state_class: Some("measurement".into()),
+                        icon: Some("mdi:application-cog".into()),
+                        attributes: {
+                            let mut attrs = HashMap::new();
+                            attrs.insert("top_processes".into(), serde_json::json!(top));
+                            attrs
+                        },
+                        update_at_interval: true,
+                    });
+                }
             }
         }
 
         // Network sensors (dynamic)
-        if self.is_enabled("network") {
-            let net_data = network::collect();
+        let traffic_enabled = self.is_enabled("network");
+        let addresses_enabled = self.is_enabled("network_addresses");
+        if let Some(net_data) = self.guarded("network", traffic_enabled || addresses_enabled, |c| {
+            let relist = relist_due(&mut c.networks_listed);
+            network::collect(&mut c.networks, relist)
+        }) {
             for iface in &net_data.interfaces {
                 let safe_name = iface.name.replace([' ', '/', '\\'], "_");
+                if traffic_enabled {
+                    sensors.push(SensorValue {
+                        unique_id: format!("network_rx_{}", safe_name),
+                        name: format!("Network RX {}", iface.name),
+                        state: serde_json::json!(iface.received_bytes),
+                        sensor_type: "sensor".into(),
+                        device_class: Some("data_size".into()),
+                        unit_of_measurement: Some("B".into()),
+                        state_class: Some("total_increasing".into()),
+                        icon: Some("mdi:download-network".into()),
+                        attributes: {
+                            let mut attrs = HashMap::new();
+                            attrs.insert("mac_address".into(), serde_json::json!(iface.mac_address));
+                            attrs.insert(
+                                "ip_addresses".into(),
+                                serde_json::json!(iface.ip_addresses),
+                            );
+                            attrs
+                        },
+                        update_at_interval: true,
+                    });
+
+                    sensors.push(SensorValue {
+                        unique_id: format!("network_tx_{}", safe_name),
+                        name: format!("Network TX {}", iface.name),
+                        state: serde_json::json!(iface.transmitted_bytes),
+                        sensor_type: "sensor".into(),
+                        device_class: Some("data_size".into()),
+                        unit_of_measurement: Some("B".into()),
+                        state_class: Some("total_increasing".into()),
+                        icon: Some("mdi:upload-network".into()),
+                        attributes: HashMap::new(),
+                        update_at_interval: true,
+                    });
+                }
+
+                // Primary address per family, so templates don't have to pick from the list.
+                // Only families the interface has a global address in get a sensor, so
+                // loopback, unplugged and IPv4-only interfaces don't report empty ones.
+                if addresses_enabled {
+                    let families = [("IPv4", &iface.primary_ipv4), ("IPv6", &iface.primary_ipv6)];
+                    for (family, address) in families {
+                        let Some(address) = address else {
+                            continue;
+                        };
+                        sensors.push(SensorValue {
+                            unique_id: format!("network_{}_{}", family.to_lowercase(), safe_name),
+                            name: format!("{} Address {}", family, iface.name),
+                            state: serde_json::json!(address),
+                            sensor_type: "sensor".into(),
+                            device_class: None,
+                            unit_of_measurement: None,
+                            state_class: None,
+                            icon: Some("mdi:ip-network".into()),
+                            attributes: {
+                                let mut attrs = HashMap::new();
+                                attrs.insert(
+                                    "ip_addresses".into(),
+                                    serde_json::json!(iface.ip_addresses),
+                                );
+                                attrs
+                            },
+                            update_at_interval: true,
+                        });
+                    }
+                }
+            }
+        }
+
+        // Active connection medium and Wi-Fi signal (dynamic) — collect once, reuse
+        let conn_enabled = self.is_enabled("network_connection_type") || self.is_enabled("wifi_signal");
+        if let Some(conn) = self.guarded("network", conn_enabled, |_| connection::collect()) {
+            // Connection type; SSID and signal as attributes on Wi-Fi
+            if self.is_enabled("network_connection_type") {
+                use connection::ConnectionType;
                 sensors.push(SensorValue {
-                    unique_id: format!("network_rx_{}", safe_name),
-                    name: format!("Network RX {}", iface.name),
-                    state: serde_json::json!(iface.received_bytes),
+                    unique_id: "network_connection_type".into(),
+                    name: "Network Connection Type".into(),
+                    state: serde_json::json!(conn.connection_type.as_str()),
                     sensor_type: "sensor".into(),
-                    device_class: Some("data_size".into()),
-                    unit_of_measurement: Some("B".into()),
-                    state_class: Some("total_increasing".into()),
-                    icon: Some("mdi:download-network".into()),
+                    device_class: None,
+                    unit_of_measurement: None,
+                    state_class: None,
+                    icon: Some(
+                        match conn.connection_type {
+                            ConnectionType::Ethernet => "mdi:ethernet",
+                            ConnectionType::Wifi => "mdi:wifi",
+                            ConnectionType::Cellular => "mdi:signal-cellular-3",
+                            ConnectionType::Unknown => "mdi:help-network",
+                        }
+                        .into(),
+                    ),
                     attributes: {
                         let mut attrs = HashMap::new();
-                        attrs.insert("mac_address".into(), serde_json::json!(iface.mac_address));
-                        attrs.insert(
-                            "ip_addresses".into(),
-                            serde_json::json!(iface.ip_addresses),
-                        );
+                        if let Some(interface) = &conn.interface {
+                            attrs.insert("interface".into(), serde_json::json!(interface));
+                        }
+                        if let Some(wifi) = &conn.wifi {
+                            if let Some(ssid) = &wifi.ssid {
+                                attrs.insert("ssid".into(), serde_json::json!(ssid));
+                            }
+                            if let Some(dbm) = wifi.signal_dbm {
+                                attrs.insert("signal_dbm".into(), serde_json::json!(dbm));
+                            }
+                            if let Some(percent) = wifi.signal_percent {
+                                attrs.insert("signal_percent".into(), serde_json::json!(percent));
+                            }
+                        }
                         attrs
                     },
                     update_at_interval: true,
                 });
-
-                sensors.push(SensorValue {
-                    unique_id: format!("network_tx_{}", safe_name),
-                    name: format!("Network TX {}", iface.name),
-                    state: serde_json::json!(iface.transmitted_bytes),
-                    sensor_type: "sensor".into(),
-                    device_class: Some("data_size".into()),
-                    unit_of_measurement: Some("B".into()),
-                    state_class: Some("total_increasing".into()),
-                    icon: Some("mdi:upload-network".into()),
-                    attributes: HashMap::new(),
-                    update_at_interval: true,
-                });
             }
-        }
-
-        // Battery sensors (dynamic)
-        if self.is_enabled("battery") {
-            let battery_data = battery::collect();
-            for (i, bat) in battery_data.batteries.iter().enumerate() {
-                let suffix = if battery_data.batteries.len() > 1 {
-                    format!("_{}", i)
-                } else {
-                    String::new()
-                };
 
+            // Wi-Fi signal; omitted when not on Wi-Fi or the RSSI can't be read
+            let wifi = conn.wifi.filter(|_| self.is_enabled("wifi_signal"));
+            if let Some((wifi, dbm)) = wifi.and_then(|w| w.signal_dbm.map(|dbm| (w, dbm))) {
                 sensors.push(SensorValue {
-                    unique_id: format!("battery_level{}", suffix),
-                    name: format!("Battery Level{}", if suffix.is_empty() { "".to_string() } else { format!(" {}", i) }),
-                    state: serde_json::json!(format!("{:.0}", bat.percentage)),
+                    unique_id: "wifi_signal".into(),
+                    name: "Wi-Fi Signal".into(),
+                    state: serde_json::json!(dbm),
                     sensor_type: "sensor".into(),
-                    device_class: Some("battery".into()),
-                    unit_of_measurement: Some("%".into()),
+                    device_class: Some("signal_strength".into()),
+                    unit_of_measurement: Some("dBm".into()),
                     state_class: Some("measurement".into()),
-                    icon: Some("mdi:battery".into()),
+                    icon: Some("mdi:wifi".into()),
                     attributes: {
                         let mut attrs = HashMap::new();
-                        attrs.insert("state".into(), serde_json::json!(bat.state));
-                        if let Some(health) = bat.state_of_health {
-                            attrs.insert(
-                                "state_of_health".into(),
-                                serde_json::json!(format!("{:.0}%", health)),
-                            );
+                        if let Some(ssid) = &wifi.ssid {
+                            attrs.insert("ssid".into(), serde_json::json!(ssid));
+                        }
+                        if let Some(bssid) = &wifi.bssid {
+                            attrs.insert("bssid".into(), serde_json::json!(bssid));
                         }
-                        if let Some(cycles) = bat.cycle_count {
-                            attrs.insert("cycle_count".into(), serde_json::json!(cycles));
+                        if let Some(channel) = wifi.channel {
+                            attrs.insert("channel".into(), serde_json::json!(channel));
+                        }
+                        if let Some(percent) = wifi.signal_percent {
+                            attrs.insert("signal_percent".into(), serde_json::json!(percent));
                         }
                         attrs
                     },
                     update_at_interval: true,
                 });
+            }
+        }
+
+        // Battery & AC power sensors (dynamic) — collect once, reuse
+        let power_enabled = self.is_enabled("battery") || self.is_enabled("on_ac_power");
+        let battery_data =
+            self.guarded("power", power_enabled, |c| battery::collect(&c.hidden_batteries));
+        if let Some(battery_data) = battery_data {
+            self.on_ac_power = Some(battery_data.on_ac_power);
+            if self.is_enabled("battery") {
+                let now = Instant::now();
+                let batteries: Vec<battery::BatteryInfo> = battery_data
+                    .batteries
+                    .iter()
+                    .filter(|b| !battery::is_hidden(b, &self.hidden_batteries))
+                    .cloned()
+                    .collect();
+                let suffixes = battery::entity_suffixes(&batteries, &self.battery_ids);
+                self.battery_history.retain(|suffix, _| suffixes.contains(suffix));
+                for (bat, suffix) in batteries.iter().zip(suffixes) {
+                    let history = self.battery_history.entry(suffix.clone()).or_default();
+                    history.record(bat, now);
+                    // "_mouse" -> " mouse" for entity names
+                    let name_suffix = suffix.replacen('_', " ", 1);
+
+                    sensors.push(SensorValue {
+                        unique_id: format!("battery_level{}", suffix),
+                        name: format!("Battery Level{}", name_suffix),
+                        state: serde_json::json!(format!("{:.0}", bat.percentage)),
+                        sensor_type: "sensor".into(),
+                        device_class: Some("battery".into()),
+                        unit_of_measurement: Some("%".into()),
+                        state_class: Some("measurement".into()),
+                        icon: Some("mdi:battery".into()),
+                        attributes: {
+                            let mut attrs = HashMap::new();
+                            attrs.insert("state".into(), serde_json::json!(bat.state));
+                            if let Some(health) = bat.state_of_health {
+                                attrs.insert(
+                                    "state_of_health".into(),
+                                    serde_json::json!(format!("{:.0}%", health)),
+                                );
+                            }
+                            if let Some(cycles) = bat.cycle_count {
+                                attrs.insert("cycle_count".into(), serde_json::json!(cycles));
+                            }
+                            if let Some(rate) = history.rate_per_hour() {
+                                attrs.insert(
+                                    "rate_per_hour".into(),
+                                    serde_json::json!(format!("{:.1}", rate)),
+                                );
+                            }
+                            if let Some(minutes) = history.minutes_remaining() {
+                                attrs.insert(
+                                    "estimated_minutes_remaining".into(),
+                                    serde_json::json!(minutes.round() as u64),
+                                );
+                            }
+                            attrs
+                        },
+                        update_at_interval: true,
+                    });
+
+                    sensors.push(SensorValue {
+                        unique_id: format!("battery_charging{}", suffix),
+                        name: format!("Battery Charging{}", name_suffix),
+                        state: serde_json::json!(bat.is_charging),
+                        sensor_type: "binary_sensor".into(),
+                        device_class: Some("battery_charging".into()),
+                        unit_of_measurement: None,
+                        state_class: None,
+                        icon: Some("mdi:battery-charging".into()),
+                        attributes: HashMap::new(),
+                        update_at_interval: true,
+                    });
+                }
+            }
 
+            if self.is_enabled("on_ac_power") {
                 sensors.push(SensorValue {
-                    unique_id: format!("battery_charging{}", suffix),
-                    name: format!("Battery Charging{}", if suffix.is_empty() { "".to_string() } else { format!(" {}", i) }),
-                    state: serde_json::json!(bat.is_charging),
+                    unique_id: "on_ac_power".into(),
+                    name: "On AC Power".into(),
+                    state: serde_json::json!(battery_data.on_ac_power),
                     sensor_type: "binary_sensor".into(),
-                    device_class: Some("battery_charging".into()),
+                    device_class: Some("plug".into()),
                     unit_of_measurement: None,
                     state_class: None,
-                    icon: Some("mdi:battery-charging".into()),
+                    icon: Some("mdi:power-plug".into()),
                     attributes: HashMap::new(),
                     update_at_interval: true,
                 });
             }
         }
 
-        // System uptime & process count (dynamic)
-        if self.is_enabled("system_uptime") || self.is_enabled("process_count") {
-            let dyn_info = system_info::collect_dynamic();
+        // Laptop lid (dynamic); omitted on machines without a lid
+        let lid_open = self.guarded("power", self.is_enabled("lid_open"), |_| lid::lid_open());
+        if let Some(open) = lid_open.flatten() {
+            sensors.push(SensorValue {
+                unique_id: "lid_open".into(),
+                name: "Lid Open".into(),
+                state: serde_json::json!(open),
+                sensor_type: "binary_sensor".into(),
+                device_class: Some("opening".into()),
+                unit_of_measurement: None,
+                state_class: None,
+                icon: Some("mdi:laptop".into()),
+                attributes: HashMap::new(),
+                update_at_interval: true,
+            });
+        }
+
+        // Active power plan/profile (dynamic); "unknown" where it can't be detected
+        if let Some(plan) = self.guarded("power", self.is_enabled("power_plan"), |_| power_plan::collect()) {
+            sensors.push(SensorValue {
+                unique_id: "power_plan".into(),
+                name: "Power Plan".into(),
+                state: serde_json::json!(plan.name),
+                sensor_type: "sensor".into(),
+                device_class: None,
+                unit_of_measurement: None,
+                state_class: None,
+                icon: Some("mdi:lightning-bolt-circle".into()),
+                attributes: {
+                    let mut attrs = HashMap::new();
+                    attrs.insert("source".into(), serde_json::json!(plan.source));
+                    attrs
+                },
+                update_at_interval: true,
+            });
+        }
 
+        // System uptime & process count (dynamic)
+        let system_enabled = self.is_enabled("system_uptime")
+            || self.is_enabled("total_uptime")
+            || self.is_enabled("process_count")
+            || self.is_enabled("thread_count");
+        if let Some(dyn_info) = self.guarded("system", system_enabled, |_| system_info::collect_dynamic()) {
             if self.is_enabled("system_uptime") {
                 let hours = dyn_info.uptime_seconds / 3600;
                 let minutes = (dyn_info.uptime_seconds % 3600) / 60;
@@ -399,6 +1131,30 @@ impl SensorCollector {
                 });
             }
 
+            // Summed over boots; the ledger persists across restarts of the app
+            if self.is_enabled("total_uptime") {
+                let total = self
+                    .uptime_ledger
+                    .update(dyn_info.boot_time, dyn_info.uptime_seconds);
+                sensors.push(SensorValue {
+                    unique_id: "total_uptime".into(),
+                    name: "Total Uptime".into(),
+                    state: serde_json::json!(total),
+                    sensor_type: "sensor".into(),
+                    device_class: Some("duration".into()),
+                    unit_of_measurement: Some("s".into()),
+                    state_class: Some("total_increasing".into()),
+                    icon: Some("mdi:timer-sand".into()),
+                    attributes: {
+                        let mut attrs = HashMap::new();
+                        attrs.insert("boots".into(), serde_json::json!(self.uptime_ledger.boots));
+                        attrs.insert("days".into(), serde_json::json!(total / 86400));
+                        attrs
+                    },
+                    update_at_interval: true,
+                });
+            }
+
             if self.is_enabled("process_count") {
                 sensors.push(SensorValue {
                     unique_id: "process_count".into(),
@@ -413,27 +1169,443 @@ impl SensorCollector {
                     update_at_interval: true,
                 });
             }
-        }
-
-        sensors
-    }
 
-    /// Collect static sensors — only at startup
-    pub fn collect_static(&mut self) -> Vec<SensorValue> {
-        let mut sensors = Vec::new();
+            if let Some(threads) = dyn_info.thread_count.filter(|_| self.is_enabled("thread_count")) {
+                sensors.push(SensorValue {
+                    unique_id: "thread_count".into(),
+                    name: "Thread Count".into(),
+                    state: serde_json::json!(threads),
+                    sensor_type: "sensor".into(),
+                    device_class: None,
+                    unit_of_measurement: Some("threads".into()),
+                    state_class: Some("measurement".into()),
+                    icon: Some("mdi:format-list-numbered".into()),
+                    attributes: HashMap::new(),
+                    update_at_interval: true,
+                });
+            }
+        }
 
-        // CPU model (static)
-        if self.is_enabled("cpu_model") {
-            let cpu_data = cpu::collect(&self.sys);
-            sensors.push(SensorValue {
-                unique_id: "cpu_model".into(),
-                name: "CPU Model".into(),
-                state: serde_json::json!(cpu_data.model),
-                sensor_type: "sensor".into(),
-                device_class: None,
-                unit_of_measurement: None,
-                state_class: None,
-                icon: Some("mdi:cpu-64-bit".into()),
+        // The companion app's own CPU/RAM (dynamic); its process was refreshed with the rest
+        let own_enabled = self.is_enabled("companion_cpu") || self.is_enabled("companion_memory");
+        if let Some(own) = self.guarded("system", own_enabled, |c| system_info::own_usage(&c.sys)).flatten() {
+            if self.is_enabled("companion_cpu") {
+                sensors.push(SensorValue {
+                    unique_id: "companion_cpu".into(),
+                    name: "Companion CPU Usage".into(),
+                    state: serde_json::json!(format!("{:.1}", own.cpu_percent)),
+                    sensor_type: "sensor".into(),
+                    device_class: None,
+                    unit_of_measurement: Some("%".into()),
+                    state_class: Some("measurement".into()),
+                    icon: Some("mdi:application-cog".into()),
+                    attributes: HashMap::new(),
+                    update_at_interval: true,
+                });
+            }
+            if self.is_enabled("companion_memory") {
+                sensors.push(SensorValue {
+                    unique_id: "companion_memory".into(),
+                    name: "Companion Memory Usage".into(),
+                    state: serde_json::json!(format!("{:.1}", own.memory_mb)),
+                    sensor_type: "sensor".into(),
+                    device_class: Some("data_size".into()),
+                    unit_of_measurement: Some("MB".into()),
+                    state_class: Some("measurement".into()),
+                    icon: Some("mdi:application-cog".into()),
+                    attributes: HashMap::new(),
+                    update_at_interval: true,
+                });
+            }
+        }
+
+        // Active sessions (dynamic)
+        let sessions = self.guarded("system", self.is_enabled("session_count"), |_| {
+            system_info::collect_sessions()
+        });
+        if let Some(sessions) = sessions.flatten() {
+            sensors.push(SensorValue {
+                unique_id: "session_count".into(),
+                name: "Session Count".into(),
+                state: serde_json::json!(sessions.count),
+                sensor_type: "sensor".into(),
+                device_class: None,
+                unit_of_measurement: Some("sessions".into()),
+                state_class: Some("measurement".into()),
+                icon: Some("mdi:account-multiple".into()),
+                attributes: {
+                    let mut attrs = HashMap::new();
+                    attrs.insert("users".into(), serde_json::json!(sessions.users));
+                    attrs
+                },
+                update_at_interval: true,
+            });
+        }
+
+        // Remote host sensors (dynamic, over SSH)
+        let remote_enabled = self.is_enabled("remote_hosts") && !self.remote_hosts.is_empty();
+        sensors.extend(self.guarded("remote", remote_enabled, |c| c.collect_remote()).unwrap_or_default());
+
+        // Last clipboard change (dynamic, opt-in). Only the time is reported, never the contents.
+        if self.is_enabled("last_clipboard_change") {
+            let watcher = self.clipboard.get_or_insert_with(clipboard::ClipboardWatcher::start);
+            sensors.push(SensorValue {
+                unique_id: "last_clipboard_change".into(),
+                name: "Last Clipboard Change".into(),
+                state: match watcher.last_change() {
+                    Some(ts) => serde_json::json!(chrono_from_timestamp(ts)),
+                    None => serde_json::json!(null),
+                },
+                sensor_type: "sensor".into(),
+                device_class: Some("timestamp".into()),
+                unit_of_measurement: None,
+                state_class: None,
+                icon: Some("mdi:clipboard-text-clock-outline".into()),
+                attributes: HashMap::new(),
+                update_at_interval: true,
+            });
+        } else {
+            // Dropping the watcher stops its thread
+            self.clipboard = None;
+        }
+
+        // Pending OS updates (dynamic): cached result of the background check.
+        // Omitted until the first check finished, and where no update source is available.
+        if self.is_enabled("os_updates_pending") {
+            let check = self.update_check.get_or_insert_with(updates::start_update_check);
+            if let Some(pending) = check.latest() {
+                let mut attrs = HashMap::new();
+                attrs.insert(
+                    "packages".into(),
+                    serde_json::json!(pending
+                        .packages
+                        .iter()
+                        .take(MAX_UPDATE_PACKAGES_ATTRIBUTE)
+                        .collect::<Vec<_>>()),
+                );
+                attrs.insert("source".into(), serde_json::json!(pending.source));
+                sensors.push(SensorValue {
+                    unique_id: "os_updates_pending".into(),
+                    name: "OS Updates Pending".into(),
+                    state: serde_json::json!(pending.count),
+                    sensor_type: "sensor".into(),
+                    device_class: None,
+                    unit_of_measurement: Some("updates".into()),
+                    state_class: Some("measurement".into()),
+                    icon: Some("mdi:package-up".into()),
+                    attributes: attrs,
+                    update_at_interval: true,
+                });
+            }
+        } else {
+            self.update_check = None;
+        }
+
+        // Reboot required (dynamic): cached result of the background check, omitted where
+        // it can't be detected
+        if self.is_enabled("reboot_required") {
+            let check = self.reboot_check.get_or_insert_with(updates::start_reboot_check);
+            if let Some(required) = check.latest() {
+                sensors.push(SensorValue {
+                    unique_id: "reboot_required".into(),
+                    name: "Reboot Required".into(),
+                    state: serde_json::json!(required),
+                    sensor_type: "binary_sensor".into(),
+                    device_class: Some("problem".into()),
+                    unit_of_measurement: None,
+                    state_class: None,
+                    icon: Some("mdi:restart-alert".into()),
+                    attributes: HashMap::new(),
+                    update_at_interval: true,
+                });
+            }
+        } else {
+            self.reboot_check = None;
+        }
+
+        // Lock keys and keyboard backlight (dynamic, opt-in); unsupported ones are omitted
+        if let Some(kb) = self.guarded("activity", self.is_enabled("keyboard"), |_| keyboard::collect()) {
+            let locks = [
+                ("caps_lock", "Caps Lock", kb.caps_lock, "mdi:keyboard-caps"),
+                ("num_lock", "Num Lock", kb.num_lock, "mdi:numeric"),
+                ("scroll_lock", "Scroll Lock", kb.scroll_lock, "mdi:mouse-scroll-wheel"),
+            ];
+            for (id, name, state, icon) in locks {
+                if let Some(on) = state {
+                    sensors.push(SensorValue {
+                        unique_id: id.into(),
+                        name: name.into(),
+                        state: serde_json::json!(on),
+                        sensor_type: "binary_sensor".into(),
+                        device_class: None,
+                        unit_of_measurement: None,
+                        state_class: None,
+                        icon: Some(icon.into()),
+                        attributes: HashMap::new(),
+                        update_at_interval: true,
+                    });
+                }
+            }
+            if let Some(level) = kb.backlight_percent {
+                sensors.push(SensorValue {
+                    unique_id: "keyboard_backlight".into(),
+                    name: "Keyboard Backlight".into(),
+                    state: serde_json::json!(format!("{:.0}", level)),
+                    sensor_type: "sensor".into(),
+                    device_class: None,
+                    unit_of_measurement: Some("%".into()),
+                    state_class: Some("measurement".into()),
+                    icon: Some("mdi:keyboard-settings".into()),
+                    attributes: HashMap::new(),
+                    update_at_interval: true,
+                });
+            }
+        }
+
+        // Seconds since the last keyboard/mouse input (dynamic, opt-in)
+        let idle = self.guarded("activity", self.is_enabled("user_idle_seconds"), |_| {
+            idle::idle_seconds()
+        });
+        if let Some(seconds) = idle.flatten() {
+            sensors.push(SensorValue {
+                unique_id: "user_idle_seconds".into(),
+                name: "User Idle Time".into(),
+                state: serde_json::json!(seconds),
+                sensor_type: "sensor".into(),
+                device_class: Some("duration".into()),
+                unit_of_measurement: Some("s".into()),
+                state_class: Some("measurement".into()),
+                icon: Some("mdi:timer-sand".into()),
+                attributes: HashMap::new(),
+                update_at_interval: true,
+            });
+        }
+
+        // User-defined command/file sensors (dynamic)
+        let custom_enabled = self.is_enabled("custom_sensors") && !self.custom_sensors.is_empty();
+        sensors.extend(self.guarded("custom", custom_enabled, |c| c.collect_custom()).unwrap_or_default());
+
+        // Dynamic sensors are collected last in every cycle, so the cycle is complete here
+        self.last_failures = std::mem::take(&mut self.cycle_failures);
+
+        // Sensor groups that failed to collect (dynamic), to tell them apart from disabled ones
+        if self.is_enabled("collection_errors") {
+            sensors.push(SensorValue {
+                unique_id: "collection_errors".into(),
+                name: "Collection Errors".into(),
+                state: serde_json::json!(self.last_failures.len()),
+                sensor_type: "sensor".into(),
+                device_class: None,
+                unit_of_measurement: None,
+                state_class: Some("measurement".into()),
+                icon: Some("mdi:alert-circle-outline".into()),
+                attributes: {
+                    let mut attrs = HashMap::new();
+                    attrs.insert(
+                        "failed_groups".into(),
+                        serde_json::json!(self.last_failures.keys().collect::<Vec<_>>()),
+                    );
+                    attrs.insert("errors".into(), serde_json::json!(self.last_failures));
+                    attrs
+                },
+                update_at_interval: true,
+            });
+        }
+
+        sensors
+    }
+
+    /// Report fake states in the next dynamic collections (see `simulate_failure`)
+    pub fn simulate(&mut self, failure: SimulatedFailure) {
+        self.simulation = Some(failure);
+    }
+
+    /// Fake the simulated failure in the states of a regular update cycle and count it down.
+    /// Only the update loop calls this, after `collect_dynamic` remembered the real states, so
+    /// support bundles, validation and one-off sends never see or use up the fake states.
+    pub fn apply_simulation(&mut self, sensors: &mut [SensorValue]) {
+        let Some(simulation) = self.simulation.as_mut() else {
+            return;
+        };
+        match simulation.kind {
+            FailureKind::Unavailable => {
+                for sensor in sensors.iter_mut() {
+                    sensor.state = serde_json::json!("unavailable");
+                    sensor.attributes.clear();
+                }
+            }
+            FailureKind::Spike => {
+                let spiked = sensors.iter_mut().find(|s| s.unique_id == simulation.sensor_id);
+                if let Some(sensor) = spiked {
+                    sensor.state = serde_json::json!(simulation.value);
+                }
+            }
+            FailureKind::FailUpdates => {}
+        }
+        simulation.remaining = simulation.remaining.saturating_sub(1);
+        log::warn!(
+            "[Collector] Simulated {:?} ({} more to go)",
+            simulation.kind,
+            simulation.remaining
+        );
+        if simulation.remaining == 0 {
+            self.simulation = None;
+        }
+    }
+
+    /// Run each custom sensor (unless its interval hasn't elapsed); failures report "unavailable"
+    fn collect_custom(&mut self) -> Vec<SensorValue> {
+        let mut sensors = Vec::new();
+        let mut failures = Vec::new();
+
+        for sensor in &self.custom_sensors {
+            let unique_id = sensor.unique_id();
+            let cached = self.custom_last.get(&unique_id).filter(|(at, _)| {
+                sensor
+                    .interval
+                    .is_some_and(|secs| at.elapsed().as_secs() < secs)
+            });
+            let state = match cached {
+                Some((_, state)) => state.clone(),
+                None => {
+                    let state = match custom::read_state(sensor) {
+                        Ok(s) => serde_json::json!(s),
+                        Err(e) => {
+                            log::warn!("[Custom] Sensor '{}' failed: {}", sensor.id, e);
+                            failures.push(format!("{}: {}", sensor.id, e));
+                            serde_json::json!("unavailable")
+                        }
+                    };
+                    self.custom_last
+                        .insert(unique_id.clone(), (Instant::now(), state.clone()));
+                    state
+                }
+            };
+
+            sensors.push(SensorValue {
+                unique_id,
+                name: sensor.name.clone(),
+                state,
+                sensor_type: "sensor".into(),
+                device_class: None,
+                unit_of_measurement: sensor.unit.clone(),
+                // A unit implies a numeric value, so keep long-term statistics for it
+                state_class: sensor.unit.as_ref().map(|_| "measurement".into()),
+                icon: Some(sensor.icon.clone().unwrap_or_else(|| "mdi:console".into())),
+                attributes: HashMap::new(),
+                update_at_interval: true,
+            });
+        }
+
+        for failure in failures {
+            self.group_failed("custom", failure);
+        }
+        sensors
+    }
+
+    /// Collect CPU/memory/disk for each configured remote host, namespaced per host
+    fn collect_remote(&mut self) -> Vec<SensorValue> {
+        let mut sensors = Vec::new();
+        let mut unreachable = Vec::new();
+
+        let results = remote::collect_all(&self.remote_hosts, &mut self.remote_cpu_prev);
+        for (host, data) in self.remote_hosts.iter().zip(results) {
+            let host_id = host.id();
+            let display = host.display_name().to_string();
+            let data = match data {
+                Some(d) => d,
+                None => {
+                    unreachable.push(display);
+                    continue;
+                }
+            };
+
+            let mut host_attrs = HashMap::new();
+            host_attrs.insert("host".to_string(), serde_json::json!(host.host));
+
+            if let Some(cpu_usage) = data.cpu_usage_percent {
+                sensors.push(SensorValue {
+                    unique_id: format!("remote_{}_cpu_usage", host_id),
+                    name: format!("{} CPU Usage", display),
+                    state: serde_json::json!(format!("{:.1}", cpu_usage)),
+                    sensor_type: "sensor".into(),
+                    device_class: None,
+                    unit_of_measurement: Some("%".into()),
+                    state_class: Some("measurement".into()),
+                    icon: Some("mdi:cpu-64-bit".into()),
+                    attributes: host_attrs.clone(),
+                    update_at_interval: true,
+                });
+            }
+
+            sensors.push(SensorValue {
+                unique_id: format!("remote_{}_memory_usage", host_id),
+                name: format!("{} Memory Usage", display),
+                state: match data.memory_usage_percent {
+                    Some(usage) => serde_json::json!(format!("{:.1}", usage)),
+                    None => serde_json::json!("unavailable"),
+                },
+                sensor_type: "sensor".into(),
+                device_class: None,
+                unit_of_measurement: Some("%".into()),
+                state_class: Some("measurement".into()),
+                icon: Some("mdi:memory".into()),
+                attributes: {
+                    let mut attrs = host_attrs.clone();
+                    attrs.insert(
+                        "total_gb".into(),
+                        serde_json::json!(format!("{:.1}", data.memory_total_bytes as f64 / 1_073_741_824.0)),
+                    );
+                    if let Some(available) = data.memory_available_bytes {
+                        attrs.insert(
+                            "available_gb".into(),
+                            serde_json::json!(format!("{:.1}", available as f64 / 1_073_741_824.0)),
+                        );
+                    }
+                    attrs
+                },
+                update_at_interval: true,
+            });
+
+            if let Some(disk_usage) = data.root_disk_usage_percent {
+                sensors.push(SensorValue {
+                    unique_id: format!("remote_{}_disk_usage", host_id),
+                    name: format!("{} Disk Usage /", display),
+                    state: serde_json::json!(format!("{:.1}", disk_usage)),
+                    sensor_type: "sensor".into(),
+                    device_class: None,
+                    unit_of_measurement: Some("%".into()),
+                    state_class: Some("measurement".into()),
+                    icon: Some("mdi:harddisk".into()),
+                    attributes: host_attrs.clone(),
+                    update_at_interval: true,
+                });
+            }
+        }
+
+        for host in unreachable {
+            self.group_failed("remote", format!("{}: no data (unreachable or SSH failed)", host));
+        }
+        sensors
+    }
+
+    /// Collect static sensors — only at startup
+    pub fn collect_static(&mut self) -> Vec<SensorValue> {
+        let mut sensors = Vec::new();
+
+        // CPU model (static)
+        if let Some(cpu_data) = self.guarded("cpu", self.is_enabled("cpu_model"), |c| {
+            cpu::collect(&c.sys, false, c.temperature_range)
+        }) {
+            sensors.push(SensorValue {
+                unique_id: "cpu_model".into(),
+                name: "CPU Model".into(),
+                state: serde_json::json!(cpu_data.model),
+                sensor_type: "sensor".into(),
+                device_class: None,
+                unit_of_measurement: None,
+                state_class: None,
+                icon: Some("mdi:cpu-64-bit".into()),
                 attributes: {
                     let mut attrs = HashMap::new();
                     attrs.insert("core_count".into(), serde_json::json!(cpu_data.core_count));
@@ -447,8 +1619,127 @@ impl SensorCollector {
             });
         }
 
-        // System info (static)
-        let sys_info = system_info::collect();
+        // CPU architecture and vendor (static)
+        let identity_enabled = self.is_enabled("cpu_architecture") || self.is_enabled("cpu_vendor");
+        if let Some(identity) = self.guarded("cpu", identity_enabled, |c| cpu::identity(&c.sys)) {
+            if self.is_enabled("cpu_architecture") {
+                let mut attrs = HashMap::new();
+                if let Some(ref features) = identity.features {
+                    attrs.insert("features".into(), serde_json::json!(features));
+                }
+                sensors.push(SensorValue {
+                    unique_id: "cpu_architecture".into(),
+                    name: "CPU Architecture".into(),
+                    state: serde_json::json!(identity.architecture),
+                    sensor_type: "sensor".into(),
+                    device_class: None,
+                    unit_of_measurement: None,
+                    state_class: None,
+                    icon: Some("mdi:chip".into()),
+                    attributes: attrs,
+                    update_at_interval: false,
+                });
+            }
+
+            if self.is_enabled("cpu_vendor") {
+                sensors.push(SensorValue {
+                    unique_id: "cpu_vendor".into(),
+                    name: "CPU Vendor".into(),
+                    state: serde_json::json!(identity.vendor),
+                    sensor_type: "sensor".into(),
+                    device_class: None,
+                    unit_of_measurement: None,
+                    state_class: None,
+                    icon: Some("mdi:factory".into()),
+                    attributes: HashMap::new(),
+                    update_at_interval: false,
+                });
+            }
+        }
+
+        // GPU model (static)
+        if let Some(gpu_data) = self.guarded("gpu", self.is_enabled("gpu"), |c| {
+            gpu::collect(false, false, c.temperature_range)
+        }) {
+            let suffixes = gpu::entity_suffixes(&gpu_data.gpus, &self.gpu_ids);
+            for (gpu_info, suffix) in gpu_data.gpus.iter().zip(suffixes) {
+                // "_dgpu" -> " dgpu" for entity names
+                let name_suffix = suffix.replacen('_', " ", 1);
+
+                sensors.push(SensorValue {
+                    unique_id: format!("gpu_model{}", suffix),
+                    name: format!("GPU Model{}", name_suffix),
+                    state: serde_json::json!(gpu_info.name),
+                    sensor_type: "sensor".into(),
+                    device_class: None,
+                    unit_of_measurement: None,
+                    state_class: None,
+                    icon: Some("mdi:expansion-card".into()),
+                    attributes: {
+                        let mut attrs = HashMap::new();
+                        attrs.insert("vendor".into(), serde_json::json!(gpu_info.vendor));
+                        if let Some(ref driver) = gpu_info.driver_version {
+                            attrs.insert("driver_version".into(), serde_json::json!(driver));
+                        }
+                        if let Some(vram) = gpu_info.vram_total_mb {
+                            attrs.insert("vram_total_mb".into(), serde_json::json!(vram));
+                        }
+                        attrs
+                    },
+                    update_at_interval: false,
+                });
+            }
+        }
+
+        // RAM total (static)
+        let mem_data = self.guarded("memory", self.is_enabled("memory_total"), |c| {
+            memory::collect(&c.sys, c.memory_used_mode)
+        });
+        if let Some(mem_data) = mem_data {
+            sensors.push(SensorValue {
+                unique_id: "memory_total".into(),
+                name: "Memory Total".into(),
+                state: serde_json::json!(format!("{:.1}", mem_data.total_gb)),
+                sensor_type: "sensor".into(),
+                device_class: Some("data_size".into()),
+                unit_of_measurement: Some("GB".into()),
+                state_class: None,
+                icon: Some("mdi:memory".into()),
+                attributes: HashMap::new(),
+                update_at_interval: false,
+            });
+        }
+
+        // Swap/pagefile locations and sizes (static); no sensor without swap
+        let swap_areas = self
+            .guarded("memory", self.is_enabled("swap_config"), |_| memory::swap_config())
+            .flatten()
+            .filter(|areas| !areas.is_empty());
+        if let Some(areas) = swap_areas {
+            let total_mb: u64 = areas.iter().filter_map(|a| a.size_mb).sum();
+            sensors.push(SensorValue {
+                unique_id: "swap_config".into(),
+                name: "Swap Configuration".into(),
+                state: serde_json::json!(areas.len()),
+                sensor_type: "sensor".into(),
+                device_class: None,
+                unit_of_measurement: None,
+                state_class: None,
+                icon: Some("mdi:file-swap".into()),
+                attributes: {
+                    let mut attrs = HashMap::new();
+                    attrs.insert("total_size_mb".into(), serde_json::json!(total_mb));
+                    attrs.insert("files".into(), serde_json::json!(areas));
+                    attrs
+                },
+                update_at_interval: false,
+            });
+        }
+
+        // System info (static); everything below depends on it
+        let Some(sys_info) = self.guarded("system", true, |_| system_info::collect()) else {
+            return sensors;
+        };
 
         if self.is_enabled("os_version") {
             sensors.push(SensorValue {
@@ -611,140 +1902,396 @@ impl SensorCollector {
             }
         }
 
+        // Elevation (static): explains sensors that need admin, e.g. the CPU temperature from
+        // ACPI thermal zones on Windows
+        if self.is_enabled("elevated") {
+            sensors.push(SensorValue {
+                unique_id: "elevated".into(),
+                name: "Running Elevated".into(),
+                state: serde_json::json!(sys_info.elevated),
+                sensor_type: "binary_sensor".into(),
+                device_class: None,
+                unit_of_measurement: None,
+                state_class: None,
+                icon: Some("mdi:shield-account".into()),
+                attributes: HashMap::new(),
+                update_at_interval: false,
+            });
+        }
+
         // Display info (static)
         if self.is_enabled("display") {
-            for (i, display) in sys_info.displays.iter().enumerate() {
-                let suffix = if sys_info.displays.len() > 1 {
-                    format!("_{}", i + 1)
-                } else {
-                    String::new()
-                };
+            sensors.extend(display_sensors(&sys_info.displays));
+        }
 
-                sensors.push(SensorValue {
-                    unique_id: format!("display_resolution{}", suffix),
-                    name: format!("Display Resolution{}", if suffix.is_empty() { "".to_string() } else { format!(" {}", i + 1) }),
-                    state: serde_json::json!(display.resolution),
-                    sensor_type: "sensor".into(),
-                    device_class: None,
-                    unit_of_measurement: None,
-                    state_class: None,
-                    icon: Some("mdi:monitor".into()),
-                    attributes: {
-                        let mut attrs = HashMap::new();
-                        attrs.insert("adapter".into(), serde_json::json!(display.name));
-                        if let Some(hz) = display.refresh_rate_hz {
-                            attrs.insert("refresh_rate_hz".into(), serde_json::json!(hz));
-                        }
-                        attrs
-                    },
-                    update_at_interval: false,
-                });
-            }
+        self.apply_overrides(&mut sensors);
+        self.remember(&sensors);
+        sensors
+    }
+
+    /// Display sensors only, freshly queried; for when monitors were attached or removed
+    pub fn collect_displays(&mut self) -> Vec<SensorValue> {
+        if !self.is_enabled("display") {
+            return Vec::new();
         }
+        let mut sensors = display_sensors(&system_info::current_displays());
+        self.apply_overrides(&mut sensors);
+        self.remember(&sensors);
+        sensors
+    }
 
-        // GPU model (static)
-        if self.is_enabled("gpu") {
-            let gpu_data = gpu::collect();
-            for (i, gpu_info) in gpu_data.gpus.iter().enumerate() {
-                let suffix = if gpu_data.gpus.len() > 1 {
-                    format!("_{}", i)
-                } else {
-                    String::new()
-                };
+    /// Sensors of toggles that were just switched on, so they can be reported right away
+    /// instead of at the next cycle (static ones: at the next re-registration). Collected
+    /// fresh; a reading that is unavailable right now falls back to the value from before the
+    /// sensor was switched off.
+    ///
+    /// The regular cycles don't notice: the delta baselines (CPU times, overheat timer,
+    /// battery history) are put back afterwards, `sys` data the last cycle refreshed is read
+    /// as it is, and there is no heartbeat.
+    pub fn collect_switched_on(&mut self, ids: &[String]) -> Vec<SensorValue> {
+        let enabled = self.enabled_sensors.clone();
+        self.enabled_sensors = self
+            .get_sensor_list()
+            .into_iter()
+            .map(|s| {
+                let on = ids.contains(&s.id);
+                (s.id, on)
+            })
+            .collect();
+        // Collecting with everything else "off" would stop the other groups' background
+        // checks and replace their failures, so keep those as they are
+        let clipboard = self.clipboard.take();
+        let update_check = self.update_check.take();
+        let reboot_check = self.reboot_check.take();
+        let last_failures = std::mem::take(&mut self.last_failures);
+        let cpu_times_prev = self.cpu_times_prev;
+        let remote_cpu_prev = self.remote_cpu_prev.clone();
+        let overheat = self.overheat.clone();
+        let battery_history = self.battery_history.clone();
+        let on_ac_power = self.on_ac_power;
 
-                sensors.push(SensorValue {
-                    unique_id: format!("gpu_model{}", suffix),
-                    name: format!("GPU Model{}", if suffix.is_empty() { "".to_string() } else { format!(" {}", i) }),
-                    state: serde_json::json!(gpu_info.name),
-                    sensor_type: "sensor".into(),
-                    device_class: None,
-                    unit_of_measurement: None,
-                    state_class: None,
-                    icon: Some("mdi:expansion-card".into()),
-                    attributes: {
-                        let mut attrs = HashMap::new();
-                        attrs.insert("vendor".into(), serde_json::json!(gpu_info.vendor));
-                        if let Some(ref driver) = gpu_info.driver_version {
-                            attrs.insert("driver_version".into(), serde_json::json!(driver));
-                        }
-                        if let Some(vram) = gpu_info.vram_total_mb {
-                            attrs.insert("vram_total_mb".into(), serde_json::json!(vram));
-                        }
-                        attrs
-                    },
-                    update_at_interval: false,
-                });
+        // Only what no cycle keeps fresh; refreshing the rest would shorten the next
+        // cycle's CPU usage window
+        let wanted = self.system_parts();
+        self.refresh_system(SystemParts {
+            cpu: wanted.cpu && !self.system_refreshed.cpu,
+            memory: wanted.memory && !self.system_refreshed.memory,
+            process: wanted.process && !self.system_refreshed.process,
+        });
+        let mut sensors = self.collect_static();
+        let mut dynamic = self.read_dynamic();
+        self.finish_dynamic(&mut dynamic);
+        sensors.extend(dynamic);
+
+        self.cpu_times_prev = cpu_times_prev;
+        self.remote_cpu_prev = remote_cpu_prev;
+        self.overheat = overheat;
+        self.battery_history = battery_history;
+        self.on_ac_power = self.on_ac_power.or(on_ac_power);
+        self.enabled_sensors = enabled;
+        self.clipboard = self.clipboard.take().or(clipboard);
+        self.update_check = self.update_check.take().or(update_check);
+        self.reboot_check = self.reboot_check.take().or(reboot_check);
+        self.last_failures = last_failures;
+
+        for sensor in &mut sensors {
+            if sensor.state.is_null() {
+                if let Some(cached) = self.last_values.get(&sensor.unique_id) {
+                    sensor.state = cached.clone();
+                }
             }
         }
+        sensors
+    }
 
-        // RAM total (static)
-        if self.is_enabled("memory_total") {
-            let mem_data = memory::collect(&self.sys);
-            sensors.push(SensorValue {
-                unique_id: "memory_total".into(),
-                name: "Memory Total".into(),
-                state: serde_json::json!(format!("{:.1}", mem_data.total_gb)),
-                sensor_type: "sensor".into(),
-                device_class: Some("data_size".into()),
-                unit_of_measurement: Some("GB".into()),
-                state_class: None,
-                icon: Some("mdi:memory".into()),
-                attributes: HashMap::new(),
-                update_at_interval: false,
-            });
+    /// Cache the values just collected; unavailable ones keep the previous value
+    fn remember(&mut self, sensors: &[SensorValue]) {
+        for sensor in sensors.iter().filter(|s| !s.state.is_null()) {
+            self.last_values
+                .insert(sensor.unique_id.clone(), sensor.state.clone());
         }
-
-        sensors
     }
 
-    /// Get list of all possible sensors and their enabled status
+    /// Get list of all possible sensors and their enabled status, sorted by category
     pub fn get_sensor_list(&self) -> Vec<SensorListItem> {
         let all_sensors = vec![
-            ("cpu_usage", "CPU Usage", true),
-            ("cpu_frequency", "CPU Frequency", true),
-            ("cpu_temperature", "CPU Temperature", true),
-            ("cpu_model", "CPU Model", false),
-            ("memory_usage", "Memory Usage", true),
-            ("memory_used", "Memory Used", true),
-            ("memory_total", "Memory Total", false),
-            ("swap_usage", "Swap Usage", true),
-            ("disk_usage", "Disk Usage", true),
-            ("gpu", "GPU Sensors", true),
-            ("network", "Network Sensors", true),
-            ("battery", "Battery Sensors", true),
-            ("os_version", "OS Version", false),
-            ("hostname", "Hostname", false),
-            ("motherboard", "Motherboard", false),
-            ("bios_version", "BIOS Version", false),
-            ("bios_vendor", "BIOS Vendor", false),
-            ("bios_date", "BIOS Date", false),
-            ("system_uptime", "System Uptime", true),
-            ("process_count", "Process Count", true),
-            ("last_boot", "Last Boot Time", false),
-            ("logged_in_user", "Logged In User", false),
-            ("display", "Display Resolution", false),
+            ("cpu_usage", "CPU Usage", true, "cpu"),
+            ("cpu_frequency", "CPU Frequency", true, "cpu"),
+            ("cpu_temperature", "CPU Temperature", true, "cpu"),
+            ("cpu_overheating", "CPU Overheating", true, "cpu"),
+            ("cpu_breakdown", "CPU Usage Breakdown", true, "cpu"),
+            ("cpu_model", "CPU Model", false, "cpu"),
+            ("cpu_architecture", "CPU Architecture", false, "cpu"),
+            ("cpu_vendor", "CPU Vendor", false, "cpu"),
+            ("memory_usage", "Memory Usage", true, "memory"),
+            ("memory_used", "Memory Used", true, "memory"),
+            ("memory_total", "Memory Total", false, "memory"),
+            ("swap_usage", "Swap Usage", true, "memory"),
+            ("swap_config", "Swap Configuration", false, "memory"),
+            ("disk_usage", "Disk Usage", true, "disk"),
+            ("gpu", "GPU Sensors", true, "gpu"),
+            ("gpu_process_count", "GPU Process Count", true, "gpu"),
+            ("gpu_memory_util", "GPU Memory Utilization", true, "gpu"),
+            ("gpu_pcie", "GPU PCIe Throughput", true, "gpu"),
+            ("gpu_throttle_reason", "GPU Throttle Reason", true, "gpu"),
+            ("active_gpu", "Active GPU", true, "gpu"),
+            ("network", "Network Sensors", true, "network"),
+            ("network_addresses", "Network Addresses", true, "network"),
+            ("network_connection_type", "Network Connection Type", true, "network"),
+            ("wifi_signal", "Wi-Fi Signal", true, "network"),
+            ("battery", "Battery Sensors", true, "power"),
+            ("on_ac_power", "On AC Power", true, "power"),
+            ("lid_open", "Lid Open", true, "power"),
+            ("power_plan", "Power Plan", true, "power"),
+            ("os_version", "OS Version", false, "system"),
+            ("hostname", "Hostname", false, "system"),
+            ("motherboard", "Motherboard", false, "system"),
+            ("bios_version", "BIOS Version", false, "system"),
+            ("bios_vendor", "BIOS Vendor", false, "system"),
+            ("bios_date", "BIOS Date", false, "system"),
+            ("system_uptime", "System Uptime", true, "system"),
+            ("total_uptime", "Total Uptime", true, "system"),
+            ("process_count", "Process Count", true, "system"),
+            ("thread_count", "Thread Count", true, "system"),
+            ("last_boot", "Last Boot Time", false, "system"),
+            ("logged_in_user", "Logged In User", false, "system"),
+            ("session_count", "Session Count", true, "system"),
+            ("companion_cpu", "Companion CPU Usage", true, "system"),
+            ("companion_memory", "Companion Memory Usage", true, "system"),
+            ("display", "Display Resolution", false, "system"),
+            ("elevated", "Running Elevated", false, "system"),
+            ("os_updates_pending", "OS Updates Pending", true, "system"),
+            ("reboot_required", "Reboot Required", true, "system"),
+            ("collection_errors", "Collection Errors", true, "system"),
+            ("remote_hosts", "Remote Host Sensors", true, "remote"),
+            ("custom_sensors", "Custom Sensors", true, "custom"),
+            ("last_clipboard_change", "Last Clipboard Change", true, "activity"),
+            ("keyboard", "Keyboard Lock Keys & Backlight", true, "activity"),
+            ("user_idle_seconds", "User Idle Time", true, "activity"),
         ];
 
-        all_sensors
+        let mut items: Vec<SensorListItem> = all_sensors
             .into_iter()
-            .map(|(id, name, updates_at_interval)| SensorListItem {
+            .map(|(id, name, updates_at_interval, group)| SensorListItem {
                 id: id.to_string(),
                 name: name.to_string(),
                 enabled: self.is_enabled(id),
                 updates_at_interval,
+                group: group.to_string(),
+                category: SensorCategory::of(id, group),
+                enabled_by_default: !OPT_IN_SENSORS.contains(&id),
             })
-            .collect()
+            .collect();
+        // Stable: the order above is kept within a category
+        items.sort_by_key(|item| item.category);
+        items
+    }
+
+    /// Per group: whether any of its sensors is enabled and why the last collection failed
+    pub fn group_status(&self) -> Vec<GroupStatus> {
+        let mut groups: Vec<GroupStatus> = Vec::new();
+        for item in self.get_sensor_list() {
+            match groups.iter_mut().find(|g| g.group == item.group) {
+                Some(status) => status.enabled |= item.enabled,
+                None => groups.push(GroupStatus {
+                    error: self.last_failures.get(&item.group).cloned(),
+                    group: item.group,
+                    enabled: item.enabled,
+                }),
+            }
+        }
+        groups
     }
 
     /// Update enabled sensors map
     pub fn set_enabled_sensors(&mut self, enabled: HashMap<String, bool>) {
         self.enabled_sensors = enabled;
     }
+
+    /// Set how "used" memory is computed on Linux
+    pub fn set_memory_used_mode(&mut self, mode: memory::MemoryUsedMode) {
+        self.memory_used_mode = mode;
+    }
+
+    /// Names/icons/intervals to use instead of the built-in ones, per unique_id
+    pub fn set_sensor_overrides(&mut self, overrides: HashMap<String, SensorOverride>) {
+        self.overrides = overrides;
+    }
+
+    fn apply_overrides(&self, sensors: &mut [SensorValue]) {
+        if self.overrides.is_empty() {
+            return;
+        }
+        for sensor in sensors {
+            if let Some(o) = self.overrides.get(&sensor.unique_id) {
+                if let Some(ref name) = o.name {
+                    sensor.name = name.clone();
+                }
+                if let Some(ref icon) = o.icon {
+                    sensor.icon = Some(icon.clone());
+                }
+            }
+        }
+    }
+
+    /// Pin GPU entity ids by part of the GPU name
+    pub fn set_gpu_ids(&mut self, ids: HashMap<String, String>) {
+        self.gpu_ids = ids;
+    }
+
+    /// Pin battery entity ids by part of the battery identity, and hide matching batteries
+    pub fn set_battery_ids(&mut self, ids: HashMap<String, String>, hidden: Vec<String>) {
+        self.battery_ids = ids;
+        self.hidden_batteries = hidden;
+    }
+
+    /// Set the rules deciding which updates are sent while on battery
+    pub fn set_battery_saver(&mut self, config: battery_saver::BatterySaverConfig) {
+        self.battery_saver.set_config(config);
+    }
+
+    /// Only the sensors worth sending this cycle: all of them on AC power, the interesting
+    /// ones on battery when the battery saver is enabled
+    pub fn battery_saver_filter(&mut self, sensors: Vec<SensorValue>) -> Vec<SensorValue> {
+        if !self.battery_saver.enabled() {
+            return sensors;
+        }
+        let on_ac_power = match self.on_ac_power {
+            Some(on_ac_power) => on_ac_power,
+            // Power sensors disabled: query the power state just for the filter
+            None => self
+                .guarded("power", true, |c| battery::on_ac_power(&c.hidden_batteries))
+                .unwrap_or(true),
+        };
+        self.battery_saver.filter(!on_ac_power, sensors)
+    }
+
+    /// Set the decimals states are rounded to and whether unchanged ones are skipped
+    pub fn set_state_rounding(&mut self, config: state_rounding::StateRoundingConfig) {
+        self.state_rounding.set_config(config);
+    }
+
+    /// Only the sensors whose (rounded) state or attributes changed since they were last sent
+    pub fn changed_only(&self, sensors: Vec<SensorValue>) -> Vec<SensorValue> {
+        self.state_rounding.changed(sensors)
+    }
+
+    /// Drop sensors whose `interval` override from the config file hasn't elapsed since HA
+    /// last accepted them
+    pub fn due_only(&self, sensors: Vec<SensorValue>) -> Vec<SensorValue> {
+        if self.interval_sent.is_empty() {
+            return sensors;
+        }
+        let now = Instant::now();
+        sensors
+            .into_iter()
+            .filter(|s| match self.overrides.get(&s.unique_id) {
+                Some(o) => o.is_due(self.interval_sent.get(&s.unique_id).copied(), now),
+                None => true,
+            })
+            .collect()
+    }
+
+    /// Remember states HA accepted, so later cycles only send what changed since. Call after
+    /// every successful send; a failed one is then simply tried again.
+    pub fn record_sent(&mut self, sensors: &[SensorValue]) {
+        self.state_rounding.record_sent(sensors);
+        self.battery_saver.record_sent(sensors);
+        let now = Instant::now();
+        for sensor in sensors {
+            if self.overrides.get(&sensor.unique_id).is_some_and(|o| o.interval.is_some()) {
+                self.interval_sent.insert(sensor.unique_id.clone(), now);
+            }
+        }
+    }
+
+    /// Set the threshold, duration and hysteresis of the cpu_overheating sensor
+    pub fn set_cpu_overheat(&mut self, config: cpu::OverheatConfig) {
+        self.overheat.set_config(config);
+    }
+
+    /// Drop the previous readings that rates and durations are computed from. After sleep
+    /// they span the whole gap; the first cycle afterwards then only takes a new baseline
+    /// and the delta sensors (CPU breakdown, remote CPU usage) skip one update; the battery
+    /// charge rate starts a new history.
+    pub fn reset_baselines(&mut self) {
+        self.cpu_times_prev = None;
+        for prev in self.remote_cpu_prev.values_mut() {
+            *prev = None;
+        }
+        self.overheat.restart_timer();
+        self.battery_history.clear();
+        // Adapters and drives may have changed while asleep
+        self.networks_listed = None;
+        self.disks_listed = None;
+    }
+
+    /// Enumerate the network interfaces again in the next collection, e.g. after an
+    /// address changed
+    pub fn relist_networks(&mut self) {
+        self.networks_listed = None;
+    }
+
+    /// Count reserved space as used (`available`) or leave it out like `df` (`filesystem`)
+    pub fn set_disk_usage_mode(&mut self, mode: disk::DiskUsageMode) {
+        self.disk_usage_mode = mode;
+    }
+
+    /// Also report (and so register) the CPU temperature sensors before any reading was seen
+    pub fn set_force_temperature_sensors(&mut self, force: bool) {
+        self.force_temperature_sensors = force;
+    }
+
+    /// Set the range of plausible CPU/GPU temperatures
+    pub fn set_temperature_range(&mut self, range: TemperatureRange) {
+        self.temperature_range = range;
+    }
+
+    /// Continue total_uptime from the uptime persisted by an earlier run
+    pub fn set_uptime_ledger(&mut self, ledger: system_info::UptimeLedger) {
+        self.uptime_ledger = ledger;
+    }
+
+    /// The uptime ledger as of the last collection, to persist
+    pub fn uptime_ledger(&self) -> system_info::UptimeLedger {
+        self.uptime_ledger
+    }
+
+    /// Update the remote hosts reported over SSH
+    pub fn set_remote_hosts(&mut self, hosts: Vec<remote::RemoteHost>) {
+        self.remote_cpu_prev
+            .retain(|id, _| hosts.iter().any(|h| &h.id() == id));
+        self.remote_hosts = hosts;
+    }
+
+    /// Update the user-defined command/file sensors
+    pub fn set_custom_sensors(&mut self, sensors: Vec<custom::CustomSensor>) {
+        self.custom_last
+            .retain(|id, _| sensors.iter().any(|s| &s.unique_id() == id));
+        self.custom_sensors = sensors;
+    }
+}
+
+/// Whether a list last enumerated at `listed` is due again; if so, it counts as enumerated now
+fn relist_due(listed: &mut Option<Instant>) -> bool {
+    let due = listed.is_none_or(|at| at.elapsed() >= DEVICE_RELIST_INTERVAL);
+    if due {
+        *listed = Some(Instant::now());
+    }
+    due
+}
+
+/// `source` attribute of a temperature sensor, so a chassis thermal zone can be told apart
+/// from a real package temperature
+fn temperature_source_attributes(source: Option<&str>) -> HashMap<String, serde_json::Value> {
+    source
+        .map(|s| HashMap::from([("source".to_string(), serde_json::json!(s))]))
+        .unwrap_or_default()
 }
 
 /// Convert a UNIX timestamp to an ISO 8601 string for HA timestamp device_class
 fn chrono_from_timestamp(timestamp: u64) -> String {
-    use std::time::{Duration, UNIX_EPOCH};
+    use std::time::UNIX_EPOCH;
     let dt = UNIX_EPOCH + Duration::from_secs(timestamp);
     // Format as ISO 8601 (HA expects this for timestamp device_class)
     let secs = timestamp;
@@ -794,10 +2341,87 @@ fn chrono_from_timestamp(timestamp: u64) -> String {
     )
 }
 
+/// Display resolution sensors, numbered when there is more than one display
+fn display_sensors(displays: &[system_info::DisplayInfo]) -> Vec<SensorValue> {
+    let mut sensors = Vec::new();
+    for (i, display) in displays.iter().enumerate() {
+        let suffix = if displays.len() > 1 {
+            format!("_{}", i + 1)
+        } else {
+            String::new()
+        };
+
+        sensors.push(SensorValue {
+            unique_id: format!("display_resolution{}", suffix),
+            name: format!("Display Resolution{}", if suffix.is_empty() { "".to_string() } else { format!(" {}", i + 1) }),
+            state: serde_json::json!(display.resolution),
+            sensor_type: "sensor".into(),
+            device_class: None,
+            unit_of_measurement: None,
+            state_class: None,
+            icon: Some("mdi:monitor".into()),
+            attributes: {
+                let mut attrs = HashMap::new();
+                attrs.insert("adapter".into(), serde_json::json!(display.name));
+                if let Some(hz) = display.refresh_rate_hz {
+                    attrs.insert("refresh_rate_hz".into(), serde_json::json!(hz));
+                }
+                attrs
+            },
+            update_at_interval: false,
+        });
+    }
+    sensors
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SensorListItem {
     pub id: String,
     pub name: String,
     pub enabled: bool,
     pub updates_at_interval: bool,
+    /// cpu | memory | disk | gpu | network | power | system | remote | custom | activity
+    pub group: String,
+    /// Where a settings UI shows the sensor; the list is sorted by it
+    pub category: SensorCategory,
+    /// Whether the sensor is on without an explicit toggle (off for the opt-in ones)
+    pub enabled_by_default: bool,
+}
+
+/// UI category of a sensor, in display order. Broader than the collection group: memory
+/// counts as System, and who is at the machine (sessions, idle time, input) as Presence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SensorCategory {
+    System,
+    Cpu,
+    Gpu,
+    Storage,
+    Network,
+    Power,
+    Presence,
+}
+
+impl SensorCategory {
+    fn of(id: &str, group: &str) -> Self {
+        match (id, group) {
+            ("logged_in_user" | "session_count", _) | (_, "activity") => Self::Presence,
+            (_, "cpu") => Self::Cpu,
+            (_, "gpu") => Self::Gpu,
+            (_, "disk") => Self::Storage,
+            (_, "network" | "remote") => Self::Network,
+            (_, "power") => Self::Power,
+            _ => Self::System,
+        }
+    }
+}
+
+/// Outcome of the last collection for one sensor group
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupStatus {
+    pub group: String,
+    /// At least one sensor in the group is enabled
+    pub enabled: bool,
+    /// Why the group (or part of it) failed; None if it collected fine or is disabled
+    pub error: Option<String>,
 }
@@ -0,0 +1,499 @@
+//! Medium of the active network connection (the interface with the default route), plus
+//! SSID and signal strength when it is Wi-Fi. Best-effort per platform:
+//! - Linux: `/proc/net/route` for the default route, sysfs for the interface type, `iw` (or
+//!   `/proc/net/wireless` for the signal) for Wi-Fi
+//! - Windows: `GetBestInterface`, `MSFT_NetAdapter.NdisPhysicalMedium` and the WLAN API
+//! - macOS: `route get default`, `networksetup` hardware ports and `airport -I` / `ipconfig`
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionType {
+    Ethernet,
+    Wifi,
+    Cellular,
+    Unknown,
+}
+
+impl ConnectionType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConnectionType::Ethernet => "ethernet",
+            ConnectionType::Wifi => "wifi",
+            ConnectionType::Cellular => "cellular",
+            ConnectionType::Unknown => "unknown",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WifiInfo {
+    pub ssid: Option<String>,
+    /// Received signal strength in dBm
+    pub signal_dbm: Option<i32>,
+    /// Signal quality 0-100 (as shown by the OS)
+    pub signal_percent: Option<u32>,
+    /// MAC address of the access point
+    pub bssid: Option<String>,
+    pub channel: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    pub connection_type: ConnectionType,
+    /// Interface carrying the default route
+    pub interface: Option<String>,
+    /// Only for Wi-Fi connections
+    pub wifi: Option<WifiInfo>,
+}
+
+impl ConnectionInfo {
+    fn unknown() -> Self {
+        Self {
+            connection_type: ConnectionType::Unknown,
+            interface: None,
+            wifi: None,
+        }
+    }
+}
+
+/// Signal quality from dBm, with the usual linear mapping (-100 dBm = 0%, -50 dBm = 100%)
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn dbm_to_percent(dbm: i32) -> u32 {
+    (2 * (dbm + 100)).clamp(0, 100) as u32
+}
+
+/// Wi-Fi channel number for a frequency in MHz (2.4, 5 and 6 GHz bands)
+#[cfg(target_os = "linux")]
+fn frequency_to_channel(mhz: u32) -> Option<u32> {
+    match mhz {
+        2484 => Some(14),
+        2412..=2472 => Some((mhz - 2407) / 5),
+        5000..=5895 => Some((mhz - 5000) / 5),
+        5955..=7115 => Some((mhz - 5950) / 5),
+        _ => None,
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(target_os = "linux")]
+pub fn collect() -> ConnectionInfo {
+    let Some(interface) = linux::default_interface() else {
+        return ConnectionInfo::unknown();
+    };
+    let connection_type = linux::interface_type(&interface);
+    let wifi = (connection_type == ConnectionType::Wifi).then(|| linux::wifi_info(&interface));
+    ConnectionInfo {
+        connection_type,
+        interface: Some(interface),
+        wifi,
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::{command_output, dbm_to_percent, frequency_to_channel, ConnectionType, WifiInfo};
+    use std::path::Path;
+
+    /// Interface of the default route with the lowest metric; IPv6 if there is no IPv4 one
+    pub fn default_interface() -> Option<String> {
+        // "Iface Destination Gateway Flags RefCnt Use Metric Mask ..." (hex, host byte order)
+        let v4 = std::fs::read_to_string("/proc/net/route").ok().and_then(|routes| {
+            routes
+                .lines()
+                .skip(1)
+                .filter_map(|line| {
+                    let fields: Vec<&str> = line.split_whitespace().collect();
+                    let is_default = fields.get(1) == Some(&"00000000") && fields.get(7) == Some(&"00000000");
+                    let is_up = u32::from_str_radix(fields.get(3)?, 16).ok()? & 1 != 0; // RTF_UP
+                    if !is_default || !is_up {
+                        return None;
+                    }
+                    let metric: u32 = fields.get(6)?.parse().ok()?;
+                    Some((metric, fields[0].to_string()))
+                })
+                .min()
+                .map(|(_, iface)| iface)
+        });
+        if v4.is_some() {
+            return v4;
+        }
+
+        // "dest plen src splen nexthop metric refcnt use flags iface", default = ::/0
+        let routes = std::fs::read_to_string("/proc/net/ipv6_route").ok()?;
+        routes
+            .lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                let is_default = fields.first()?.chars().all(|c| c == '0') && fields.get(1) == Some(&"00");
+                let iface = fields.get(9)?;
+                if !is_default || *iface == "lo" {
+                    return None;
+                }
+                let metric = u32::from_str_radix(fields.get(5)?, 16).ok()?;
+                Some((metric, iface.to_string()))
+            })
+            .min()
+            .map(|(_, iface)| iface)
+    }
+
+    pub fn interface_type(interface: &str) -> ConnectionType {
+        let dir = Path::new("/sys/class/net").join(interface);
+        if dir.join("wireless").exists() || dir.join("phy80211").exists() {
+            return ConnectionType::Wifi;
+        }
+        let uevent = std::fs::read_to_string(dir.join("uevent")).unwrap_or_default();
+        if uevent.lines().any(|l| l == "DEVTYPE=wwan") || interface.starts_with("wwan") {
+            return ConnectionType::Cellular;
+        }
+        // ARPHRD_ETHER; tunnels and VPNs (tun, wireguard, ppp) have other types
+        match std::fs::read_to_string(dir.join("type")).map(|t| t.trim().to_string()) {
+            Ok(t) if t == "1" => ConnectionType::Ethernet,
+            _ => ConnectionType::Unknown,
+        }
+    }
+
+    pub fn wifi_info(interface: &str) -> WifiInfo {
+        let mut info = WifiInfo::default();
+        // "Connected to aa:bb:.. (on wlan0)\n\tSSID: name\n\tfreq: 5180\n\tsignal: -52 dBm ..."
+        if let Some(link) = command_output("iw", &["dev", interface, "link"]) {
+            for line in link.lines().map(str::trim) {
+                if let Some(rest) = line.strip_prefix("Connected to") {
+                    info.bssid = rest.split_whitespace().next().map(str::to_string);
+                } else if let Some(ssid) = line.strip_prefix("SSID:") {
+                    info.ssid = Some(ssid.trim().to_string());
+                } else if let Some(freq) = line.strip_prefix("freq:") {
+                    // "5180" or "5180.0"
+                    let mhz = freq.trim().split('.').next().and_then(|f| f.parse().ok());
+                    info.channel = mhz.and_then(frequency_to_channel);
+                } else if let Some(signal) = line.strip_prefix("signal:") {
+                    info.signal_dbm = signal.split_whitespace().next().and_then(|s| s.parse().ok());
+                }
+            }
+        }
+        if info.ssid.is_none() {
+            info.ssid = command_output("iwgetid", &[interface, "-r"])
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty());
+        }
+        if info.signal_dbm.is_none() {
+            info.signal_dbm = proc_wireless_level(interface);
+        }
+        info.signal_percent = info.signal_dbm.map(dbm_to_percent);
+        info
+    }
+
+    /// Signal level from the wireless extensions table (no `iw` needed):
+    /// " wlan0: 0000   54.  -56.  -256        0      0      0      0      0        0"
+    fn proc_wireless_level(interface: &str) -> Option<i32> {
+        let table = std::fs::read_to_string("/proc/net/wireless").ok()?;
+        let line = table
+            .lines()
+            .find(|l| l.trim_start().starts_with(&format!("{}:", interface)))?;
+        let level: f64 = line.split_whitespace().nth(3)?.trim_end_matches('.').parse().ok()?;
+        // Drivers that report a relative level use positive values
+        (level < 0.0).then_some(level as i32)
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn collect() -> ConnectionInfo {
+    // "   interface: en0"
+    let Some(interface) = command_output("route", &["-n", "get", "default"]).and_then(|out| {
+        out.lines()
+            .find_map(|l| l.trim().strip_prefix("interface:").map(|i| i.trim().to_string()))
+    }) else {
+        return ConnectionInfo::unknown();
+    };
+
+    // Blocks of "Hardware Port: Wi-Fi\nDevice: en0\nEthernet Address: ..."
+    let port = command_output("networksetup", &["-listallhardwareports"]).and_then(|out| {
+        let lines: Vec<&str> = out.lines().collect();
+        lines.windows(2).find_map(|pair| {
+            let port = pair[0].strip_prefix("Hardware Port:")?.trim();
+            (pair[1].strip_prefix("Device:")?.trim() == interface).then(|| port.to_string())
+        })
+    });
+    let connection_type = match port.as_deref() {
+        Some(p) if p.contains("Wi-Fi") || p.contains("AirPort") => ConnectionType::Wifi,
+        Some(p) if p.contains("iPhone") => ConnectionType::Cellular,
+        Some(p) if p.contains("Ethernet") || p.contains("LAN") || p.contains("Thunderbolt") => {
+            ConnectionType::Ethernet
+        }
+        _ => ConnectionType::Unknown,
+    };
+    let wifi = (connection_type == ConnectionType::Wifi).then(|| macos_wifi_info(&interface));
+    ConnectionInfo {
+        connection_type,
+        interface: Some(interface),
+        wifi,
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn macos_wifi_info(interface: &str) -> WifiInfo {
+    const AIRPORT: &str = "/System/Library/PrivateFrameworks/Apple80211.framework/Versions/Current/Resources/airport";
+
+    let mut info = WifiInfo::default();
+    // airport was removed in macOS 14.4; "     agrCtlRSSI: -55\n            SSID: name"
+    if let Some(out) = command_output(AIRPORT, &["-I"]) {
+        for line in out.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            match key.trim() {
+                "SSID" => info.ssid = Some(value.trim().to_string()),
+                // The value itself contains colons
+                "BSSID" => info.bssid = line.split_once("BSSID:").map(|(_, b)| b.trim().to_string()),
+                "agrCtlRSSI" => info.signal_dbm = value.trim().parse().ok(),
+                // "36,80" (channel, width)
+                "channel" => info.channel = value.trim().split(',').next().and_then(|c| c.parse().ok()),
+                _ => {}
+            }
+        }
+    }
+    if info.ssid.is_none() {
+        // "  SSID : name" (redacted without location permission on recent macOS)
+        info.ssid = command_output("ipconfig", &["getsummary", interface]).and_then(|out| {
+            out.lines().find_map(|l| {
+                let (key, value) = l.split_once(" : ")?;
+                (key.trim() == "SSID").then(|| value.trim().to_string())
+            })
+        });
+    }
+    info.signal_percent = info.signal_dbm.map(dbm_to_percent);
+    info
+}
+
+#[cfg(windows)]
+pub fn collect() -> ConnectionInfo {
+    use super::wmi_conn::{self, Namespace};
+    use wmi::Variant;
+
+    let Some(index) = windows_net::default_interface_index() else {
+        return ConnectionInfo::unknown();
+    };
+    let query = format!(
+        "SELECT Name, NdisPhysicalMedium FROM MSFT_NetAdapter WHERE InterfaceIndex = {}",
+        index
+    );
+    let adapter = match wmi_conn::raw_query(Namespace::StandardCimv2, &query) {
+        Some(Ok(results)) => results.into_iter().next(),
+        Some(Err(e)) => {
+            log::debug!("[Network] MSFT_NetAdapter query failed: {}", e);
+            None
+        }
+        None => None,
+    };
+    let Some(adapter) = adapter else {
+        // Not a physical adapter (e.g. a VPN)
+        return ConnectionInfo::unknown();
+    };
+
+    let interface = match adapter.get("Name") {
+        Some(Variant::String(s)) => Some(s.clone()),
+        _ => None,
+    };
+    // NDIS_PHYSICAL_MEDIUM: 1 = WirelessLan, 8 = WirelessWan, 9 = Native802_11, 14 = 802_3
+    let medium = match adapter.get("NdisPhysicalMedium") {
+        Some(Variant::UI4(v)) => Some(*v),
+        Some(Variant::I4(v)) => Some(*v as u32),
+        _ => None,
+    };
+    let connection_type = match medium {
+        Some(1 | 9) => ConnectionType::Wifi,
+        Some(8) => ConnectionType::Cellular,
+        Some(14) => ConnectionType::Ethernet,
+        _ => ConnectionType::Unknown,
+    };
+    let wifi = (connection_type == ConnectionType::Wifi).then(windows_net::wifi_info);
+    ConnectionInfo {
+        connection_type,
+        interface,
+        wifi,
+    }
+}
+
+#[cfg(windows)]
+mod windows_net {
+    use super::WifiInfo;
+    use std::ffi::c_void;
+
+    #[link(name = "iphlpapi")]
+    extern "system" {
+        fn GetBestInterface(dest_addr: u32, best_if_index: *mut u32) -> u32;
+    }
+
+    /// Index of the interface Windows would route internet traffic through
+    pub fn default_interface_index() -> Option<u32> {
+        // Any public address works; nothing is sent. 8.8.8.8 reads the same in either byte order.
+        let dest = u32::from_ne_bytes([8, 8, 8, 8]);
+        let mut index = 0u32;
+        // SAFETY: plain IP Helper call writing to a local u32
+        let result = unsafe { GetBestInterface(dest, &mut index) };
+        (result == 0).then_some(index)
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Guid {
+        data1: u32,
+        data2: u16,
+        data3: u16,
+        data4: [u8; 8],
+    }
+
+    #[repr(C)]
+    struct WlanInterfaceInfo {
+        interface_guid: Guid,
+        description: [u16; 256],
+        state: u32,
+    }
+
+    #[repr(C)]
+    struct WlanInterfaceInfoList {
+        count: u32,
+        index: u32,
+        // Followed by `count` entries
+        interfaces: [WlanInterfaceInfo; 1],
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Dot11Ssid {
+        length: u32,
+        ssid: [u8; 32],
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct WlanAssociationAttributes {
+        ssid: Dot11Ssid,
+        bss_type: u32,
+        bssid: [u8; 6],
+        phy_type: u32,
+        phy_index: u32,
+        signal_quality: u32,
+        rx_rate: u32,
+        tx_rate: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct WlanConnectionAttributes {
+        state: u32,
+        connection_mode: u32,
+        profile_name: [u16; 256],
+        association: WlanAssociationAttributes,
+        // WLAN_SECURITY_ATTRIBUTES follows; not needed
+    }
+
+    const WLAN_INTERFACE_STATE_CONNECTED: u32 = 1;
+    const WLAN_INTF_OPCODE_CURRENT_CONNECTION: u32 = 7;
+    const WLAN_INTF_OPCODE_CHANNEL_NUMBER: u32 = 8;
+    const WLAN_INTF_OPCODE_RSSI: u32 = 0x1000_0102;
+
+    #[link(name = "wlanapi")]
+    extern "system" {
+        fn WlanOpenHandle(
+            client_version: u32,
+            reserved: *mut c_void,
+            negotiated_version: *mut u32,
+            client_handle: *mut *mut c_void,
+        ) -> u32;
+        fn WlanCloseHandle(client_handle: *mut c_void, reserved: *mut c_void) -> u32;
+        fn WlanEnumInterfaces(
+            client_handle: *mut c_void,
+            reserved: *mut c_void,
+            interface_list: *mut *mut WlanInterfaceInfoList,
+        ) -> u32;
+        fn WlanQueryInterface(
+            client_handle: *mut c_void,
+            interface_guid: *const Guid,
+            opcode: u32,
+            reserved: *mut c_void,
+            data_size: *mut u32,
+            data: *mut *mut c_void,
+            opcode_value_type: *mut u32,
+        ) -> u32;
+        fn WlanFreeMemory(memory: *mut c_void);
+    }
+
+    /// Query a WLAN interface property; the value is copied out and the buffer freed.
+    ///
+    /// # Safety
+    /// `handle` must be an open WLAN handle and `T` must match the opcode's value type.
+    unsafe fn query<T: Copy>(handle: *mut c_void, guid: &Guid, opcode: u32) -> Option<T> {
+        let mut size = 0u32;
+        let mut data = std::ptr::null_mut();
+        let result = WlanQueryInterface(
+            handle,
+            guid,
+            opcode,
+            std::ptr::null_mut(),
+            &mut size,
+            &mut data,
+            std::ptr::null_mut(),
+        );
+        if result != 0 || data.is_null() {
+            return None;
+        }
+        let value = (size as usize >= std::mem::size_of::<T>()).then(|| *(data as *const T));
+        WlanFreeMemory(data);
+        value
+    }
+
+    /// SSID, BSSID, channel and signal of the first connected wireless interface
+    pub fn wifi_info() -> WifiInfo {
+        let mut info = WifiInfo::default();
+        let mut handle = std::ptr::null_mut();
+        let mut version = 0u32;
+        // SAFETY: the WLAN API allocates the returned lists, which are freed with WlanFreeMemory
+        // before the handle is closed; entries are only read within `count`.
+        unsafe {
+            if WlanOpenHandle(2, std::ptr::null_mut(), &mut version, &mut handle) != 0 {
+                return info;
+            }
+            let mut list = std::ptr::null_mut();
+            if WlanEnumInterfaces(handle, std::ptr::null_mut(), &mut list) == 0 && !list.is_null() {
+                let count = (*list).count as usize;
+                let interfaces = std::slice::from_raw_parts((*list).interfaces.as_ptr(), count);
+                if let Some(iface) = interfaces.iter().find(|i| i.state == WLAN_INTERFACE_STATE_CONNECTED) {
+                    let guid = &iface.interface_guid;
+                    if let Some(attrs) =
+                        query::<WlanConnectionAttributes>(handle, guid, WLAN_INTF_OPCODE_CURRENT_CONNECTION)
+                    {
+                        let assoc = &attrs.association;
+                        let len = (assoc.ssid.length as usize).min(assoc.ssid.ssid.len());
+                        info.ssid = Some(String::from_utf8_lossy(&assoc.ssid.ssid[..len]).to_string());
+                        info.bssid = Some(
+                            assoc.bssid.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":"),
+                        );
+                        info.signal_percent = Some(assoc.signal_quality.min(100));
+                    }
+                    info.channel = query::<u32>(handle, guid, WLAN_INTF_OPCODE_CHANNEL_NUMBER);
+                    info.signal_dbm = query::<i32>(handle, guid, WLAN_INTF_OPCODE_RSSI);
+                }
+                WlanFreeMemory(list as *mut c_void);
+            }
+            WlanCloseHandle(handle, std::ptr::null_mut());
+        }
+        info
+    }
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+pub fn collect() -> ConnectionInfo {
+    ConnectionInfo::unknown()
+}
@@ -1,24 +1,73 @@
+use std::time::{Duration, Instant};
+
 use serde::{Deserialize, Serialize};
 use sysinfo::System;
 
+use super::TemperatureRange;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CpuData {
     pub model: String,
     pub usage_percent: f32,
+    /// Average frequency across all cores
     pub frequency_mhz: u64,
+    pub core_frequencies_mhz: Vec<u64>,
+    pub frequency_min_mhz: u64,
+    pub frequency_max_mhz: u64,
+    /// Nominal (non-turbo) frequency, where the platform exposes it
+    pub base_frequency_mhz: Option<u64>,
     pub temperature: Option<f32>,
+    /// Where the temperature was read, e.g. "sysinfo: k10temp Tctl" or a WMI class; tells a
+    /// package temperature apart from an ACPI/chassis thermal zone
+    pub temperature_source: Option<String>,
     pub core_count: usize,
     pub logical_core_count: usize,
 }
 
-pub fn collect(sys: &System) -> CpuData {
+/// Collect CPU info; `include_temperature` also probes the temperature (sysinfo components,
+/// WMI on Windows). Temperatures outside `temperature_range` count as unavailable.
+pub fn collect(
+    sys: &System,
+    include_temperature: bool,
+    temperature_range: TemperatureRange,
+) -> CpuData {
     let cpus = sys.cpus();
     let model = cpus.first().map(|c| c.brand().to_string()).unwrap_or_default();
     let usage_percent = sys.global_cpu_usage();
-    let frequency_mhz = cpus.first().map(|c| c.frequency()).unwrap_or(0);
+    let core_frequencies_mhz: Vec<u64> = cpus.iter().map(|c| c.frequency()).collect();
+    let frequency_mhz = if core_frequencies_mhz.is_empty() {
+        0
+    } else {
+        core_frequencies_mhz.iter().sum::<u64>() / core_frequencies_mhz.len() as u64
+    };
+    let frequency_min_mhz = core_frequencies_mhz.iter().copied().min().unwrap_or(0);
+    let frequency_max_mhz = core_frequencies_mhz.iter().copied().max().unwrap_or(0);
+    let base_frequency_mhz = collect_base_frequency();
     let core_count = sys.physical_core_count().unwrap_or(0);
     let logical_core_count = cpus.len();
 
+    let (temperature, temperature_source) = include_temperature
+        .then(|| collect_temperature(temperature_range))
+        .flatten()
+        .unzip();
+
+    CpuData {
+        model,
+        usage_percent,
+        frequency_mhz,
+        core_frequencies_mhz,
+        frequency_min_mhz,
+        frequency_max_mhz,
+        base_frequency_mhz,
+        temperature,
+        temperature_source,
+        core_count,
+        logical_core_count,
+    }
+}
+
+/// CPU temperature and its source from the sysinfo components, on Windows falling back to WMI
+fn collect_temperature(temperature_range: TemperatureRange) -> Option<(f32, String)> {
     // Try to get CPU temperature from sysinfo components first
     #[cfg_attr(not(windows), allow(unused_mut))]
     let mut temperature = {
@@ -37,7 +86,9 @@ pub fn collect(sys: &System) -> CpuData {
             });
         if let Some(comp) = found {
             log::info!("[CPU] sysinfo temperature from '{}': {:.1}°C", comp.label(), comp.temperature());
-            Some(comp.temperature())
+            temperature_range
+                .check(comp.label(), comp.temperature())
+                .map(|t| (t, format!("sysinfo: {}", comp.label())))
         } else {
             log::debug!("[CPU] sysinfo: no CPU/core/package component found");
             None
@@ -47,91 +98,249 @@ pub fn collect(sys: &System) -> CpuData {
     // Fallback: on Windows, try WMI thermal zone if sysinfo returned None
     #[cfg(windows)]
     if temperature.is_none() {
-        temperature = collect_cpu_temp_wmi();
+        temperature = super::probe::run("cpu_temperature_wmi", move || {
+            collect_cpu_temp_wmi(temperature_range)
+        })
+        .flatten();
     }
+    temperature
+}
 
-    CpuData {
-        model,
-        usage_percent,
-        frequency_mhz,
-        temperature,
-        core_count,
-        logical_core_count,
+/// Architecture, vendor and notable instruction set extensions, for inventory
+#[derive(Debug, Clone)]
+pub struct CpuIdentity {
+    /// e.g. "x86_64", "aarch64"
+    pub architecture: &'static str,
+    /// "Intel", "AMD", "Apple", else the vendor id as reported
+    pub vendor: Option<String>,
+    /// Detected features from a fixed list of notable ones; None where they can't be read
+    pub features: Option<Vec<&'static str>>,
+}
+
+pub fn identity(sys: &System) -> CpuIdentity {
+    let vendor = sys
+        .cpus()
+        .first()
+        .map(|c| c.vendor_id().trim().to_string())
+        .filter(|v| !v.is_empty())
+        .map(|v| match v.as_str() {
+            "GenuineIntel" => "Intel".to_string(),
+            "AuthenticAMD" => "AMD".to_string(),
+            _ if v.starts_with("Apple") => "Apple".to_string(),
+            _ => v,
+        });
+    CpuIdentity {
+        architecture: std::env::consts::ARCH,
+        vendor,
+        features: cpu_features(),
+    }
+}
+
+/// The CPU's features as the Rust runtime detects them (CPUID on x86)
+#[cfg(target_arch = "x86_64")]
+fn cpu_features() -> Option<Vec<&'static str>> {
+    let mut features = Vec::new();
+    macro_rules! detect {
+        ($($feature:tt),*) => {
+            $(if std::is_x86_feature_detected!($feature) {
+                features.push($feature);
+            })*
+        };
+    }
+    detect!("sse4.2", "popcnt", "aes", "pclmulqdq", "sha", "avx", "avx2", "fma", "bmi2", "avx512f");
+    Some(features)
+}
+
+#[cfg(target_arch = "aarch64")]
+fn cpu_features() -> Option<Vec<&'static str>> {
+    let mut features = Vec::new();
+    macro_rules! detect {
+        ($($feature:tt),*) => {
+            $(if std::arch::is_aarch64_feature_detected!($feature) {
+                features.push($feature);
+            })*
+        };
+    }
+    detect!("neon", "aes", "sha2", "crc", "lse", "dotprod", "sve", "sve2");
+    Some(features)
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn cpu_features() -> Option<Vec<&'static str>> {
+    None
+}
+
+/// Thresholds for the `cpu_overheating` binary sensor (`cpu_overheat` in settings.json)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverheatConfig {
+    /// CPU temperature (°C) above which the CPU counts as overheating
+    pub threshold_c: f32,
+    /// How long the temperature must stay above the threshold before the sensor turns on
+    pub duration_secs: u64,
+    /// The sensor turns off again once the temperature drops below threshold − hysteresis
+    pub hysteresis_c: f32,
+}
+
+impl Default for OverheatConfig {
+    fn default() -> Self {
+        Self {
+            threshold_c: 90.0,
+            duration_secs: 60,
+            hysteresis_c: 5.0,
+        }
+    }
+}
+
+/// State machine behind `cpu_overheating`: on after a sustained period above the
+/// threshold, off only after dropping below the hysteresis margin
+#[derive(Debug, Clone, Default)]
+pub struct OverheatDetector {
+    config: OverheatConfig,
+    /// Start of the current run of samples above the threshold
+    above_since: Option<Instant>,
+    overheating: bool,
+}
+
+impl OverheatDetector {
+    pub fn config(&self) -> OverheatConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: OverheatConfig) {
+        self.config = config;
+        self.above_since = None;
+    }
+
+    /// Forget the current run above the threshold (e.g. after sleep, when the samples
+    /// before and after the gap don't form a sustained period)
+    pub fn restart_timer(&mut self) {
+        self.above_since = None;
+    }
+
+    /// Feed a temperature sample and return whether the CPU is overheating
+    pub fn update(&mut self, temperature: f32, now: Instant) -> bool {
+        let config = self.config;
+        if self.overheating {
+            if temperature < config.threshold_c - config.hysteresis_c {
+                self.overheating = false;
+            }
+        } else if temperature > config.threshold_c {
+            let since = *self.above_since.get_or_insert(now);
+            if now.duration_since(since) >= Duration::from_secs(config.duration_secs) {
+                self.overheating = true;
+                self.above_since = None;
+            }
+        } else {
+            self.above_since = None;
+        }
+        self.overheating
     }
 }
 
-/// Try to read CPU temperature from WMI.
+/// Nominal CPU frequency in MHz (intel_pstate/amd-pstate expose `base_frequency` in kHz)
+#[cfg(target_os = "linux")]
+fn collect_base_frequency() -> Option<u64> {
+    std::fs::read_to_string("/sys/devices/system/cpu/cpu0/cpufreq/base_frequency")
+        .ok()
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .map(|khz| khz / 1000)
+}
+
+/// Nominal CPU frequency in MHz (Win32_Processor.MaxClockSpeed is the rated clock, not turbo)
+#[cfg(windows)]
+fn collect_base_frequency() -> Option<u64> {
+    use super::wmi_conn::{self, Namespace};
+    use wmi::Variant;
+
+    let results = wmi_conn::raw_query(Namespace::Cimv2, "SELECT MaxClockSpeed FROM Win32_Processor")?
+        .ok()?;
+    results.first().and_then(|r| match r.get("MaxClockSpeed") {
+        Some(Variant::UI4(n)) => Some(*n as u64),
+        Some(Variant::UI2(n)) => Some(*n as u64),
+        Some(Variant::I4(n)) => Some(*n as u64),
+        _ => None,
+    })
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+fn collect_base_frequency() -> Option<u64> {
+    None
+}
+
+/// Try to read CPU temperature from WMI, with the class it came from.
 /// Attempts multiple WMI classes in order of reliability.
 #[cfg(windows)]
-fn collect_cpu_temp_wmi() -> Option<f32> {
-    use std::collections::HashMap;
-    use wmi::{COMLibrary, Variant, WMIConnection};
+fn collect_cpu_temp_wmi(temperature_range: TemperatureRange) -> Option<(f32, String)> {
+    use super::wmi_conn::{self, Namespace};
+    use wmi::Variant;
 
     // Attempt 1: MSAcpi_ThermalZoneTemperature (root\WMI, requires admin)
     // Values are in tenths of Kelvin.
-    if let Ok(com_lib) = COMLibrary::new() {
-        if let Ok(wmi_con) = WMIConnection::with_namespace_path("root\\WMI", com_lib) {
-            match wmi_con.raw_query::<HashMap<String, Variant>>(
-                "SELECT CurrentTemperature FROM MSAcpi_ThermalZoneTemperature",
-            ) {
-                Ok(results) => {
-                    for result in &results {
-                        if let Some(variant) = result.get("CurrentTemperature") {
-                            let raw_temp = match variant {
-                                Variant::UI4(n) => Some(*n as f32),
-                                Variant::UI2(n) => Some(*n as f32),
-                                Variant::I4(n) => Some(*n as f32),
-                                _ => None,
-                            };
-                            if let Some(tenths_kelvin) = raw_temp {
-                                let celsius = (tenths_kelvin / 10.0) - 273.15;
-                                if celsius > 0.0 && celsius < 150.0 {
-                                    log::info!("[CPU] Temperature from MSAcpi_ThermalZone: {:.1}°C", celsius);
-                                    return Some(celsius);
-                                }
+    if let Some(query_result) = wmi_conn::raw_query(
+        Namespace::RootWmi,
+        "SELECT CurrentTemperature FROM MSAcpi_ThermalZoneTemperature",
+    ) {
+        match query_result {
+            Ok(results) => {
+                for result in &results {
+                    if let Some(variant) = result.get("CurrentTemperature") {
+                        let raw_temp = match variant {
+                            Variant::UI4(n) => Some(*n as f32),
+                            Variant::UI2(n) => Some(*n as f32),
+                            Variant::I4(n) => Some(*n as f32),
+                            _ => None,
+                        };
+                        if let Some(tenths_kelvin) = raw_temp {
+                            let celsius = (tenths_kelvin / 10.0) - 273.15;
+                            if let Some(celsius) = temperature_range.check("MSAcpi_ThermalZone", celsius) {
+                                log::info!("[CPU] Temperature from MSAcpi_ThermalZone: {:.1}°C", celsius);
+                                return Some((celsius, "WMI: MSAcpi_ThermalZoneTemperature".into()));
                             }
                         }
                     }
-                    log::debug!("[CPU] MSAcpi_ThermalZone returned {} results but no valid temp", results.len());
-                }
-                Err(e) => {
-                    log::debug!("[CPU] MSAcpi_ThermalZoneTemperature query failed (needs admin?): {}", e);
                 }
+                log::debug!("[CPU] MSAcpi_ThermalZone returned {} results but no valid temp", results.len());
+            }
+            Err(e) => {
+                log::debug!("[CPU] MSAcpi_ThermalZoneTemperature query failed (needs admin?): {}", e);
             }
         }
     }
 
     // Attempt 2: Win32_PerfFormattedData_Counters_ThermalZoneInformation (root\CIMV2, no admin needed)
     // Temperature is in Kelvin (not tenths). Available on Windows 10+.
-    if let Ok(com_lib) = COMLibrary::new() {
-        if let Ok(wmi_con) = WMIConnection::new(com_lib) {
-            match wmi_con.raw_query::<HashMap<String, Variant>>(
-                "SELECT Temperature FROM Win32_PerfFormattedData_Counters_ThermalZoneInformation",
-            ) {
-                Ok(results) => {
-                    for result in &results {
-                        if let Some(variant) = result.get("Temperature") {
-                            let kelvin = match variant {
-                                Variant::UI4(n) => Some(*n as f32),
-                                Variant::UI2(n) => Some(*n as f32),
-                                Variant::I4(n) => Some(*n as f32),
-                                Variant::UI8(n) => Some(*n as f32),
-                                _ => None,
-                            };
-                            if let Some(k) = kelvin {
-                                let celsius = k - 273.15;
-                                if celsius > 0.0 && celsius < 150.0 {
-                                    log::info!("[CPU] Temperature from ThermalZoneInformation: {:.1}°C", celsius);
-                                    return Some(celsius);
-                                }
+    if let Some(query_result) = wmi_conn::raw_query(
+        Namespace::Cimv2,
+        "SELECT Temperature FROM Win32_PerfFormattedData_Counters_ThermalZoneInformation",
+    ) {
+        match query_result {
+            Ok(results) => {
+                for result in &results {
+                    if let Some(variant) = result.get("Temperature") {
+                        let kelvin = match variant {
+                            Variant::UI4(n) => Some(*n as f32),
+                            Variant::UI2(n) => Some(*n as f32),
+                            Variant::I4(n) => Some(*n as f32),
+                            Variant::UI8(n) => Some(*n as f32),
+                            _ => None,
+                        };
+                        if let Some(k) = kelvin {
+                            let celsius = k - 273.15;
+                            if let Some(celsius) = temperature_range.check("ThermalZoneInformation", celsius) {
+                                log::info!("[CPU] Temperature from ThermalZoneInformation: {:.1}°C", celsius);
+                                return Some((
+                                    celsius,
+                                    "WMI: Win32_PerfFormattedData_Counters_ThermalZoneInformation".into(),
+                                ));
                             }
                         }
                     }
-                    log::debug!("[CPU] ThermalZoneInformation returned {} results but no valid temp", results.len());
-                }
-                Err(e) => {
-                    log::debug!("[CPU] ThermalZoneInformation query failed: {}", e);
                 }
+                log::debug!("[CPU] ThermalZoneInformation returned {} results but no valid temp", results.len());
+            }
+            Err(e) => {
+                log::debug!("[CPU] ThermalZoneInformation query failed: {}", e);
             }
         }
     }
@@ -139,3 +348,194 @@ fn collect_cpu_temp_wmi() -> Option<f32> {
     log::warn!("[CPU] No CPU temperature available from any WMI source");
     None
 }
+
+/// Cumulative CPU time per state since boot (jiffies on Linux, ticks on macOS, 100ns units
+/// on Windows). Only differences between two readings are meaningful.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CpuTimes {
+    user: u64,
+    system: u64,
+    idle: u64,
+    /// None where the platform doesn't track I/O wait separately
+    iowait: Option<u64>,
+    total: u64,
+}
+
+/// Share of CPU time per state between two readings, in percent
+#[derive(Debug, Clone, Copy)]
+pub struct CpuBreakdown {
+    pub user: f64,
+    pub system: f64,
+    pub idle: f64,
+    pub iowait: Option<f64>,
+}
+
+/// Breakdown between two readings; None when no time passed or the counters went backwards
+pub fn breakdown_between(previous: CpuTimes, current: CpuTimes) -> Option<CpuBreakdown> {
+    let total = current.total.checked_sub(previous.total).filter(|t| *t > 0)?;
+    let share = |cur: u64, prev: u64| super::usage_percent(cur.saturating_sub(prev), total);
+    Some(CpuBreakdown {
+        user: share(current.user, previous.user),
+        system: share(current.system, previous.system),
+        idle: share(current.idle, previous.idle),
+        iowait: current
+            .iowait
+            .zip(previous.iowait)
+            .map(|(cur, prev)| share(cur, prev)),
+    })
+}
+
+/// Aggregate line of /proc/stat: user nice system idle iowait irq softirq steal ...
+#[cfg(target_os = "linux")]
+pub fn read_cpu_times() -> Option<CpuTimes> {
+    let stat = std::fs::read_to_string("/proc/stat").ok()?;
+    let line = stat.lines().find(|l| l.starts_with("cpu "))?;
+    let v: Vec<u64> = line
+        .split_whitespace()
+        .skip(1)
+        .take(8)
+        .filter_map(|x| x.parse().ok())
+        .collect();
+    if v.len() < 5 {
+        return None;
+    }
+    let field = |i: usize| v.get(i).copied().unwrap_or(0);
+    Some(CpuTimes {
+        // nice is user code at a lower priority; irq/softirq count as system time
+        user: field(0) + field(1),
+        system: field(2) + field(5) + field(6),
+        idle: field(3),
+        iowait: Some(field(4)),
+        total: v.iter().sum(),
+    })
+}
+
+#[cfg(target_os = "macos")]
+pub fn read_cpu_times() -> Option<CpuTimes> {
+    extern "C" {
+        fn mach_host_self() -> u32;
+        fn host_statistics(host: u32, flavor: i32, info: *mut u32, count: *mut u32) -> i32;
+    }
+    const HOST_CPU_LOAD_INFO: i32 = 3;
+    // user, system, idle, nice
+    let mut ticks = [0u32; 4];
+    let mut count = ticks.len() as u32;
+    // SAFETY: the buffer holds `count` integers, as host_statistics expects for this flavor
+    let status = unsafe {
+        host_statistics(mach_host_self(), HOST_CPU_LOAD_INFO, ticks.as_mut_ptr(), &mut count)
+    };
+    if status != 0 {
+        return None;
+    }
+    let [user, system, idle, nice] = ticks.map(u64::from);
+    Some(CpuTimes {
+        user: user + nice,
+        system,
+        idle,
+        iowait: None,
+        total: user + system + idle + nice,
+    })
+}
+
+#[cfg(windows)]
+pub fn read_cpu_times() -> Option<CpuTimes> {
+    #[repr(C)]
+    #[derive(Default)]
+    struct FileTime {
+        low: u32,
+        high: u32,
+    }
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetSystemTimes(idle: *mut FileTime, kernel: *mut FileTime, user: *mut FileTime) -> i32;
+    }
+
+    let (mut idle, mut kernel, mut user) = (FileTime::default(), FileTime::default(), FileTime::default());
+    // SAFETY: three valid out-pointers
+    if unsafe { GetSystemTimes(&mut idle, &mut kernel, &mut user) } == 0 {
+        return None;
+    }
+    let ticks = |t: &FileTime| (u64::from(t.high) << 32) | u64::from(t.low);
+    let (idle, kernel, user) = (ticks(&idle), ticks(&kernel), ticks(&user));
+    // Kernel time includes idle time
+    Some(CpuTimes {
+        user,
+        system: kernel.saturating_sub(idle),
+        idle,
+        iowait: None,
+        total: kernel + user,
+    })
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+pub fn read_cpu_times() -> Option<CpuTimes> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector() -> OverheatDetector {
+        let mut detector = OverheatDetector::default();
+        detector.set_config(OverheatConfig {
+            threshold_c: 90.0,
+            duration_secs: 60,
+            hysteresis_c: 5.0,
+        });
+        detector
+    }
+
+    #[test]
+    fn stays_off_below_the_threshold() {
+        let mut detector = detector();
+        let start = Instant::now();
+        for secs in [0, 60, 120] {
+            assert!(!detector.update(90.0, start + Duration::from_secs(secs)));
+        }
+    }
+
+    #[test]
+    fn a_short_spike_does_not_trip() {
+        let mut detector = detector();
+        let start = Instant::now();
+        assert!(!detector.update(95.0, start));
+        assert!(!detector.update(95.0, start + Duration::from_secs(59)));
+        // Dropping below the threshold starts the period over
+        assert!(!detector.update(85.0, start + Duration::from_secs(60)));
+        assert!(!detector.update(95.0, start + Duration::from_secs(61)));
+        assert!(!detector.update(95.0, start + Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn trips_after_a_sustained_period() {
+        let mut detector = detector();
+        let start = Instant::now();
+        assert!(!detector.update(95.0, start));
+        assert!(!detector.update(92.0, start + Duration::from_secs(30)));
+        assert!(detector.update(91.0, start + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn resets_only_below_the_hysteresis_margin() {
+        let mut detector = detector();
+        let start = Instant::now();
+        detector.update(95.0, start);
+        assert!(detector.update(95.0, start + Duration::from_secs(60)));
+        assert!(detector.update(86.0, start + Duration::from_secs(70)));
+        assert!(detector.update(85.0, start + Duration::from_secs(80)));
+        assert!(!detector.update(84.9, start + Duration::from_secs(90)));
+        // Off again: a new sustained period is needed
+        assert!(!detector.update(95.0, start + Duration::from_secs(100)));
+    }
+
+    #[test]
+    fn restart_timer_forgets_the_current_period() {
+        let mut detector = detector();
+        let start = Instant::now();
+        detector.update(95.0, start);
+        detector.restart_timer();
+        assert!(!detector.update(95.0, start + Duration::from_secs(60)));
+        assert!(detector.update(95.0, start + Duration::from_secs(120)));
+    }
+}
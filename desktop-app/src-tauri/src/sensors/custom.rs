@@ -0,0 +1,117 @@
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Default time a custom command may run before it is killed
+pub const DEFAULT_TIMEOUT_SECS: u64 = 10;
+
+/// HA rejects sensor states longer than 255 characters
+const MAX_STATE_LEN: usize = 255;
+
+/// A user-defined sensor whose state comes from a shell command or a file.
+/// Only runs when `custom_sensors_enabled` is set: commands execute with the app's privileges.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomSensor {
+    /// Used in the unique_id (`custom_<id>`)
+    pub id: String,
+    pub name: String,
+    /// Shell command (`sh -c` / `cmd /C`); its trimmed stdout is the state
+    #[serde(default)]
+    pub command: Option<String>,
+    /// File whose trimmed contents are the state (used when `command` is not set)
+    #[serde(default)]
+    pub file: Option<String>,
+    #[serde(default)]
+    pub unit: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// Minimum seconds between runs; the last value is reused in between (None = every cycle)
+    #[serde(default)]
+    pub interval: Option<u64>,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+impl CustomSensor {
+    pub fn unique_id(&self) -> String {
+        format!("custom_{}", self.id.replace(['.', ':', '-', ' ', '/', '\\'], "_"))
+    }
+}
+
+/// Read the sensor's current state. Errors are returned as a message and logged by the caller.
+pub fn read_state(sensor: &CustomSensor) -> Result<String, String> {
+    let raw = if let Some(ref command) = sensor.command {
+        let timeout = Duration::from_secs(sensor.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS));
+        run_command(command, timeout)?
+    } else if let Some(ref file) = sensor.file {
+        std::fs::read_to_string(file).map_err(|e| format!("Failed to read {}: {}", file, e))?
+    } else {
+        return Err("neither command nor file is set".to_string());
+    };
+
+    let state = raw.trim();
+    if state.is_empty() {
+        return Err("empty output".to_string());
+    }
+    Ok(state.chars().take(MAX_STATE_LEN).collect())
+}
+
+fn shell_command(command: &str) -> Command {
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command).creation_flags(CREATE_NO_WINDOW);
+        cmd
+    }
+    #[cfg(not(windows))]
+    {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    }
+}
+
+/// Run a shell command, killing it when it exceeds `timeout`
+fn run_command(command: &str, timeout: Duration) -> Result<String, String> {
+    let mut child = shell_command(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to start command: {}", e))?;
+
+    // Drain stdout on a thread so a chatty command can't block on a full pipe
+    let mut stdout = child.stdout.take();
+    let reader = std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(ref mut out) = stdout {
+            let _ = out.read_to_end(&mut buf);
+        }
+        buf
+    });
+
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if started.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("timed out after {}s", timeout.as_secs()));
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(50)),
+            Err(e) => return Err(format!("Failed to wait for command: {}", e)),
+        }
+    };
+
+    let output = reader.join().unwrap_or_default();
+    if !status.success() {
+        return Err(format!("command exited with {}", status));
+    }
+    Ok(String::from_utf8_lossy(&output).to_string())
+}
@@ -1,6 +1,33 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 use sysinfo::Disks;
 
+/// How disk_usage counts "used" (`disk_usage_mode` in settings.json). The two differ by the
+/// space the filesystem keeps back: ext4 reserves 5% for root by default, so `available`
+/// reads higher than `df` there. On Windows they normally agree; only disk quotas make
+/// Explorer (which shows the whole volume) differ from what the app may use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiskUsageMode {
+    /// Everything the app can't write to counts as used: total - available
+    #[default]
+    Available,
+    /// Like `df` and Explorer: used = total - free, as a share of used + available, so
+    /// reserved space counts neither as used nor as available
+    Filesystem,
+}
+
+impl DiskUsageMode {
+    /// Human-readable formula, exposed as a sensor attribute so graphs are interpretable
+    pub fn description(&self) -> &'static str {
+        match self {
+            DiskUsageMode::Available => "(total - available) / total",
+            DiskUsageMode::Filesystem => "(total - free) / (total - free + available)",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiskData {
     pub partitions: Vec<PartitionData>,
@@ -13,23 +40,47 @@ pub struct PartitionData {
     pub total_bytes: u64,
     pub used_bytes: u64,
     pub available_bytes: u64,
-    pub usage_percent: f32,
+    pub usage_percent: f64,
+    /// Free space only root/the system may use; None in the `available` mode or when the
+    /// filesystem couldn't be asked
+    pub reserved_bytes: Option<u64>,
     pub filesystem: String,
     pub disk_type: String,
 }
 
-pub fn collect() -> DiskData {
-    let disks = Disks::new_with_refreshed_list();
+/// Read the disks kept by the collector. `refresh` only re-reads the space of known disks;
+/// `relist` enumerates them again (drives plugged in or removed).
+pub fn collect(disks: &mut Disks, relist: bool, mode: DiskUsageMode) -> DiskData {
+    if relist {
+        disks.refresh_list();
+    } else {
+        disks.refresh();
+    }
+    // sysinfo only knows the available space; the free space comes from the OS
+    let free = match mode {
+        DiskUsageMode::Available => HashMap::new(),
+        DiskUsageMode::Filesystem => free_space(disks),
+    };
     let partitions: Vec<PartitionData> = disks
         .iter()
         .map(|disk| {
             let total = disk.total_space();
             let available = disk.available_space();
-            let used = total.saturating_sub(available);
-            let usage_percent = if total > 0 {
-                (used as f32 / total as f32) * 100.0
-            } else {
-                0.0
+            let mount_point = disk.mount_point().to_string_lossy().to_string();
+            // Falls back to the `available` numbers for disks the OS didn't report
+            let (used, usage_percent, reserved) = match free.get(&mount_point) {
+                Some(&free) => {
+                    // Not `clamp`: some FUSE and network filesystems report more available
+                    // than total space
+                    let free = free.max(available).min(total);
+                    let used = total - free;
+                    let percent = super::usage_percent(used, used + available);
+                    (used, percent, Some(free.saturating_sub(available)))
+                }
+                None => {
+                    let used = total.saturating_sub(available);
+                    (used, super::usage_percent(used, total), None)
+                }
             };
 
             let disk_type = match disk.kind() {
@@ -40,11 +91,12 @@ pub fn collect() -> DiskData {
 
             PartitionData {
                 name: disk.name().to_string_lossy().to_string(),
-                mount_point: disk.mount_point().to_string_lossy().to_string(),
+                mount_point,
                 total_bytes: total,
                 used_bytes: used,
                 available_bytes: available,
                 usage_percent,
+                reserved_bytes: reserved,
                 filesystem: disk.file_system().to_string_lossy().to_string(),
                 disk_type,
             }
@@ -53,3 +105,69 @@ pub fn collect() -> DiskData {
 
     DiskData { partitions }
 }
+
+/// Free bytes per mount point, reserved space included, from one `df` run
+/// (`-P` for the portable column layout, `-k` for 1024-byte blocks). Runs as a probe, so a
+/// `df` hanging on a stale network mount only costs the probe timeout.
+#[cfg(unix)]
+fn free_space(_disks: &Disks) -> HashMap<String, u64> {
+    let output = super::probe::run("df", || {
+        std::process::Command::new("df").args(["-P", "-k"]).output()
+    });
+    let output = match output {
+        Some(Ok(output)) if output.status.success() || !output.stdout.is_empty() => output,
+        _ => {
+            log::debug!("[Disk] df failed, disk usage counts reserved space as used");
+            return HashMap::new();
+        }
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            // Filesystem, 1024-blocks, Used, Available, Capacity, Mounted on (may hold spaces)
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 6 {
+                return None;
+            }
+            let blocks: u64 = fields[1].parse().ok()?;
+            let used: u64 = fields[2].parse().ok()?;
+            let free = blocks.saturating_sub(used) * 1024;
+            Some((fields[5..].join(" "), free))
+        })
+        .collect()
+}
+
+/// Free bytes per mount point, for all users (Explorer's "free space")
+#[cfg(windows)]
+fn free_space(disks: &Disks) -> HashMap<String, u64> {
+    use std::os::windows::ffi::OsStrExt;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetDiskFreeSpaceExW(
+            directory: *const u16,
+            free_to_caller: *mut u64,
+            total: *mut u64,
+            total_free: *mut u64,
+        ) -> i32;
+    }
+
+    disks
+        .iter()
+        .filter_map(|disk| {
+            let path: Vec<u16> = disk.mount_point().as_os_str().encode_wide().chain([0]).collect();
+            let (mut free_to_caller, mut total, mut total_free) = (0u64, 0u64, 0u64);
+            // SAFETY: `path` is NUL-terminated and the out pointers are local u64s
+            let ok = unsafe {
+                GetDiskFreeSpaceExW(path.as_ptr(), &mut free_to_caller, &mut total, &mut total_free)
+            };
+            (ok != 0).then(|| (disk.mount_point().to_string_lossy().to_string(), total_free))
+        })
+        .collect()
+}
+
+#[cfg(not(any(unix, windows)))]
+fn free_space(_disks: &Disks) -> HashMap<String, u64> {
+    HashMap::new()
+}
@@ -1,5 +1,13 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
+use super::{StaticCache, TemperatureRange};
+
+/// GPUs found via WMI / rocm-smi / sysfs / system_profiler. Those only report static
+/// details (name, VRAM size, driver), so the slow queries are cached.
+static PLATFORM_GPUS: StaticCache<Option<Vec<GpuInfo>>> = StaticCache::new();
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GpuData {
     pub gpus: Vec<GpuInfo>,
@@ -11,23 +19,81 @@ pub struct GpuInfo {
     pub vendor: String,
     pub usage_percent: Option<f32>,
     pub temperature: Option<f32>,
+    /// Where the temperature was read ("NVML"); None without a temperature
+    pub temperature_source: Option<String>,
     pub vram_total_mb: Option<u64>,
     pub vram_used_mb: Option<u64>,
     pub driver_version: Option<String>,
+    /// Memory controller busy time in percent (NVIDIA only)
+    pub memory_util_percent: Option<f32>,
+    /// PCIe transmit/receive throughput in KB/s (NVIDIA only, None when the card doesn't support it)
+    pub pcie_tx_kbps: Option<u32>,
+    pub pcie_rx_kbps: Option<u32>,
+    /// NVML clock throttle reasons bitmask (NVIDIA only), decoded by [`throttle_reasons`]
+    pub throttle_reasons: Option<u64>,
+    /// Processes using the GPU, largest VRAM user first (NVIDIA only, None elsewhere)
+    pub processes: Option<Vec<GpuProcess>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GpuProcess {
+    pub pid: u32,
+    pub name: String,
+    /// None when the driver doesn't report per-process memory (e.g. Windows WDDM)
+    pub vram_used_mb: Option<u64>,
+}
+
+/// Entity id suffix per GPU (e.g. `_dgpu` in `gpu_usage_dgpu`), stable when the enumeration
+/// order changes between boots. `ids` pins GPUs by a case-insensitive part of their name
+/// (`{"nvidia": "dgpu", "intel": "igpu"}`; the longest matching key wins). Other GPUs are
+/// named after the model, with `_2`, `_3` for identical cards. A single GPU without a pin
+/// keeps the bare ids (`gpu_usage`).
+pub fn entity_suffixes(gpus: &[GpuInfo], ids: &HashMap<String, String>) -> Vec<String> {
+    let devices: Vec<(&str, &str)> = gpus.iter().map(|g| (g.name.as_str(), g.name.as_str())).collect();
+    super::entity_suffixes(&devices, ids)
 }
 
-pub fn collect() -> GpuData {
+/// NVML clock throttle reason bits (`nvmlClocksThrottleReason*`) with an id and a description
+const THROTTLE_REASONS: &[(u64, &str, &str)] = &[
+    (0x1, "gpu_idle", "Nothing is running on the GPU"),
+    (0x2, "applications_clocks_setting", "Limited by the applications clocks setting"),
+    (0x4, "sw_power_cap", "Software power cap"),
+    (0x8, "hw_slowdown", "Hardware slowdown (temperature, power brake or power draw)"),
+    (0x10, "sync_boost", "Held back by another GPU in its sync boost group"),
+    (0x20, "sw_thermal_slowdown", "Software thermal slowdown (GPU or memory too hot)"),
+    (0x40, "hw_thermal_slowdown", "Hardware thermal slowdown"),
+    (0x80, "hw_power_brake_slowdown", "Hardware power brake (external power supply signal)"),
+    (0x100, "display_clock_setting", "Limited by the display clock setting"),
+];
+
+/// Active throttle reasons in a bitmask as (id, description); empty when the clocks aren't held back
+pub fn throttle_reasons(bits: u64) -> Vec<(&'static str, &'static str)> {
+    THROTTLE_REASONS
+        .iter()
+        .filter(|(bit, ..)| bits & bit != 0)
+        .map(|&(_, id, description)| (id, description))
+        .collect()
+}
+
+/// Collect GPU info; `include_processes` also enumerates per-GPU processes and `include_pcie`
+/// samples PCIe throughput (both NVIDIA only). Temperatures outside `temperature_range` count
+/// as unavailable.
+pub fn collect(
+    include_processes: bool,
+    include_pcie: bool,
+    temperature_range: TemperatureRange,
+) -> GpuData {
     let mut gpus = Vec::new();
 
     // Try NVIDIA via NVML
-    if let Some(nvidia_gpus) = collect_nvidia() {
+    if let Some(nvidia_gpus) = collect_nvidia(include_processes, include_pcie, temperature_range) {
         gpus.extend(nvidia_gpus);
     }
 
     // Try WMI on Windows for AMD/Intel
     #[cfg(windows)]
     {
-        if let Some(wmi_gpus) = collect_wmi() {
+        if let Some(wmi_gpus) = PLATFORM_GPUS.get_or_probe("gpu_wmi", collect_wmi).flatten() {
             // Only add WMI GPUs that weren't already found via NVML
             for wmi_gpu in wmi_gpus {
                 let already_found = gpus.iter().any(|g: &GpuInfo| {
@@ -44,7 +110,7 @@ pub fn collect() -> GpuData {
     #[cfg(target_os = "linux")]
     {
         if gpus.is_empty() {
-            if let Some(linux_gpus) = collect_linux() {
+            if let Some(linux_gpus) = PLATFORM_GPUS.get_or_probe("rocm-smi", collect_linux).flatten() {
                 gpus.extend(linux_gpus);
             }
         }
@@ -54,7 +120,7 @@ pub fn collect() -> GpuData {
     #[cfg(target_os = "macos")]
     {
         if gpus.is_empty() {
-            if let Some(mac_gpus) = collect_macos() {
+            if let Some(mac_gpus) = PLATFORM_GPUS.get_or_probe("system_profiler", collect_macos).flatten() {
                 gpus.extend(mac_gpus);
             }
         }
@@ -63,7 +129,193 @@ pub fn collect() -> GpuData {
     GpuData { gpus }
 }
 
-fn collect_nvidia() -> Option<Vec<GpuInfo>> {
+/// One GPU in the `active_gpu` sensor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GpuPowerState {
+    pub name: String,
+    pub integrated: bool,
+    /// As the OS or driver reports it: Linux runtime PM ("active", "suspended") or the NVML
+    /// performance state ("P8"). None when it can't be read.
+    pub power_state: Option<String>,
+    /// Whether the GPU is powered up or doing work; None when that can't be told
+    pub active: Option<bool>,
+}
+
+/// Which GPU of a hybrid-graphics laptop is doing the work
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActiveGpu {
+    /// "igpu", "dgpu" or "unknown"
+    pub state: &'static str,
+    pub gpus: Vec<GpuPowerState>,
+}
+
+/// Best-effort check whether the discrete GPU is spun up. Linux reads the runtime power
+/// state from sysfs, which doesn't wake a sleeping dGPU; elsewhere NVML tells whether the
+/// NVIDIA GPU has processes or load. None without a hybrid pair (an integrated and a
+/// discrete GPU), e.g. on a desktop or a MUX-switched laptop with one GPU visible.
+pub fn active_gpu() -> Option<ActiveGpu> {
+    #[cfg(target_os = "linux")]
+    let gpus = linux_power_states();
+
+    #[cfg(not(target_os = "linux"))]
+    let gpus = {
+        let mut gpus = nvidia_power_states();
+        #[cfg(windows)]
+        let platform = PLATFORM_GPUS.get_or_probe("gpu_wmi", collect_wmi).flatten();
+        #[cfg(target_os = "macos")]
+        let platform = PLATFORM_GPUS.get_or_probe("system_profiler", collect_macos).flatten();
+        #[cfg(not(any(windows, target_os = "macos")))]
+        let platform: Option<Vec<GpuInfo>> = None;
+        // NVML already covers the NVIDIA cards, with their power state
+        for gpu in platform.unwrap_or_default() {
+            if gpu.vendor != "NVIDIA" {
+                gpus.push(GpuPowerState {
+                    integrated: looks_integrated(&gpu.vendor, &gpu.name),
+                    name: gpu.name,
+                    power_state: None,
+                    active: None,
+                });
+            }
+        }
+        gpus
+    };
+
+    let hybrid = gpus.iter().any(|g| g.integrated) && gpus.iter().any(|g| !g.integrated);
+    if !hybrid {
+        return None;
+    }
+    Some(ActiveGpu {
+        state: active_gpu_state(&gpus),
+        gpus,
+    })
+}
+
+/// "dgpu" when a discrete GPU is active, "igpu" when all of them are asleep
+fn active_gpu_state(gpus: &[GpuPowerState]) -> &'static str {
+    let discrete: Vec<&GpuPowerState> = gpus.iter().filter(|g| !g.integrated).collect();
+    if discrete.iter().any(|g| g.active == Some(true)) {
+        "dgpu"
+    } else if discrete.iter().all(|g| g.active == Some(false)) {
+        "igpu"
+    } else {
+        "unknown"
+    }
+}
+
+/// Whether a GPU found by WMI or system_profiler is built into the CPU, from its name
+#[cfg(not(target_os = "linux"))]
+fn looks_integrated(vendor: &str, name: &str) -> bool {
+    let name = name.to_lowercase();
+    match vendor {
+        // Arc A- and B-series cards are discrete; "Intel(R) Arc(TM) Graphics" is a Core Ultra iGPU
+        "Intel" => !(name.contains("arc(tm) a") || name.contains("arc(tm) b")),
+        // Discrete Radeons carry "RX" (including the mobile RX ...M parts)
+        "AMD" => !name.contains(" rx "),
+        _ => false,
+    }
+}
+
+/// NVIDIA GPUs with their performance state; a GPU counts as active when it has processes
+/// or load. NVML wakes a suspended GPU on some laptops, so Linux uses sysfs instead.
+#[cfg(not(target_os = "linux"))]
+fn nvidia_power_states() -> Vec<GpuPowerState> {
+    use nvml_wrapper::enum_wrappers::device::PerformanceState;
+
+    let Ok(nvml) = nvml_wrapper::Nvml::init() else {
+        return Vec::new();
+    };
+    let count = nvml.device_count().unwrap_or(0);
+    (0..count)
+        .filter_map(|i| nvml.device_by_index(i).ok())
+        .map(|device| {
+            let processes = device.running_graphics_processes().map(|p| p.len()).ok();
+            let compute = device.running_compute_processes().map(|p| p.len()).ok();
+            let usage = device.utilization_rates().ok().map(|u| u.gpu);
+            let busy = [processes, compute, usage.map(|u| u as usize)];
+            let active = if busy.iter().any(|b| b.is_some_and(|n| n > 0)) {
+                Some(true)
+            } else if busy.iter().any(|b| b.is_some()) {
+                Some(false)
+            } else {
+                None
+            };
+            let power_state = device
+                .performance_state()
+                .ok()
+                .filter(|p| *p != PerformanceState::Unknown)
+                .map(|p| format!("P{}", p.as_c()));
+            GpuPowerState {
+                name: device.name().unwrap_or_else(|_| "NVIDIA GPU".to_string()),
+                integrated: false,
+                power_state,
+                active,
+            }
+        })
+        .collect()
+}
+
+/// Display controllers (PCI class 0x03) with their runtime power state. Intel and AMD GPUs
+/// on the root bus (`0000:00:...`) are built into the CPU; discrete cards, Intel Arc
+/// included, sit behind a PCIe port. AMD APUs often hang off an internal bridge instead, so
+/// an AMD GPU the firmware booted on (`boot_vga`) next to another GPU counts as integrated too.
+#[cfg(target_os = "linux")]
+fn linux_power_states() -> Vec<GpuPowerState> {
+    let Ok(entries) = std::fs::read_dir("/sys/bus/pci/devices") else {
+        return Vec::new();
+    };
+    let read = |dir: &std::path::Path, file: &str| {
+        std::fs::read_to_string(dir.join(file))
+            .ok()
+            .map(|s| s.trim().to_string())
+    };
+
+    let devices: Vec<std::path::PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|dir| read(dir, "class").is_some_and(|class| class.starts_with("0x03")))
+        .collect();
+    let mut gpus: Vec<GpuPowerState> = devices
+        .iter()
+        .map(|dir| {
+            let address = dir
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let root_bus = address.split(':').nth(1) == Some("00");
+            let boot_vga = read(dir, "boot_vga").as_deref() == Some("1");
+            let (vendor, integrated) = match read(dir, "vendor").as_deref() {
+                Some("0x10de") => ("NVIDIA", false),
+                Some("0x1002") => ("AMD", root_bus || (boot_vga && devices.len() > 1)),
+                Some("0x8086") => ("Intel", root_bus),
+                _ => ("GPU", false),
+            };
+            // "unsupported" when runtime PM is off for the device: it is always powered,
+            // but whether it does any work can't be told
+            let power_state = read(dir, "power/runtime_status");
+            let active = match power_state.as_deref() {
+                Some("active") | Some("resuming") => Some(true),
+                Some("suspended") | Some("suspending") => Some(false),
+                _ => None,
+            };
+            GpuPowerState {
+                name: format!("{} {}", vendor, address),
+                integrated,
+                power_state,
+                active,
+            }
+        })
+        .collect();
+    gpus.sort_by(|a, b| a.name.cmp(&b.name));
+    gpus
+}
+
+fn collect_nvidia(
+    include_processes: bool,
+    include_pcie: bool,
+    temperature_range: TemperatureRange,
+) -> Option<Vec<GpuInfo>> {
+    use nvml_wrapper::enum_wrappers::device::PcieUtilCounter;
+
     let nvml = nvml_wrapper::Nvml::init().ok()?;
     let count = nvml.device_count().ok()?;
     let mut gpus = Vec::new();
@@ -74,24 +326,45 @@ fn collect_nvidia() -> Option<Vec<GpuInfo>> {
             let temperature = device
                 .temperature(nvml_wrapper::enum_wrappers::device::TemperatureSensor::Gpu)
                 .ok()
-                .map(|t| t as f32);
-            let utilization = device
-                .utilization_rates()
-                .ok()
-                .map(|u| u.gpu as f32);
+                .and_then(|t| temperature_range.check(&name, t as f32));
+            let rates = device.utilization_rates().ok();
+            let utilization = rates.as_ref().map(|u| u.gpu as f32);
+            let memory_util = rates.as_ref().map(|u| u.memory as f32);
             let memory = device.memory_info().ok();
             let vram_total = memory.as_ref().map(|m| m.total / 1_048_576);
             let vram_used = memory.as_ref().map(|m| m.used / 1_048_576);
             let driver_version = nvml.sys_driver_version().ok();
+            let processes = if include_processes {
+                Some(collect_nvidia_processes(&nvml, &device))
+            } else {
+                None
+            };
+            // NVML already reports a rate (sampled over ~20ms per counter), so no delta is
+            // needed; cards without PCIe counters (older or non-PCIe) return NotSupported
+            let (pcie_tx, pcie_rx) = if include_pcie {
+                (
+                    device.pcie_throughput(PcieUtilCounter::Send).ok(),
+                    device.pcie_throughput(PcieUtilCounter::Receive).ok(),
+                )
+            } else {
+                (None, None)
+            };
+            let throttle_reasons = device.current_throttle_reasons().ok().map(|r| r.bits());
 
             gpus.push(GpuInfo {
                 name,
                 vendor: "NVIDIA".to_string(),
                 usage_percent: utilization,
                 temperature,
+                temperature_source: temperature.map(|_| "NVML".to_string()),
                 vram_total_mb: vram_total,
                 vram_used_mb: vram_used,
                 driver_version,
+                memory_util_percent: memory_util,
+                pcie_tx_kbps: pcie_tx,
+                pcie_rx_kbps: pcie_rx,
+                throttle_reasons,
+                processes,
             });
         }
     }
@@ -103,17 +376,55 @@ fn collect_nvidia() -> Option<Vec<GpuInfo>> {
     }
 }
 
+/// Compute and graphics processes on one GPU, merged by pid (best-effort: errors give an empty list)
+fn collect_nvidia_processes(
+    nvml: &nvml_wrapper::Nvml,
+    device: &nvml_wrapper::Device,
+) -> Vec<GpuProcess> {
+    use nvml_wrapper::enums::device::UsedGpuMemory;
+
+    let mut processes: Vec<GpuProcess> = Vec::new();
+    let compute = device.running_compute_processes().unwrap_or_default();
+    let graphics = device.running_graphics_processes().unwrap_or_default();
+
+    for info in compute.into_iter().chain(graphics) {
+        let vram_used_mb = match info.used_gpu_memory {
+            UsedGpuMemory::Used(bytes) => Some(bytes / 1_048_576),
+            UsedGpuMemory::Unavailable => None,
+        };
+        if let Some(existing) = processes.iter_mut().find(|p| p.pid == info.pid) {
+            existing.vram_used_mb = existing.vram_used_mb.max(vram_used_mb);
+            continue;
+        }
+        let name = nvml
+            .sys_process_name(info.pid, 256)
+            .ok()
+            .and_then(|path| {
+                std::path::Path::new(&path)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+            })
+            .unwrap_or_else(|| format!("pid {}", info.pid));
+        processes.push(GpuProcess {
+            pid: info.pid,
+            name,
+            vram_used_mb,
+        });
+    }
+
+    processes.sort_by_key(|p| std::cmp::Reverse(p.vram_used_mb));
+    processes
+}
+
 #[cfg(windows)]
 fn collect_wmi() -> Option<Vec<GpuInfo>> {
-    use std::collections::HashMap;
-    use wmi::{COMLibrary, WMIConnection};
-
-    let com_lib = COMLibrary::new().ok()?;
-    let wmi_con = WMIConnection::new(com_lib).ok()?;
+    use super::wmi_conn::{self, Namespace};
 
-    let results: Vec<HashMap<String, wmi::Variant>> = wmi_con
-        .raw_query("SELECT Name, AdapterRAM, DriverVersion FROM Win32_VideoController")
-        .ok()?;
+    let results = wmi_conn::raw_query(
+        Namespace::Cimv2,
+        "SELECT Name, AdapterRAM, DriverVersion FROM Win32_VideoController",
+    )?
+    .ok()?;
 
     let mut gpus = Vec::new();
     for result in results {
@@ -146,9 +457,15 @@ fn collect_wmi() -> Option<Vec<GpuInfo>> {
             vendor,
             usage_percent: None, // WMI doesn't provide real-time usage
             temperature: None,
+            temperature_source: None,
             vram_total_mb: vram_total,
             vram_used_mb: None,
             driver_version,
+            memory_util_percent: None,
+            pcie_tx_kbps: None,
+            pcie_rx_kbps: None,
+            throttle_reasons: None,
+            processes: None,
         });
     }
 
@@ -180,9 +497,15 @@ fn collect_linux() -> Option<Vec<GpuInfo>> {
                     vendor: "AMD".to_string(),
                     usage_percent: None,
                     temperature: None,
+                    temperature_source: None,
                     vram_total_mb: None,
                     vram_used_mb: None,
                     driver_version: None,
+                    memory_util_percent: None,
+                    pcie_tx_kbps: None,
+                    pcie_rx_kbps: None,
+                    throttle_reasons: None,
+                    processes: None,
                 });
             }
         }
@@ -198,9 +521,15 @@ fn collect_linux() -> Option<Vec<GpuInfo>> {
                     vendor: "Intel".to_string(),
                     usage_percent: None,
                     temperature: None,
+                    temperature_source: None,
                     vram_total_mb: None,
                     vram_used_mb: None,
                     driver_version: None,
+                    memory_util_percent: None,
+                    pcie_tx_kbps: None,
+                    pcie_rx_kbps: None,
+                    throttle_reasons: None,
+                    processes: None,
                 });
             }
         }
@@ -256,9 +585,15 @@ fn collect_macos() -> Option<Vec<GpuInfo>> {
             vendor,
             usage_percent: None,
             temperature: None,
+            temperature_source: None,
             vram_total_mb: vram.map(|v| v * 1024), // Convert GB to MB
             vram_used_mb: None,
             driver_version: None,
+            memory_util_percent: None,
+            pcie_tx_kbps: None,
+            pcie_rx_kbps: None,
+            throttle_reasons: None,
+            processes: None,
         });
     }
 
@@ -0,0 +1,91 @@
+//! Time since the last keyboard/mouse input (opt-in), in seconds. None where it can't be read:
+//! - Windows: `GetLastInputInfo`
+//! - Linux: `xprintidle` on X11, else GNOME's Mutter idle monitor over D-Bus (Wayland)
+//! - macOS: CoreGraphics `CGEventSourceSecondsSinceLastEventType`
+
+#[cfg(windows)]
+pub fn idle_seconds() -> Option<u64> {
+    #[repr(C)]
+    struct LastInputInfo {
+        size: u32,
+        time: u32,
+    }
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn GetLastInputInfo(info: *mut LastInputInfo) -> i32;
+    }
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetTickCount() -> u32;
+    }
+
+    let mut info = LastInputInfo {
+        size: std::mem::size_of::<LastInputInfo>() as u32,
+        time: 0,
+    };
+    // SAFETY: `info` is a properly sized LASTINPUTINFO
+    if unsafe { GetLastInputInfo(&mut info) } == 0 {
+        return None;
+    }
+    // Both are milliseconds since boot in 32 bits; wrapping_sub survives the 49.7 day rollover
+    // SAFETY: plain Win32 call without arguments
+    let idle_ms = unsafe { GetTickCount() }.wrapping_sub(info.time);
+    Some(u64::from(idle_ms) / 1000)
+}
+
+#[cfg(target_os = "linux")]
+pub fn idle_seconds() -> Option<u64> {
+    let run = |program: &str, args: &[&str]| -> Option<String> {
+        let output = std::process::Command::new(program).args(args).output().ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+
+    // X11: milliseconds
+    if let Some(ms) = run("xprintidle", &[]).and_then(|s| s.parse::<u64>().ok()) {
+        return Some(ms / 1000);
+    }
+    // GNOME on Wayland: "(uint64 12345,)" in milliseconds
+    let reply = run(
+        "gdbus",
+        &[
+            "call",
+            "--session",
+            "--dest",
+            "org.gnome.Mutter.IdleMonitor",
+            "--object-path",
+            "/org/gnome/Mutter/IdleMonitor/Core",
+            "--method",
+            "org.gnome.Mutter.IdleMonitor.GetIdletime",
+        ],
+    )?;
+    let ms: u64 = reply
+        .trim_start_matches("(uint64 ")
+        .trim_end_matches(",)")
+        .parse()
+        .ok()?;
+    Some(ms / 1000)
+}
+
+#[cfg(target_os = "macos")]
+pub fn idle_seconds() -> Option<u64> {
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGEventSourceSecondsSinceLastEventType(state_id: i32, event_type: u32) -> f64;
+    }
+    const COMBINED_SESSION_STATE: i32 = 0;
+    const ANY_INPUT_EVENT_TYPE: u32 = u32::MAX;
+
+    // SAFETY: reads the time since the last input event; no pointers involved
+    let seconds =
+        unsafe { CGEventSourceSecondsSinceLastEventType(COMBINED_SESSION_STATE, ANY_INPUT_EVENT_TYPE) };
+    (seconds.is_finite() && seconds >= 0.0).then_some(seconds as u64)
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+pub fn idle_seconds() -> Option<u64> {
+    None
+}
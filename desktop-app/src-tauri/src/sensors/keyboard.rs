@@ -0,0 +1,96 @@
+//! Lock key states and keyboard backlight level (opt-in). Each value is None where the
+//! platform or hardware doesn't expose it:
+//! - Windows: `GetKeyState` toggle bits; no backlight API
+//! - Linux: input LEDs and `*::kbd_backlight` in `/sys/class/leds`
+//! - macOS: caps lock from the CoreGraphics modifier flags; no num/scroll lock or backlight
+
+#[derive(Debug, Clone, Default)]
+pub struct KeyboardState {
+    pub caps_lock: Option<bool>,
+    pub num_lock: Option<bool>,
+    pub scroll_lock: Option<bool>,
+    /// Backlight brightness in percent of the maximum
+    pub backlight_percent: Option<f64>,
+}
+
+#[cfg(windows)]
+pub fn collect() -> KeyboardState {
+    #[link(name = "user32")]
+    extern "system" {
+        fn GetKeyState(virtual_key: i32) -> i16;
+    }
+    const VK_CAPITAL: i32 = 0x14;
+    const VK_NUMLOCK: i32 = 0x90;
+    const VK_SCROLL: i32 = 0x91;
+
+    // The low bit is the toggle state of lock keys
+    // SAFETY: plain Win32 call with a constant virtual-key code
+    let toggled = |key: i32| Some(unsafe { GetKeyState(key) } & 1 != 0);
+    KeyboardState {
+        caps_lock: toggled(VK_CAPITAL),
+        num_lock: toggled(VK_NUMLOCK),
+        scroll_lock: toggled(VK_SCROLL),
+        backlight_percent: None,
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn collect() -> KeyboardState {
+    use std::path::Path;
+
+    let read_u64 = |path: &Path| -> Option<u64> {
+        std::fs::read_to_string(path).ok()?.trim().parse().ok()
+    };
+
+    let mut state = KeyboardState::default();
+    let Ok(entries) = std::fs::read_dir("/sys/class/leds") else {
+        return state;
+    };
+    // LED names look like "input3::capslock" (one per keyboard) or "tpacpi::kbd_backlight"
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some((_, function)) = name.rsplit_once("::") else {
+            continue;
+        };
+        let Some(brightness) = read_u64(&entry.path().join("brightness")) else {
+            continue;
+        };
+        // With several keyboards, a lock is on if any keyboard's LED is on
+        let lock = match function {
+            "capslock" => &mut state.caps_lock,
+            "numlock" => &mut state.num_lock,
+            "scrolllock" => &mut state.scroll_lock,
+            "kbd_backlight" => {
+                if let Some(max) = read_u64(&entry.path().join("max_brightness")).filter(|m| *m > 0) {
+                    state.backlight_percent = Some(super::usage_percent(brightness, max));
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        *lock = Some(lock.unwrap_or(false) || brightness > 0);
+    }
+    state
+}
+
+#[cfg(target_os = "macos")]
+pub fn collect() -> KeyboardState {
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGEventSourceFlagsState(state_id: i32) -> u64;
+    }
+    const COMBINED_SESSION_STATE: i32 = 0;
+    const FLAG_MASK_ALPHA_SHIFT: u64 = 0x0001_0000;
+
+    // SAFETY: reads the current modifier flags; no pointers involved
+    let flags = unsafe { CGEventSourceFlagsState(COMBINED_SESSION_STATE) };
+    KeyboardState {
+        caps_lock: Some(flags & FLAG_MASK_ALPHA_SHIFT != 0),
+        ..KeyboardState::default()
+    }
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+pub fn collect() -> KeyboardState {
+    KeyboardState::default()
+}
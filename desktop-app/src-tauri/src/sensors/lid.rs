@@ -0,0 +1,157 @@
+//! Laptop lid state (best-effort). None on machines without a lid or where it can't be read:
+//! - Linux: ACPI button state in `/proc/acpi/button/lid/*/state`
+//! - Windows: `GUID_LIDSWITCH_STATE_CHANGE` power setting notifications
+//! - macOS: `AppleClamshellState` from IOKit (via `ioreg`)
+
+/// Whether the lid is open
+#[cfg(target_os = "linux")]
+pub fn lid_open() -> Option<bool> {
+    let entries = std::fs::read_dir("/proc/acpi/button/lid").ok()?;
+    for entry in entries.flatten() {
+        // "state:      open" / "state:      closed"
+        let Ok(state) = std::fs::read_to_string(entry.path().join("state")) else {
+            continue;
+        };
+        if state.contains("open") {
+            return Some(true);
+        }
+        if state.contains("closed") {
+            return Some(false);
+        }
+    }
+    None
+}
+
+#[cfg(target_os = "macos")]
+pub fn lid_open() -> Option<bool> {
+    // Only portables have the key: "AppleClamshellState" = Yes (closed) / No (open)
+    let output = std::process::Command::new("ioreg")
+        .args(["-r", "-k", "AppleClamshellState", "-d", "1"])
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    let line = text.lines().find(|l| l.contains("\"AppleClamshellState\""))?;
+    match line.rsplit('=').next()?.trim() {
+        "Yes" => Some(false),
+        "No" => Some(true),
+        _ => None,
+    }
+}
+
+#[cfg(windows)]
+pub fn lid_open() -> Option<bool> {
+    windows_lid::state()
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+pub fn lid_open() -> Option<bool> {
+    None
+}
+
+/// Windows reports the lid only through power setting notifications. Registering delivers
+/// the current state right away, and again on every change; without a lid switch nothing
+/// is delivered and the state stays unknown.
+#[cfg(windows)]
+mod windows_lid {
+    use std::ffi::c_void;
+    use std::sync::atomic::{AtomicU8, Ordering};
+    use std::sync::Once;
+
+    const UNKNOWN: u8 = 0;
+    const CLOSED: u8 = 1;
+    const OPEN: u8 = 2;
+
+    static STATE: AtomicU8 = AtomicU8::new(UNKNOWN);
+    static REGISTER: Once = Once::new();
+
+    #[repr(C)]
+    #[derive(PartialEq)]
+    struct Guid {
+        data1: u32,
+        data2: u16,
+        data3: u16,
+        data4: [u8; 8],
+    }
+
+    /// GUID_LIDSWITCH_STATE_CHANGE {BA3E0F4D-B817-4094-A2D1-D56379E6A0F3}
+    static GUID_LIDSWITCH_STATE_CHANGE: Guid = Guid {
+        data1: 0xBA3E_0F4D,
+        data2: 0xB817,
+        data3: 0x4094,
+        data4: [0xA2, 0xD1, 0xD5, 0x63, 0x79, 0xE6, 0xA0, 0xF3],
+    };
+
+    const DEVICE_NOTIFY_CALLBACK: u32 = 2;
+    const PBT_POWERSETTINGCHANGE: u32 = 0x8013;
+
+    /// POWERBROADCAST_SETTING header; `data` holds a DWORD (0 = closed, 1 = open)
+    #[repr(C)]
+    struct PowerBroadcastSetting {
+        power_setting: Guid,
+        data_length: u32,
+        data: [u8; 4],
+    }
+
+    type Callback = unsafe extern "system" fn(*mut c_void, u32, *mut c_void) -> u32;
+
+    #[repr(C)]
+    struct DeviceNotifySubscribeParameters {
+        callback: Callback,
+        context: *mut c_void,
+    }
+
+    #[link(name = "powrprof")]
+    extern "system" {
+        fn PowerSettingRegisterNotification(
+            setting_guid: *const Guid,
+            flags: u32,
+            recipient: *mut c_void,
+            registration_handle: *mut *mut c_void,
+        ) -> u32;
+    }
+
+    unsafe extern "system" fn on_power_setting(
+        _context: *mut c_void,
+        kind: u32,
+        setting: *mut c_void,
+    ) -> u32 {
+        if kind != PBT_POWERSETTINGCHANGE || setting.is_null() {
+            return 0;
+        }
+        // SAFETY: for PBT_POWERSETTINGCHANGE, Windows passes a POWERBROADCAST_SETTING
+        let setting = &*(setting as *const PowerBroadcastSetting);
+        if setting.power_setting == GUID_LIDSWITCH_STATE_CHANGE && setting.data_length >= 4 {
+            let open = u32::from_le_bytes(setting.data) != 0;
+            STATE.store(if open { OPEN } else { CLOSED }, Ordering::Relaxed);
+        }
+        0
+    }
+
+    pub fn state() -> Option<bool> {
+        REGISTER.call_once(|| {
+            // Leaked: the registration lives for the whole process
+            let params = Box::leak(Box::new(DeviceNotifySubscribeParameters {
+                callback: on_power_setting,
+                context: std::ptr::null_mut(),
+            }));
+            let mut handle = std::ptr::null_mut();
+            // SAFETY: params outlives the registration and the callback matches the ABI
+            let result = unsafe {
+                PowerSettingRegisterNotification(
+                    &GUID_LIDSWITCH_STATE_CHANGE,
+                    DEVICE_NOTIFY_CALLBACK,
+                    params as *mut DeviceNotifySubscribeParameters as *mut c_void,
+                    &mut handle,
+                )
+            };
+            if result != 0 {
+                log::debug!("[Lid] PowerSettingRegisterNotification failed: {}", result);
+            }
+        });
+        match STATE.load(Ordering::Relaxed) {
+            OPEN => Some(true),
+            CLOSED => Some(false),
+            _ => None,
+        }
+    }
+}
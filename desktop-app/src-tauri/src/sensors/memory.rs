@@ -1,12 +1,35 @@
 use serde::{Deserialize, Serialize};
 use sysinfo::System;
 
+/// How "used" memory is computed on Linux (ignored on other platforms).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryUsedMode {
+    /// MemTotal - MemAvailable: the "used" column of current `free -h`
+    /// (page cache that can be reclaimed counts as available).
+    #[default]
+    Available,
+    /// MemTotal - MemFree - Buffers - Cached - SReclaimable: what htop and older
+    /// `free` versions show (excludes all cache, so it reads a bit lower).
+    ExcludeCache,
+}
+
+impl MemoryUsedMode {
+    /// Human-readable formula, exposed as a sensor attribute so graphs are interpretable
+    pub fn description(&self) -> &'static str {
+        match self {
+            MemoryUsedMode::Available => "total - available",
+            MemoryUsedMode::ExcludeCache => "total - free - buffers - cache",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemoryData {
     pub total_bytes: u64,
     pub used_bytes: u64,
     pub available_bytes: u64,
-    pub usage_percent: f32,
+    pub usage_percent: f64,
     pub total_gb: f64,
     pub used_gb: f64,
     pub available_gb: f64,
@@ -14,15 +37,11 @@ pub struct MemoryData {
     pub swap_used_bytes: u64,
 }
 
-pub fn collect(sys: &System) -> MemoryData {
+pub fn collect(sys: &System, mode: MemoryUsedMode) -> MemoryData {
     let total = sys.total_memory();
-    let used = sys.used_memory();
     let available = sys.available_memory();
-    let usage_percent = if total > 0 {
-        (used as f32 / total as f32) * 100.0
-    } else {
-        0.0
-    };
+    let used = linux_used_memory(mode).unwrap_or_else(|| sys.used_memory());
+    let usage_percent = super::usage_percent(used, total);
 
     MemoryData {
         total_bytes: total,
@@ -36,3 +55,172 @@ pub fn collect(sys: &System) -> MemoryData {
         swap_used_bytes: sys.used_swap(),
     }
 }
+
+/// Compute used memory from /proc/meminfo according to `mode`
+#[cfg(target_os = "linux")]
+fn linux_used_memory(mode: MemoryUsedMode) -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let read_kib = |key: &str| -> Option<u64> {
+        meminfo
+            .lines()
+            .find(|l| l.split(':').next() == Some(key))
+            .and_then(|l| l.split_whitespace().nth(1))
+            .and_then(|v| v.parse::<u64>().ok())
+    };
+
+    let total = read_kib("MemTotal")?;
+    let used_kib = match mode {
+        MemoryUsedMode::Available => total.saturating_sub(read_kib("MemAvailable")?),
+        MemoryUsedMode::ExcludeCache => total
+            .saturating_sub(read_kib("MemFree")?)
+            .saturating_sub(read_kib("Buffers").unwrap_or(0))
+            .saturating_sub(read_kib("Cached").unwrap_or(0))
+            .saturating_sub(read_kib("SReclaimable").unwrap_or(0)),
+    };
+    Some(used_kib * 1024)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn linux_used_memory(_mode: MemoryUsedMode) -> Option<u64> {
+    None
+}
+
+/// A configured swap area: a swap file or partition on Linux, a pagefile on Windows,
+/// a dynamic swap file on macOS
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SwapArea {
+    pub path: String,
+    /// "file" or "partition" (Linux only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    /// Current size in MB
+    pub size_mb: Option<u64>,
+    /// Windows: configured initial and maximum size in MB; both 0 when Windows manages the size
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub initial_size_mb: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_size_mb: Option<u64>,
+    /// Linux swap priority
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i64>,
+}
+
+static SWAP_CONFIG: super::StaticCache<Vec<SwapArea>> = super::StaticCache::new();
+
+/// Configured swap areas (cached like the other static info). Empty when there is no swap;
+/// None if the query timed out.
+pub fn swap_config() -> Option<Vec<SwapArea>> {
+    SWAP_CONFIG.get_or_probe("swap_config", collect_swap_config)
+}
+
+/// /proc/swaps: "Filename Type Size Used Priority", sizes in KiB
+#[cfg(target_os = "linux")]
+fn collect_swap_config() -> Vec<SwapArea> {
+    let swaps = std::fs::read_to_string("/proc/swaps").unwrap_or_default();
+    swaps
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [path, kind, size_kib, _used, priority] = fields[..] else {
+                return None;
+            };
+            Some(SwapArea {
+                // The kernel escapes spaces in paths as \040
+                path: path.replace("\\040", " "),
+                kind: Some(kind.to_string()),
+                size_mb: size_kib.parse::<u64>().ok().map(|kib| kib / 1024),
+                initial_size_mb: None,
+                max_size_mb: None,
+                priority: priority.parse().ok(),
+            })
+        })
+        .collect()
+}
+
+/// Win32_PageFileUsage lists the pagefiles in use; Win32_PageFileSetting has the configured
+/// sizes, but only for pagefiles whose size isn't managed by Windows
+#[cfg(windows)]
+fn collect_swap_config() -> Vec<SwapArea> {
+    use super::wmi_conn::{self, Namespace};
+    use wmi::Variant;
+
+    let as_u64 = |v: &Variant| match v {
+        Variant::UI4(n) => Some(u64::from(*n)),
+        Variant::I4(n) => u64::try_from(*n).ok(),
+        Variant::UI8(n) => Some(*n),
+        Variant::String(s) => s.trim().parse().ok(),
+        _ => None,
+    };
+    let as_string = |v: &Variant| match v {
+        Variant::String(s) => Some(s.trim().to_string()).filter(|s| !s.is_empty()),
+        _ => None,
+    };
+
+    let settings: Vec<(String, Option<u64>, Option<u64>)> = match wmi_conn::raw_query(
+        Namespace::Cimv2,
+        "SELECT Name, InitialSize, MaximumSize FROM Win32_PageFileSetting",
+    ) {
+        Some(Ok(rows)) => rows
+            .iter()
+            .filter_map(|row| {
+                let name = row.get("Name").and_then(as_string)?;
+                Some((
+                    name,
+                    row.get("InitialSize").and_then(as_u64),
+                    row.get("MaximumSize").and_then(as_u64),
+                ))
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let Some(Ok(rows)) = wmi_conn::raw_query(
+        Namespace::Cimv2,
+        "SELECT Name, AllocatedBaseSize FROM Win32_PageFileUsage",
+    ) else {
+        return Vec::new();
+    };
+    rows.iter()
+        .filter_map(|row| {
+            let path = row.get("Name").and_then(as_string)?;
+            let setting = settings.iter().find(|(name, ..)| name.eq_ignore_ascii_case(&path));
+            Some(SwapArea {
+                size_mb: row.get("AllocatedBaseSize").and_then(as_u64),
+                kind: None,
+                // No setting: the size is managed by Windows
+                initial_size_mb: Some(setting.and_then(|s| s.1).unwrap_or(0)),
+                max_size_mb: Some(setting.and_then(|s| s.2).unwrap_or(0)),
+                priority: None,
+                path,
+            })
+        })
+        .collect()
+}
+
+/// macOS creates swap files in /private/var/vm on demand
+#[cfg(target_os = "macos")]
+fn collect_swap_config() -> Vec<SwapArea> {
+    let Ok(entries) = std::fs::read_dir("/private/var/vm") else {
+        return Vec::new();
+    };
+    let mut areas: Vec<SwapArea> = entries
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with("swapfile"))
+        .map(|e| SwapArea {
+            path: e.path().to_string_lossy().to_string(),
+            kind: None,
+            size_mb: e.metadata().ok().map(|m| m.len() / 1_048_576),
+            initial_size_mb: None,
+            max_size_mb: None,
+            priority: None,
+        })
+        .collect();
+    areas.sort_by(|a, b| a.path.cmp(&b.path));
+    areas
+}
+
+#[cfg(not(any(target_os = "linux", windows, target_os = "macos")))]
+fn collect_swap_config() -> Vec<SwapArea> {
+    Vec::new()
+}
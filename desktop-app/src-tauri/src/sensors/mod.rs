@@ -1,8 +1,289 @@
 pub mod battery;
+pub mod battery_saver;
+pub mod clipboard;
 pub mod collector;
+pub mod connection;
 pub mod cpu;
+pub mod custom;
 pub mod disk;
 pub mod gpu;
+pub mod idle;
+pub mod keyboard;
+pub mod lid;
 pub mod memory;
 pub mod network;
+pub mod power_plan;
+pub mod probe;
+pub mod remote;
+pub mod state_rounding;
 pub mod system_info;
+pub mod updates;
+pub mod validation;
+#[cfg(windows)]
+pub mod wmi_conn;
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// How long effectively static hardware info (board, BIOS, displays, GPU models) is reused
+const STATIC_INFO_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Bumped by `invalidate_static_info`; caches filled under an older generation are stale
+static STATIC_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Process-wide cache for slow queries of static info (WMI, system_profiler, rocm-smi),
+/// so registrations and static refreshes don't pay for them every time
+pub struct StaticCache<T> {
+    slot: Mutex<Option<(u64, Instant, T)>>,
+}
+
+impl<T: Clone> StaticCache<T> {
+    pub const fn new() -> Self {
+        Self {
+            slot: Mutex::new(None),
+        }
+    }
+
+    /// Cached value, or `query`'s result when the cache is empty, expired or invalidated
+    pub fn get_or_query(&self, query: impl FnOnce() -> T) -> T {
+        let generation = STATIC_GENERATION.load(Ordering::Relaxed);
+        if let Ok(slot) = self.slot.lock() {
+            if let Some((gen, at, ref value)) = *slot {
+                if gen == generation && at.elapsed() < STATIC_INFO_TTL {
+                    return value.clone();
+                }
+            }
+        }
+        let value = query();
+        self.store(generation, value.clone());
+        value
+    }
+
+    /// Like `get_or_query`, but the query runs as a `probe` with a timeout. None if it timed
+    /// out; that isn't cached, so the next call tries again.
+    pub fn get_or_probe(
+        &self,
+        name: &'static str,
+        query: impl FnOnce() -> T + Send + 'static,
+    ) -> Option<T>
+    where
+        T: Send + 'static,
+    {
+        let generation = STATIC_GENERATION.load(Ordering::Relaxed);
+        if let Ok(slot) = self.slot.lock() {
+            if let Some((gen, at, ref value)) = *slot {
+                if gen == generation && at.elapsed() < STATIC_INFO_TTL {
+                    return Some(value.clone());
+                }
+            }
+        }
+        let value = probe::run(name, query)?;
+        self.store(generation, value.clone());
+        Some(value)
+    }
+
+    fn store(&self, generation: u64, value: T) {
+        if let Ok(mut slot) = self.slot.lock() {
+            *slot = Some((generation, Instant::now(), value));
+        }
+    }
+}
+
+/// Entity id suffix per device of a kind (GPUs, batteries), stable when the enumeration order
+/// changes. Each device is `(label, fallback)`: `ids` pins it by a case-insensitive part of
+/// the label (the longest matching key wins), otherwise it is named after the fallback, with
+/// `_2`, `_3` for identical devices. A key ending in `#n` only pins the n-th device matching
+/// the rest of the key, for identical devices. A single device without a pin keeps the bare ids.
+pub fn entity_suffixes(devices: &[(&str, &str)], ids: &HashMap<String, String>) -> Vec<String> {
+    let labels: Vec<String> = devices.iter().map(|(label, _)| label.to_lowercase()).collect();
+    let mut suffixes: Vec<String> = Vec::with_capacity(devices.len());
+    for (i, (_, fallback)) in devices.iter().enumerate() {
+        let label = &labels[i];
+        let pinned = ids
+            .iter()
+            .filter_map(|(key, id)| {
+                let (part, nth) = split_occurrence(key);
+                let part = part.to_lowercase();
+                if part.is_empty() || !label.contains(&part) {
+                    return None;
+                }
+                if let Some(nth) = nth {
+                    let occurrence = labels[..=i].iter().filter(|l| l.contains(&part)).count();
+                    if occurrence != nth {
+                        return None;
+                    }
+                }
+                // An occurrence key beats the plain key with the same part
+                Some(((part.len(), nth.is_some()), id))
+            })
+            .max_by_key(|(rank, _)| *rank)
+            .map(|(_, id)| slug(id));
+        let base = match pinned {
+            Some(id) => id,
+            None if devices.len() == 1 => {
+                suffixes.push(String::new());
+                continue;
+            }
+            None => slug(fallback),
+        };
+        let mut suffix = format!("_{}", base);
+        let mut n = 2;
+        while suffixes.contains(&suffix) {
+            suffix = format!("_{}_{}", base, n);
+            n += 1;
+        }
+        suffixes.push(suffix);
+    }
+    suffixes
+}
+
+/// `("name", Some(2))` for a `name#2` pin key, `(key, None)` for any other key
+fn split_occurrence(key: &str) -> (&str, Option<usize>) {
+    match key.rsplit_once('#') {
+        Some((part, n)) => match n.parse::<usize>() {
+            Ok(n) if n > 0 => (part, Some(n)),
+            _ => (key, None),
+        },
+        None => (key, None),
+    }
+}
+
+/// Pin key per device that `entity_suffixes` matches to that device only: the label itself,
+/// or `label#n` when other labels contain it too (identical devices)
+pub fn occurrence_keys(labels: &[String]) -> Vec<String> {
+    let lower: Vec<String> = labels.iter().map(|l| l.to_lowercase()).collect();
+    labels
+        .iter()
+        .enumerate()
+        .map(|(i, label)| {
+            let matching = |l: &&String| l.contains(&lower[i]);
+            if lower.iter().filter(matching).count() < 2 {
+                return label.clone();
+            }
+            format!("{}#{}", label, lower[..=i].iter().filter(matching).count())
+        })
+        .collect()
+}
+
+/// Lowercase name with runs of other characters turned into `_`
+fn slug(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Drop all cached static info, e.g. after resume from sleep (a dock or monitor may have
+/// been attached in the meantime)
+pub fn invalidate_static_info() {
+    STATIC_GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Lowest temperature (°C, exclusive) accepted from a sensor by default
+pub const DEFAULT_MIN_TEMPERATURE_C: f32 = 0.0;
+/// Highest temperature (°C, exclusive) accepted from a sensor by default
+pub const DEFAULT_MAX_TEMPERATURE_C: f32 = 150.0;
+
+/// Plausible temperatures (`temperature_range` in settings.json). CPU and GPU readings
+/// outside it are treated as sensor glitches (unset thermal zones, Fahrenheit firmware)
+/// and reported as unavailable.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TemperatureRange {
+    pub min_c: f32,
+    pub max_c: f32,
+}
+
+impl Default for TemperatureRange {
+    fn default() -> Self {
+        Self {
+            min_c: DEFAULT_MIN_TEMPERATURE_C,
+            max_c: DEFAULT_MAX_TEMPERATURE_C,
+        }
+    }
+}
+
+impl TemperatureRange {
+    /// `celsius` if it is plausible; `source` only names the reading in the debug log
+    pub fn check(&self, source: &str, celsius: f32) -> Option<f32> {
+        if celsius > self.min_c && celsius < self.max_c {
+            Some(celsius)
+        } else {
+            log::debug!(
+                "[Sensors] Ignoring implausible temperature from {}: {:.1}°C (accepted {}..{}°C)",
+                source,
+                celsius,
+                self.min_c,
+                self.max_c
+            );
+            None
+        }
+    }
+}
+
+/// `part / total` as a percentage, computed exactly and truncated to one decimal.
+/// Truncating (instead of rounding) keeps a nearly-full disk at a stable 99.9;
+/// only a completely full one reads 100.0.
+pub fn usage_percent(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    let permille = part.min(total) as u128 * 1000 / total as u128;
+    permille as f64 / 10.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suffixes(labels: &[&str], ids: &[(&str, &str)]) -> Vec<String> {
+        let devices: Vec<(&str, &str)> = labels.iter().map(|l| (*l, *l)).collect();
+        let ids = ids
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        entity_suffixes(&devices, &ids)
+    }
+
+    #[test]
+    fn identical_devices_keep_their_pinned_index() {
+        let labels = ["NVIDIA GeForce RTX 3080", "NVIDIA GeForce RTX 3080"];
+        let keys = occurrence_keys(&labels.map(String::from));
+        assert_eq!(
+            keys,
+            ["NVIDIA GeForce RTX 3080#1", "NVIDIA GeForce RTX 3080#2"]
+        );
+
+        let ids: Vec<(&str, &str)> = vec![(&keys[0], "0"), (&keys[1], "1")];
+        assert_eq!(suffixes(&labels, &ids), ["_0", "_1"]);
+    }
+
+    #[test]
+    fn occurrence_keys_count_devices_containing_the_label() {
+        let labels = ["RTX 3080 Ti", "RTX 3080", "Intel UHD"];
+        let keys = occurrence_keys(&labels.map(String::from));
+        assert_eq!(keys, ["RTX 3080 Ti", "RTX 3080#2", "Intel UHD"]);
+
+        let ids: Vec<(&str, &str)> = keys
+            .iter()
+            .map(String::as_str)
+            .zip(["0", "1", "2"])
+            .collect();
+        assert_eq!(suffixes(&labels, &ids), ["_0", "_1", "_2"]);
+    }
+
+    #[test]
+    fn plain_keys_still_match_by_part() {
+        let labels = ["NVIDIA GeForce RTX 3060", "Intel UHD Graphics 770"];
+        assert_eq!(
+            suffixes(&labels, &[("nvidia", "dgpu"), ("intel", "igpu")]),
+            ["_dgpu", "_igpu"]
+        );
+        assert_eq!(suffixes(&labels[..1], &[]), [""]);
+    }
+}
@@ -1,3 +1,5 @@
+use std::net::IpAddr;
+
 use serde::{Deserialize, Serialize};
 use sysinfo::Networks;
 
@@ -13,26 +15,149 @@ pub struct NetworkInterface {
     pub received_bytes: u64,
     pub transmitted_bytes: u64,
     pub ip_addresses: Vec<String>,
+    /// First global-scope address of each family (see [`primary_address`])
+    pub primary_ipv4: Option<String>,
+    pub primary_ipv6: Option<String>,
 }
 
-pub fn collect() -> NetworkData {
-    let networks = Networks::new_with_refreshed_list();
+/// Read the interfaces kept by the collector. `refresh` only re-reads the counters of known
+/// interfaces; `relist` enumerates them again (new adapters, changed IP addresses).
+pub fn collect(networks: &mut Networks, relist: bool) -> NetworkData {
+    if relist {
+        networks.refresh_list();
+    } else {
+        networks.refresh();
+    }
     let interfaces: Vec<NetworkInterface> = networks
         .iter()
         .map(|(name, data)| {
+            let addrs: Vec<IpAddr> = data.ip_networks().iter().map(|ip| ip.addr).collect();
             NetworkInterface {
                 name: name.clone(),
                 mac_address: data.mac_address().to_string(),
                 received_bytes: data.total_received(),
                 transmitted_bytes: data.total_transmitted(),
-                ip_addresses: data
-                    .ip_networks()
-                    .iter()
-                    .map(|ip| ip.addr.to_string())
-                    .collect(),
+                ip_addresses: addrs.iter().map(|ip| ip.to_string()).collect(),
+                primary_ipv4: primary_address(&addrs, false).map(|ip| ip.to_string()),
+                primary_ipv6: primary_address(&addrs, true).map(|ip| ip.to_string()),
             }
         })
         .collect();
 
     NetworkData { interfaces }
 }
+
+/// Error from `get_my_public_ip` while the lookup is not enabled
+pub const PUBLIC_IP_DISABLED: &str = "Public IP lookup is disabled";
+
+/// IP family for the public IP lookup
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IpVersion {
+    #[default]
+    Ipv4,
+    Ipv6,
+    /// Whichever the connection prefers
+    Any,
+}
+
+/// Public IP lookup ("My IP" in settings). It contacts an external service, so it's off
+/// unless explicitly enabled.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PublicIpConfig {
+    pub enabled: bool,
+    pub ip_version: IpVersion,
+    /// Proxy URL for the lookup; when unset, HTTPS_PROXY / NO_PROXY from the environment apply
+    pub proxy: Option<String>,
+}
+
+/// This machine's public (outbound) IP as seen by ipify, sent from `local_address` if set
+pub async fn lookup_public_ip(
+    config: &PublicIpConfig,
+    local_address: Option<IpAddr>,
+) -> Result<String, String> {
+    let url = match config.ip_version {
+        IpVersion::Ipv4 => "https://api.ipify.org",
+        IpVersion::Ipv6 => "https://api6.ipify.org",
+        IpVersion::Any => "https://api64.ipify.org",
+    };
+    let mut builder = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(5))
+        .local_address(local_address);
+    if let Some(proxy) = config.proxy.as_deref().filter(|p| !p.trim().is_empty()) {
+        let proxy = reqwest::Proxy::all(proxy.trim())
+            .map_err(|e| {
+                let proxy = crate::diagnostics::mask_credentials(proxy.trim());
+                format!("Invalid proxy '{}': {}", proxy, e)
+            })?;
+        builder = builder.proxy(proxy);
+    }
+    let client = builder
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+    log::info!("[Network] Looking up public IP via {}", url);
+    let body = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("Network error: {}", e))?
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read response: {}", e))?;
+    Ok(body.trim().to_string())
+}
+
+/// False for private, loopback, link-local and CGNAT (100.64.0.0/10) addresses and their
+/// IPv6 counterparts: addresses an allowlist on a public proxy never sees
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            let cgnat = a == 100 && (64..128).contains(&b);
+            !(v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || cgnat)
+        }
+        IpAddr::V6(v6) => {
+            let first = v6.segments()[0];
+            let unique_local = first & 0xfe00 == 0xfc00;
+            let link_local = first & 0xffc0 == 0xfe80;
+            !(v6.is_loopback() || v6.is_unspecified() || unique_local || link_local)
+        }
+    }
+}
+
+/// The first global-scope address of one family: not loopback, link-local or unspecified.
+/// Private and unique local addresses count, as they are global scope on the interface.
+pub fn primary_address(addrs: &[IpAddr], ipv6: bool) -> Option<IpAddr> {
+    addrs.iter().copied().find(|ip| {
+        ip.is_ipv6() == ipv6
+            && !ip.is_loopback()
+            && !ip.is_unspecified()
+            && match ip {
+                IpAddr::V4(v4) => !v4.is_link_local(),
+                IpAddr::V6(v6) => v6.segments()[0] & 0xffc0 != 0xfe80,
+            }
+    })
+}
+
+/// IP addresses currently assigned to each local interface
+pub fn interface_addresses() -> Vec<(String, IpAddr)> {
+    addresses(&Networks::new_with_refreshed_list())
+}
+
+/// IP addresses of each interface in `networks`, as of its last `refresh_list`
+pub fn addresses(networks: &Networks) -> Vec<(String, IpAddr)> {
+    networks
+        .iter()
+        .flat_map(|(name, data)| {
+            data.ip_networks()
+                .iter()
+                .map(move |ip| (name.clone(), ip.addr))
+        })
+        .collect()
+}
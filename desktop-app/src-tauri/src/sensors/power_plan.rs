@@ -0,0 +1,172 @@
+//! Active OS power plan/profile (read-only). `unknown` where it can't be detected:
+//! - Windows: active power scheme (`PowerGetActiveScheme`); built-in schemes by GUID, so the
+//!   value doesn't depend on the display language, custom schemes by their name
+//! - Linux: `powerprofilesctl get`, else the ACPI platform profile, else the cpufreq governor
+//! - macOS: low power mode from `pmset -g`
+
+/// Reported when no power plan can be detected
+pub const UNKNOWN_PLAN: &str = "unknown";
+
+#[derive(Debug, Clone)]
+pub struct PowerPlan {
+    /// e.g. "balanced", "performance", "power_saver"
+    pub name: String,
+    /// Where the value came from (sent as attribute)
+    pub source: &'static str,
+}
+
+impl PowerPlan {
+    fn unknown() -> Self {
+        Self {
+            name: UNKNOWN_PLAN.to_string(),
+            source: "none",
+        }
+    }
+}
+
+#[cfg(windows)]
+pub fn collect() -> PowerPlan {
+    #[repr(C)]
+    struct Guid {
+        data1: u32,
+        data2: u16,
+        data3: u16,
+        data4: [u8; 8],
+    }
+
+    #[link(name = "powrprof")]
+    extern "system" {
+        fn PowerGetActiveScheme(root_key: *mut std::ffi::c_void, scheme: *mut *mut Guid) -> u32;
+        fn PowerReadFriendlyName(
+            root_key: *mut std::ffi::c_void,
+            scheme: *const Guid,
+            sub_group: *const Guid,
+            setting: *const Guid,
+            buffer: *mut u8,
+            buffer_size: *mut u32,
+        ) -> u32;
+    }
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn LocalFree(mem: *mut std::ffi::c_void) -> *mut std::ffi::c_void;
+    }
+
+    let mut scheme: *mut Guid = std::ptr::null_mut();
+    // SAFETY: on success the API allocates the GUID, which is freed with LocalFree below
+    if unsafe { PowerGetActiveScheme(std::ptr::null_mut(), &mut scheme) } != 0 || scheme.is_null() {
+        return PowerPlan::unknown();
+    }
+    // SAFETY: `scheme` points to a valid GUID until it is freed
+    let guid = unsafe { &*scheme };
+    let builtin = match (guid.data1, guid.data2, guid.data3) {
+        (0x381b_4222, 0xf694, 0x41f0) => Some("balanced"),
+        (0x8c5e_7fda, 0xe8bf, 0x4a96) => Some("performance"),
+        (0xa184_1308, 0x3541, 0x4fab) => Some("power_saver"),
+        (0xe9a4_2b02, 0xd5df, 0x448d) => Some("ultimate_performance"),
+        _ => None,
+    };
+    let name = builtin.map(str::to_string).or_else(|| {
+        let mut size: u32 = 0;
+        // SAFETY: first call only asks for the buffer size; the second fills a buffer of that size
+        unsafe {
+            PowerReadFriendlyName(
+                std::ptr::null_mut(),
+                scheme,
+                std::ptr::null(),
+                std::ptr::null(),
+                std::ptr::null_mut(),
+                &mut size,
+            );
+            if size == 0 {
+                return None;
+            }
+            let mut buffer = vec![0u8; size as usize];
+            let status = PowerReadFriendlyName(
+                std::ptr::null_mut(),
+                scheme,
+                std::ptr::null(),
+                std::ptr::null(),
+                buffer.as_mut_ptr(),
+                &mut size,
+            );
+            if status != 0 {
+                return None;
+            }
+            let wide: Vec<u16> = buffer
+                .chunks_exact(2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]))
+                .take_while(|c| *c != 0)
+                .collect();
+            Some(String::from_utf16_lossy(&wide))
+        }
+    });
+    // SAFETY: allocated by PowerGetActiveScheme and not used after this
+    unsafe { LocalFree(scheme.cast()) };
+
+    match name {
+        Some(name) if !name.is_empty() => PowerPlan {
+            name,
+            source: "power_scheme",
+        },
+        _ => PowerPlan::unknown(),
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn collect() -> PowerPlan {
+    let read = |path: &str| -> Option<String> {
+        let value = std::fs::read_to_string(path).ok()?.trim().to_string();
+        (!value.is_empty()).then_some(value)
+    };
+
+    // power-profiles-daemon (GNOME/KDE power mode): performance / balanced / power-saver
+    if let Ok(output) = std::process::Command::new("powerprofilesctl").arg("get").output() {
+        let profile = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if output.status.success() && !profile.is_empty() {
+            return PowerPlan {
+                name: profile.replace('-', "_"),
+                source: "power_profiles_daemon",
+            };
+        }
+    }
+    // ACPI platform profile: low-power / balanced / performance / quiet / ...
+    if let Some(profile) = read("/sys/firmware/acpi/platform_profile") {
+        return PowerPlan {
+            name: profile.replace('-', "_"),
+            source: "platform_profile",
+        };
+    }
+    if let Some(governor) = read("/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor") {
+        return PowerPlan {
+            name: governor,
+            source: "cpufreq_governor",
+        };
+    }
+    PowerPlan::unknown()
+}
+
+#[cfg(target_os = "macos")]
+pub fn collect() -> PowerPlan {
+    // " lowpowermode         1" in the settings of the active power source
+    let Ok(output) = std::process::Command::new("pmset").arg("-g").output() else {
+        return PowerPlan::unknown();
+    };
+    let text = String::from_utf8_lossy(&output.stdout);
+    let low_power = text
+        .lines()
+        .map(str::trim)
+        .find_map(|l| l.strip_prefix("lowpowermode"))
+        .map(|v| v.trim() == "1");
+    match low_power {
+        Some(low_power) => PowerPlan {
+            name: if low_power { "low_power" } else { "normal" }.to_string(),
+            source: "low_power_mode",
+        },
+        None => PowerPlan::unknown(),
+    }
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+pub fn collect() -> PowerPlan {
+    PowerPlan::unknown()
+}
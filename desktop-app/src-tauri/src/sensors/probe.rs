@@ -0,0 +1,125 @@
+//! Timeouts for blocking platform probes (WMI queries, `rocm-smi`, `system_profiler`, `df`), so a
+//! single stuck probe (e.g. a WMI deadlock) can't stall a whole collection cycle.
+//!
+//! Each probe runs on its own long-lived worker thread, which keeps its thread-local WMI
+//! connection between runs. The caller waits at most the probe timeout
+//! (`probe_timeout_secs` in settings.json) and then gives up on the result; the collector
+//! skips the group for that cycle. A probe that is still stuck from an earlier cycle fails
+//! right away instead of piling up more work behind it.
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub const DEFAULT_PROBE_TIMEOUT_SECS: u64 = 10;
+
+static TIMEOUT_SECS: AtomicU64 = AtomicU64::new(DEFAULT_PROBE_TIMEOUT_SECS);
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct Worker {
+    name: &'static str,
+    jobs: mpsc::Sender<Job>,
+    /// Set while a job runs (or is queued)
+    busy: Arc<AtomicBool>,
+}
+
+static WORKERS: Mutex<Vec<Worker>> = Mutex::new(Vec::new());
+
+thread_local! {
+    /// Probes that failed on this thread since the last `take_failures`
+    static FAILURES: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// How long a probe may take before its result is abandoned (at least 1 second)
+pub fn set_timeout(secs: u64) {
+    TIMEOUT_SECS.store(secs.max(1), Ordering::Relaxed);
+}
+
+/// Run `probe` on its worker thread and wait for it up to the probe timeout.
+/// None if it timed out, panicked or is still stuck from an earlier call; the reason is
+/// logged and kept for `take_failures`.
+pub fn run<T: Send + 'static>(
+    name: &'static str,
+    probe: impl FnOnce() -> T + Send + 'static,
+) -> Option<T> {
+    let (tx, rx) = mpsc::sync_channel(1);
+    let job: Job = Box::new(move || {
+        let _ = tx.send(probe());
+    });
+    if !submit(name, job) {
+        fail(name, "still running from an earlier cycle".to_string());
+        return None;
+    }
+
+    let timeout = TIMEOUT_SECS.load(Ordering::Relaxed);
+    match rx.recv_timeout(Duration::from_secs(timeout)) {
+        Ok(value) => Some(value),
+        Err(RecvTimeoutError::Timeout) => {
+            fail(name, format!("timed out after {}s", timeout));
+            None
+        }
+        // The job was dropped without sending: the probe panicked
+        Err(RecvTimeoutError::Disconnected) => {
+            fail(name, "panicked".to_string());
+            None
+        }
+    }
+}
+
+/// Probe failures on this thread since the last call, as "name: reason"
+pub fn take_failures() -> Vec<String> {
+    FAILURES.with(|f| std::mem::take(&mut *f.borrow_mut()))
+}
+
+fn fail(name: &str, reason: String) {
+    log::warn!("[Probe] {} {}", name, reason);
+    FAILURES.with(|f| f.borrow_mut().push(format!("{}: {}", name, reason)));
+}
+
+/// Hand the job to the probe's worker, starting it if needed. False if the worker is busy.
+fn submit(name: &'static str, job: Job) -> bool {
+    let Ok(mut workers) = WORKERS.lock() else {
+        return false;
+    };
+    if let Some(i) = workers.iter().position(|w| w.name == name) {
+        if workers[i].busy.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+        match workers[i].jobs.send(job) {
+            Ok(()) => return true,
+            // The worker thread is gone; start a new one below
+            Err(mpsc::SendError(returned)) => {
+                workers.swap_remove(i);
+                return spawn_worker(&mut workers, name, returned);
+            }
+        }
+    }
+    spawn_worker(&mut workers, name, job)
+}
+
+fn spawn_worker(workers: &mut Vec<Worker>, name: &'static str, job: Job) -> bool {
+    let (jobs, queue) = mpsc::channel::<Job>();
+    let busy = Arc::new(AtomicBool::new(true));
+    let worker_busy = busy.clone();
+    let spawned = std::thread::Builder::new()
+        .name(format!("probe-{}", name))
+        .spawn(move || {
+            for job in queue {
+                // A panicking probe only loses its own result, not the worker
+                let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(job));
+                worker_busy.store(false, Ordering::SeqCst);
+            }
+        });
+    if let Err(e) = spawned {
+        log::error!("[Probe] Failed to start worker for {}: {}", name, e);
+        return false;
+    }
+    if jobs.send(job).is_err() {
+        return false;
+    }
+    workers.push(Worker { name, jobs, busy });
+    true
+}
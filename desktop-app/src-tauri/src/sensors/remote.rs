@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Longest a host may take, connecting included, before its ssh is killed
+const SSH_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// A remote Linux host whose metrics are read over SSH.
+/// Uses the system `ssh` client in batch mode, so key-based auth must already work. An SSH
+/// library (ssh2, russh) would have to reimplement what the user's setup relies on:
+/// ~/.ssh/config host aliases and ProxyJump, the ssh agent, and known_hosts checking.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteHost {
+    /// Friendly name used in sensor names (falls back to `host`)
+    #[serde(default)]
+    pub name: String,
+    pub host: String,
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub identity_file: Option<String>,
+}
+
+impl RemoteHost {
+    pub fn display_name(&self) -> &str {
+        if self.name.trim().is_empty() {
+            &self.host
+        } else {
+            &self.name
+        }
+    }
+
+    /// Identifier used to namespace unique_ids per host. User and port are part of it, so
+    /// two accounts or ports on one machine don't share sensors. The readable part is
+    /// sanitized, which can map different hosts to the same text (`a.b`, `a-b`, `a_b`), so a
+    /// hash of the unsanitized `user@host:port` is appended.
+    pub fn id(&self) -> String {
+        let mut id = self.host.clone();
+        if let Some(user) = self.user.as_deref().filter(|u| !u.is_empty()) {
+            id = format!("{}@{}", user, id);
+        }
+        if let Some(port) = self.port {
+            id = format!("{}:{}", id, port);
+        }
+        // FNV-1a: stable across builds, unlike std's DefaultHasher
+        let hash = id
+            .bytes()
+            .fold(0x811c_9dc5u32, |h, b| (h ^ b as u32).wrapping_mul(0x0100_0193));
+        let readable: String = id
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        format!("{}_{:08x}", readable, hash)
+    }
+}
+
+/// Raw CPU jiffies from the first line of /proc/stat: (idle, total)
+pub type CpuTimes = (u64, u64);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteData {
+    pub cpu_usage_percent: Option<f64>,
+    pub memory_total_bytes: u64,
+    /// None when the kernel has no MemAvailable (before 3.14)
+    pub memory_available_bytes: Option<u64>,
+    pub memory_usage_percent: Option<f64>,
+    pub root_disk_usage_percent: Option<f64>,
+}
+
+/// Single round-trip command; sections are separated by `---`
+const REMOTE_COMMAND: &str =
+    "LC_ALL=C head -n1 /proc/stat; echo ---; cat /proc/meminfo; echo ---; df -Pk /";
+
+/// Collect every host at once, so the cycle waits for the slowest host rather than the sum
+/// of all of them. `prev_cpu` holds the previous /proc/stat reading per host id.
+pub fn collect_all(
+    hosts: &[RemoteHost],
+    prev_cpu: &mut HashMap<String, Option<CpuTimes>>,
+) -> Vec<Option<RemoteData>> {
+    let mut previous: Vec<Option<CpuTimes>> = hosts
+        .iter()
+        .map(|host| prev_cpu.get(&host.id()).copied().flatten())
+        .collect();
+    let results = std::thread::scope(|scope| {
+        let handles: Vec<_> = hosts
+            .iter()
+            .zip(previous.iter_mut())
+            .map(|(host, prev)| scope.spawn(move || collect(host, prev)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().ok().flatten())
+            .collect()
+    });
+    for (host, prev) in hosts.iter().zip(previous) {
+        prev_cpu.insert(host.id(), prev);
+    }
+    results
+}
+
+/// Collect CPU/memory/disk from a remote host. `prev_cpu` holds the previous /proc/stat
+/// reading for this host (CPU usage needs two samples, so the first cycle reports None).
+pub fn collect(host: &RemoteHost, prev_cpu: &mut Option<CpuTimes>) -> Option<RemoteData> {
+    let stdout = match run_ssh(ssh_command(host)) {
+        Ok(stdout) => stdout,
+        Err(e) => {
+            log::warn!("[Remote] ssh to {} failed: {}", host.host, e);
+            return None;
+        }
+    };
+    let sections: Vec<&str> = stdout.split("---").collect();
+    if sections.len() < 3 {
+        log::warn!("[Remote] Unexpected output from {}", host.host);
+        return None;
+    }
+
+    let cpu_usage_percent = parse_proc_stat(sections[0]).and_then(|current| {
+        let usage = prev_cpu.and_then(|previous| cpu_usage_between(previous, current));
+        *prev_cpu = Some(current);
+        usage
+    });
+
+    let (memory_total_bytes, memory_available_bytes) = parse_meminfo(sections[1])?;
+    if memory_available_bytes.is_none() {
+        log::warn!("[Remote] {} reports no MemAvailable; memory usage unavailable", host.host);
+    }
+    let memory_usage_percent = memory_available_bytes.map(|available| {
+        super::usage_percent(memory_total_bytes.saturating_sub(available), memory_total_bytes)
+    });
+
+    Some(RemoteData {
+        cpu_usage_percent,
+        memory_total_bytes,
+        memory_available_bytes,
+        memory_usage_percent,
+        root_disk_usage_percent: parse_df(sections[2]),
+    })
+}
+
+/// The ssh invocation for `host`: batch mode, no window on Windows, and `--` so a host
+/// starting with `-` can't be taken for an option
+fn ssh_command(host: &RemoteHost) -> Command {
+    let mut cmd = Command::new("ssh");
+    cmd.arg("-o")
+        .arg("BatchMode=yes")
+        .arg("-o")
+        .arg("ConnectTimeout=5");
+    if let Some(port) = host.port {
+        cmd.arg("-p").arg(port.to_string());
+    }
+    if let Some(ref identity) = host.identity_file {
+        cmd.arg("-i").arg(identity);
+    }
+    let target = match host.user {
+        Some(ref user) if !user.is_empty() => format!("{}@{}", user, host.host),
+        _ => host.host.clone(),
+    };
+    cmd.arg("--").arg(target).arg(REMOTE_COMMAND);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        cmd.creation_flags(CREATE_NO_WINDOW);
+    }
+    cmd
+}
+
+/// Run ssh and return its stdout, killing it after [`SSH_TIMEOUT`]
+fn run_ssh(mut cmd: Command) -> Result<String, String> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run ssh: {}", e))?;
+
+    // Drain both pipes on threads so a full pipe can't block ssh
+    let drain = |pipe: Option<Box<dyn Read + Send>>| {
+        std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buf);
+            }
+            String::from_utf8_lossy(&buf).into_owned()
+        })
+    };
+    let stdout = drain(child.stdout.take().map(|p| Box::new(p) as _));
+    let stderr = drain(child.stderr.take().map(|p| Box::new(p) as _));
+
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if started.elapsed() >= SSH_TIMEOUT => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("timed out after {}s", SSH_TIMEOUT.as_secs()));
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(50)),
+            Err(e) => return Err(format!("Failed to wait for ssh: {}", e)),
+        }
+    };
+
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    if !status.success() {
+        return Err(format!("{}: {}", status, stderr.trim()));
+    }
+    Ok(stdout)
+}
+
+/// Parse the aggregate `cpu` line of /proc/stat into (idle, total) jiffies
+fn parse_proc_stat(section: &str) -> Option<CpuTimes> {
+    let line = section.lines().find(|l| l.starts_with("cpu "))?;
+    let values: Vec<u64> = line
+        .split_whitespace()
+        .skip(1)
+        .take(8)
+        .filter_map(|v| v.parse().ok())
+        .collect();
+    if values.len() < 5 {
+        return None;
+    }
+    // idle + iowait count as idle time
+    let idle = values[3] + values[4];
+    let total = values.iter().sum();
+    Some((idle, total))
+}
+
+fn cpu_usage_between(previous: CpuTimes, current: CpuTimes) -> Option<f64> {
+    let total_delta = current.1.checked_sub(previous.1)?;
+    let idle_delta = current.0.checked_sub(previous.0)?;
+    if total_delta == 0 {
+        return None;
+    }
+    Some(super::usage_percent(
+        total_delta.saturating_sub(idle_delta),
+        total_delta,
+    ))
+}
+
+/// Parse MemTotal / MemAvailable (kB) from /proc/meminfo into bytes. Without MemAvailable
+/// the available memory is unknown: MemFree leaves out the page cache the kernel can reclaim.
+fn parse_meminfo(section: &str) -> Option<(u64, Option<u64>)> {
+    let read_kb = |key: &str| -> Option<u64> {
+        section
+            .lines()
+            .find(|l| l.starts_with(key))
+            .and_then(|l| l.split_whitespace().nth(1))
+            .and_then(|v| v.parse::<u64>().ok())
+    };
+    let total = read_kb("MemTotal:")?;
+    let available = read_kb("MemAvailable:").map(|kb| kb * 1024);
+    Some((total * 1024, available))
+}
+
+/// Parse `df -Pk /` output into a usage percentage (used / (used + available), like df)
+fn parse_df(section: &str) -> Option<f64> {
+    let line = section.lines().filter(|l| !l.trim().is_empty()).nth(1)?;
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let used: u64 = fields.get(2)?.parse().ok()?;
+    let available: u64 = fields.get(3)?.parse().ok()?;
+    let total = used + available;
+    if total == 0 {
+        return None;
+    }
+    Some(super::usage_percent(used, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host(host: &str, user: Option<&str>, port: Option<u16>) -> RemoteHost {
+        RemoteHost {
+            name: String::new(),
+            host: host.into(),
+            user: user.map(Into::into),
+            port,
+            identity_file: None,
+        }
+    }
+
+    #[test]
+    fn ids_include_user_and_port() {
+        assert!(host("nas.local", None, None).id().starts_with("nas_local_"));
+        assert!(host("nas.local", Some("root"), None)
+            .id()
+            .starts_with("root_nas_local_"));
+        assert!(host("nas.local", Some("root"), Some(2222))
+            .id()
+            .starts_with("root_nas_local_2222_"));
+        // An empty user is no user
+        assert_eq!(
+            host("nas.local", Some(""), Some(22)).id(),
+            host("nas.local", None, Some(22)).id()
+        );
+        assert_ne!(
+            host("nas.local", Some("admin"), None).id(),
+            host("nas.local", Some("backup"), None).id()
+        );
+        // Stable across runs: unique_ids in HA depend on it
+        assert_eq!(
+            host("nas.local", None, None).id(),
+            host("nas.local", None, None).id()
+        );
+    }
+
+    #[test]
+    fn ids_do_not_collide_after_sanitizing() {
+        let ids: Vec<String> = ["a.b", "a-b", "a_b", "a b", "a:b"]
+            .iter()
+            .map(|h| host(h, None, None).id())
+            .collect();
+        for (i, id) in ids.iter().enumerate() {
+            assert!(id.starts_with("a_b_"), "{}", id);
+            assert!(!ids[i + 1..].contains(id), "{} collides", id);
+        }
+    }
+
+    #[test]
+    fn meminfo_without_memavailable_is_unknown() {
+        let meminfo = "MemTotal:       2048 kB\nMemFree:         512 kB\n";
+        assert_eq!(parse_meminfo(meminfo), Some((2048 * 1024, None)));
+        let meminfo = "MemTotal:       2048 kB\nMemAvailable:   1024 kB\n";
+        assert_eq!(
+            parse_meminfo(meminfo),
+            Some((2048 * 1024, Some(1024 * 1024)))
+        );
+        assert_eq!(parse_meminfo("MemFree: 512 kB\n"), None);
+    }
+
+    #[test]
+    fn host_is_never_an_option() {
+        let hostile = host("-oProxyCommand=evil", Some("me"), Some(2222));
+        // Only [a-z0-9_] ends up in unique_ids
+        assert!(hostile
+            .id()
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_'));
+        let cmd = ssh_command(&hostile);
+        let args: Vec<String> = cmd
+            .get_args()
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect();
+        let separator = args.iter().position(|a| a == "--").unwrap();
+        assert_eq!(args[separator + 1], "me@-oProxyCommand=evil");
+        assert_eq!(args[separator + 2], REMOTE_COMMAND);
+        assert!(args[..separator].contains(&"2222".to_string()));
+    }
+}
@@ -0,0 +1,179 @@
+//! Rounding of numeric sensor states (`state_rounding` in settings.json), so float noise
+//! like a CPU usage going from 12.3 to 12.4 doesn't reach HA as a new state every interval.
+//!
+//! - States are rounded to the decimals of the most specific rule (`id`, `prefix*` or `*`);
+//!   sensors without a rule are sent as collected
+//! - With `skip_unchanged`, a normal cycle leaves out sensors whose rounded state and
+//!   attributes are the same as what was last sent. The full update every 10th cycle still
+//!   sends everything. Every path that sends states records them once HA accepted them, so
+//!   a failed send is tried again next cycle.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::collector::SensorValue;
+use crate::ha_client::most_specific_match;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StateRoundingConfig {
+    /// Decimals per sensor unique_id, `prefix*` or `*` (the most specific key wins)
+    pub decimals: HashMap<String, u32>,
+    /// Leave out sensors whose state and attributes didn't change since they were last sent
+    pub skip_unchanged: bool,
+}
+
+impl Default for StateRoundingConfig {
+    fn default() -> Self {
+        // The usage percentages that jitter every cycle. Disk usage is left alone: it is
+        // truncated on purpose, so rounding would turn a nearly-full 99.9 into 100.
+        let decimals = [
+            "cpu_usage",
+            "memory_usage",
+            "swap_usage",
+            "gpu_usage*",
+            "gpu_memory_util*",
+            "companion_cpu",
+        ];
+        Self {
+            decimals: decimals.iter().map(|id| (id.to_string(), 0)).collect(),
+            skip_unchanged: true,
+        }
+    }
+}
+
+/// Rounds states and remembers what was last sent per sensor
+#[derive(Debug, Default)]
+pub struct StateRounding {
+    config: StateRoundingConfig,
+    last_sent: HashMap<String, (serde_json::Value, HashMap<String, serde_json::Value>)>,
+}
+
+impl StateRounding {
+    pub fn set_config(&mut self, config: StateRoundingConfig) {
+        self.config = config;
+        self.last_sent.clear();
+    }
+
+    /// Round the numeric states that have a rule; other states are left as they are
+    pub fn round(&self, sensors: &mut [SensorValue]) {
+        if self.config.decimals.is_empty() {
+            return;
+        }
+        for sensor in sensors {
+            let Some(&decimals) = most_specific_match(&self.config.decimals, &sensor.unique_id)
+            else {
+                continue;
+            };
+            if let Some(rounded) = round_state(&sensor.state, decimals) {
+                sensor.state = rounded;
+            }
+        }
+    }
+
+    /// Sensors whose state or attributes changed since they were last sent (all of them
+    /// when `skip_unchanged` is off)
+    pub fn changed(&self, sensors: Vec<SensorValue>) -> Vec<SensorValue> {
+        if !self.config.skip_unchanged {
+            return sensors;
+        }
+        let total = sensors.len();
+        let sensors: Vec<SensorValue> = sensors
+            .into_iter()
+            .filter(|s| {
+                self.last_sent
+                    .get(&s.unique_id)
+                    .is_none_or(|(state, attributes)| {
+                        *state != s.state || *attributes != s.attributes
+                    })
+            })
+            .collect();
+        if sensors.len() < total {
+            log::debug!(
+                "[Rounding] {} of {} sensors unchanged, not sent",
+                total - sensors.len(),
+                total
+            );
+        }
+        sensors
+    }
+
+    /// Remember states HA accepted, from any send path (normal cycle, full update, manual)
+    pub fn record_sent(&mut self, sensors: &[SensorValue]) {
+        if !self.config.skip_unchanged {
+            return;
+        }
+        for sensor in sensors {
+            self.last_sent.insert(
+                sensor.unique_id.clone(),
+                (sensor.state.clone(), sensor.attributes.clone()),
+            );
+        }
+    }
+}
+
+/// `state` rounded to `decimals`, in the same form (number or formatted string).
+/// None for states that aren't numbers.
+fn round_state(state: &serde_json::Value, decimals: u32) -> Option<serde_json::Value> {
+    let decimals = decimals.min(6) as usize;
+    if let Some(text) = state.as_str() {
+        let value: f64 = text.trim().parse().ok()?;
+        if !value.is_finite() {
+            return None;
+        }
+        return Some(serde_json::json!(format!("{:.*}", decimals, value)));
+    }
+    // Integers have nothing to round
+    if state.is_i64() || state.is_u64() {
+        return None;
+    }
+    let value = state.as_f64()?;
+    let factor = 10f64.powi(decimals as i32);
+    let rounded = (value * factor).round() / factor;
+    Some(if decimals == 0 {
+        serde_json::json!(rounded as i64)
+    } else {
+        serde_json::json!(rounded)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sensor(state: serde_json::Value) -> SensorValue {
+        SensorValue {
+            unique_id: "cpu_usage".into(),
+            name: "CPU Usage".into(),
+            state,
+            sensor_type: "sensor".into(),
+            device_class: None,
+            unit_of_measurement: Some("%".into()),
+            state_class: Some("measurement".into()),
+            icon: None,
+            attributes: HashMap::new(),
+            update_at_interval: true,
+        }
+    }
+
+    #[test]
+    fn only_sent_states_count_as_unchanged() {
+        let mut rounding = StateRounding::default();
+        let a = vec![sensor(serde_json::json!(10))];
+        let b = vec![sensor(serde_json::json!(20))];
+
+        // Normal cycle A, full update B, normal cycle A again: A has to go out
+        rounding.record_sent(&rounding.changed(a.clone()));
+        rounding.record_sent(&b);
+        assert_eq!(rounding.changed(a.clone()).len(), 1);
+
+        // A failed send records nothing, so the next cycle tries again
+        let pending = rounding.changed(a.clone());
+        assert_eq!(pending.len(), 1);
+        assert_eq!(rounding.changed(a.clone()).len(), 1);
+
+        rounding.record_sent(&pending);
+        assert!(rounding.changed(a).is_empty());
+    }
+}
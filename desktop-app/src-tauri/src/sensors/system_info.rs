@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use sysinfo::System;
 
+use super::StaticCache;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemInfoData {
     pub os_name: String,
@@ -16,6 +18,8 @@ pub struct SystemInfoData {
     pub logged_in_user: Option<String>,
     pub process_count: usize,
     pub displays: Vec<DisplayInfo>,
+    /// Running as administrator (Windows, elevated token) or root
+    pub elevated: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,22 +29,59 @@ pub struct DisplayInfo {
     pub refresh_rate_hz: Option<u32>,
 }
 
+/// This machine's hostname. sysinfo occasionally comes up empty, so fall back to the
+/// environment (`COMPUTERNAME` on Windows, `HOSTNAME` elsewhere) and on Unix to the
+/// hostname files. None only when every source is empty.
+pub fn hostname() -> Option<String> {
+    let env_var = if cfg!(windows) { "COMPUTERNAME" } else { "HOSTNAME" };
+    let from_env = || std::env::var(env_var).ok();
+    #[allow(unused_mut)] // Only Unix adds the hostname files
+    let mut sources: Vec<HostnameSource> = vec![("sysinfo", &System::host_name), (env_var, &from_env)];
+    #[cfg(unix)]
+    let etc_hostname = || std::fs::read_to_string("/etc/hostname").ok();
+    #[cfg(unix)]
+    let proc_hostname = || std::fs::read_to_string("/proc/sys/kernel/hostname").ok();
+    #[cfg(unix)]
+    sources.extend([
+        ("/etc/hostname", &etc_hostname as &dyn Fn() -> Option<String>),
+        ("/proc/sys/kernel/hostname", &proc_hostname),
+    ]);
+    first_hostname(&sources)
+}
+
+/// A named place to read the hostname from
+type HostnameSource<'a> = (&'a str, &'a dyn Fn() -> Option<String>);
+
+/// The first usable name from `sources`, tried in order. Empty names and "unknown" don't count.
+fn first_hostname(sources: &[HostnameSource]) -> Option<String> {
+    for (i, (source, read)) in sources.iter().enumerate() {
+        let name = read()
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty() && !name.eq_ignore_ascii_case("unknown"));
+        if let Some(name) = name {
+            if i > 0 {
+                log::debug!("[System] Hostname from {}", source);
+            }
+            return Some(name);
+        }
+    }
+    log::warn!("[System] Hostname unknown");
+    None
+}
+
 pub fn collect() -> SystemInfoData {
     let os_name = System::name().unwrap_or_else(|| "Unknown".to_string());
     let os_version = System::os_version().unwrap_or_else(|| "Unknown".to_string());
-    let hostname = System::host_name().unwrap_or_else(|| "Unknown".to_string());
+    let hostname = hostname().unwrap_or_else(|| "Unknown".to_string());
     let uptime_seconds = System::uptime();
     let boot_time = System::boot_time();
 
-    let platform_info = collect_platform_info();
+    let platform_info = PLATFORM_INFO.get_or_query(collect_platform_info);
     let logged_in_user = collect_logged_in_user();
-    let process_count = {
-        let sys = System::new_with_specifics(
-            sysinfo::RefreshKind::new().with_processes(sysinfo::ProcessRefreshKind::new()),
-        );
-        sys.processes().len()
-    };
-    let displays = collect_displays();
+    let process_count = count_processes().processes;
+    let displays = DISPLAYS
+        .get_or_probe("display_wmi", collect_displays)
+        .unwrap_or_default();
 
     SystemInfoData {
         os_name,
@@ -56,31 +97,164 @@ pub fn collect() -> SystemInfoData {
         logged_in_user,
         process_count,
         displays,
+        elevated: is_elevated(),
     }
 }
 
 /// Dynamic system info that changes over time
 pub fn collect_dynamic() -> DynamicSystemInfo {
     let uptime_seconds = System::uptime();
-    let process_count = {
-        let sys = System::new_with_specifics(
-            sysinfo::RefreshKind::new().with_processes(sysinfo::ProcessRefreshKind::new()),
-        );
-        sys.processes().len()
-    };
+    let counts = count_processes();
 
     DynamicSystemInfo {
         uptime_seconds,
-        process_count,
+        boot_time: System::boot_time(),
+        process_count: counts.processes,
+        thread_count: counts.threads,
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct DynamicSystemInfo {
     pub uptime_seconds: u64,
+    pub boot_time: u64,
     pub process_count: usize,
+    /// Threads across all processes, where the platform reports it
+    pub thread_count: Option<usize>,
+}
+
+/// Boot times this close together are the same boot; Windows derives the boot time from
+/// the tick count, so it can shift by a second between reads
+const SAME_BOOT_TOLERANCE_SECS: u64 = 120;
+
+/// Uptime summed over boots for the total_uptime sensor, kept in the settings store
+/// (`uptime_ledger`). A reset store starts counting again from the current boot.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UptimeLedger {
+    /// Uptime of the boots before the current one, in seconds
+    pub previous_secs: u64,
+    /// Boot time (UNIX seconds) of the boot last seen
+    pub boot_time: u64,
+    /// Uptime of that boot when it was last seen
+    pub last_uptime_secs: u64,
+    /// Boots counted so far, the current one included
+    pub boots: u64,
+}
+
+impl UptimeLedger {
+    /// Account for the current boot and return the total uptime in seconds. On a new boot
+    /// the last seen uptime of the previous one is added to the total.
+    pub fn update(&mut self, boot_time: u64, uptime_secs: u64) -> u64 {
+        let new_boot = self.boot_time.abs_diff(boot_time) > SAME_BOOT_TOLERANCE_SECS
+            || uptime_secs < self.last_uptime_secs;
+        if self.boots == 0 || new_boot {
+            self.previous_secs += self.last_uptime_secs;
+            self.boots += 1;
+            self.boot_time = boot_time;
+        }
+        self.last_uptime_secs = uptime_secs;
+        self.previous_secs + uptime_secs
+    }
+}
+
+/// CPU and memory used by the companion app itself
+#[derive(Debug, Clone, Copy)]
+pub struct OwnUsage {
+    /// Share of the whole machine (all cores), like Task Manager shows it
+    pub cpu_percent: f32,
+    pub memory_mb: f64,
+}
+
+/// This process's resource usage. `sys` must have refreshed the process this cycle; the
+/// CPU share is measured since the refresh before that.
+pub fn own_usage(sys: &System) -> Option<OwnUsage> {
+    let pid = sysinfo::get_current_pid().ok()?;
+    let process = sys.process(pid)?;
+    let cores = sys.cpus().len().max(1) as f32;
+    Some(OwnUsage {
+        cpu_percent: process.cpu_usage() / cores,
+        memory_mb: process.memory() as f64 / 1024.0 / 1024.0,
+    })
+}
+
+struct ProcessCounts {
+    processes: usize,
+    threads: Option<usize>,
+}
+
+/// Process and thread counts as the OS tools show them: the Performance tab of
+/// Task Manager on Windows, `top`'s "Tasks" (including kernel threads) on Linux.
+#[cfg(windows)]
+fn count_processes() -> ProcessCounts {
+    use super::wmi_conn::{self, Namespace};
+
+    let perf = wmi_conn::raw_query(
+        Namespace::Cimv2,
+        "SELECT Processes, Threads FROM Win32_PerfFormattedData_PerfOS_System",
+    )
+    .and_then(|r| r.ok())
+    .and_then(|results| results.into_iter().next());
+    let counter = |name: &str| -> Option<usize> {
+        perf.as_ref()?.get(name).and_then(variant_to_string)?.parse().ok()
+    };
+    if let Some(processes) = counter("Processes") {
+        return ProcessCounts {
+            processes,
+            threads: counter("Threads"),
+        };
+    }
+
+    // sysinfo lists the System Idle Process (PID 0), which Task Manager doesn't count
+    let sys = System::new_with_specifics(
+        sysinfo::RefreshKind::new().with_processes(sysinfo::ProcessRefreshKind::new()),
+    );
+    ProcessCounts {
+        processes: sys
+            .processes()
+            .keys()
+            .filter(|pid| pid.as_u32() != 0)
+            .count(),
+        threads: None,
+    }
+}
+
+#[cfg(not(windows))]
+fn count_processes() -> ProcessCounts {
+    let sys = System::new_with_specifics(
+        sysinfo::RefreshKind::new().with_processes(sysinfo::ProcessRefreshKind::new()),
+    );
+    // On Linux sysinfo also lists each process's threads (tasks) as entries; kernel
+    // threads are real processes to ps/top and are kept
+    let processes = sys
+        .processes()
+        .values()
+        .filter(|p| p.thread_kind() != Some(sysinfo::ThreadKind::Userland))
+        .count();
+    ProcessCounts {
+        processes,
+        threads: linux_thread_count(),
+    }
 }
 
+/// Total scheduling entities (threads, including kernel threads) from /proc/loadavg:
+/// the 4th field is "running/total"
+#[cfg(not(windows))]
+fn linux_thread_count() -> Option<usize> {
+    let loadavg = std::fs::read_to_string("/proc/loadavg").ok()?;
+    loadavg
+        .split_whitespace()
+        .nth(3)?
+        .split_once('/')?
+        .1
+        .parse()
+        .ok()
+}
+
+static PLATFORM_INFO: StaticCache<PlatformInfo> = StaticCache::new();
+static DISPLAYS: StaticCache<Vec<DisplayInfo>> = StaticCache::new();
+
+#[derive(Clone)]
 struct PlatformInfo {
     motherboard_manufacturer: Option<String>,
     motherboard_model: Option<String>,
@@ -117,42 +291,26 @@ fn variant_to_string(v: &wmi::Variant) -> Option<String> {
 
 #[cfg(windows)]
 fn collect_platform_info() -> PlatformInfo {
-    use std::collections::HashMap;
-    use wmi::{COMLibrary, WMIConnection, Variant};
-
-    let com_lib = match COMLibrary::new() {
-        Ok(c) => c,
-        Err(e) => {
-            log::error!("[SystemInfo] WMI COM init failed: {}", e);
-            return PlatformInfo {
-                motherboard_manufacturer: None,
-                motherboard_model: None,
-                bios_version: None,
-                bios_vendor: None,
-                bios_release_date: None,
-            };
-        }
-    };
-    let wmi_con = match WMIConnection::new(com_lib) {
-        Ok(w) => w,
-        Err(e) => {
-            log::error!("[SystemInfo] WMI connection failed: {}", e);
-            return PlatformInfo {
-                motherboard_manufacturer: None,
-                motherboard_model: None,
-                bios_version: None,
-                bios_vendor: None,
-                bios_release_date: None,
-            };
-        }
+    use super::wmi_conn::{self, Namespace};
+
+    // COM/WMI init failures are logged once by wmi_conn
+    let Some(board_result) = wmi_conn::raw_query(
+        Namespace::Cimv2,
+        "SELECT Manufacturer, Product FROM Win32_BaseBoard",
+    ) else {
+        return PlatformInfo {
+            motherboard_manufacturer: None,
+            motherboard_model: None,
+            bios_version: None,
+            bios_vendor: None,
+            bios_release_date: None,
+        };
     };
 
     // Get motherboard info
     let mut mb_manufacturer = None;
     let mut mb_model = None;
-    match wmi_con.raw_query::<HashMap<String, Variant>>(
-        "SELECT Manufacturer, Product FROM Win32_BaseBoard",
-    ) {
+    match board_result {
         Ok(results) => {
             if let Some(result) = results.first() {
                 mb_manufacturer = result.get("Manufacturer").and_then(variant_to_string);
@@ -172,10 +330,11 @@ fn collect_platform_info() -> PlatformInfo {
     let mut bios_version = None;
     let mut bios_vendor = None;
     let mut bios_release_date = None;
-    match wmi_con.raw_query::<HashMap<String, Variant>>(
+    match wmi_conn::raw_query(
+        Namespace::Cimv2,
         "SELECT SMBIOSBIOSVersion, Manufacturer, ReleaseDate FROM Win32_BIOS",
     ) {
-        Ok(results) => {
+        Some(Ok(results)) => {
             if let Some(result) = results.first() {
                 bios_version = result.get("SMBIOSBIOSVersion").and_then(variant_to_string);
                 bios_vendor = result.get("Manufacturer").and_then(variant_to_string);
@@ -200,7 +359,8 @@ fn collect_platform_info() -> PlatformInfo {
                 log::warn!("[SystemInfo] Win32_BIOS query returned empty results");
             }
         }
-        Err(e) => log::error!("[SystemInfo] Win32_BIOS query failed: {}", e),
+        Some(Err(e)) => log::error!("[SystemInfo] Win32_BIOS query failed: {}", e),
+        None => {}
     }
 
     PlatformInfo {
@@ -301,37 +461,211 @@ fn collect_platform_info() -> PlatformInfo {
 
 // --- Logged-in user ---
 
+/// Console user as `DOMAIN\user`. Win32_ComputerSystem.UserName reports the interactive
+/// console session, so it stays correct when the app runs as a service; it is empty for
+/// RDP-only logins, in which case the process user is used.
 #[cfg(windows)]
 fn collect_logged_in_user() -> Option<String> {
-    std::env::var("USERNAME").ok()
+    use super::wmi_conn::{self, Namespace};
+
+    let console_user = wmi_conn::raw_query(Namespace::Cimv2, "SELECT UserName FROM Win32_ComputerSystem")
+        .and_then(|r| r.ok())
+        .and_then(|results| {
+            results
+                .first()
+                .and_then(|r| r.get("UserName"))
+                .and_then(variant_to_string)
+        });
+    if console_user.is_some() {
+        return console_user;
+    }
+
+    let user = std::env::var("USERNAME").ok()?;
+    match std::env::var("USERDOMAIN") {
+        Ok(domain) if !domain.is_empty() => Some(format!("{}\\{}", domain, user)),
+        _ => Some(user),
+    }
 }
 
+/// Owner of the active desktop session, falling back to the process owner
+/// (`USER` is wrong when the app runs as a service or under another account).
 #[cfg(not(windows))]
 fn collect_logged_in_user() -> Option<String> {
-    std::env::var("USER")
-        .or_else(|_| std::env::var("LOGNAME"))
-        .ok()
+    active_desktop_user().or_else(|| {
+        std::env::var("USER")
+            .or_else(|_| std::env::var("LOGNAME"))
+            .ok()
+    })
 }
 
-// --- Display info ---
+#[cfg(not(windows))]
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if stdout.is_empty() {
+        None
+    } else {
+        Some(stdout)
+    }
+}
+
+/// User of seat0's active session (the graphical one) via systemd-logind
+#[cfg(target_os = "linux")]
+fn active_desktop_user() -> Option<String> {
+    let session = command_output("loginctl", &["show-seat", "seat0", "-p", "ActiveSession", "--value"])?;
+    command_output("loginctl", &["show-session", &session, "-p", "Name", "--value"])
+}
+
+/// Owner of /dev/console is the user logged in at the GUI ("root" at the login window)
+#[cfg(target_os = "macos")]
+fn active_desktop_user() -> Option<String> {
+    command_output("stat", &["-f", "%Su", "/dev/console"]).filter(|u| u != "root")
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+fn active_desktop_user() -> Option<String> {
+    None
+}
 
+// --- Active sessions ---
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub count: usize,
+    /// Unique usernames with at least one session, sorted
+    pub users: Vec<String>,
+}
+
+fn session_info_from_users(session_users: Vec<String>) -> SessionInfo {
+    let count = session_users.len();
+    let mut users = session_users;
+    users.sort();
+    users.dedup();
+    SessionInfo { count, users }
+}
+
+/// Active login sessions (best-effort). None where it can't be determined.
 #[cfg(windows)]
-fn collect_displays() -> Vec<DisplayInfo> {
-    use std::collections::HashMap;
-    use wmi::{COMLibrary, WMIConnection, Variant};
+pub fn collect_sessions() -> Option<SessionInfo> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    // `query user` lists WTS sessions; it exits non-zero when nobody is logged in
+    let output = std::process::Command::new("query")
+        .arg("user")
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if stdout.trim().is_empty() {
+        return if output.status.code() == Some(1) {
+            Some(session_info_from_users(Vec::new()))
+        } else {
+            None
+        };
+    }
 
-    let com_lib = match COMLibrary::new() {
-        Ok(c) => c,
-        Err(_) => return Vec::new(),
-    };
-    let wmi_con = match WMIConnection::new(com_lib) {
-        Ok(w) => w,
-        Err(_) => return Vec::new(),
+    let users = stdout
+        .lines()
+        .skip(1) // header
+        .filter_map(|l| l.trim_start_matches('>').split_whitespace().next())
+        .map(|u| u.to_string())
+        .collect();
+    Some(session_info_from_users(users))
+}
+
+#[cfg(not(windows))]
+pub fn collect_sessions() -> Option<SessionInfo> {
+    // `who` reads utmp: one line per login session ("user tty date ...")
+    let output = std::process::Command::new("who").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let users = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|l| l.split_whitespace().next())
+        .map(|u| u.to_string())
+        .collect();
+    Some(session_info_from_users(users))
+}
+
+// --- Elevation ---
+
+/// Whether the process token is elevated (UAC "Run as administrator")
+#[cfg(windows)]
+pub fn is_elevated() -> bool {
+    use std::ffi::c_void;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentProcess() -> *mut c_void;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+    }
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn OpenProcessToken(process: *mut c_void, access: u32, token: *mut *mut c_void) -> i32;
+        fn GetTokenInformation(
+            token: *mut c_void,
+            class: u32,
+            info: *mut c_void,
+            length: u32,
+            return_length: *mut u32,
+        ) -> i32;
+    }
+    const TOKEN_QUERY: u32 = 0x0008;
+    const TOKEN_ELEVATION: u32 = 20;
+
+    let mut token: *mut c_void = std::ptr::null_mut();
+    // SAFETY: the pseudo handle from GetCurrentProcess needs no closing; the token does
+    if unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) } == 0 {
+        return false;
+    }
+    let mut elevation: u32 = 0;
+    let mut length: u32 = 0;
+    // SAFETY: TOKEN_ELEVATION is a single u32 (TokenIsElevated)
+    let ok = unsafe {
+        GetTokenInformation(
+            token,
+            TOKEN_ELEVATION,
+            (&mut elevation as *mut u32).cast(),
+            std::mem::size_of::<u32>() as u32,
+            &mut length,
+        )
     };
+    // SAFETY: opened above and not used after this
+    unsafe { CloseHandle(token) };
+    ok != 0 && elevation != 0
+}
+
+/// Whether the process runs as root
+#[cfg(unix)]
+pub fn is_elevated() -> bool {
+    extern "C" {
+        fn geteuid() -> u32;
+    }
+    // SAFETY: geteuid has no preconditions and can't fail
+    unsafe { geteuid() == 0 }
+}
+
+#[cfg(not(any(windows, unix)))]
+pub fn is_elevated() -> bool {
+    false
+}
+
+// --- Display info ---
+
+#[cfg(windows)]
+fn collect_displays() -> Vec<DisplayInfo> {
+    use super::wmi_conn::{self, Namespace};
+    use wmi::Variant;
 
     let mut displays = Vec::new();
 
-    if let Ok(results) = wmi_con.raw_query::<HashMap<String, Variant>>(
+    if let Some(Ok(results)) = wmi_conn::raw_query(
+        Namespace::Cimv2,
         "SELECT Name, CurrentHorizontalResolution, CurrentVerticalResolution, CurrentRefreshRate FROM Win32_VideoController",
     ) {
         for (i, result) in results.iter().enumerate() {
@@ -375,3 +709,208 @@ fn collect_displays() -> Vec<DisplayInfo> {
     // (xrandr, system_profiler). For now, return empty.
     Vec::new()
 }
+
+/// Displays as last queried (cached like the other static info)
+pub fn current_displays() -> Vec<DisplayInfo> {
+    DISPLAYS
+        .get_or_probe("display_wmi", collect_displays)
+        .unwrap_or_default()
+}
+
+/// Notices monitors being attached or removed (docking) by polling a cheap summary of the
+/// display configuration, so the display sensors don't stay stale until the next restart
+pub struct DisplayWatcher {
+    signature: Option<String>,
+}
+
+impl DisplayWatcher {
+    /// Start watching; the current configuration counts as already reported
+    pub fn new() -> Self {
+        Self {
+            signature: display_signature(),
+        }
+    }
+
+    /// Whether the display configuration changed since the last poll
+    pub fn poll(&mut self) -> bool {
+        let signature = display_signature();
+        if signature.is_none() || signature == self.signature {
+            return false;
+        }
+        log::info!(
+            "[Display] Configuration changed: {} -> {}",
+            self.signature.as_deref().unwrap_or("?"),
+            signature.as_deref().unwrap_or("?")
+        );
+        self.signature = signature;
+        true
+    }
+}
+
+impl Default for DisplayWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Monitor count and (virtual) screen size
+#[cfg(windows)]
+fn display_signature() -> Option<String> {
+    #[link(name = "user32")]
+    extern "system" {
+        fn GetSystemMetrics(index: i32) -> i32;
+    }
+    const SM_CXSCREEN: i32 = 0;
+    const SM_CYSCREEN: i32 = 1;
+    const SM_CXVIRTUALSCREEN: i32 = 78;
+    const SM_CYVIRTUALSCREEN: i32 = 79;
+    const SM_CMONITORS: i32 = 80;
+
+    // SAFETY: GetSystemMetrics has no preconditions
+    let metric = |index| unsafe { GetSystemMetrics(index) };
+    Some(format!(
+        "{} monitor(s), primary {}x{}, desktop {}x{}",
+        metric(SM_CMONITORS),
+        metric(SM_CXSCREEN),
+        metric(SM_CYSCREEN),
+        metric(SM_CXVIRTUALSCREEN),
+        metric(SM_CYVIRTUALSCREEN)
+    ))
+}
+
+/// Connected DRM connectors and their preferred mode (works for X11 and Wayland alike)
+#[cfg(target_os = "linux")]
+fn display_signature() -> Option<String> {
+    let mut connected: Vec<String> = std::fs::read_dir("/sys/class/drm")
+        .ok()?
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let status = std::fs::read_to_string(path.join("status")).ok()?;
+            if status.trim() != "connected" {
+                return None;
+            }
+            let modes = std::fs::read_to_string(path.join("modes")).unwrap_or_default();
+            let mode = modes.lines().next().unwrap_or("?");
+            Some(format!("{} {}", entry.file_name().to_string_lossy(), mode))
+        })
+        .collect();
+    connected.sort();
+    Some(connected.join(", "))
+}
+
+/// Active displays and their size in pixels
+#[cfg(target_os = "macos")]
+fn display_signature() -> Option<String> {
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGGetActiveDisplayList(max: u32, displays: *mut u32, count: *mut u32) -> i32;
+        fn CGDisplayPixelsWide(display: u32) -> usize;
+        fn CGDisplayPixelsHigh(display: u32) -> usize;
+    }
+
+    let mut ids = [0u32; 16];
+    let mut count: u32 = 0;
+    // SAFETY: the buffer holds `ids.len()` display ids
+    if unsafe { CGGetActiveDisplayList(ids.len() as u32, ids.as_mut_ptr(), &mut count) } != 0 {
+        return None;
+    }
+    let displays: Vec<String> = ids[..count as usize]
+        .iter()
+        // SAFETY: ids returned by CGGetActiveDisplayList
+        .map(|&id| unsafe { format!("{}x{}", CGDisplayPixelsWide(id), CGDisplayPixelsHigh(id)) })
+        .collect();
+    Some(displays.join(", "))
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+fn display_signature() -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uptime_accumulates_within_a_boot() {
+        let mut ledger = UptimeLedger::default();
+        assert_eq!(ledger.update(1_000, 60), 60);
+        assert_eq!(ledger.update(1_000, 120), 120);
+        // Windows derives the boot time from the tick count; a small shift is the same boot
+        assert_eq!(ledger.update(1_001, 180), 180);
+        assert_eq!(ledger.boots, 1);
+        assert_eq!(ledger.previous_secs, 0);
+    }
+
+    #[test]
+    fn a_new_boot_adds_the_previous_uptime() {
+        let mut ledger = UptimeLedger::default();
+        ledger.update(1_000, 500);
+        assert_eq!(ledger.update(10_000, 30), 530);
+        assert_eq!(ledger.boots, 2);
+        assert_eq!(ledger.boot_time, 10_000);
+        // Uptime going backwards is a reboot too, even with a close boot time
+        assert_eq!(ledger.update(10_050, 10), 540);
+        assert_eq!(ledger.boots, 3);
+    }
+
+    #[test]
+    fn a_stored_ledger_continues_after_a_restart() {
+        let mut ledger = UptimeLedger {
+            previous_secs: 1_000,
+            boot_time: 5_000,
+            last_uptime_secs: 200,
+            boots: 4,
+        };
+        // Same boot, app restarted
+        assert_eq!(ledger.update(5_000, 300), 1_300);
+        assert_eq!(ledger.boots, 4);
+    }
+
+    #[test]
+    fn falls_back_when_sysinfo_has_no_name() {
+        let none = || None;
+        let env = || Some("desk-01\n".to_string());
+        assert_eq!(
+            first_hostname(&[("sysinfo", &none), ("HOSTNAME", &env)]),
+            Some("desk-01".to_string())
+        );
+    }
+
+    #[test]
+    fn skips_empty_and_unknown_names() {
+        let unknown = || Some("Unknown".to_string());
+        let blank = || Some("  ".to_string());
+        let file = || Some("laptop".to_string());
+        assert_eq!(
+            first_hostname(&[
+                ("sysinfo", &unknown),
+                ("HOSTNAME", &blank),
+                ("/etc/hostname", &file)
+            ]),
+            Some("laptop".to_string())
+        );
+    }
+
+    #[test]
+    fn none_without_a_usable_source() {
+        let none = || None;
+        let blank = || Some(String::new());
+        assert_eq!(
+            first_hostname(&[("sysinfo", &none), ("HOSTNAME", &blank)]),
+            None
+        );
+        assert_eq!(first_hostname(&[]), None);
+    }
+
+    #[test]
+    fn first_source_wins() {
+        let sysinfo = || Some("from-sysinfo".to_string());
+        let env = || Some("from-env".to_string());
+        assert_eq!(
+            first_hostname(&[("sysinfo", &sysinfo), ("HOSTNAME", &env)]),
+            Some("from-sysinfo".to_string())
+        );
+    }
+}
@@ -0,0 +1,292 @@
+//! Pending OS updates and "reboot required", checked on background threads.
+//!
+//! Package manager queries take seconds to minutes, so they run on their own slow
+//! cadence and the collector only reads the cached result:
+//! - Linux: `apt list --upgradable`, `dnf check-update` or `checkupdates` / `pacman -Qu`;
+//!   reboot flag from `/var/run/reboot-required` or `needs-restarting -r`
+//! - Windows: Windows Update Agent (`Microsoft.Update.Session`) via PowerShell;
+//!   reboot flag from the Component Based Servicing / WindowsUpdate registry keys
+//! - macOS: `softwareupdate -l` (a listed update marked restart counts as reboot required)
+
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Time between update checks
+const UPDATE_CHECK_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Time between reboot-required checks
+const REBOOT_CHECK_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+/// Package names kept from a check (the count covers all of them)
+const MAX_LISTED_UPDATES: usize = 100;
+
+/// Result of the last successful check
+#[derive(Debug, Clone)]
+pub struct PendingUpdates {
+    pub count: usize,
+    /// Package names / update titles, capped at `MAX_LISTED_UPDATES`
+    pub packages: Vec<String>,
+    /// Tool the result came from, e.g. "apt" or "softwareupdate"
+    pub source: &'static str,
+}
+
+/// Runs a slow check on a background thread every `interval` and caches the result.
+/// The thread stops when this is dropped.
+pub struct PeriodicCheck<T> {
+    latest: Arc<Mutex<Option<T>>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl<T: Clone + Send + 'static> PeriodicCheck<T> {
+    fn start(name: &str, interval: Duration, check: fn() -> Option<T>) -> Self {
+        let periodic = Self {
+            latest: Arc::new(Mutex::new(None)),
+            stop: Arc::new(AtomicBool::new(false)),
+        };
+
+        let latest = periodic.latest.clone();
+        let stop = periodic.stop.clone();
+        let spawned = std::thread::Builder::new()
+            .name(name.into())
+            .spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let started = Instant::now();
+                    if let Some(result) = check() {
+                        if let Ok(mut slot) = latest.lock() {
+                            *slot = Some(result);
+                        }
+                    }
+                    // Sleep in short steps so dropping the check ends the thread promptly
+                    while !stop.load(Ordering::Relaxed) && started.elapsed() < interval {
+                        std::thread::sleep(Duration::from_secs(1));
+                    }
+                }
+            });
+        if let Err(e) = spawned {
+            log::error!("[Updates] Failed to start {} thread: {}", name, e);
+        }
+        periodic
+    }
+
+    /// Last result; None until a check succeeded, or where detection isn't available
+    pub fn latest(&self) -> Option<T> {
+        self.latest.lock().ok().and_then(|v| v.clone())
+    }
+}
+
+impl<T> Drop for PeriodicCheck<T> {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Background check for pending OS updates
+pub fn start_update_check() -> PeriodicCheck<PendingUpdates> {
+    PeriodicCheck::start("update-check", UPDATE_CHECK_INTERVAL, || {
+        let started = Instant::now();
+        let result = check_pending();
+        match result {
+            Some(ref r) => log::info!(
+                "[Updates] {} pending update(s) via {} (took {:?})",
+                r.count,
+                r.source,
+                started.elapsed()
+            ),
+            None => log::debug!("[Updates] No supported update source found"),
+        }
+        result
+    })
+}
+
+/// Background check for whether the OS wants a reboot (e.g. after installing updates)
+pub fn start_reboot_check() -> PeriodicCheck<bool> {
+    PeriodicCheck::start("reboot-check", REBOOT_CHECK_INTERVAL, || {
+        let result = check_reboot_required();
+        if result.is_none() {
+            log::debug!("[Updates] Reboot-required detection not available");
+        }
+        result
+    })
+}
+
+/// Run a command and return its stdout, or None if it cannot be started.
+/// The exit status is returned too: some tools signal "updates available" with it.
+fn run(program: &str, args: &[&str]) -> Option<(Option<i32>, String)> {
+    let mut cmd = Command::new(program);
+    cmd.args(args).stdin(Stdio::null()).stderr(Stdio::null());
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        cmd.creation_flags(CREATE_NO_WINDOW);
+    }
+    let output = cmd.output().ok()?;
+    Some((
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+    ))
+}
+
+fn pending_updates(mut packages: Vec<String>, source: &'static str) -> PendingUpdates {
+    let count = packages.len();
+    packages.truncate(MAX_LISTED_UPDATES);
+    PendingUpdates {
+        count,
+        packages,
+        source,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn check_pending() -> Option<PendingUpdates> {
+    // apt: "name/suite version arch [upgradable from: old]"
+    if let Some((Some(0), out)) = run("apt", &["list", "--upgradable"]) {
+        let packages = out
+            .lines()
+            .filter(|l| l.contains("[upgradable from"))
+            .filter_map(|l| l.split('/').next())
+            .map(str::to_string)
+            .collect();
+        return Some(pending_updates(packages, "apt"));
+    }
+
+    // dnf: exit code 100 = updates available, 0 = none; lines are "name.arch version repo"
+    if let Some((Some(code @ (0 | 100)), out)) = run("dnf", &["check-update", "-q"]) {
+        let packages = if code == 100 {
+            out.lines()
+                .take_while(|l| !l.starts_with("Obsoleting"))
+                .filter(|l| l.split_whitespace().count() == 3)
+                .filter_map(|l| l.split_whitespace().next())
+                .map(|name| name.rsplit_once('.').map_or(name, |(n, _)| n).to_string())
+                .collect()
+        } else {
+            Vec::new()
+        };
+        return Some(pending_updates(packages, "dnf"));
+    }
+
+    // pacman: checkupdates (pacman-contrib) syncs a temporary database; pacman -Qu uses
+    // the local one. Both exit non-zero with no output when nothing is pending.
+    for (program, args, source) in [
+        ("checkupdates", &[][..], "checkupdates"),
+        ("pacman", &["-Qu"][..], "pacman"),
+    ] {
+        if let Some((_, out)) = run(program, args) {
+            let packages = out
+                .lines()
+                .filter_map(|l| l.split_whitespace().next())
+                .map(str::to_string)
+                .collect();
+            return Some(pending_updates(packages, source));
+        }
+    }
+
+    None
+}
+
+#[cfg(windows)]
+fn check_pending() -> Option<PendingUpdates> {
+    // Same query as the Settings app: software updates not yet installed or hidden
+    let script = "$ErrorActionPreference = 'Stop'; \
+        $s = (New-Object -ComObject Microsoft.Update.Session).CreateUpdateSearcher(); \
+        $s.Search('IsInstalled=0 and IsHidden=0 and Type=''Software''').Updates | \
+        ForEach-Object { $_.Title }";
+    let (code, out) = run(
+        "powershell",
+        &["-NoProfile", "-NonInteractive", "-Command", script],
+    )?;
+    if code != Some(0) {
+        log::debug!("[Updates] Windows Update search failed (exit {:?})", code);
+        return None;
+    }
+    let packages = out
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect();
+    Some(pending_updates(packages, "windows_update"))
+}
+
+#[cfg(target_os = "macos")]
+fn check_pending() -> Option<PendingUpdates> {
+    // "* Label: macOS Sonoma 14.5-23F79" (macOS 10.15+) or "   * Safari15.1" (older)
+    let (code, out) = run("softwareupdate", &["-l"])?;
+    if code != Some(0) {
+        return None;
+    }
+    let packages = out
+        .lines()
+        .map(str::trim)
+        .filter_map(|l| l.strip_prefix('*'))
+        .map(|l| l.trim().trim_start_matches("Label:").trim().to_string())
+        .filter(|l| !l.is_empty())
+        .collect();
+    Some(pending_updates(packages, "softwareupdate"))
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+fn check_pending() -> Option<PendingUpdates> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn check_reboot_required() -> Option<bool> {
+    // Debian/Ubuntu: created by package scripts (kernel, libc, ...) until the next boot
+    if std::path::Path::new("/var/run/reboot-required").exists() {
+        return Some(true);
+    }
+    // Fedora/RHEL (dnf-utils): exit code 1 = reboot needed, 0 = not needed
+    if let Some((Some(code @ (0 | 1)), _)) = run("needs-restarting", &["-r"]) {
+        return Some(code == 1);
+    }
+    // On Debian-based systems the absence of the flag file means no reboot is needed
+    if std::path::Path::new("/var/lib/dpkg").exists() {
+        return Some(false);
+    }
+    None
+}
+
+#[cfg(windows)]
+fn check_reboot_required() -> Option<bool> {
+    // Either key exists only while a reboot is pending
+    const KEYS: &[&str] = &[
+        r"HKLM\SOFTWARE\Microsoft\Windows\CurrentVersion\Component Based Servicing\RebootPending",
+        r"HKLM\SOFTWARE\Microsoft\Windows\CurrentVersion\WindowsUpdate\Auto Update\RebootRequired",
+    ];
+    let mut pending = false;
+    for key in KEYS {
+        // reg exits 0 when the key exists and 1 when it doesn't
+        match run("reg", &["query", key])? {
+            (Some(0), _) => pending = true,
+            (Some(1), _) => {}
+            (code, _) => {
+                log::debug!("[Updates] reg query {} failed (exit {:?})", key, code);
+                return None;
+            }
+        }
+    }
+    Some(pending)
+}
+
+#[cfg(target_os = "macos")]
+fn check_reboot_required() -> Option<bool> {
+    // macOS has no reboot flag; report whether an already-scanned update needs a restart.
+    // --no-scan reuses the last scan (from the update check or the system) instead of
+    // contacting Apple's servers every time.
+    let (code, out) = run("softwareupdate", &["-l", "--no-scan"])?;
+    if code != Some(0) {
+        return None;
+    }
+    Some(
+        out.lines()
+            .any(|l| l.contains("Action: restart") || l.contains("[restart]")),
+    )
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+fn check_reboot_required() -> Option<bool> {
+    None
+}
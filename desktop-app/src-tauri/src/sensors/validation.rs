@@ -0,0 +1,447 @@
+use serde::{Deserialize, Serialize};
+
+use super::collector::SensorValue;
+
+/// A sensor whose metadata HA will reject or that breaks long-term statistics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SensorWarning {
+    pub unique_id: String,
+    pub message: String,
+}
+
+/// HA rules for a numeric sensor device_class: allowed units and state_classes
+struct DeviceClassRule {
+    device_class: &'static str,
+    units: &'static [&'static str],
+    state_classes: &'static [&'static str],
+}
+
+const MEASUREMENT: &[&str] = &["measurement"];
+const ANY_STATE_CLASS: &[&str] = &["measurement", "total", "total_increasing"];
+
+/// Subset of HA's sensor device classes (homeassistant/components/sensor/const.py)
+const SENSOR_RULES: &[DeviceClassRule] = &[
+    DeviceClassRule {
+        device_class: "battery",
+        units: &["%"],
+        state_classes: MEASUREMENT,
+    },
+    DeviceClassRule {
+        device_class: "temperature",
+        units: &["°C", "°F", "K"],
+        state_classes: MEASUREMENT,
+    },
+    DeviceClassRule {
+        device_class: "frequency",
+        units: &["Hz", "kHz", "MHz", "GHz"],
+        state_classes: MEASUREMENT,
+    },
+    DeviceClassRule {
+        device_class: "data_size",
+        units: &[
+            "bit", "kbit", "Mbit", "Gbit", "B", "kB", "MB", "GB", "TB", "PB", "EB", "ZB", "YB",
+            "KiB", "MiB", "GiB", "TiB", "PiB", "EiB", "ZiB", "YiB",
+        ],
+        state_classes: ANY_STATE_CLASS,
+    },
+    DeviceClassRule {
+        device_class: "data_rate",
+        units: &[
+            "bit/s", "kbit/s", "Mbit/s", "Gbit/s", "B/s", "kB/s", "MB/s", "GB/s", "KiB/s",
+            "MiB/s", "GiB/s",
+        ],
+        state_classes: MEASUREMENT,
+    },
+    DeviceClassRule {
+        device_class: "duration",
+        units: &["d", "h", "min", "s", "ms", "μs"],
+        state_classes: ANY_STATE_CLASS,
+    },
+    DeviceClassRule {
+        device_class: "power",
+        units: &["mW", "W", "kW", "MW", "GW", "TW"],
+        state_classes: MEASUREMENT,
+    },
+    DeviceClassRule {
+        device_class: "energy",
+        units: &["Wh", "kWh", "MWh", "GWh", "TWh", "MJ", "GJ"],
+        state_classes: &["total", "total_increasing"],
+    },
+    DeviceClassRule {
+        device_class: "voltage",
+        units: &["mV", "V", "kV"],
+        state_classes: MEASUREMENT,
+    },
+    DeviceClassRule {
+        device_class: "current",
+        units: &["mA", "A"],
+        state_classes: MEASUREMENT,
+    },
+    DeviceClassRule {
+        device_class: "signal_strength",
+        units: &["dB", "dBm"],
+        state_classes: MEASUREMENT,
+    },
+];
+
+/// Sensor device classes whose state is not a number (no unit or state_class allowed)
+const NON_NUMERIC_SENSOR_CLASSES: &[&str] = &["timestamp", "date", "enum"];
+
+/// Binary sensor device classes known to HA
+const BINARY_SENSOR_CLASSES: &[&str] = &[
+    "battery", "battery_charging", "connectivity", "plug", "power", "problem", "running",
+    "update", "safety", "presence", "occupancy", "moving", "lock", "opening",
+];
+
+const STATE_CLASSES: &[&str] = &["measurement", "total", "total_increasing"];
+
+/// Check sensor metadata against HA's device_class/unit/state_class rules
+pub fn validate(sensors: &[SensorValue]) -> Vec<SensorWarning> {
+    let mut warnings = Vec::new();
+    for sensor in sensors {
+        let mut warn = |message: String| {
+            warnings.push(SensorWarning {
+                unique_id: sensor.unique_id.clone(),
+                message,
+            })
+        };
+
+        if sensor.sensor_type == "binary_sensor" {
+            if let Some(ref dc) = sensor.device_class {
+                if !BINARY_SENSOR_CLASSES.contains(&dc.as_str()) {
+                    warn(format!("unknown binary_sensor device_class '{}'", dc));
+                }
+            }
+            if sensor.unit_of_measurement.is_some() {
+                warn("binary sensors cannot have a unit_of_measurement".into());
+            }
+            if sensor.state_class.is_some() {
+                warn("binary sensors cannot have a state_class".into());
+            }
+            continue;
+        }
+
+        let unit = sensor.unit_of_measurement.as_deref();
+        let state_class = sensor.state_class.as_deref();
+
+        if let Some(sc) = state_class {
+            if !STATE_CLASSES.contains(&sc) {
+                warn(format!("unknown state_class '{}'", sc));
+            }
+        }
+
+        // Long-term statistics need a numeric state
+        if (state_class.is_some() || unit.is_some()) && !is_numeric_or_unavailable(&sensor.state) {
+            warn(format!(
+                "state {} is not numeric but the sensor has a unit or state_class",
+                sensor.state
+            ));
+        }
+
+        let Some(dc) = sensor.device_class.as_deref() else {
+            continue;
+        };
+
+        if NON_NUMERIC_SENSOR_CLASSES.contains(&dc) {
+            if unit.is_some() {
+                warn(format!("device_class '{}' cannot have a unit_of_measurement", dc));
+            }
+            if state_class.is_some() {
+                warn(format!("device_class '{}' cannot have a state_class", dc));
+            }
+            continue;
+        }
+
+        let Some(rule) = SENSOR_RULES.iter().find(|r| r.device_class == dc) else {
+            warn(format!("unknown sensor device_class '{}'", dc));
+            continue;
+        };
+
+        match unit {
+            Some(u) if !rule.units.contains(&u) => warn(format!(
+                "unit '{}' is not valid for device_class '{}' (expected one of: {})",
+                u,
+                dc,
+                rule.units.join(", ")
+            )),
+            None => warn(format!("device_class '{}' requires a unit_of_measurement", dc)),
+            _ => {}
+        }
+        if let Some(sc) = state_class {
+            if STATE_CLASSES.contains(&sc) && !rule.state_classes.contains(&sc) {
+                warn(format!(
+                    "state_class '{}' is not valid for device_class '{}' (expected one of: {})",
+                    sc,
+                    dc,
+                    rule.state_classes.join(", ")
+                ));
+            }
+        }
+    }
+    warnings
+}
+
+fn is_numeric_or_unavailable(state: &serde_json::Value) -> bool {
+    match state {
+        serde_json::Value::Null | serde_json::Value::Number(_) => true,
+        serde_json::Value::String(s) => {
+            s == "unavailable" || s == "unknown" || s.trim().parse::<f64>().is_ok()
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// (sensor_type, device_class, unit, state_class, state)
+    type Case = (
+        &'static str,
+        Option<&'static str>,
+        Option<&'static str>,
+        Option<&'static str>,
+        serde_json::Value,
+    );
+
+    fn warnings(case: &Case) -> Vec<String> {
+        let (sensor_type, device_class, unit, state_class, state) = case.clone();
+        let sensor = SensorValue {
+            unique_id: "test".into(),
+            name: "Test".into(),
+            state,
+            sensor_type: sensor_type.into(),
+            device_class: device_class.map(Into::into),
+            unit_of_measurement: unit.map(Into::into),
+            state_class: state_class.map(Into::into),
+            icon: None,
+            attributes: HashMap::new(),
+            update_at_interval: true,
+        };
+        validate(&[sensor]).into_iter().map(|w| w.message).collect()
+    }
+
+    /// Each case yields exactly the warnings containing the expected fragments, in order
+    fn check(cases: &[(Case, &[&str])]) {
+        for (case, expected) in cases {
+            let messages = warnings(case);
+            assert_eq!(messages.len(), expected.len(), "{:?}: {:?}", case, messages);
+            for (message, fragment) in messages.iter().zip(expected.iter()) {
+                assert!(message.contains(fragment), "{:?}: {}", case, message);
+            }
+        }
+    }
+
+    #[test]
+    fn binary_sensor_rules() {
+        let on = serde_json::json!(true);
+        check(&[
+            (("binary_sensor", Some("plug"), None, None, on.clone()), &[]),
+            (("binary_sensor", None, None, None, on.clone()), &[]),
+            (
+                ("binary_sensor", Some("hot"), None, None, on.clone()),
+                &["unknown binary_sensor device_class 'hot'"],
+            ),
+            (
+                ("binary_sensor", None, Some("%"), None, on.clone()),
+                &["cannot have a unit_of_measurement"],
+            ),
+            (
+                ("binary_sensor", None, None, Some("measurement"), on),
+                &["cannot have a state_class"],
+            ),
+        ]);
+    }
+
+    #[test]
+    fn state_class_must_be_known() {
+        let state = serde_json::json!(1);
+        check(&[
+            (("sensor", None, None, Some("total"), state.clone()), &[]),
+            (
+                ("sensor", None, None, Some("average"), state),
+                &["unknown state_class 'average'"],
+            ),
+        ]);
+    }
+
+    #[test]
+    fn state_with_a_unit_or_state_class_must_be_numeric() {
+        check(&[
+            (
+                ("sensor", None, Some("%"), None, serde_json::json!(42.5)),
+                &[],
+            ),
+            (
+                ("sensor", None, Some("%"), None, serde_json::json!(" 7 ")),
+                &[],
+            ),
+            (
+                (
+                    "sensor",
+                    None,
+                    Some("%"),
+                    None,
+                    serde_json::json!("unavailable"),
+                ),
+                &[],
+            ),
+            (
+                (
+                    "sensor",
+                    None,
+                    Some("%"),
+                    None,
+                    serde_json::json!("unknown"),
+                ),
+                &[],
+            ),
+            (
+                ("sensor", None, Some("%"), None, serde_json::Value::Null),
+                &[],
+            ),
+            (("sensor", None, None, None, serde_json::json!("idle")), &[]),
+            (
+                ("sensor", None, Some("%"), None, serde_json::json!("idle")),
+                &["is not numeric"],
+            ),
+            (
+                (
+                    "sensor",
+                    None,
+                    None,
+                    Some("measurement"),
+                    serde_json::json!(true),
+                ),
+                &["is not numeric"],
+            ),
+        ]);
+    }
+
+    #[test]
+    fn non_numeric_device_classes_have_no_unit_or_state_class() {
+        let time = serde_json::json!("2024-01-01T00:00:00+00:00");
+        check(&[
+            (("sensor", Some("timestamp"), None, None, time.clone()), &[]),
+            (
+                (
+                    "sensor",
+                    Some("timestamp"),
+                    Some("s"),
+                    None,
+                    serde_json::json!(0),
+                ),
+                &["device_class 'timestamp' cannot have a unit_of_measurement"],
+            ),
+            (
+                (
+                    "sensor",
+                    Some("enum"),
+                    None,
+                    Some("measurement"),
+                    serde_json::json!(0),
+                ),
+                &["device_class 'enum' cannot have a state_class"],
+            ),
+        ]);
+    }
+
+    #[test]
+    fn device_class_must_be_known() {
+        check(&[(
+            (
+                "sensor",
+                Some("loudness"),
+                Some("dB"),
+                None,
+                serde_json::json!(3),
+            ),
+            &["unknown sensor device_class 'loudness'"],
+        )]);
+    }
+
+    #[test]
+    fn unit_must_fit_the_device_class() {
+        let state = serde_json::json!(50);
+        check(&[
+            (
+                (
+                    "sensor",
+                    Some("temperature"),
+                    Some("°C"),
+                    None,
+                    state.clone(),
+                ),
+                &[],
+            ),
+            (
+                (
+                    "sensor",
+                    Some("data_size"),
+                    Some("GiB"),
+                    None,
+                    state.clone(),
+                ),
+                &[],
+            ),
+            (
+                (
+                    "sensor",
+                    Some("temperature"),
+                    Some("C"),
+                    None,
+                    state.clone(),
+                ),
+                &["unit 'C' is not valid for device_class 'temperature'"],
+            ),
+            (
+                ("sensor", Some("power"), None, None, state),
+                &["device_class 'power' requires a unit_of_measurement"],
+            ),
+        ]);
+    }
+
+    #[test]
+    fn state_class_must_fit_the_device_class() {
+        let state = serde_json::json!(50);
+        check(&[
+            (
+                (
+                    "sensor",
+                    Some("energy"),
+                    Some("kWh"),
+                    Some("total_increasing"),
+                    state.clone(),
+                ),
+                &[],
+            ),
+            (
+                (
+                    "sensor",
+                    Some("duration"),
+                    Some("s"),
+                    Some("total"),
+                    state.clone(),
+                ),
+                &[],
+            ),
+            (
+                (
+                    "sensor",
+                    Some("energy"),
+                    Some("kWh"),
+                    Some("measurement"),
+                    state.clone(),
+                ),
+                &["state_class 'measurement' is not valid for device_class 'energy'"],
+            ),
+            // An unknown state_class is reported once, not again per device_class
+            (
+                ("sensor", Some("battery"), Some("%"), Some("average"), state),
+                &["unknown state_class 'average'"],
+            ),
+        ]);
+    }
+}
@@ -0,0 +1,69 @@
+//! Shared COM/WMI connections (Windows only).
+//!
+//! COM must be initialized per thread, so the COM library and the WMI connections are
+//! cached thread-locally: each thread pays the init cost once and failures are logged here.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::thread::LocalKey;
+
+use wmi::{COMLibrary, Variant, WMIConnection, WMIResult};
+
+/// WMI namespaces we query
+#[derive(Debug, Clone, Copy)]
+pub enum Namespace {
+    /// root\CIMV2 (hardware/OS classes)
+    Cimv2,
+    /// root\WMI (ACPI thermal zones, requires admin)
+    RootWmi,
+    /// root\StandardCimv2 (network adapters)
+    StandardCimv2,
+}
+
+thread_local! {
+    static COM_LIB: Option<COMLibrary> = match COMLibrary::new() {
+        Ok(c) => Some(c),
+        Err(e) => {
+            log::error!("[WMI] COM init failed on this thread: {}", e);
+            None
+        }
+    };
+    static CIMV2: RefCell<Option<WMIConnection>> = const { RefCell::new(None) };
+    static ROOT_WMI: RefCell<Option<WMIConnection>> = const { RefCell::new(None) };
+    static STANDARD_CIMV2: RefCell<Option<WMIConnection>> = const { RefCell::new(None) };
+}
+
+fn with_connection<T>(
+    namespace: Namespace,
+    f: impl FnOnce(&WMIConnection) -> T,
+) -> Option<T> {
+    let (cache, path): (&'static LocalKey<RefCell<Option<WMIConnection>>>, &str) = match namespace {
+        Namespace::Cimv2 => (&CIMV2, "root\\CIMV2"),
+        Namespace::RootWmi => (&ROOT_WMI, "root\\WMI"),
+        Namespace::StandardCimv2 => (&STANDARD_CIMV2, "root\\StandardCimv2"),
+    };
+    let com_lib = COM_LIB.with(|c| *c)?;
+
+    cache.with(|cell| {
+        let mut slot = cell.borrow_mut();
+        if slot.is_none() {
+            match WMIConnection::with_namespace_path(path, com_lib) {
+                Ok(con) => *slot = Some(con),
+                Err(e) => {
+                    log::error!("[WMI] Connection to {} failed: {}", path, e);
+                    return None;
+                }
+            }
+        }
+        slot.as_ref().map(f)
+    })
+}
+
+/// Run a raw WMI query on this thread's cached connection.
+/// Returns None when COM/WMI could not be initialized.
+pub fn raw_query(
+    namespace: Namespace,
+    query: &str,
+) -> Option<WMIResult<Vec<HashMap<String, Variant>>>> {
+    with_connection(namespace, |con| con.raw_query(query))
+}
@@ -1,10 +1,67 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 use tauri::AppHandle;
 use tauri_plugin_store::StoreExt;
 
+use crate::diagnostics::{
+    DEFAULT_WEBHOOK_GONE_THRESHOLD, DEFAULT_WEBHOOK_VERIFY_INTERVAL_SECS,
+    MIN_WEBHOOK_VERIFY_INTERVAL_SECS,
+};
+use crate::events::ImmediateUpdateConfig;
+use crate::ha_client::{
+    Capabilities, Transport, WebhookCommands, DEFAULT_MAX_ATTRIBUTE_ITEMS, DEFAULT_UPDATE_RETRIES,
+    DEFAULT_UPDATE_RETRY_DELAY_SECS,
+};
+use crate::registration::{SeedBurstConfig, DEFAULT_REGISTRATION_ATTEMPTS};
+use crate::sensors::{self, battery};
+use crate::sensors::battery_saver::BatterySaverConfig;
+use crate::sensors::cpu::OverheatConfig;
+use crate::sensors::custom::CustomSensor;
+use crate::sensors::disk::DiskUsageMode;
+use crate::sensors::gpu;
+use crate::sensors::memory::MemoryUsedMode;
+use crate::sensors::network::{self, PublicIpConfig};
+use crate::sensors::remote::RemoteHost;
+use crate::sensors::state_rounding::StateRoundingConfig;
+use crate::sensors::system_info::UptimeLedger;
+use crate::sensors::probe::DEFAULT_PROBE_TIMEOUT_SECS;
+use crate::sensors::system_info;
+use crate::sensors::TemperatureRange;
+
 const STORE_PATH: &str = "settings.json";
 
+/// Must match `identifier` in tauri.conf.json (the store lives in the app data dir)
+const APP_IDENTIFIER: &str = "com.ha-companion.desktop";
+
+/// Optional per-machine prefix for the unique_ids sent to HA
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UniqueIdPrefix {
+    /// Ids as collected (`cpu_usage`)
+    #[default]
+    None,
+    /// Hostname (`my_pc_cpu_usage`)
+    Hostname,
+    /// Short hash of the device_id (`1a2b3c4d_cpu_usage`); survives renaming the machine
+    DeviceId,
+}
+
+/// What the window close button does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CloseBehavior {
+    /// Hide the window and keep running in the tray
+    #[default]
+    Hide,
+    /// Minimize the window to the taskbar/dock
+    Minimize,
+    /// Quit the app
+    Quit,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppSettings {
     pub server_url: String,
@@ -15,6 +72,105 @@ pub struct AppSettings {
     pub language: String,
     pub enabled_sensors: HashMap<String, bool>,
     pub autostart: bool,
+    /// Maximum number of items sent for list-valued sensor attributes
+    pub max_attribute_items: usize,
+    /// Remote Linux hosts to report over SSH (empty = feature off)
+    pub remote_hosts: Vec<RemoteHost>,
+    /// Registration metadata fingerprint per unique_id, as last registered with HA
+    pub sensor_fingerprints: HashMap<String, String>,
+    /// How "used" memory is computed on Linux ("available" or "exclude_cache")
+    pub memory_used_mode: MemoryUsedMode,
+    /// Attempts per registration step before a transient network error is reported
+    pub registration_attempts: u32,
+    /// User-defined command/file sensors
+    pub custom_sensors: Vec<CustomSensor>,
+    /// Custom sensors run arbitrary commands, so they must be switched on explicitly
+    pub custom_sensors_enabled: bool,
+    /// Gzip-compress large sensor update bodies
+    pub compress_payloads: bool,
+    /// Register sensors on their first update instead of all at once during registration
+    pub lazy_registration: bool,
+    /// Threshold, duration and hysteresis of the cpu_overheating binary sensor
+    pub cpu_overheat: OverheatConfig,
+    /// CPU/GPU temperatures outside this range are sensor glitches and reported as unavailable
+    pub temperature_range: TemperatureRange,
+    /// Seconds a blocking platform probe (WMI, rocm-smi, system_profiler) may take before
+    /// its sensor group is skipped for the cycle
+    pub probe_timeout_secs: u64,
+    /// Local IP address to send HA traffic from (multi-homed machines)
+    pub source_ip: Option<String>,
+    /// Local interface to send HA traffic from; used when `source_ip` is not set
+    pub source_interface: Option<String>,
+    /// Consecutive 410/404 webhook responses before the device is registered again
+    pub webhook_gone_threshold: u32,
+    /// Attributes to send per sensor unique_id, `prefix*` or `*` (missing = all attributes)
+    pub sensor_attributes: HashMap<String, Vec<String>>,
+    /// Area HA assigns the device to when it is created; only sent at registration
+    pub suggested_area: Option<String>,
+    /// Show the HA dashboard in the main window (off = sensor reporting only, no token
+    /// injection into a webview)
+    pub dashboard_enabled: bool,
+    /// Webhook command names (only for HA integration forks or protocol changes)
+    pub webhook_commands: WebhookCommands,
+    /// Opt-in public IP lookup shown in settings
+    pub public_ip: PublicIpConfig,
+    /// What the window close button does (read at startup)
+    pub close_behavior: CloseBehavior,
+    /// Keep the main window hidden at launch (e.g. autostart); ignored until the app is set up
+    pub start_hidden: bool,
+    /// On battery, only send updates whose values cross the configured thresholds
+    pub battery_saver: BatterySaverConfig,
+    /// GPU entity id suffix per case-insensitive part of the GPU name (multi-GPU machines)
+    pub gpu_ids: HashMap<String, String>,
+    /// Prefix unique_ids sent to HA with the hostname or a device_id hash (opt-in)
+    pub unique_id_prefix: UniqueIdPrefix,
+    /// TOML file with sensor toggles, overrides and custom sensors that override the store
+    pub config_file: Option<String>,
+    /// Send states through the integration's webhook or HA's REST states API
+    pub transport: Transport,
+    /// Path under the server URL the dashboard opens at, e.g. `/lovelace/pc-stats`
+    pub dashboard_path: Option<String>,
+    /// Battery entity id suffix per case-insensitive part of vendor/model/serial
+    pub battery_ids: HashMap<String, String>,
+    /// Batteries (case-insensitive parts of vendor/model/serial) that get no sensors
+    pub hidden_batteries: Vec<String>,
+    /// Events that trigger an update before the interval is up
+    pub immediate_updates: ImmediateUpdateConfig,
+    /// Link on the HA device page; only sent at registration
+    pub configuration_url: Option<String>,
+    /// Quick extra updates right after registration
+    pub seed_burst: SeedBurstConfig,
+    /// Name of the device in HA; None = the hostname
+    pub device_name: Option<String>,
+    /// Renamed sensor payload fields for integration forks (v1 name -> sent name)
+    pub payload_fields: HashMap<String, String>,
+    /// Decimals per sensor and whether unchanged states are skipped in normal cycles
+    pub state_rounding: StateRoundingConfig,
+    /// Seconds between checks that the webhook still exists (0 = never); read at startup
+    pub webhook_verify_interval_secs: u64,
+    /// What the integration announced at the last registration (empty = the v1 baseline)
+    pub integration_capabilities: Capabilities,
+    /// Uptime of earlier boots behind the total_uptime sensor (written by the app)
+    pub uptime_ledger: UptimeLedger,
+    /// Whether disk usage counts reserved space as used ("available") or not ("filesystem")
+    pub disk_usage_mode: DiskUsageMode,
+    /// Retries of a state update that failed on a network error or 5xx (0 = none)
+    pub update_retries: u32,
+    /// Seconds before the first retry; doubles per retry, capped at the update interval
+    pub update_retry_delay_secs: u64,
+    /// Ask HA for a secret at registration and encrypt the webhook with it
+    pub webhook_encryption: bool,
+    /// Secret HA returned at registration (written by the app; None = plain webhook)
+    pub webhook_secret: Option<String>,
+    /// Register the CPU temperature sensors even when no source has produced a reading
+    pub force_temperature_sensors: bool,
+    /// Loaded from a registered install without `gpu_ids` yet, whose GPU entities still use
+    /// the index ids (not persisted)
+    #[serde(skip)]
+    pub legacy_gpu_ids: bool,
+    /// Same for `battery_ids` and the `battery_level_0` ids (not persisted)
+    #[serde(skip)]
+    pub legacy_battery_ids: bool,
 }
 
 impl Default for AppSettings {
@@ -28,61 +184,348 @@ impl Default for AppSettings {
             language: "en".to_string(),
             enabled_sensors: HashMap::new(),
             autostart: false,
+            max_attribute_items: DEFAULT_MAX_ATTRIBUTE_ITEMS,
+            remote_hosts: Vec::new(),
+            sensor_fingerprints: HashMap::new(),
+            memory_used_mode: MemoryUsedMode::default(),
+            registration_attempts: DEFAULT_REGISTRATION_ATTEMPTS,
+            custom_sensors: Vec::new(),
+            custom_sensors_enabled: false,
+            compress_payloads: false,
+            lazy_registration: false,
+            cpu_overheat: OverheatConfig::default(),
+            temperature_range: TemperatureRange::default(),
+            probe_timeout_secs: DEFAULT_PROBE_TIMEOUT_SECS,
+            source_ip: None,
+            source_interface: None,
+            webhook_gone_threshold: DEFAULT_WEBHOOK_GONE_THRESHOLD,
+            sensor_attributes: HashMap::new(),
+            suggested_area: None,
+            dashboard_enabled: true,
+            webhook_commands: WebhookCommands::default(),
+            public_ip: PublicIpConfig::default(),
+            close_behavior: CloseBehavior::default(),
+            start_hidden: false,
+            battery_saver: BatterySaverConfig::default(),
+            gpu_ids: HashMap::new(),
+            unique_id_prefix: UniqueIdPrefix::default(),
+            config_file: None,
+            transport: Transport::default(),
+            dashboard_path: None,
+            battery_ids: HashMap::new(),
+            hidden_batteries: Vec::new(),
+            immediate_updates: ImmediateUpdateConfig::default(),
+            configuration_url: None,
+            seed_burst: SeedBurstConfig::default(),
+            device_name: None,
+            payload_fields: HashMap::new(),
+            state_rounding: StateRoundingConfig::default(),
+            webhook_verify_interval_secs: DEFAULT_WEBHOOK_VERIFY_INTERVAL_SECS,
+            integration_capabilities: Capabilities::default(),
+            uptime_ledger: UptimeLedger::default(),
+            disk_usage_mode: DiskUsageMode::default(),
+            update_retries: DEFAULT_UPDATE_RETRIES,
+            update_retry_delay_secs: DEFAULT_UPDATE_RETRY_DELAY_SECS,
+            webhook_encryption: false,
+            webhook_secret: None,
+            force_temperature_sensors: false,
+            legacy_gpu_ids: false,
+            legacy_battery_ids: false,
         }
     }
 }
 
+/// Key/value persistence behind `AppSettings`. The app uses the Tauri store through its
+/// `AppHandle`; `MemoryStore` lets tests load and save settings without a Tauri runtime.
+pub trait SettingsStore {
+    fn get(&self, key: &str) -> Option<Value>;
+    fn set(&self, key: &str, value: Value) -> Result<(), String>;
+    /// Write pending changes to disk now instead of whenever the store gets to it
+    fn flush(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl SettingsStore for AppHandle {
+    fn get(&self, key: &str) -> Option<Value> {
+        self.store(STORE_PATH).ok()?.get(key)
+    }
+
+    fn set(&self, key: &str, value: Value) -> Result<(), String> {
+        let store = self.store(STORE_PATH).map_err(|e| e.to_string())?;
+        store.set(key, value);
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), String> {
+        let store = self.store(STORE_PATH).map_err(|e| e.to_string())?;
+        store.save().map_err(|e| e.to_string())
+    }
+}
+
+/// In-memory store for tests
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    values: std::sync::Mutex<serde_json::Map<String, Value>>,
+}
+
+#[cfg(test)]
+impl SettingsStore for MemoryStore {
+    fn get(&self, key: &str) -> Option<Value> {
+        self.values.lock().ok()?.get(key).cloned()
+    }
+
+    fn set(&self, key: &str, value: Value) -> Result<(), String> {
+        let mut values = self.values.lock().map_err(|e| e.to_string())?;
+        values.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
 impl AppSettings {
-    /// Load settings from the Tauri store
-    pub fn load(app: &AppHandle) -> Self {
-        let store = match app.store(STORE_PATH) {
-            Ok(s) => s,
-            Err(_) => return Self::default(),
-        };
+    /// Load settings from a store (the Tauri store when given an `AppHandle`)
+    pub fn load(store: &impl SettingsStore) -> Self {
+        let settings = Self::from_lookup(|key| store.get(key));
+        // Persist a freshly generated device id right away so it stays stable
+        if store.get("device_id").is_none() {
+            let _ = store.set("device_id", serde_json::json!(settings.device_id));
+        }
+        settings
+    }
 
-        let server_url = store
-            .get("server_url")
+    /// Build settings from stored values (missing or invalid keys fall back to defaults)
+    fn from_lookup(get: impl Fn(&str) -> Option<Value>) -> Self {
+        let server_url = get("server_url")
             .and_then(|v| v.as_str().map(|s| s.to_string()))
             .unwrap_or_default();
 
-        let access_token = store
-            .get("access_token")
+        let access_token = get("access_token")
             .and_then(|v| v.as_str().map(|s| s.to_string()))
             .unwrap_or_default();
 
-        let webhook_id = store
-            .get("webhook_id")
-            .and_then(|v| v.as_str().map(|s| s.to_string()));
+        let webhook_id = get("webhook_id").and_then(|v| v.as_str().map(|s| s.to_string()));
 
-        let device_id = store
-            .get("device_id")
+        let device_id = get("device_id")
             .and_then(|v| v.as_str().map(|s| s.to_string()))
-            .unwrap_or_else(|| {
-                let id = uuid::Uuid::new_v4().to_string();
-                let _ = store.set("device_id", serde_json::json!(id));
-                id
-            });
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
 
-        let update_interval = store
-            .get("update_interval")
+        let update_interval = get("update_interval")
             .and_then(|v| v.as_u64())
             .unwrap_or(60);
 
-        let language = store
-            .get("language")
+        let language = get("language")
             .and_then(|v| v.as_str().map(|s| s.to_string()))
             .unwrap_or_else(|| "en".to_string());
 
-        let enabled_sensors: HashMap<String, bool> = store
-            .get("enabled_sensors")
-            .and_then(|v| serde_json::from_value(v.clone()).ok())
+        let enabled_sensors: HashMap<String, bool> = get("enabled_sensors")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        let autostart = get("autostart").and_then(|v| v.as_bool()).unwrap_or(false);
+
+        let max_attribute_items = get("max_attribute_items")
+            .and_then(|v| v.as_u64())
+            .map(|v| (v as usize).max(1))
+            .unwrap_or(DEFAULT_MAX_ATTRIBUTE_ITEMS);
+
+        let remote_hosts: Vec<RemoteHost> = get("remote_hosts")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        let sensor_fingerprints: HashMap<String, String> = get("sensor_fingerprints")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        let memory_used_mode: MemoryUsedMode = get("memory_used_mode")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        let registration_attempts = get("registration_attempts")
+            .and_then(|v| v.as_u64())
+            .map(|v| v.clamp(1, 10) as u32)
+            .unwrap_or(DEFAULT_REGISTRATION_ATTEMPTS);
+
+        let custom_sensors: Vec<CustomSensor> = get("custom_sensors")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        let custom_sensors_enabled = get("custom_sensors_enabled")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let compress_payloads = get("compress_payloads")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let lazy_registration = get("lazy_registration")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let cpu_overheat: OverheatConfig = get("cpu_overheat")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        let temperature_range: TemperatureRange = get("temperature_range")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        let probe_timeout_secs = get("probe_timeout_secs")
+            .and_then(|v| v.as_u64())
+            .filter(|secs| *secs > 0)
+            .unwrap_or(DEFAULT_PROBE_TIMEOUT_SECS);
+
+        let source_ip = get("source_ip")
+            .and_then(|v| v.as_str().map(|s| s.trim().to_string()))
+            .filter(|s| !s.is_empty());
+
+        let source_interface = get("source_interface")
+            .and_then(|v| v.as_str().map(|s| s.trim().to_string()))
+            .filter(|s| !s.is_empty());
+
+        let webhook_gone_threshold = get("webhook_gone_threshold")
+            .and_then(|v| v.as_u64())
+            .map(|v| v.clamp(1, 20) as u32)
+            .unwrap_or(DEFAULT_WEBHOOK_GONE_THRESHOLD);
+
+        let sensor_attributes: HashMap<String, Vec<String>> = get("sensor_attributes")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        let suggested_area = get("suggested_area")
+            .and_then(|v| v.as_str().map(|s| s.trim().to_string()))
+            .filter(|s| !s.is_empty());
+
+        let dashboard_enabled = get("dashboard_enabled")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
+
+        let webhook_commands: WebhookCommands = get("webhook_commands")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        let public_ip: PublicIpConfig = get("public_ip")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        let close_behavior: CloseBehavior = get("close_behavior")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        let start_hidden = get("start_hidden").and_then(|v| v.as_bool()).unwrap_or(false);
+
+        let battery_saver: BatterySaverConfig = get("battery_saver")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        let gpu_ids: HashMap<String, String> = get("gpu_ids")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        let unique_id_prefix: UniqueIdPrefix = get("unique_id_prefix")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        let config_file = get("config_file")
+            .and_then(|v| v.as_str().map(|s| s.trim().to_string()))
+            .filter(|s| !s.is_empty());
+
+        let transport: Transport = get("transport")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        let dashboard_path = get("dashboard_path")
+            .and_then(|v| v.as_str().map(|s| s.trim().to_string()))
+            .filter(|s| !s.is_empty());
+
+        let battery_ids: HashMap<String, String> = get("battery_ids")
+            .and_then(|v| serde_json::from_value(v).ok())
             .unwrap_or_default();
 
-        let autostart = store
-            .get("autostart")
+        let hidden_batteries: Vec<String> = get("hidden_batteries")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        let immediate_updates: ImmediateUpdateConfig = get("immediate_updates")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        // HA only accepts http(s) and homeassistant:// links here
+        let configuration_url = get("configuration_url")
+            .and_then(|v| v.as_str().map(|s| s.trim().to_string()))
+            .filter(|s| !s.is_empty())
+            .filter(|s| {
+                let valid = url::Url::parse(s)
+                    .is_ok_and(|u| matches!(u.scheme(), "http" | "https" | "homeassistant"));
+                if !valid {
+                    log::warn!(
+                        "[HA] Ignoring configuration_url '{}': not an http(s) or homeassistant:// URL",
+                        s
+                    );
+                }
+                valid
+            });
+
+        let seed_burst: SeedBurstConfig = get("seed_burst")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        let device_name = get("device_name")
+            .and_then(|v| v.as_str().map(|s| s.trim().to_string()))
+            .filter(|s| !s.is_empty());
+
+        let payload_fields: HashMap<String, String> = get("payload_fields")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        let state_rounding: StateRoundingConfig = get("state_rounding")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        let webhook_verify_interval_secs = get("webhook_verify_interval_secs")
+            .and_then(|v| v.as_u64())
+            .map(|v| if v == 0 { 0 } else { v.max(MIN_WEBHOOK_VERIFY_INTERVAL_SECS) })
+            .unwrap_or(DEFAULT_WEBHOOK_VERIFY_INTERVAL_SECS);
+
+        let integration_capabilities: Capabilities = get("integration_capabilities")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        let uptime_ledger: UptimeLedger = get("uptime_ledger")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        let disk_usage_mode: DiskUsageMode = get("disk_usage_mode")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default();
+
+        let update_retries = get("update_retries")
+            .and_then(|v| v.as_u64())
+            .map(|v| v.min(10) as u32)
+            .unwrap_or(DEFAULT_UPDATE_RETRIES);
+
+        let update_retry_delay_secs = get("update_retry_delay_secs")
+            .and_then(|v| v.as_u64())
+            .map(|v| v.max(1))
+            .unwrap_or(DEFAULT_UPDATE_RETRY_DELAY_SECS);
+
+        let webhook_encryption = get("webhook_encryption")
             .and_then(|v| v.as_bool())
             .unwrap_or(false);
 
+        let webhook_secret = get("webhook_secret")
+            .and_then(|v| v.as_str().map(|s| s.trim().to_string()))
+            .filter(|s| !s.is_empty());
+
+        let force_temperature_sensors = get("force_temperature_sensors")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        // Every save writes these keys, so a registered install without them was set up by a
+        // version that numbered GPUs and batteries by their index
+        let legacy_gpu_ids = webhook_id.is_some() && get("gpu_ids").is_none();
+        let legacy_battery_ids = webhook_id.is_some() && get("battery_ids").is_none();
+
         Self {
             server_url,
             access_token,
@@ -92,25 +535,473 @@ impl AppSettings {
             language,
             enabled_sensors,
             autostart,
+            max_attribute_items,
+            remote_hosts,
+            sensor_fingerprints,
+            memory_used_mode,
+            registration_attempts,
+            custom_sensors,
+            custom_sensors_enabled,
+            compress_payloads,
+            lazy_registration,
+            cpu_overheat,
+            temperature_range,
+            probe_timeout_secs,
+            source_ip,
+            source_interface,
+            webhook_gone_threshold,
+            sensor_attributes,
+            suggested_area,
+            dashboard_enabled,
+            webhook_commands,
+            public_ip,
+            close_behavior,
+            start_hidden,
+            battery_saver,
+            gpu_ids,
+            unique_id_prefix,
+            config_file,
+            transport,
+            dashboard_path,
+            battery_ids,
+            hidden_batteries,
+            immediate_updates,
+            configuration_url,
+            seed_burst,
+            device_name,
+            payload_fields,
+            state_rounding,
+            webhook_verify_interval_secs,
+            integration_capabilities,
+            uptime_ledger,
+            disk_usage_mode,
+            update_retries,
+            update_retry_delay_secs,
+            webhook_encryption,
+            webhook_secret,
+            force_temperature_sensors,
+            legacy_gpu_ids,
+            legacy_battery_ids,
+        }
+    }
+
+    /// Save settings to a store (the Tauri store when given an `AppHandle`)
+    pub fn save(&self, store: &impl SettingsStore) -> Result<(), String> {
+        for (key, value) in self.to_values() {
+            store.set(key, value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Save and write the store to disk before returning, e.g. right before a restart
+    pub fn persist(&self, store: &impl SettingsStore) -> Result<(), String> {
+        self.save(store)?;
+        store.flush()
+    }
+
+    /// Key/value pairs as persisted in settings.json
+    fn to_values(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("server_url", serde_json::json!(self.server_url)),
+            ("access_token", serde_json::json!(self.access_token)),
+            ("webhook_id", serde_json::json!(self.webhook_id)),
+            ("device_id", serde_json::json!(self.device_id)),
+            ("update_interval", serde_json::json!(self.update_interval)),
+            ("language", serde_json::json!(self.language)),
+            (
+                "enabled_sensors",
+                serde_json::to_value(&self.enabled_sensors).unwrap_or_default(),
+            ),
+            ("autostart", serde_json::json!(self.autostart)),
+            (
+                "max_attribute_items",
+                serde_json::json!(self.max_attribute_items),
+            ),
+            (
+                "remote_hosts",
+                serde_json::to_value(&self.remote_hosts).unwrap_or_default(),
+            ),
+            (
+                "sensor_fingerprints",
+                serde_json::to_value(&self.sensor_fingerprints).unwrap_or_default(),
+            ),
+            (
+                "memory_used_mode",
+                serde_json::to_value(self.memory_used_mode).unwrap_or_default(),
+            ),
+            (
+                "registration_attempts",
+                serde_json::json!(self.registration_attempts),
+            ),
+            (
+                "custom_sensors",
+                serde_json::to_value(&self.custom_sensors).unwrap_or_default(),
+            ),
+            (
+                "custom_sensors_enabled",
+                serde_json::json!(self.custom_sensors_enabled),
+            ),
+            ("compress_payloads", serde_json::json!(self.compress_payloads)),
+            ("lazy_registration", serde_json::json!(self.lazy_registration)),
+            (
+                "cpu_overheat",
+                serde_json::to_value(self.cpu_overheat).unwrap_or_default(),
+            ),
+            (
+                "temperature_range",
+                serde_json::to_value(self.temperature_range).unwrap_or_default(),
+            ),
+            ("probe_timeout_secs", serde_json::json!(self.probe_timeout_secs)),
+            ("source_ip", serde_json::json!(self.source_ip)),
+            ("source_interface", serde_json::json!(self.source_interface)),
+            (
+                "webhook_gone_threshold",
+                serde_json::json!(self.webhook_gone_threshold),
+            ),
+            (
+                "sensor_attributes",
+                serde_json::to_value(&self.sensor_attributes).unwrap_or_default(),
+            ),
+            ("suggested_area", serde_json::json!(self.suggested_area)),
+            ("dashboard_enabled", serde_json::json!(self.dashboard_enabled)),
+            (
+                "webhook_commands",
+                serde_json::to_value(&self.webhook_commands).unwrap_or_default(),
+            ),
+            (
+                "public_ip",
+                serde_json::to_value(&self.public_ip).unwrap_or_default(),
+            ),
+            (
+                "close_behavior",
+                serde_json::to_value(self.close_behavior).unwrap_or_default(),
+            ),
+            ("start_hidden", serde_json::json!(self.start_hidden)),
+            (
+                "battery_saver",
+                serde_json::to_value(&self.battery_saver).unwrap_or_default(),
+            ),
+            ("gpu_ids", serde_json::to_value(&self.gpu_ids).unwrap_or_default()),
+            (
+                "unique_id_prefix",
+                serde_json::to_value(self.unique_id_prefix).unwrap_or_default(),
+            ),
+            ("config_file", serde_json::json!(self.config_file)),
+            (
+                "transport",
+                serde_json::to_value(self.transport).unwrap_or_default(),
+            ),
+            ("dashboard_path", serde_json::json!(self.dashboard_path)),
+            ("battery_ids", serde_json::to_value(&self.battery_ids).unwrap_or_default()),
+            ("hidden_batteries", serde_json::json!(self.hidden_batteries)),
+            (
+                "immediate_updates",
+                serde_json::to_value(&self.immediate_updates).unwrap_or_default(),
+            ),
+            ("configuration_url", serde_json::json!(self.configuration_url)),
+            (
+                "seed_burst",
+                serde_json::to_value(&self.seed_burst).unwrap_or_default(),
+            ),
+            ("device_name", serde_json::json!(self.device_name)),
+            (
+                "payload_fields",
+                serde_json::to_value(&self.payload_fields).unwrap_or_default(),
+            ),
+            (
+                "state_rounding",
+                serde_json::to_value(&self.state_rounding).unwrap_or_default(),
+            ),
+            (
+                "webhook_verify_interval_secs",
+                serde_json::json!(self.webhook_verify_interval_secs),
+            ),
+            (
+                "integration_capabilities",
+                serde_json::to_value(&self.integration_capabilities).unwrap_or_default(),
+            ),
+            (
+                "uptime_ledger",
+                serde_json::to_value(self.uptime_ledger).unwrap_or_default(),
+            ),
+            (
+                "disk_usage_mode",
+                serde_json::to_value(self.disk_usage_mode).unwrap_or_default(),
+            ),
+            ("update_retries", serde_json::json!(self.update_retries)),
+            (
+                "update_retry_delay_secs",
+                serde_json::json!(self.update_retry_delay_secs),
+            ),
+            ("webhook_encryption", serde_json::json!(self.webhook_encryption)),
+            ("webhook_secret", serde_json::json!(self.webhook_secret)),
+            (
+                "force_temperature_sensors",
+                serde_json::json!(self.force_temperature_sensors),
+            ),
+        ]
+    }
+
+    /// Whether the app can send states: a webhook from registration, or with the REST states
+    /// transport just a server URL and token
+    pub fn is_registered(&self) -> bool {
+        match self.transport {
+            Transport::Webhook => self.webhook_id.is_some(),
+            Transport::RestStates => !self.server_url.is_empty() && !self.access_token.is_empty(),
+        }
+    }
+
+    /// Prefix for the unique_ids sent to HA, including the trailing `_` (empty when off)
+    pub fn unique_id_prefix(&self) -> String {
+        match self.unique_id_prefix {
+            UniqueIdPrefix::None => String::new(),
+            UniqueIdPrefix::Hostname => {
+                let hostname = system_info::hostname().unwrap_or_default().to_lowercase();
+                let slug: Vec<&str> = hostname
+                    .split(|c: char| !c.is_ascii_alphanumeric())
+                    .filter(|part| !part.is_empty())
+                    .collect();
+                if slug.is_empty() {
+                    log::warn!("[HA] unique_id_prefix: hostname unknown, sending ids without prefix");
+                    return String::new();
+                }
+                format!("{}_", slug.join("_"))
+            }
+            UniqueIdPrefix::DeviceId => {
+                // FNV-1a: stable across builds, unlike std's DefaultHasher
+                let hash = self
+                    .device_id
+                    .bytes()
+                    .fold(0x811c_9dc5u32, |h, b| (h ^ b as u32).wrapping_mul(0x0100_0193));
+                format!("{:08x}_", hash)
+            }
         }
     }
 
-    /// Save settings to the Tauri store
-    pub fn save(&self, app: &AppHandle) -> Result<(), String> {
-        let store = app.store(STORE_PATH).map_err(|e| e.to_string())?;
+    /// GPU entity ids used to be the enumeration index (`gpu_usage_0`). For installs that
+    /// registered those, pin the current GPUs to their index so existing entities keep their
+    /// id; identical cards are told apart by a `#n` key. Returns whether `gpu_ids` changed and
+    /// should be saved.
+    pub fn migrate_gpu_ids(&mut self) -> bool {
+        if !self.legacy_gpu_ids || !self.gpu_ids.is_empty() {
+            return false;
+        }
+        let names: Vec<String> = gpu::collect(false, false, self.temperature_range)
+            .gpus
+            .into_iter()
+            .map(|gpu| gpu.name)
+            .collect();
+        for (i, key) in sensors::occurrence_keys(&names).into_iter().enumerate() {
+            log::info!("[Sensors] Pinning GPU '{}' to its old id suffix _{}", key, i);
+            self.gpu_ids.insert(key, i.to_string());
+        }
+        !self.gpu_ids.is_empty()
+    }
 
-        store.set("server_url", serde_json::json!(self.server_url));
-        store.set("access_token", serde_json::json!(self.access_token));
-        store.set("webhook_id", serde_json::json!(self.webhook_id));
-        store.set("device_id", serde_json::json!(self.device_id));
-        store.set("update_interval", serde_json::json!(self.update_interval));
-        store.set("language", serde_json::json!(self.language));
-        store.set(
-            "enabled_sensors",
-            serde_json::to_value(&self.enabled_sensors).unwrap_or_default(),
+    /// Battery entity ids used to be the enumeration index (`battery_level_0`). Like
+    /// `migrate_gpu_ids`, pin the current batteries to their index so existing entities keep
+    /// their id. Returns whether `battery_ids` changed and should be saved.
+    pub fn migrate_battery_ids(&mut self) -> bool {
+        if !self.legacy_battery_ids || !self.battery_ids.is_empty() {
+            return false;
+        }
+        let identities: Vec<String> = battery::collect(&self.hidden_batteries)
+            .batteries
+            .iter()
+            .map(battery::BatteryInfo::identity)
+            .collect();
+        for (i, key) in sensors::occurrence_keys(&identities).into_iter().enumerate() {
+            if identities[i].is_empty() {
+                // Nothing to pin by; the suffix falls back to the index anyway
+                continue;
+            }
+            log::info!("[Sensors] Pinning battery '{}' to its old id suffix _{}", key, i);
+            self.battery_ids.insert(key, i.to_string());
+        }
+        !self.battery_ids.is_empty()
+    }
+
+    /// Custom sensors the collector may run: none unless `custom_sensors_enabled` is set
+    pub fn active_custom_sensors(&self) -> Vec<CustomSensor> {
+        if self.custom_sensors.is_empty() {
+            return Vec::new();
+        }
+        if !self.custom_sensors_enabled {
+            log::warn!(
+                "[Custom] {} custom sensor(s) configured but custom_sensors_enabled is false; ignoring them",
+                self.custom_sensors.len()
+            );
+            return Vec::new();
+        }
+        log::warn!(
+            "[Custom] Custom sensors enabled: {} command(s)/file(s) will run with this app's permissions",
+            self.custom_sensors.len()
         );
-        store.set("autostart", serde_json::json!(self.autostart));
+        self.custom_sensors.clone()
+    }
 
-        Ok(())
+    /// Local address for outgoing HA requests from `source_ip` / `source_interface`.
+    /// Ok(None) when neither is set. An invalid address, or one that is not assigned to this
+    /// machine, is an error: traffic must not silently leave through the default route.
+    pub fn source_address(&self) -> Result<Option<IpAddr>, String> {
+        if self.source_ip.is_none() && self.source_interface.is_none() {
+            return Ok(None);
+        }
+        let assigned = network::interface_addresses();
+
+        if let Some(ref ip) = self.source_ip {
+            let addr: IpAddr = ip
+                .parse()
+                .map_err(|_| format!("source_ip '{}' is not a valid IP address", ip))?;
+            if !assigned.iter().any(|(_, a)| *a == addr) {
+                return Err(format!(
+                    "source_ip {} is not assigned to any local interface",
+                    addr
+                ));
+            }
+            return Ok(Some(addr));
+        }
+
+        let Some(name) = self.source_interface.as_deref() else {
+            return Ok(None);
+        };
+        let addrs: Vec<IpAddr> = assigned
+            .into_iter()
+            .filter(|(iface, _)| iface == name)
+            .map(|(_, addr)| addr)
+            .collect();
+        // Prefer IPv4: most HA servers are reached over IPv4
+        let addr = addrs
+            .iter()
+            .find(|a| a.is_ipv4())
+            .or_else(|| addrs.first())
+            .copied();
+        match addr {
+            Some(addr) => Ok(Some(addr)),
+            None => Err(format!(
+                "source_interface '{}' not found or has no IP address",
+                name
+            )),
+        }
+    }
+
+    /// Location of settings.json in the app data dir, for use without a running Tauri app
+    /// (Windows: %APPDATA%, macOS: ~/Library/Application Support, Linux: $XDG_DATA_HOME or ~/.local/share)
+    pub fn store_file_path() -> Option<PathBuf> {
+        let data_dir = if cfg!(windows) {
+            std::env::var_os("APPDATA").map(PathBuf::from)
+        } else if cfg!(target_os = "macos") {
+            std::env::var_os("HOME").map(|home| {
+                PathBuf::from(home)
+                    .join("Library")
+                    .join("Application Support")
+            })
+        } else {
+            std::env::var_os("XDG_DATA_HOME")
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| {
+                    std::env::var_os("HOME")
+                        .map(|home| PathBuf::from(home).join(".local").join("share"))
+                })
+        }?;
+        Some(data_dir.join(APP_IDENTIFIER).join(STORE_PATH))
+    }
+
+    /// Load settings straight from a settings.json file (no Tauri runtime needed)
+    pub fn load_from_file(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let values: serde_json::Map<String, Value> = serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid settings file {}: {}", path.display(), e))?;
+        Ok(Self::from_lookup(|key| values.get(key).cloned()))
+    }
+
+    /// Write settings into a settings.json file, keeping keys this struct does not know about
+    pub fn save_to_file(&self, path: &Path) -> Result<(), String> {
+        let mut values: serde_json::Map<String, Value> = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|c| serde_json::from_str(&c).ok())
+            .unwrap_or_default();
+        for (key, value) in self.to_values() {
+            values.insert(key.to_string(), value);
+        }
+        let content = serde_json::to_string_pretty(&values).map_err(|e| e.to_string())?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_the_store() {
+        let store = MemoryStore::default();
+        let mut settings = AppSettings {
+            server_url: "http://ha.local:8123".to_string(),
+            webhook_id: Some("abc123".to_string()),
+            update_interval: 30,
+            language: "nl".to_string(),
+            ..AppSettings::default()
+        };
+        settings
+            .enabled_sensors
+            .insert("cpu_usage".to_string(), false);
+        settings.save(&store).unwrap();
+
+        let loaded = AppSettings::load(&store);
+        assert_eq!(
+            serde_json::to_value(&loaded).unwrap(),
+            serde_json::to_value(&settings).unwrap()
+        );
+    }
+
+    #[test]
+    fn load_persists_a_new_device_id() {
+        let store = MemoryStore::default();
+        let first = AppSettings::load(&store);
+        assert_eq!(
+            store.get("device_id"),
+            Some(serde_json::json!(first.device_id))
+        );
+        assert_eq!(AppSettings::load(&store).device_id, first.device_id);
+    }
+
+    #[test]
+    fn invalid_values_fall_back_to_defaults() {
+        let store = MemoryStore::default();
+        store
+            .set("update_interval", serde_json::json!("soon"))
+            .unwrap();
+        store
+            .set("enabled_sensors", serde_json::json!([1, 2]))
+            .unwrap();
+        store.set("language", serde_json::json!(7)).unwrap();
+        let settings = AppSettings::load(&store);
+        assert_eq!(settings.update_interval, 60);
+        assert!(settings.enabled_sensors.is_empty());
+        assert_eq!(settings.language, "en");
+    }
+
+    #[test]
+    fn legacy_ids_only_for_registered_installs_without_ids() {
+        let store = MemoryStore::default();
+        assert!(!AppSettings::load(&store).legacy_gpu_ids);
+
+        store
+            .set("webhook_id", serde_json::json!("abc123"))
+            .unwrap();
+        let settings = AppSettings::load(&store);
+        assert!(settings.legacy_gpu_ids);
+        assert!(settings.legacy_battery_ids);
+
+        // Once saved, the (possibly empty) id maps are in the store
+        settings.save(&store).unwrap();
+        let settings = AppSettings::load(&store);
+        assert!(!settings.legacy_gpu_ids);
+        assert!(!settings.legacy_battery_ids);
     }
 }
@@ -11,8 +11,16 @@ const translations = {
         server_url: "Server URL",
         access_token: "Long-Lived Access Token",
         token_help: "Create a token in HA: Profile → Long-Lived Access Tokens",
+        device_name: "Device Name",
+        device_name_help: "How the device appears in Home Assistant. Leave empty to use the hostname",
         connect: "Connect",
         registering: "Registering device...",
+        progress_pinging: "Contacting Home Assistant...",
+        progress_registering_device: "Registering device...",
+        progress_waiting: "Waiting for Home Assistant...",
+        progress_registering_sensors: "Registering sensors",
+        progress_sending_states: "Sending sensor states...",
+        progress_done: "Done",
 
         // Settings
         settings: "Settings",
@@ -22,31 +30,75 @@ const translations = {
         language: "Language",
         autostart: "Start at login",
         sensors: "Sensors",
+        enable_all: "Enable all",
+        disable_all: "Disable all",
         device_info: "Device Info",
         device_id: "Device ID",
         webhook_id: "Webhook ID",
         status: "Status",
         my_ip_for_proxy: "My IP (for proxy allowlist)",
         show_ip: "Show",
+        public_ip_disabled: "Disabled (public_ip in settings.json)",
+        ip_ha_sees_same: "Home Assistant sees the same IP, so this is the one to allow",
+        ip_ha_sees_other: "Home Assistant sees {ip}: a proxy or CGNAT sits in between. Allow the IP the proxy in front of HA receives",
+        ip_ha_sees_local: "Home Assistant sees the local address {ip}: HA is reached over the LAN/VPN, or the proxy doesn't forward the client IP",
+        ip_ha_sees_unknown: "Home Assistant doesn't report which IP it sees; check the proxy logs to confirm",
+        elevation: "Administrator rights",
+        elevated_yes: "Yes",
+        elevated_no: "No",
+        elevation_hint: "Some sensors, like the CPU temperature on many PCs, are only available when the app runs as administrator",
         save: "Save",
         cancel: "Cancel",
         registered: "Registered",
         not_registered: "Not registered",
+        resend_states: "Resend all",
+        states_resent: "Sent {count} sensor states",
         updates_at_interval: "Updates at interval",
         static_sensor: "Static (startup only)",
+        dashboard_disabled: "The dashboard is disabled. Sensors are still reported to Home Assistant.",
 
         // Sensor names
         cpu_usage: "CPU Usage",
         cpu_frequency: "CPU Frequency",
         cpu_temperature: "CPU Temperature",
+        cpu_overheating: "CPU Overheating",
+        cpu_breakdown: "CPU Usage Breakdown",
         cpu_model: "CPU Model",
+        cpu_architecture: "CPU Architecture",
+        cpu_vendor: "CPU Vendor",
         memory_usage: "Memory Usage",
         memory_used: "Memory Used",
         memory_total: "Memory Total",
+        swap_config: "Swap/Pagefile Configuration",
         disk_usage: "Disk Usage",
         gpu: "GPU Sensors",
         network: "Network Sensors",
+        network_addresses: "Network Addresses (IPv4/IPv6)",
+        network_connection_type: "Network Connection Type",
+        wifi_signal: "Wi-Fi Signal",
         battery: "Battery Sensors",
+        on_ac_power: "On AC Power",
+        lid_open: "Lid Open",
+        power_plan: "Power Plan",
+        remote_hosts: "Remote Host Sensors",
+        custom_sensors: "Custom Sensors",
+        last_clipboard_change: "Last Clipboard Change",
+        keyboard: "Keyboard Lock Keys & Backlight",
+        user_idle_seconds: "User Idle Time",
+        session_count: "Session Count",
+        thread_count: "Thread Count",
+        total_uptime: "Total Uptime",
+        gpu_process_count: "GPU Process Count",
+        gpu_memory_util: "GPU Memory Utilization",
+        gpu_pcie: "GPU PCIe Throughput",
+        gpu_throttle_reason: "GPU Throttle Reason",
+        active_gpu: "Active GPU",
+        os_updates_pending: "OS Updates Pending",
+        reboot_required: "Reboot Required",
+        collection_errors: "Collection Errors",
+        elevated: "Running Elevated",
+        companion_cpu: "Companion CPU Usage",
+        companion_memory: "Companion Memory Usage",
         os_version: "OS Version",
         hostname: "Hostname",
         motherboard: "Motherboard",
@@ -56,8 +108,14 @@ const translations = {
         error_server_url: "Please enter a valid server URL",
         error_token: "Please enter an access token",
         error_connection: "Could not connect to Home Assistant",
+        confirm_adopt_device: "This device is already registered in Home Assistant (for example from an earlier installation). Reuse the existing device instead of creating a new one?",
         success_saved: "Settings saved successfully",
         success_registered: "Device registered successfully",
+        displays_changed: "Displays changed: {count} connected",
+        webhook_healed: "The webhook was gone; the device registered again",
+        webhook_heal_failed: "The webhook was gone; registering again failed: {error}",
+        seed_update_sent: "Initial updates: {current}/{total} sent",
+        seed_update_failed: "Initial update {current}/{total} failed: {error}",
     },
 
     nl: {
@@ -67,8 +125,16 @@ const translations = {
         server_url: "Server URL",
         access_token: "Langlevend Toegangstoken",
         token_help: "Maak een token aan in HA: Profiel → Langlevende Toegangstokens",
+        device_name: "Apparaatnaam",
+        device_name_help: "Zo verschijnt het apparaat in Home Assistant. Laat leeg om de hostnaam te gebruiken",
         connect: "Verbinden",
         registering: "Apparaat registreren...",
+        progress_pinging: "Verbinden met Home Assistant...",
+        progress_registering_device: "Apparaat registreren...",
+        progress_waiting: "Wachten op Home Assistant...",
+        progress_registering_sensors: "Sensoren registreren",
+        progress_sending_states: "Sensorwaarden versturen...",
+        progress_done: "Klaar",
 
         // Settings
         settings: "Instellingen",
@@ -78,31 +144,75 @@ const translations = {
         language: "Taal",
         autostart: "Starten bij inloggen",
         sensors: "Sensoren",
+        enable_all: "Alles aan",
+        disable_all: "Alles uit",
         device_info: "Apparaat Info",
         device_id: "Apparaat ID",
         webhook_id: "Webhook ID",
         status: "Status",
         my_ip_for_proxy: "Mijn IP (voor proxy allowlist)",
         show_ip: "Tonen",
+        public_ip_disabled: "Uitgeschakeld (public_ip in settings.json)",
+        ip_ha_sees_same: "Home Assistant ziet hetzelfde IP, dit is dus het IP om toe te staan",
+        ip_ha_sees_other: "Home Assistant ziet {ip}: er zit een proxy of CGNAT tussen. Sta het IP toe dat de proxy voor HA ontvangt",
+        ip_ha_sees_local: "Home Assistant ziet het lokale adres {ip}: HA wordt via het LAN/VPN bereikt, of de proxy geeft het client-IP niet door",
+        ip_ha_sees_unknown: "Home Assistant meldt niet welk IP het ziet; controleer de proxy-logs om dit te bevestigen",
+        elevation: "Beheerdersrechten",
+        elevated_yes: "Ja",
+        elevated_no: "Nee",
+        elevation_hint: "Sommige sensoren, zoals de CPU-temperatuur op veel pc's, zijn alleen beschikbaar als de app als administrator draait",
         save: "Opslaan",
         cancel: "Annuleren",
         registered: "Geregistreerd",
         not_registered: "Niet geregistreerd",
+        resend_states: "Alles opnieuw",
+        states_resent: "{count} sensorwaarden verstuurd",
         updates_at_interval: "Update bij interval",
         static_sensor: "Statisch (alleen bij start)",
+        dashboard_disabled: "Het dashboard is uitgeschakeld. Sensoren worden nog steeds naar Home Assistant gestuurd.",
 
         // Sensor names
         cpu_usage: "CPU Gebruik",
         cpu_frequency: "CPU Snelheid",
         cpu_temperature: "CPU Temperatuur",
+        cpu_overheating: "CPU Oververhitting",
+        cpu_breakdown: "CPU Gebruik per Type",
         cpu_model: "CPU Model",
+        cpu_architecture: "CPU Architectuur",
+        cpu_vendor: "CPU Fabrikant",
         memory_usage: "Geheugen Gebruik",
         memory_used: "Geheugen Gebruikt",
         memory_total: "Geheugen Totaal",
+        swap_config: "Swap/Wisselbestand Configuratie",
         disk_usage: "Schijf Gebruik",
         gpu: "GPU Sensoren",
         network: "Netwerk Sensoren",
+        network_addresses: "Netwerkadressen (IPv4/IPv6)",
+        network_connection_type: "Netwerk Verbindingstype",
+        wifi_signal: "Wifi-signaal",
         battery: "Batterij Sensoren",
+        on_ac_power: "Op Netstroom",
+        lid_open: "Klep Open",
+        power_plan: "Energiebeheerschema",
+        remote_hosts: "Externe Host Sensoren",
+        custom_sensors: "Eigen Sensoren",
+        last_clipboard_change: "Laatste Klembordwijziging",
+        keyboard: "Toetsenbord Lock-toetsen & Verlichting",
+        user_idle_seconds: "Inactiviteitsduur Gebruiker",
+        session_count: "Aantal Sessies",
+        thread_count: "Aantal Threads",
+        total_uptime: "Totale Uptime",
+        gpu_process_count: "Aantal GPU-processen",
+        gpu_memory_util: "GPU Geheugenbelasting",
+        gpu_pcie: "GPU PCIe Doorvoer",
+        gpu_throttle_reason: "GPU Throttle-oorzaak",
+        active_gpu: "Actieve GPU",
+        os_updates_pending: "Openstaande OS-updates",
+        reboot_required: "Herstart Vereist",
+        collection_errors: "Verzamelfouten",
+        elevated: "Draait als Administrator",
+        companion_cpu: "CPU-gebruik Companion",
+        companion_memory: "Geheugengebruik Companion",
         os_version: "OS Versie",
         hostname: "Hostnaam",
         motherboard: "Moederbord",
@@ -112,8 +222,14 @@ const translations = {
         error_server_url: "Voer een geldige server URL in",
         error_token: "Voer een toegangstoken in",
         error_connection: "Kan geen verbinding maken met Home Assistant",
+        confirm_adopt_device: "Dit apparaat is al geregistreerd in Home Assistant (bijvoorbeeld door een eerdere installatie). Het bestaande apparaat hergebruiken in plaats van een nieuw apparaat aan te maken?",
         success_saved: "Instellingen opgeslagen",
         success_registered: "Apparaat succesvol geregistreerd",
+        displays_changed: "Beeldschermen gewijzigd: {count} aangesloten",
+        webhook_healed: "De webhook was verdwenen; het apparaat is opnieuw geregistreerd",
+        webhook_heal_failed: "De webhook was verdwenen; opnieuw registreren mislukt: {error}",
+        seed_update_sent: "Eerste updates: {current}/{total} verzonden",
+        seed_update_failed: "Eerste update {current}/{total} mislukt: {error}",
     },
 };
 
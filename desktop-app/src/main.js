@@ -3,6 +3,9 @@
  * Handles app initialization, setup form, tray events
  */
 
+/** Start of the registration error when HA already has this device (see registration.rs) */
+const DEVICE_EXISTS_ERROR = "Device already registered";
+
 /**
  * Show the setup screen
  */
@@ -17,6 +20,17 @@ function hideSetupScreen() {
     document.getElementById("setup-screen").classList.add("hidden");
 }
 
+/**
+ * Show the HA dashboard, or the status screen when the dashboard is disabled in settings
+ */
+async function showMainView(settings) {
+    if (settings.dashboard_enabled) {
+        await window.__TAURI__.core.invoke("load_dashboard");
+    } else {
+        document.getElementById("status-screen").classList.remove("hidden");
+    }
+}
+
 /**
  * Handle initial setup form
  */
@@ -30,6 +44,8 @@ async function handleSetup(e) {
 
     errorEl.classList.add("hidden");
     loadingEl.classList.remove("hidden");
+    document.getElementById("setup-loading-text").textContent = t("registering");
+    document.getElementById("setup-progress").classList.add("hidden");
 
     try {
         // Save settings first
@@ -41,12 +57,19 @@ async function handleSetup(e) {
             autostart: false,
         });
 
-        // Register device
-        await window.__TAURI__.core.invoke("register_device");
+        // Register device; after a reinstall HA may already know it: offer to adopt it
+        try {
+            await window.__TAURI__.core.invoke("register_device");
+        } catch (err) {
+            if (!err.toString().startsWith(DEVICE_EXISTS_ERROR) || !confirm(t("confirm_adopt_device"))) {
+                throw err;
+            }
+            await window.__TAURI__.core.invoke("adopt_existing_device");
+        }
 
         // Success — open HA dashboard as child webview overlay
         hideSetupScreen();
-        await window.__TAURI__.core.invoke("load_dashboard");
+        await showMainView(await window.__TAURI__.core.invoke("get_settings"));
     } catch (err) {
         errorEl.textContent = err.toString();
         errorEl.classList.remove("hidden");
@@ -55,6 +78,37 @@ async function handleSetup(e) {
     }
 }
 
+/**
+ * Show registration progress events from the backend in the setup screen
+ */
+function showRegistrationProgress(progress) {
+    const textEl = document.getElementById("setup-loading-text");
+    const barEl = document.getElementById("setup-progress");
+
+    let text = t("progress_" + progress.step);
+    if (progress.total > 0) {
+        text += ` (${progress.current}/${progress.total})`;
+        barEl.max = progress.total;
+        barEl.value = progress.current;
+        barEl.classList.remove("hidden");
+    } else {
+        barEl.classList.add("hidden");
+    }
+    textEl.textContent = text;
+}
+
+/**
+ * Show a message from the backend (e.g. a display change) in the status screen and in the
+ * device info of the settings
+ */
+function showBackendStatus(text, ok = true) {
+    for (const el of document.querySelectorAll(".backend-status")) {
+        el.textContent = text;
+        el.classList.toggle("status-error", !ok);
+        el.classList.remove("hidden");
+    }
+}
+
 /**
  * Initialize the app
  */
@@ -77,7 +131,7 @@ async function initApp() {
         } else {
             // Already registered — open HA dashboard as child webview
             hideSetupScreen();
-            await window.__TAURI__.core.invoke("load_dashboard");
+            await showMainView(settings);
         }
     } catch (err) {
         console.error("Failed to initialize app:", err);
@@ -89,12 +143,35 @@ async function initApp() {
 document.addEventListener("DOMContentLoaded", () => {
     // Setup form
     document.getElementById("setup-form").addEventListener("submit", handleSetup);
+    document.getElementById("status-open-settings").addEventListener("click", openSettings);
 
     // Listen for tray events
     if (window.__TAURI__) {
         window.__TAURI__.event.listen("tray-show-settings", () => {
             openSettings();
         });
+        window.__TAURI__.event.listen("registration-progress", (event) => {
+            showRegistrationProgress(event.payload);
+        });
+        window.__TAURI__.event.listen("displays-changed", (event) => {
+            showBackendStatus(t("displays_changed").replace("{count}", event.payload.length));
+        });
+        window.__TAURI__.event.listen("webhook-healed", (event) => {
+            const { success, error } = event.payload;
+            if (success) {
+                showBackendStatus(t("webhook_healed"));
+            } else {
+                showBackendStatus(t("webhook_heal_failed").replace("{error}", error), false);
+            }
+        });
+        window.__TAURI__.event.listen("seed-burst-progress", (event) => {
+            const { current, total, error } = event.payload;
+            const text = t(error ? "seed_update_failed" : "seed_update_sent")
+                .replace("{current}", current)
+                .replace("{total}", total)
+                .replace("{error}", error);
+            showBackendStatus(text, !error);
+        });
     }
 
     // Initialize
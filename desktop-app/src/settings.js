@@ -14,6 +14,7 @@ async function openSettings() {
         // Populate fields
         document.getElementById("settings-server-url").value = currentSettings.server_url || "";
         document.getElementById("settings-token").value = currentSettings.access_token || "";
+        document.getElementById("settings-device-name").value = currentSettings.device_name || "";
         document.getElementById("settings-interval").value = currentSettings.update_interval || 60;
         document.getElementById("settings-language").value = currentSettings.language || "en";
         document.getElementById("settings-autostart").checked = currentSettings.autostart || false;
@@ -28,9 +29,21 @@ async function openSettings() {
             : t("not_registered");
         document.getElementById("info-status").className =
             "info-value " + (currentSettings.is_registered ? "status-ok" : "status-error");
-
-        // Reset "My IP" until user clicks Show
-        document.getElementById("info-my-ip").textContent = "-";
+        document.getElementById("settings-resend-states").disabled = !currentSettings.is_registered;
+
+        // Reset "My IP" until user clicks Show; the lookup is opt-in
+        document.getElementById("info-my-ip").textContent = currentSettings.public_ip_enabled
+            ? "-"
+            : t("public_ip_disabled");
+        document.getElementById("settings-show-ip").disabled = !currentSettings.public_ip_enabled;
+        document.getElementById("info-ip-hint").classList.add("hidden");
+
+        // Explain sensors that are blank without admin rights
+        const elevation = await window.__TAURI__.core.invoke("get_elevation_status");
+        document.getElementById("info-elevated").textContent = elevation.elevated
+            ? t("elevated_yes")
+            : t("elevated_no");
+        document.getElementById("info-elevation-hint").classList.toggle("hidden", !elevation.elevation_helps);
 
         // Populate sensor list
         await populateSensorList();
@@ -47,6 +60,10 @@ async function openSettings() {
  */
 async function closeSettings() {
     document.getElementById("settings-overlay").classList.add("hidden");
+    if (currentSettings && !currentSettings.dashboard_enabled) {
+        // The status screen underneath stays visible
+        return;
+    }
     // Re-open the HA child webview on top
     try {
         await window.__TAURI__.core.invoke("load_dashboard");
@@ -61,6 +78,7 @@ async function closeSettings() {
 async function saveSettings() {
     const serverUrl = document.getElementById("settings-server-url").value.trim();
     const token = document.getElementById("settings-token").value.trim();
+    const deviceName = document.getElementById("settings-device-name").value.trim();
     const interval = parseInt(document.getElementById("settings-interval").value) || 60;
     const language = document.getElementById("settings-language").value;
     const autostart = document.getElementById("settings-autostart").checked;
@@ -72,6 +90,7 @@ async function saveSettings() {
             updateInterval: interval,
             language: language,
             autostart: autostart,
+            deviceName: deviceName,
         });
 
         // Update language
@@ -85,6 +104,20 @@ async function saveSettings() {
     }
 }
 
+/**
+ * Enable or disable every sensor in the list with a single settings write
+ */
+async function setAllSensors(enabled) {
+    const checkboxes = document.querySelectorAll("#sensor-list input[type=checkbox]");
+    const changes = Array.from(checkboxes).map((cb) => [cb.id.replace(/^sensor-/, ""), enabled]);
+    try {
+        await window.__TAURI__.core.invoke("set_sensors_enabled", { changes });
+    } catch (err) {
+        console.error("Failed to update sensors:", err);
+    }
+    await populateSensorList();
+}
+
 /**
  * Populate sensor list with checkboxes
  */
@@ -141,16 +174,25 @@ function togglePassword(inputId) {
 }
 
 /**
- * Show this machine's public IP (for proxy allowlist)
+ * Show this machine's public IP (for proxy allowlist) and whether HA sees the same IP
  */
 async function showMyIp() {
     const el = document.getElementById("info-my-ip");
     const btn = document.getElementById("settings-show-ip");
+    const hint = document.getElementById("info-ip-hint");
     el.textContent = "...";
+    hint.classList.add("hidden");
     btn.disabled = true;
     try {
-        const ip = await window.__TAURI__.core.invoke("get_my_public_ip");
-        el.textContent = ip || "-";
+        const check = await window.__TAURI__.core.invoke("check_allowlist_ip");
+        el.textContent = check.public_ip || "-";
+        if (check.matches === false) {
+            hint.textContent = t(check.ha_seen_local ? "ip_ha_sees_local" : "ip_ha_sees_other")
+                .replace("{ip}", check.ha_seen_ip);
+        } else {
+            hint.textContent = t(check.matches ? "ip_ha_sees_same" : "ip_ha_sees_unknown");
+        }
+        hint.classList.remove("hidden");
     } catch (err) {
         console.error("Failed to get IP:", err);
         el.textContent = t("error") || "Error";
@@ -158,12 +200,34 @@ async function showMyIp() {
     btn.disabled = false;
 }
 
+/**
+ * Send the current state of every sensor to HA again (e.g. after a recorder purge)
+ */
+async function resendAllStates() {
+    const status = document.getElementById("info-status");
+    const btn = document.getElementById("settings-resend-states");
+    btn.disabled = true;
+    try {
+        const count = await window.__TAURI__.core.invoke("resend_all_states");
+        status.textContent = t("states_resent").replace("{count}", count);
+        status.className = "info-value status-ok";
+    } catch (err) {
+        console.error("Failed to resend states:", err);
+        status.textContent = t("error_connection");
+        status.className = "info-value status-error";
+    }
+    btn.disabled = false;
+}
+
 // Event listeners
 document.addEventListener("DOMContentLoaded", () => {
     document.getElementById("settings-close").addEventListener("click", closeSettings);
     document.getElementById("settings-cancel").addEventListener("click", closeSettings);
     document.getElementById("settings-save").addEventListener("click", saveSettings);
     document.getElementById("settings-show-ip").addEventListener("click", showMyIp);
+    document.getElementById("settings-resend-states").addEventListener("click", resendAllStates);
+    document.getElementById("sensors-enable-all").addEventListener("click", () => setAllSensors(true));
+    document.getElementById("sensors-disable-all").addEventListener("click", () => setAllSensors(false));
 
     // Close on overlay click
     document.getElementById("settings-overlay").addEventListener("click", (e) => {